@@ -17,13 +17,34 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         );
     }
 
+    // Descriptor set served by gRPC server reflection
+    let descriptor_path =
+        std::path::PathBuf::from(std::env::var("OUT_DIR")?).join("satsconnect_descriptor.bin");
+
     tonic_build::configure()
         .build_server(true)
         .build_client(true)
         .out_dir("src/proto")
-        .compile_protos(&["proto/wallet.proto", "proto/payment.proto"], &["proto"])?;
+        .file_descriptor_set_path(descriptor_path)
+        // Messages double as the HTTP/JSON gateway's request and response bodies
+        .message_attribute(
+            ".",
+            "#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]",
+        )
+        .message_attribute(".", "#[serde(rename_all = \"camelCase\", default)]")
+        .compile_protos(
+            &[
+                "proto/wallet.proto",
+                "proto/payment.proto",
+                "proto/channel.proto",
+                "proto/peer.proto",
+            ],
+            &["proto"],
+        )?;
 
     println!("cargo:rerun-if-changed=proto/wallet.proto");
     println!("cargo:rerun-if-changed=proto/payment.proto");
+    println!("cargo:rerun-if-changed=proto/channel.proto");
+    println!("cargo:rerun-if-changed=proto/peer.proto");
     Ok(())
 }
@@ -0,0 +1,135 @@
+//! Export the engine's view of the Lightning network graph and show its
+//! most central nodes. Needs an admin token.
+//!
+//! ```text
+//! engine_graph --token-file ./data/auth/admin.token export --format dot --out graph.dot
+//! engine_graph --token-file ./data/auth/admin.token stats --center <node_id> --hops 3
+//! ```
+
+use anyhow::Result;
+use clap::{Parser, Subcommand, ValueEnum};
+use satsconnect_rust_engine::proto::satsconnect::channel::v1::channel_service_client::ChannelServiceClient;
+use satsconnect_rust_engine::proto::satsconnect::channel::v1::{
+    ExportNetworkGraphRequest, GraphFormat,
+};
+use satsconnect_rust_engine::tls::ClientConnectArgs;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "engine_graph",
+    about = "Export and analyse the SatsConnect engine's network graph"
+)]
+struct Args {
+    #[command(flatten)]
+    connect: ClientConnectArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Dump the graph, whole or around a node
+    Export {
+        #[arg(long, value_enum, default_value = "json")]
+        format: Format,
+        #[command(flatten)]
+        scope: ScopeArgs,
+        /// Write the graph to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Show graph totals and the most central nodes
+    Stats {
+        /// How many nodes to list
+        #[arg(long, default_value_t = 10)]
+        top: u32,
+        #[command(flatten)]
+        scope: ScopeArgs,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    /// Graphviz DOT, e.g. for `dot -Tsvg`
+    Dot,
+}
+
+#[derive(Debug, clap::Args)]
+struct ScopeArgs {
+    /// Only the part of the graph around this node
+    #[arg(long)]
+    center: Option<String>,
+    /// Hops around `--center` to include
+    #[arg(long, default_value_t = 2, requires = "center")]
+    hops: u32,
+}
+
+impl ScopeArgs {
+    fn request(&self, format: GraphFormat, top_nodes: u32) -> ExportNetworkGraphRequest {
+        ExportNetworkGraphRequest {
+            format: format as i32,
+            center_node_id: self.center.clone().unwrap_or_default(),
+            max_hops: self.hops,
+            top_nodes,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let channel = args.connect.connect().await?;
+    let mut client = ChannelServiceClient::with_interceptor(channel, args.connect.interceptor()?);
+
+    match &args.command {
+        Command::Export { format, scope, out } => {
+            let format = match format {
+                Format::Json => GraphFormat::Json,
+                Format::Dot => GraphFormat::Dot,
+            };
+            let response = client
+                .export_network_graph(scope.request(format, 0))
+                .await?
+                .into_inner();
+            match out {
+                Some(out) => {
+                    std::fs::write(out, &response.graph)?;
+                    eprintln!(
+                        "Wrote {} nodes and {} channels to {}",
+                        response.total_nodes,
+                        response.total_channels,
+                        out.display()
+                    );
+                }
+                None => println!("{}", response.graph),
+            }
+        }
+        Command::Stats { top, scope } => {
+            let response = client
+                .export_network_graph(scope.request(GraphFormat::Json, *top))
+                .await?
+                .into_inner();
+            println!("nodes: {}", response.total_nodes);
+            println!(
+                "channels: {} ({} enabled)",
+                response.total_channels, response.enabled_channels
+            );
+            println!("capacity_sats: {}", response.total_capacity_sats);
+            println!();
+            println!(
+                "{:<66}  {:>6}  {:>14}  {:>11}",
+                "node_id", "degree", "capacity_sats", "betweenness"
+            );
+            for node in &response.top_nodes {
+                println!(
+                    "{:<66}  {:>6}  {:>14}  {:>11.4}",
+                    node.node_id, node.degree, node.capacity_sats, node.betweenness
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,243 @@
+//! Mint, attenuate and inspect scoped API tokens for `engine_server`.
+//!
+//! ```text
+//! engine_token --data-dir ./data mint --preset invoice-only --expires-in 30d
+//! engine_token attenuate <token> --max-amount-sats 50000
+//! engine_token inspect <token>
+//! ```
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use satsconnect_rust_engine::engine_config::{EngineArgs, EngineConfig};
+use satsconnect_rust_engine::security::scoped_token::{self, permissions};
+use satsconnect_rust_engine::security::{Caveat, ScopedToken};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Parser)]
+#[command(name = "engine_token", about = "Manage SatsConnect engine API tokens")]
+struct Args {
+    /// Engine TOML config file, used to find the root key
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Engine data directory, used to find the root key
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// Root key file; overrides the engine configuration
+    #[arg(long, global = true)]
+    root_key: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Mint a new token signed with the engine's root key
+    Mint {
+        /// Permission set to start from
+        #[arg(long, value_enum, default_value = "admin")]
+        preset: Preset,
+        #[command(flatten)]
+        caveats: CaveatArgs,
+        /// Write the token to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Narrow an existing token; needs no root key
+    Attenuate {
+        token: String,
+        #[command(flatten)]
+        caveats: CaveatArgs,
+    },
+    /// Show a token's caveats and check its signature if the root key is available
+    Inspect { token: String },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Preset {
+    /// Every permission
+    Admin,
+    /// Balances, fee estimates and payment status
+    ReadOnly,
+    /// Read plus creating invoices
+    InvoiceOnly,
+    /// Read, invoices and sending payments
+    Send,
+}
+
+impl Preset {
+    fn permissions(self) -> Option<Vec<&'static str>> {
+        match self {
+            Preset::Admin => None,
+            Preset::ReadOnly => Some(vec![permissions::READ]),
+            Preset::InvoiceOnly => Some(vec![permissions::READ, permissions::INVOICE]),
+            Preset::Send => Some(vec![
+                permissions::READ,
+                permissions::INVOICE,
+                permissions::SEND,
+            ]),
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct CaveatArgs {
+    /// Comma-separated permissions to keep (read, invoice, send, refund, admin)
+    #[arg(long, value_delimiter = ',')]
+    permissions: Vec<String>,
+    /// Lifetime such as 3600s, 90m, 12h or 30d
+    #[arg(long, value_parser = parse_duration)]
+    expires_in: Option<Duration>,
+    /// Largest single payment or refund the token may make
+    #[arg(long)]
+    max_amount_sats: Option<u64>,
+    /// Only allow requests for this wallet
+    #[arg(long)]
+    wallet_id: Option<String>,
+}
+
+impl CaveatArgs {
+    fn caveats(&self) -> Result<Vec<Caveat>> {
+        let mut caveats = Vec::new();
+        if !self.permissions.is_empty() {
+            if let Some(unknown) = self
+                .permissions
+                .iter()
+                .find(|p| !permissions::ALL.contains(&p.as_str()))
+            {
+                return Err(anyhow!("Unknown permission '{}'", unknown));
+            }
+            caveats.push(Caveat::Permissions(self.permissions.clone()));
+        }
+        if let Some(duration) = self.expires_in {
+            caveats.push(Caveat::expires_in(duration));
+        }
+        if let Some(amount) = self.max_amount_sats {
+            caveats.push(Caveat::MaxAmountSats(amount));
+        }
+        if let Some(wallet_id) = &self.wallet_id {
+            caveats.push(Caveat::WalletId(wallet_id.clone()));
+        }
+        Ok(caveats)
+    }
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value.len() - value.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration '{}'", value))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "Unknown duration unit '{}', use s, m, h or d",
+                unit
+            ))
+        }
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+impl Args {
+    fn root_key_path(&self) -> Result<PathBuf> {
+        if let Some(path) = &self.root_key {
+            return Ok(path.clone());
+        }
+        let config = EngineConfig::load(&EngineArgs {
+            config: self.config.clone(),
+            data_dir: self.data_dir.clone(),
+            ..Default::default()
+        })?;
+        Ok(config.server.auth.root_key_path(&config.lightning.data_dir))
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    match &args.command {
+        Command::Mint {
+            preset,
+            caveats,
+            out,
+        } => {
+            let path = args.root_key_path()?;
+            if !path.exists() {
+                return Err(anyhow!(
+                    "No root key at {}; start engine_server once to create it",
+                    path.display()
+                ));
+            }
+            let root_key = scoped_token::load_root_key(&path)?;
+
+            let mut all_caveats = Vec::new();
+            if let Some(permissions) = preset.permissions() {
+                all_caveats.push(Caveat::Permissions(
+                    permissions.into_iter().map(String::from).collect(),
+                ));
+            }
+            all_caveats.extend(caveats.caveats()?);
+
+            let token = ScopedToken::mint(&root_key, uuid::Uuid::new_v4().to_string(), all_caveats);
+            match out {
+                Some(out) => {
+                    scoped_token::save_token(out, &token)?;
+                    eprintln!("Wrote token {} to {}", token.id(), out.display());
+                }
+                None => println!("{}", token.encode()),
+            }
+        }
+        Command::Attenuate { token, caveats } => {
+            let token = caveats
+                .caveats()?
+                .into_iter()
+                .fold(ScopedToken::decode(token.trim())?, ScopedToken::attenuate);
+            println!("{}", token.encode());
+        }
+        Command::Inspect { token } => {
+            let token = ScopedToken::decode(token.trim())?;
+            println!("id: {}", token.id());
+            for caveat in token.caveats() {
+                println!("caveat: {}", caveat);
+            }
+
+            let restrictions = token.restrictions()?;
+            println!("permissions: {}", restrictions.permissions.join(","));
+            if let Some(expires_at) = restrictions.expires_at {
+                let timestamp = expires_at.duration_since(UNIX_EPOCH)?.as_secs();
+                let expired = expires_at <= SystemTime::now();
+                println!(
+                    "expires_at: {}{}",
+                    timestamp,
+                    if expired { " (expired)" } else { "" }
+                );
+            }
+            if let Some(amount) = restrictions.max_amount_sats {
+                println!("max_amount_sats: {}", amount);
+            }
+            if let Some(wallet_id) = &restrictions.wallet_id {
+                println!("wallet_id: {}", wallet_id);
+            }
+
+            match args
+                .root_key_path()
+                .ok()
+                .filter(|path| path.exists())
+                .map(|path| scoped_token::load_root_key(&path))
+            {
+                Some(Ok(root_key)) => match token.verify(&root_key) {
+                    Ok(()) => println!("signature: valid"),
+                    Err(e) => println!("signature: {}", e),
+                },
+                _ => println!("signature: not checked, root key unavailable"),
+            }
+        }
+    }
+
+    Ok(())
+}
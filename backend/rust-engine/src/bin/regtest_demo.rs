@@ -1,76 +1,100 @@
-use satsconnect_engine::lightning::regtest_setup::{RegtestSetup, RegtestNode};
+//! Run a local regtest Lightning network end to end: start bitcoind and
+//! electrs, create node wallets, fund them, open channels, pay over them and
+//! close a channel.
+//!
+//! ```text
+//! regtest_demo --nodes 3 --chain-source bitcoind --keep-running 60
+//! ```
+
+use clap::Parser;
+use satsconnect_rust_engine::config::ChainSource;
+use satsconnect_rust_engine::lightning::regtest_setup::RegtestSetup;
 use std::path::PathBuf;
 use tracing::{info, Level};
 
+#[derive(Debug, Parser)]
+#[command(name = "regtest_demo", about = "Run a local regtest Lightning network")]
+struct Args {
+    /// Empty directory for bitcoind, electrs and the nodes; a fresh
+    /// temporary one by default
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+    /// Nodes to start, paid along a chain node_1 -> node_2 -> ...
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u16).range(2..))]
+    nodes: u16,
+    #[arg(long, value_enum, default_value = "esplora")]
+    chain_source: ChainSource,
+    /// Seconds to keep the network running before shutting down
+    #[arg(long, default_value_t = 0)]
+    keep_running: u64,
+}
+
+const FUNDING_SATS: u64 = 1_000_000;
+const CHANNEL_SATS: u64 = 500_000;
+const PAYMENT_SATS: u64 = 10_000;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging
-    tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
-        .init();
-
-    info!("🚀 Starting SatsConnect Regtest Lightning Network Demo");
+    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
 
-    // Create regtest setup
-    let data_dir = PathBuf::from("./regtest_data");
-    let mut setup = RegtestSetup::new(data_dir);
+    let args = Args::parse();
+    let data_dir = args.data_dir.unwrap_or_else(|| {
+        std::env::temp_dir().join(format!("satsconnect_regtest_{}", std::process::id()))
+    });
+    let names: Vec<String> = (1..=args.nodes).map(|i| format!("node_{}", i)).collect();
 
-    // Create 3 test nodes
-    info!("Creating regtest Lightning nodes...");
-    setup.create_test_nodes(3).await?;
-
-    // Print network status
+    info!("🚀 Starting SatsConnect Regtest Lightning Network Demo");
+    let mut setup = RegtestSetup::new(data_dir).with_chain_source(args.chain_source);
+    setup.start().await?;
+
+    // Every node gets its own wallet
+    info!("Creating {} regtest Lightning nodes...", args.nodes);
+    setup.create_test_nodes(names.len()).await?;
+
+    // Every node but the last opens a channel to the next one
+    info!("\n💰 Funding nodes...");
+    for name in &names[..names.len() - 1] {
+        setup.fund_node(name, FUNDING_SATS).await?;
+    }
+
+    info!("\n🔗 Opening channels...");
+    for pair in names.windows(2) {
+        setup.connect_nodes(&pair[0], &pair[1]).await?;
+        setup
+            .open_channel(&pair[0], &pair[1], CHANNEL_SATS, None)
+            .await?;
+    }
     setup.print_network_status();
 
-    // Connect nodes
-    info!("\n🔗 Connecting nodes...");
-    setup.connect_nodes("node_1", "node_2").await?;
-    setup.connect_nodes("node_2", "node_3").await?;
-    setup.connect_nodes("node_1", "node_3").await?;
-
-    // Create test invoices
-    info!("\n💰 Creating test invoices...");
-    let (invoice1, hash1) = setup
-        .create_test_invoice("node_1", 1000, "Test invoice 1")
-        .await?;
-    let (invoice2, hash2) = setup
-        .create_test_invoice("node_2", 2500, "Test invoice 2")
-        .await?;
-
-    info!("Invoice 1: {} (Hash: {})", invoice1, hash1);
-    info!("Invoice 2: {} (Hash: {})", invoice2, hash2);
-
-    // Send test payments
     info!("\n💸 Sending test payments...");
-    let payment1 = setup
-        .send_test_payment("node_2", "node_1", 1000)
+    let direct = setup
+        .send_test_payment(&names[0], &names[1], PAYMENT_SATS)
         .await?;
-    let payment2 = setup
-        .send_test_payment("node_3", "node_2", 2500)
-        .await?;
-
-    info!("Payment 1 completed: {}", payment1);
-    info!("Payment 2 completed: {}", payment2);
-
-    // Get final network stats
-    let stats = setup.get_network_stats();
-    info!("\n📊 Final Network Statistics:");
-    info!("   Total Nodes: {}", stats.total_nodes);
-    info!("   Active Nodes: {}", stats.active_nodes);
-    info!("   Network Type: {}", stats.network_type);
-
-    info!("\n✅ Regtest Lightning Network demo completed successfully!");
-    info!("🎯 All nodes are running and can be used for development and testing");
-
-    // Keep nodes running for a bit to demonstrate
-    info!("\n⏳ Keeping nodes running for 30 seconds for demonstration...");
-    tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+    info!("Paid {} directly: {}", names[1], direct);
+    let last = &names[names.len() - 1];
+    if last != &names[1] {
+        let routed = setup
+            .send_test_payment(&names[0], last, PAYMENT_SATS)
+            .await?;
+        info!("Paid {} through the chain of channels: {}", last, routed);
+    }
+
+    info!("\n🔒 Closing the first channel...");
+    setup.close_channel(&names[0], &names[1]).await?;
+    setup.print_network_status();
 
-    // Stop all nodes
-    info!("\n🛑 Stopping all regtest nodes...");
-    setup.stop_all_nodes().await?;
+    if args.keep_running > 0 {
+        info!(
+            "\n⏳ Keeping the network running for {} seconds...",
+            args.keep_running
+        );
+        tokio::time::sleep(tokio::time::Duration::from_secs(args.keep_running)).await;
+    }
 
-    info!("👋 Demo completed. All nodes stopped.");
+    info!("\n🛑 Stopping the regtest network...");
+    setup.shutdown().await?;
 
+    info!("✅ Regtest Lightning Network demo completed successfully!");
     Ok(())
 }
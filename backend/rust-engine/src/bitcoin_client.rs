@@ -1,8 +1,8 @@
+use crate::network;
 use anyhow::Result;
 use bitcoin::{Address, BlockHash, Network, Transaction, Txid};
 use bitcoincore_rpc::{Client, RpcApi};
 use bitcoincore_rpc_json::{GetBlockchainInfoResult, GetNetworkInfoResult, GetWalletInfoResult};
-use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
@@ -89,7 +89,7 @@ impl BitcoinClient {
             .ok_or_else(|| anyhow::anyhow!("Bitcoin Core client not initialized"))?;
 
         // Parse address
-        let addr = Address::from_str(address)?.require_network(self.network)?;
+        let addr = network::parse_address(address, self.network)?;
 
         // Get unspent outputs for the address
         let utxos = client.list_unspent(Some(0), None, Some(&[addr]))?;
@@ -128,7 +128,7 @@ impl BitcoinClient {
             .ok_or_else(|| anyhow::anyhow!("Bitcoin Core client not initialized"))?;
 
         // Parse address
-        let addr = Address::from_str(address)?.require_network(self.network)?;
+        let addr = network::parse_address(address, self.network)?;
 
         // Send transaction
         let txid = client.send_to_address(&addr, amount, comment, None, false)?;
@@ -1,20 +1,33 @@
+use crate::network::{check_mainnet_allowed, default_network};
 use anyhow::Result;
 use bitcoin::Network;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::env;
 use std::path::PathBuf;
 
 /// Configuration for SatsConnect Lightning Engine
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LightningConfig {
     /// Bitcoin network to use
+    #[serde(deserialize_with = "deserialize_network")]
     pub network: Network,
+    /// Explicit opt-in required (together with the `mainnet` feature) to run on mainnet
+    pub allow_mainnet: bool,
     /// Data directory for Lightning node
     pub data_dir: PathBuf,
+    /// Where the node sources chain data from
+    pub chain_source: ChainSource,
     /// Esplora server URL for blockchain data
     pub esplora_url: String,
     /// Whether to use LDK gossip source
     pub use_ldk_gossip: bool,
+    /// Where the node learns the public graph from when gossip is enabled
+    pub gossip_source: GossipSource,
+    /// Rapid Gossip Sync server; defaults to LDK's public server for the network
+    pub rgs_server_url: Option<String>,
+    /// Age after which the last Rapid Gossip Sync snapshot counts as stale
+    pub rgs_max_snapshot_age_secs: u64,
     /// Whether to persist network graph
     pub persist_network_graph: bool,
     /// Bitcoin Core RPC configuration
@@ -23,8 +36,29 @@ pub struct LightningConfig {
     pub lightning_node: LightningNodeConfig,
 }
 
+/// Chain data backend for the Lightning node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainSource {
+    #[default]
+    Esplora,
+    Bitcoind,
+}
+
+/// Where the node learns the public channel graph from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GossipSource {
+    /// Gossip from connected peers
+    #[default]
+    P2p,
+    /// Snapshots from a Rapid Gossip Sync server; much lighter, for mobile
+    Rgs,
+}
+
 /// Bitcoin Core RPC configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BitcoinRpcConfig {
     pub url: String,
     pub username: String,
@@ -34,6 +68,7 @@ pub struct BitcoinRpcConfig {
 
 /// Lightning node specific configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LightningNodeConfig {
     /// Channel confirmation timeout in blocks
     pub channel_confirmation_timeout: u32,
@@ -52,10 +87,15 @@ pub struct LightningNodeConfig {
 impl Default for LightningConfig {
     fn default() -> Self {
         Self {
-            network: Network::Testnet,
+            network: default_network(),
+            allow_mainnet: false,
             data_dir: Self::default_data_dir(),
+            chain_source: ChainSource::default(),
             esplora_url: Self::default_esplora_url(),
             use_ldk_gossip: true,
+            gossip_source: GossipSource::default(),
+            rgs_server_url: None,
+            rgs_max_snapshot_age_secs: 2 * 24 * 3600,
             persist_network_graph: false,
             bitcoin_rpc: BitcoinRpcConfig::default(),
             lightning_node: LightningNodeConfig::default(),
@@ -91,44 +131,79 @@ impl LightningConfig {
     /// Create configuration from environment variables
     pub fn from_env() -> Result<Self> {
         let mut config = Self::default();
+        config.apply_env(|key| env::var(key).ok())?;
+        Ok(config)
+    }
 
-        // Override with environment variables if present
-        if let Ok(network_str) = env::var("BITCOIN_NETWORK") {
-            config.network = match network_str.to_lowercase().as_str() {
-                "mainnet" => Network::Bitcoin,
-                "testnet" => Network::Testnet,
-                "regtest" => Network::Regtest,
-                "signet" => Network::Signet,
-                _ => Network::Testnet,
+    /// Override fields with the environment variables documented in CONFIG.md
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(network) = var("BITCOIN_NETWORK") {
+            self.network = parse_network(&network)?;
+        }
+        if let Some(allow) = var("ALLOW_MAINNET") {
+            self.allow_mainnet = parse_var("ALLOW_MAINNET", &allow)?;
+        }
+        if let Some(data_dir) = var("DATA_DIR") {
+            self.data_dir = PathBuf::from(data_dir);
+        }
+        if let Some(chain_source) = var("CHAIN_SOURCE") {
+            self.chain_source = match chain_source.to_lowercase().as_str() {
+                "esplora" => ChainSource::Esplora,
+                "bitcoind" => ChainSource::Bitcoind,
+                other => return Err(anyhow::anyhow!("Unknown chain source: {}", other)),
             };
         }
-
-        if let Ok(data_dir) = env::var("DATA_DIR") {
-            config.data_dir = PathBuf::from(data_dir);
+        if let Some(esplora_url) = var("ESPLORA_URL") {
+            self.esplora_url = esplora_url;
         }
-
-        if let Ok(esplora_url) = env::var("ESPLORA_URL") {
-            config.esplora_url = esplora_url;
+        if let Some(gossip_source) = var("GOSSIP_SOURCE") {
+            self.gossip_source = match gossip_source.to_lowercase().as_str() {
+                "p2p" => GossipSource::P2p,
+                "rgs" => GossipSource::Rgs,
+                other => return Err(anyhow::anyhow!("Unknown gossip source: {}", other)),
+            };
+        }
+        if let Some(rgs_server_url) = var("RGS_SERVER_URL") {
+            self.rgs_server_url = Some(rgs_server_url);
         }
 
         // Bitcoin RPC configuration
-        if let Ok(rpc_url) = env::var("BITCOIN_RPC_URL") {
-            config.bitcoin_rpc.url = rpc_url;
+        if let Some(rpc_url) = var("BITCOIN_RPC_URL") {
+            self.bitcoin_rpc.url = rpc_url;
         }
-
-        if let Ok(rpc_user) = env::var("BITCOIN_RPC_USER") {
-            config.bitcoin_rpc.username = rpc_user;
+        if let Some(rpc_user) = var("BITCOIN_RPC_USER") {
+            self.bitcoin_rpc.username = rpc_user;
         }
-
-        if let Ok(rpc_pass) = env::var("BITCOIN_RPC_PASS") {
-            config.bitcoin_rpc.password = rpc_pass;
+        if let Some(rpc_pass) = var("BITCOIN_RPC_PASS") {
+            self.bitcoin_rpc.password = rpc_pass;
+        }
+        if let Some(wallet_name) = var("BITCOIN_WALLET_NAME") {
+            self.bitcoin_rpc.wallet_name = Some(wallet_name);
         }
 
-        if let Ok(wallet_name) = env::var("BITCOIN_WALLET_NAME") {
-            config.bitcoin_rpc.wallet_name = Some(wallet_name);
+        // Lightning node configuration
+        let node = &mut self.lightning_node;
+        if let Some(v) = var("LIGHTNING_CHANNEL_CONFIRMATION_TIMEOUT") {
+            node.channel_confirmation_timeout =
+                parse_var("LIGHTNING_CHANNEL_CONFIRMATION_TIMEOUT", &v)?;
+        }
+        if let Some(v) = var("LIGHTNING_MAX_CHANNEL_SIZE") {
+            node.max_channel_size = parse_var("LIGHTNING_MAX_CHANNEL_SIZE", &v)?;
+        }
+        if let Some(v) = var("LIGHTNING_MIN_CHANNEL_SIZE") {
+            node.min_channel_size = parse_var("LIGHTNING_MIN_CHANNEL_SIZE", &v)?;
+        }
+        if let Some(v) = var("LIGHTNING_CHANNEL_RESERVE") {
+            node.channel_reserve = parse_var("LIGHTNING_CHANNEL_RESERVE", &v)?;
+        }
+        if let Some(v) = var("LIGHTNING_ANNOUNCE_CHANNELS") {
+            node.announce_channels = parse_var("LIGHTNING_ANNOUNCE_CHANNELS", &v)?;
+        }
+        if let Some(v) = var("LIGHTNING_ACCEPT_INCOMING_CHANNELS") {
+            node.accept_incoming_channels = parse_var("LIGHTNING_ACCEPT_INCOMING_CHANNELS", &v)?;
         }
 
-        Ok(config)
+        Ok(())
     }
 
     /// Get the default data directory
@@ -142,21 +217,65 @@ impl LightningConfig {
 
     /// Get the default Esplora URL based on network
     fn default_esplora_url() -> String {
-        match env::var("BITCOIN_NETWORK")
-            .unwrap_or_default()
-            .to_lowercase()
-            .as_str()
-        {
-            "mainnet" => "https://blockstream.info/api".to_string(),
-            "testnet" => "https://blockstream.info/testnet/api".to_string(),
-            "regtest" => "http://127.0.0.1:3000".to_string(),
-            "signet" => "https://blockstream.info/signet/api".to_string(),
-            _ => "https://blockstream.info/testnet/api".to_string(),
+        let network = env::var("BITCOIN_NETWORK")
+            .ok()
+            .and_then(|n| parse_network(&n).ok())
+            .unwrap_or_else(default_network);
+        Self::esplora_url_for(network).to_string()
+    }
+
+    /// Default Esplora URL for a network
+    pub fn esplora_url_for(network: Network) -> &'static str {
+        match network {
+            Network::Bitcoin => "https://blockstream.info/api",
+            Network::Regtest => "http://127.0.0.1:3000",
+            Network::Signet => "https://blockstream.info/signet/api",
+            _ => "https://blockstream.info/testnet/api",
+        }
+    }
+
+    /// Default Bitcoin Core RPC URL for a network
+    pub fn bitcoin_rpc_url_for(network: Network) -> &'static str {
+        match network {
+            Network::Bitcoin => "http://127.0.0.1:8332",
+            Network::Regtest => "http://127.0.0.1:18443",
+            Network::Signet => "http://127.0.0.1:38332",
+            Network::Testnet4 => "http://127.0.0.1:48332",
+            _ => "http://127.0.0.1:18332",
+        }
+    }
+
+    /// LDK's public Rapid Gossip Sync server for a network, if it runs one
+    pub fn rgs_server_url_for(network: Network) -> Option<&'static str> {
+        match network {
+            Network::Bitcoin => Some("https://rapidsync.lightningdevkit.org/snapshot"),
+            Network::Testnet => Some("https://rapidsync.lightningdevkit.org/testnet/snapshot"),
+            _ => None,
         }
     }
 
+    /// The Rapid Gossip Sync server to use, configured or the network's default
+    pub fn rgs_server_url(&self) -> Result<String> {
+        self.rgs_server_url
+            .clone()
+            .or_else(|| Self::rgs_server_url_for(self.network).map(str::to_string))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No public Rapid Gossip Sync server for {}; set rgs_server_url",
+                    self.network
+                )
+            })
+    }
+
+    /// Whether the node syncs its graph from a Rapid Gossip Sync server
+    pub fn uses_rgs(&self) -> bool {
+        self.use_ldk_gossip && self.gossip_source == GossipSource::Rgs
+    }
+
     /// Validate the configuration
     pub fn validate(&self) -> Result<()> {
+        check_mainnet_allowed(self.network, self.allow_mainnet)?;
+
         // Ensure data directory exists
         std::fs::create_dir_all(&self.data_dir)?;
 
@@ -169,7 +288,7 @@ impl LightningConfig {
                     ));
                 }
             }
-            Network::Testnet => {
+            Network::Testnet | Network::Testnet4 => {
                 if !self.esplora_url.contains("testnet") && !self.esplora_url.contains("regtest") {
                     return Err(anyhow::anyhow!(
                         "Testnet configuration with mainnet Esplora URL"
@@ -194,6 +313,17 @@ impl LightningConfig {
             }
         }
 
+        if self.chain_source == ChainSource::Bitcoind {
+            self.bitcoin_rpc.host_port()?;
+        }
+
+        if self.uses_rgs() {
+            let url = self.rgs_server_url()?;
+            reqwest::Url::parse(&url).map_err(|e| {
+                anyhow::anyhow!("Invalid Rapid Gossip Sync server URL {}: {}", url, e)
+            })?;
+        }
+
         // Validate Lightning node configuration
         if self.lightning_node.min_channel_size >= self.lightning_node.max_channel_size {
             return Err(anyhow::anyhow!(
@@ -226,6 +356,44 @@ impl LightningConfig {
     }
 }
 
+impl BitcoinRpcConfig {
+    /// Host and port of the RPC endpoint, as LDK's bitcoind chain source expects them
+    pub fn host_port(&self) -> Result<(String, u16)> {
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|e| anyhow::anyhow!("Invalid Bitcoin RPC URL {}: {}", self.url, e))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("Bitcoin RPC URL {} has no host", self.url))?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| anyhow::anyhow!("Bitcoin RPC URL {} has no port", self.url))?;
+        Ok((host.to_string(), port))
+    }
+}
+
+/// Parse a network name, accepting `mainnet` as an alias for `bitcoin`
+pub fn parse_network(name: &str) -> Result<Network> {
+    match name.to_lowercase().as_str() {
+        "mainnet" | "bitcoin" => Ok(Network::Bitcoin),
+        "testnet" => Ok(Network::Testnet),
+        "testnet4" => Ok(Network::Testnet4),
+        "signet" => Ok(Network::Signet),
+        "regtest" => Ok(Network::Regtest),
+        other => Err(anyhow::anyhow!("Unknown network: {}", other)),
+    }
+}
+
+fn deserialize_network<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Network, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    parse_network(&name).map_err(serde::de::Error::custom)
+}
+
+fn parse_var<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid value for {}: {}", key, value))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,7 +402,8 @@ mod tests {
     #[test]
     fn test_default_config() {
         let config = LightningConfig::default();
-        assert_eq!(config.network, Network::Testnet);
+        assert_eq!(config.network, default_network());
+        assert!(!config.allow_mainnet);
         assert!(config.use_ldk_gossip);
         assert!(!config.persist_network_graph);
     }
@@ -269,4 +438,54 @@ mod tests {
         let loaded_config = LightningConfig::load_from_file(&config_path).unwrap();
         assert_eq!(config.network, loaded_config.network);
     }
+
+    #[test]
+    fn test_apply_env() {
+        let vars = [
+            ("BITCOIN_NETWORK", "mainnet"),
+            ("CHAIN_SOURCE", "bitcoind"),
+            ("LIGHTNING_MIN_CHANNEL_SIZE", "200000"),
+        ];
+        let mut config = LightningConfig::default();
+        config
+            .apply_env(|key| {
+                vars.iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string())
+            })
+            .unwrap();
+        assert_eq!(config.network, Network::Bitcoin);
+        assert_eq!(config.chain_source, ChainSource::Bitcoind);
+        assert_eq!(config.lightning_node.min_channel_size, 200_000);
+
+        let err = config.apply_env(|key| (key == "BITCOIN_NETWORK").then(|| "moonnet".to_string()));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_rgs_gossip_source() {
+        let mut config = LightningConfig {
+            network: Network::Regtest,
+            esplora_url: LightningConfig::esplora_url_for(Network::Regtest).to_string(),
+            ..LightningConfig::default()
+        };
+        config
+            .apply_env(|key| (key == "GOSSIP_SOURCE").then(|| "rgs".to_string()))
+            .unwrap();
+        assert!(config.uses_rgs());
+        // Regtest has no public RGS server
+        assert!(config.validate().is_err());
+
+        config.rgs_server_url = Some("http://127.0.0.1:8011/snapshot".to_string());
+        assert!(config.validate().is_ok());
+        config.rgs_server_url = Some("not a url".to_string());
+        assert!(config.validate().is_err());
+
+        config.network = Network::Bitcoin;
+        config.rgs_server_url = None;
+        assert_eq!(
+            config.rgs_server_url().unwrap(),
+            "https://rapidsync.lightningdevkit.org/snapshot"
+        );
+    }
 }
@@ -0,0 +1,1175 @@
+use crate::config::{parse_network, ChainSource, LightningConfig};
+use crate::lightning::{ChannelConfig, LiquidityConfig, PeerConfig};
+use anyhow::Result;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::Network;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::warn;
+
+/// Placeholder printed instead of secrets
+const REDACTED: &str = "<redacted>";
+
+/// Bitcoin Core RPC password shipped in the defaults and examples
+const DEFAULT_RPC_PASSWORD: &str = "password";
+
+/// Default time in-flight work gets to drain on shutdown
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+/// Command line flags for `engine_server`. They override the config file and
+/// environment; secrets are deliberately only accepted from those two sources.
+#[derive(Debug, Default, Parser)]
+#[command(
+    name = "engine_server",
+    about = "SatsConnect Lightning engine gRPC server"
+)]
+pub struct EngineArgs {
+    /// TOML config file (also `SATSCONNECT_CONFIG`)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// gRPC listen address
+    #[arg(long)]
+    pub listen: Option<SocketAddr>,
+    /// HTTP/JSON gateway listen address
+    #[arg(long)]
+    pub http_listen: Option<SocketAddr>,
+    /// mainnet, testnet, testnet4, signet or regtest
+    #[arg(long, value_parser = network_arg)]
+    pub network: Option<Network>,
+    /// Opt in to mainnet; the binary must also be built with the `mainnet` feature
+    #[arg(long)]
+    pub allow_mainnet: bool,
+    /// Directory for node, wallet and payment state
+    #[arg(long)]
+    pub data_dir: Option<PathBuf>,
+    /// Chain data backend
+    #[arg(long, value_enum)]
+    pub chain_source: Option<ChainSource>,
+    #[arg(long)]
+    pub esplora_url: Option<String>,
+    /// Bitcoin Core RPC URL; the password comes from the config file or `BITCOIN_RPC_PASS`
+    #[arg(long)]
+    pub bitcoind_rpc_url: Option<String>,
+    #[arg(long)]
+    pub bitcoind_rpc_user: Option<String>,
+    /// LSP as `node_id@host:port`; repeat for several, replaces configured LSPs
+    #[arg(long = "lsp", value_name = "NODE_ID@HOST:PORT")]
+    pub lsps: Vec<LspEndpoint>,
+    /// Public base URL for LNURL-withdraw refund links
+    #[arg(long)]
+    pub lnurl_base_url: Option<String>,
+    /// Serve gRPC over TLS
+    #[arg(long)]
+    pub tls: bool,
+    /// PEM certificate chain; a self-signed one is generated when unset
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key for `--tls-cert`
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// PEM CA bundle; only clients with a certificate chaining to it are accepted
+    #[arg(long)]
+    pub tls_client_ca: Option<PathBuf>,
+    /// Accept API requests without a bearer token (local development only)
+    #[arg(long)]
+    pub disable_auth: bool,
+    /// Print the effective configuration and exit
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+fn network_arg(name: &str) -> std::result::Result<Network, String> {
+    parse_network(name).map_err(|e| e.to_string())
+}
+
+/// Effective configuration of `engine_server`, layered from defaults, a TOML
+/// file, environment variables and command line flags (later wins)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    pub server: ServerConfig,
+    pub lightning: LightningConfig,
+    /// Limits on channels opened through `ChannelService`
+    pub channels: ChannelConfig,
+    /// Reconnecting peers added through `PeerService`
+    pub peers: PeerConfig,
+    /// Rebalancing reports and automatic channel opens
+    pub liquidity: LiquidityConfig,
+    pub lsps: Vec<LspEndpoint>,
+    pub fiat: FiatConfig,
+}
+
+/// gRPC and HTTP/JSON server settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub listen_addr: SocketAddr,
+    /// Address of the HTTP/JSON gateway, which also serves LNURL endpoints
+    pub http_listen_addr: SocketAddr,
+    /// Browser origins allowed to call the HTTP and gRPC-web APIs; empty allows any
+    pub cors_allowed_origins: Vec<String>,
+    /// Public base URL of the HTTP gateway, which LNURL-withdraw refund links
+    /// point at; LNURL refunds are refused until it is set
+    pub lnurl_base_url: Option<String>,
+    /// Seconds in-flight calls get to finish on shutdown before the engine
+    /// stops waiting and exits with an error
+    pub shutdown_timeout_secs: u64,
+    pub tls: TlsConfig,
+    pub auth: ApiAuthConfig,
+    pub rate_limit: RateLimitConfig,
+}
+
+/// Bearer token authentication for the gRPC and HTTP APIs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiAuthConfig {
+    pub enabled: bool,
+    /// Key tokens are signed with; defaults to `<data_dir>/auth/root.key`,
+    /// created on first start together with `admin.token` next to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_key_path: Option<PathBuf>,
+}
+
+impl Default for ApiAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            root_key_path: None,
+        }
+    }
+}
+
+impl ApiAuthConfig {
+    /// Where the root key lives for a node using `data_dir`
+    pub fn root_key_path(&self, data_dir: &Path) -> PathBuf {
+        self.root_key_path
+            .clone()
+            .unwrap_or_else(|| data_dir.join("auth").join("root.key"))
+    }
+}
+
+/// Token bucket limits on API calls, applied separately to each API token,
+/// client IP and wallet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Limit for RPCs without a built-in or configured one
+    pub default: RateLimit,
+    /// Limits by method name, e.g. `SendPayment`; these replace the built-in
+    /// limits for the RPCs they name
+    pub rpc: BTreeMap<String, RateLimit>,
+}
+
+/// A token bucket: `burst` calls back to back, refilled at `per_minute`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+/// Built-in limits for RPCs that create wallets, move funds or touch channels
+/// and peers
+const DEFAULT_RPC_LIMITS: &[(&str, RateLimit)] = &[
+    (
+        "CreateWallet",
+        RateLimit {
+            burst: 3,
+            per_minute: 6,
+        },
+    ),
+    (
+        "SendPayment",
+        RateLimit {
+            burst: 10,
+            per_minute: 30,
+        },
+    ),
+    (
+        "ProcessPayment",
+        RateLimit {
+            burst: 10,
+            per_minute: 30,
+        },
+    ),
+    (
+        "ProcessRefund",
+        RateLimit {
+            burst: 5,
+            per_minute: 20,
+        },
+    ),
+    (
+        "OpenChannel",
+        RateLimit {
+            burst: 5,
+            per_minute: 10,
+        },
+    ),
+    (
+        "CloseChannel",
+        RateLimit {
+            burst: 5,
+            per_minute: 10,
+        },
+    ),
+    (
+        "ConnectPeer",
+        RateLimit {
+            burst: 5,
+            per_minute: 20,
+        },
+    ),
+];
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default: RateLimit {
+                burst: 50,
+                per_minute: 600,
+            },
+            rpc: BTreeMap::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Limit applied to calls of `method`
+    pub fn limit(&self, method: &str) -> RateLimit {
+        self.rpc
+            .get(method)
+            .or_else(|| {
+                DEFAULT_RPC_LIMITS
+                    .iter()
+                    .find(|(name, _)| *name == method)
+                    .map(|(_, limit)| limit)
+            })
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let limits = std::iter::once(("default", &self.default))
+            .chain(self.rpc.iter().map(|(name, limit)| (name.as_str(), limit)));
+        for (name, limit) in limits {
+            if limit.burst == 0 || limit.per_minute == 0 {
+                return Err(anyhow::anyhow!(
+                    "Rate limit for {} needs a burst and per_minute above zero",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// TLS for the gRPC server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM certificate chain; when unset a self-signed certificate is
+    /// generated under `<data_dir>/tls` on first start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert_path: Option<PathBuf>,
+    /// PEM private key for `cert_path`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_path: Option<PathBuf>,
+    /// PEM CA bundle; when set the server requires client certificates
+    /// chaining to it (mutual TLS)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ca_path: Option<PathBuf>,
+    /// Extra DNS names and IPs for the self-signed certificate
+    pub self_signed_names: Vec<String>,
+}
+
+/// A Lightning Service Provider the node can buy liquidity from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LspEndpoint {
+    #[serde(default)]
+    pub name: String,
+    /// Hex-encoded node public key
+    pub node_id: String,
+    /// `host:port` the LSP accepts peer connections on
+    pub address: String,
+    /// Access token some LSPs require
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// HTTP endpoint taking LSPS1 JSON-RPC calls; channels can only be
+    /// bought from LSPs that have one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lsps1_url: Option<String>,
+    /// HTTP endpoint taking LSPS2 JSON-RPC calls, used to quote just-in-time
+    /// channel fees; the node opens such channels only with this LSP, so at
+    /// most one may have it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lsps2_url: Option<String>,
+}
+
+/// Credentials for the mobile money providers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FiatConfig {
+    pub mpesa: MpesaConfig,
+    pub airtel: AirtelConfig,
+    pub mtn: MtnConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MpesaConfig {
+    pub consumer_key: String,
+    pub consumer_secret: String,
+    pub business_short_code: String,
+    pub passkey: String,
+    pub callback_url: String,
+    /// "sandbox" or "production"
+    pub environment: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AirtelConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub environment: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MtnConfig {
+    pub subscription_key: String,
+    pub environment: String,
+    pub country: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: SocketAddr::from(([127, 0, 0, 1], 50051)),
+            http_listen_addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            cors_allowed_origins: Vec::new(),
+            lnurl_base_url: None,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            tls: TlsConfig::default(),
+            auth: ApiAuthConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// How long shutdown waits for in-flight calls
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+impl Default for MpesaConfig {
+    fn default() -> Self {
+        Self {
+            consumer_key: "test_key".to_string(),
+            consumer_secret: "test_secret".to_string(),
+            business_short_code: "174379".to_string(),
+            passkey: "test_passkey".to_string(),
+            callback_url: "https://api.satsconnect.com/webhooks/mpesa".to_string(),
+            environment: "sandbox".to_string(),
+        }
+    }
+}
+
+impl Default for AirtelConfig {
+    fn default() -> Self {
+        Self {
+            client_id: "test_client_id".to_string(),
+            client_secret: "test_client_secret".to_string(),
+            environment: "sandbox".to_string(),
+        }
+    }
+}
+
+impl Default for MtnConfig {
+    fn default() -> Self {
+        Self {
+            subscription_key: "test_subscription_key".to_string(),
+            environment: "sandbox".to_string(),
+            country: "UG".to_string(),
+        }
+    }
+}
+
+impl FromStr for LspEndpoint {
+    type Err = anyhow::Error;
+
+    /// Parse `node_id@host:port`
+    fn from_str(s: &str) -> Result<Self> {
+        let (node_id, address) = s
+            .split_once('@')
+            .ok_or_else(|| anyhow::anyhow!("LSP must be node_id@host:port, got {}", s))?;
+        Ok(Self {
+            name: format!("lsp-{}", node_id.get(..8).unwrap_or(node_id)),
+            node_id: node_id.to_string(),
+            address: address.to_string(),
+            token: None,
+            lsps1_url: None,
+            lsps2_url: None,
+        })
+    }
+}
+
+impl LspEndpoint {
+    fn validate(&self) -> Result<()> {
+        PublicKey::from_str(&self.node_id)
+            .map_err(|_| anyhow::anyhow!("LSP {} has an invalid node id", self.name))?;
+
+        let port = self
+            .address
+            .rsplit_once(':')
+            .map(|(_, port)| port.parse::<u16>());
+        if !matches!(port, Some(Ok(_))) {
+            return Err(anyhow::anyhow!(
+                "LSP {} address must be host:port, got {}",
+                self.name,
+                self.address
+            ));
+        }
+
+        for (field, url) in [
+            ("lsps1_url", &self.lsps1_url),
+            ("lsps2_url", &self.lsps2_url),
+        ] {
+            if let Some(url) = url {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return Err(anyhow::anyhow!(
+                        "LSP {} {} must be an http(s) URL, got {}",
+                        self.name,
+                        field,
+                        url
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TlsConfig {
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(enabled) = var("TLS_ENABLED") {
+            self.enabled = enabled
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid TLS_ENABLED: {}", enabled))?;
+        }
+        if let Some(path) = var("TLS_CERT_PATH") {
+            self.cert_path = Some(PathBuf::from(path));
+        }
+        if let Some(path) = var("TLS_KEY_PATH") {
+            self.key_path = Some(PathBuf::from(path));
+        }
+        if let Some(path) = var("TLS_CLIENT_CA_PATH") {
+            self.client_ca_path = Some(PathBuf::from(path));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.cert_path.is_some() != self.key_path.is_some() {
+            return Err(anyhow::anyhow!(
+                "TLS certificate and key must be configured together"
+            ));
+        }
+        if !self.enabled && (self.cert_path.is_some() || self.client_ca_path.is_some()) {
+            return Err(anyhow::anyhow!(
+                "TLS certificates are configured but TLS is not enabled"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl FiatConfig {
+    /// Provider settings from the environment, falling back to sandbox defaults
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        config.apply_env(|key| env::var(key).ok());
+        config
+    }
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        let set = |field: &mut String, key: &str| {
+            if let Some(value) = var(key) {
+                *field = value;
+            }
+        };
+
+        set(&mut self.mpesa.consumer_key, "MPESA_CONSUMER_KEY");
+        set(&mut self.mpesa.consumer_secret, "MPESA_CONSUMER_SECRET");
+        set(
+            &mut self.mpesa.business_short_code,
+            "MPESA_BUSINESS_SHORT_CODE",
+        );
+        set(&mut self.mpesa.passkey, "MPESA_PASSKEY");
+        set(&mut self.mpesa.callback_url, "MPESA_CALLBACK_URL");
+        set(&mut self.mpesa.environment, "MPESA_ENVIRONMENT");
+        set(&mut self.airtel.client_id, "AIRTEL_CLIENT_ID");
+        set(&mut self.airtel.client_secret, "AIRTEL_CLIENT_SECRET");
+        set(&mut self.airtel.environment, "AIRTEL_ENVIRONMENT");
+        set(&mut self.mtn.subscription_key, "MTN_SUBSCRIPTION_KEY");
+        set(&mut self.mtn.environment, "MTN_ENVIRONMENT");
+        set(&mut self.mtn.country, "MTN_COUNTRY");
+    }
+
+    /// Providers still running against their sandbox
+    fn sandbox_providers(&self) -> Vec<&'static str> {
+        [
+            ("M-Pesa", &self.mpesa.environment),
+            ("Airtel Money", &self.airtel.environment),
+            ("MTN MoMo", &self.mtn.environment),
+        ]
+        .into_iter()
+        .filter(|(_, environment)| *environment != "production")
+        .map(|(provider, _)| provider)
+        .collect()
+    }
+
+    fn validate(&self) -> Result<()> {
+        let defaults = Self::default();
+        let checks = [
+            (
+                "M-Pesa",
+                &self.mpesa.environment,
+                self.mpesa.consumer_secret == defaults.mpesa.consumer_secret
+                    || self.mpesa.passkey == defaults.mpesa.passkey,
+            ),
+            (
+                "Airtel Money",
+                &self.airtel.environment,
+                self.airtel.client_secret == defaults.airtel.client_secret,
+            ),
+            (
+                "MTN MoMo",
+                &self.mtn.environment,
+                self.mtn.subscription_key == defaults.mtn.subscription_key,
+            ),
+        ];
+
+        for (provider, environment, default_secret) in checks {
+            if environment == "production" && default_secret {
+                return Err(anyhow::anyhow!(
+                    "{} is set to production with sandbox credentials",
+                    provider
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EngineConfig {
+    /// Load the configuration `engine_server` runs with and validate it
+    pub fn load(args: &EngineArgs) -> Result<Self> {
+        let config = Self::load_layers(args, |key| env::var(key).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn load_layers(args: &EngineArgs, var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        // Network-dependent URLs follow the final network unless some layer set them
+        let mut esplora_set = var("ESPLORA_URL").is_some() || args.esplora_url.is_some();
+        let mut rpc_url_set = var("BITCOIN_RPC_URL").is_some() || args.bitcoind_rpc_url.is_some();
+
+        let path = args
+            .config
+            .clone()
+            .or_else(|| var("SATSCONNECT_CONFIG").map(PathBuf::from));
+        let mut config = match path {
+            Some(path) => {
+                let content = std::fs::read_to_string(&path).map_err(|e| {
+                    anyhow::anyhow!("Failed to read config file {}: {}", path.display(), e)
+                })?;
+                let table: toml::Table = toml::from_str(&content)?;
+                let lightning = table.get("lightning");
+                esplora_set |= lightning.and_then(|l| l.get("esplora_url")).is_some();
+                rpc_url_set |= lightning
+                    .and_then(|l| l.get("bitcoin_rpc"))
+                    .and_then(|rpc| rpc.get("url"))
+                    .is_some();
+                table.try_into()?
+            }
+            None => Self::default(),
+        };
+
+        // Environment
+        if let Some(addr) = var("GRPC_SERVER_ADDRESS") {
+            config.server.listen_addr = addr
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid GRPC_SERVER_ADDRESS: {}", addr))?;
+        }
+        if let Some(addr) = var("HTTP_SERVER_ADDRESS") {
+            config.server.http_listen_addr = addr
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid HTTP_SERVER_ADDRESS: {}", addr))?;
+        }
+        if let Some(origins) = var("CORS_ALLOWED_ORIGINS") {
+            config.server.cors_allowed_origins = origins
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect();
+        }
+        if let Some(url) = var("LNURL_BASE_URL") {
+            config.server.lnurl_base_url = Some(url);
+        }
+        config.server.tls.apply_env(&var)?;
+        if let Some(enabled) = var("AUTH_ENABLED") {
+            config.server.auth.enabled = enabled
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid AUTH_ENABLED: {}", enabled))?;
+        }
+        if let Some(enabled) = var("RATE_LIMIT_ENABLED") {
+            config.server.rate_limit.enabled = enabled
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid RATE_LIMIT_ENABLED: {}", enabled))?;
+        }
+        if let Some(secs) = var("SHUTDOWN_TIMEOUT_SECS") {
+            config.server.shutdown_timeout_secs = secs
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid SHUTDOWN_TIMEOUT_SECS: {}", secs))?;
+        }
+        config.lightning.apply_env(&var)?;
+        if let Some(lsps) = var("LSP_NODES") {
+            config.lsps = lsps
+                .split(',')
+                .filter(|lsp| !lsp.trim().is_empty())
+                .map(|lsp| lsp.trim().parse())
+                .collect::<Result<_>>()?;
+        }
+        config.fiat.apply_env(&var);
+
+        // Command line
+        if let Some(addr) = args.listen {
+            config.server.listen_addr = addr;
+        }
+        if let Some(addr) = args.http_listen {
+            config.server.http_listen_addr = addr;
+        }
+        if let Some(url) = &args.lnurl_base_url {
+            config.server.lnurl_base_url = Some(url.clone());
+        }
+        if args.tls {
+            config.server.tls.enabled = true;
+        }
+        if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+            config.server.tls.cert_path = Some(cert.clone());
+            config.server.tls.key_path = Some(key.clone());
+        }
+        if let Some(ca) = &args.tls_client_ca {
+            config.server.tls.client_ca_path = Some(ca.clone());
+        }
+        if args.disable_auth {
+            config.server.auth.enabled = false;
+        }
+        if let Some(network) = args.network {
+            config.lightning.network = network;
+        }
+        if args.allow_mainnet {
+            config.lightning.allow_mainnet = true;
+        }
+        if let Some(data_dir) = &args.data_dir {
+            config.lightning.data_dir = data_dir.clone();
+        }
+        if let Some(chain_source) = args.chain_source {
+            config.lightning.chain_source = chain_source;
+        }
+        if let Some(url) = &args.esplora_url {
+            config.lightning.esplora_url = url.clone();
+        }
+        if let Some(url) = &args.bitcoind_rpc_url {
+            config.lightning.bitcoin_rpc.url = url.clone();
+        }
+        if let Some(user) = &args.bitcoind_rpc_user {
+            config.lightning.bitcoin_rpc.username = user.clone();
+        }
+        if !args.lsps.is_empty() {
+            config.lsps = args.lsps.clone();
+        }
+
+        let network = config.lightning.network;
+        if !esplora_set {
+            config.lightning.esplora_url = LightningConfig::esplora_url_for(network).to_string();
+        }
+        if !rpc_url_set {
+            config.lightning.bitcoin_rpc.url =
+                LightningConfig::bitcoin_rpc_url_for(network).to_string();
+        }
+
+        Ok(config)
+    }
+
+    /// Refuse combinations the engine must not start with
+    pub fn validate(&self) -> Result<()> {
+        if self.lightning.network == Network::Bitcoin {
+            self.check_mainnet_secrets()?;
+        }
+
+        self.lightning.validate()?;
+
+        let mut names = HashSet::new();
+        for lsp in &self.lsps {
+            lsp.validate()?;
+            if !names.insert(&lsp.name) {
+                return Err(anyhow::anyhow!("Duplicate LSP name: {}", lsp.name));
+            }
+        }
+        let jit_lsps = self.lsps.iter().filter(|lsp| lsp.lsps2_url.is_some());
+        if jit_lsps.count() > 1 {
+            return Err(anyhow::anyhow!(
+                "Only one LSP can have an lsps2_url; the node takes just-in-time channels from a single LSP"
+            ));
+        }
+
+        if let Some(url) = &self.server.lnurl_base_url {
+            reqwest::Url::parse(url)
+                .map_err(|e| anyhow::anyhow!("Invalid LNURL base URL: {}", e))?;
+        }
+        if self.server.http_listen_addr == self.server.listen_addr {
+            return Err(anyhow::anyhow!(
+                "gRPC and HTTP servers cannot both listen on {}",
+                self.server.listen_addr
+            ));
+        }
+        self.server.tls.validate()?;
+        self.server.rate_limit.validate()?;
+        self.channels.validate()?;
+        self.peers.validate()?;
+        self.liquidity.validate()?;
+        if self.liquidity.enabled {
+            self.channels
+                .check_capacity(self.liquidity.channel_size_sats)
+                .map_err(|e| anyhow::anyhow!("Liquidity manager channel size: {}", e))?;
+        }
+        if self.server.shutdown_timeout_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "Shutdown timeout must be at least 1 second"
+            ));
+        }
+
+        self.fiat.validate()
+    }
+
+    /// Secrets still at the values shipped in the defaults and examples must
+    /// not guard real funds
+    fn check_mainnet_secrets(&self) -> Result<()> {
+        if !self.server.auth.enabled {
+            return Err(anyhow::anyhow!(
+                "Refusing to run on mainnet with API authentication disabled"
+            ));
+        }
+
+        // The RPC password only guards anything when bitcoind is the chain source
+        let rpc = &self.lightning.bitcoin_rpc;
+        if self.lightning.chain_source == ChainSource::Bitcoind
+            && (rpc.password.is_empty() || rpc.password == DEFAULT_RPC_PASSWORD)
+        {
+            return Err(anyhow::anyhow!(
+                "Refusing to run on mainnet with the default Bitcoin RPC password; set BITCOIN_RPC_PASS"
+            ));
+        }
+
+        for provider in self.fiat.sandbox_providers() {
+            warn!(
+                "{} still uses sandbox settings on mainnet; fiat payouts will not settle",
+                provider
+            );
+        }
+        Ok(())
+    }
+
+    /// The LSP just-in-time channels come from, if any
+    pub fn jit_lsp(&self) -> Option<&LspEndpoint> {
+        self.lsps.iter().find(|lsp| lsp.lsps2_url.is_some())
+    }
+
+    /// Copy of the configuration with every secret replaced, safe to log
+    pub fn redacted(&self) -> Self {
+        fn redact(secret: &mut String) {
+            if !secret.is_empty() {
+                *secret = REDACTED.to_string();
+            }
+        }
+
+        let mut config = self.clone();
+        redact(&mut config.lightning.bitcoin_rpc.password);
+        for lsp in &mut config.lsps {
+            if let Some(token) = &mut lsp.token {
+                redact(token);
+            }
+        }
+        redact(&mut config.fiat.mpesa.consumer_key);
+        redact(&mut config.fiat.mpesa.consumer_secret);
+        redact(&mut config.fiat.mpesa.passkey);
+        redact(&mut config.fiat.airtel.client_secret);
+        redact(&mut config.fiat.mtn.subscription_key);
+        config
+    }
+}
+
+/// Renders the redacted configuration as TOML
+impl fmt::Display for EngineConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered = toml::to_string_pretty(&self.redacted()).map_err(|_| fmt::Error)?;
+        f.write_str(&rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const LSP_NODE_ID: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+
+    fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    fn config_file(name: &str, content: &str) -> PathBuf {
+        let path = env::temp_dir().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_layer_precedence() {
+        let path = config_file(
+            "satsconnect_layer_test.toml",
+            r#"
+            [server]
+            listen_addr = "0.0.0.0:50051"
+
+            [lightning]
+            network = "signet"
+            data_dir = "/var/lib/satsconnect"
+
+            [[lsps]]
+            name = "file-lsp"
+            node_id = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619"
+            address = "lsp.example.com:9735"
+            lsps1_url = "https://lsp.example.com/lsps1"
+            "#,
+        );
+        let args = EngineArgs {
+            config: Some(path.clone()),
+            listen: Some("127.0.0.1:6000".parse().unwrap()),
+            ..Default::default()
+        };
+        let vars = vars(&[
+            ("GRPC_SERVER_ADDRESS", "127.0.0.1:5000"),
+            ("HTTP_SERVER_ADDRESS", "127.0.0.1:5080"),
+            ("BITCOIN_NETWORK", "regtest"),
+        ]);
+
+        let config = EngineConfig::load_layers(&args, vars).unwrap();
+        // CLI beats env, env beats file, file beats defaults
+        assert_eq!(config.server.listen_addr.port(), 6000);
+        assert_eq!(config.server.http_listen_addr.port(), 5080);
+        assert_eq!(config.lightning.network, Network::Regtest);
+        assert_eq!(
+            config.lightning.data_dir,
+            PathBuf::from("/var/lib/satsconnect")
+        );
+        assert_eq!(config.lsps[0].name, "file-lsp");
+        assert_eq!(
+            config.lsps[0].lsps1_url.as_deref(),
+            Some("https://lsp.example.com/lsps1")
+        );
+        // URLs nobody set follow the final network
+        assert_eq!(config.lightning.esplora_url, "http://127.0.0.1:3000");
+        assert_eq!(config.lightning.bitcoin_rpc.url, "http://127.0.0.1:18443");
+        // No public URL, so no LNURL refunds
+        assert_eq!(config.server.lnurl_base_url, None);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_mainnet_with_default_rpc_password_is_refused() {
+        let args = EngineArgs {
+            network: Some(Network::Bitcoin),
+            allow_mainnet: true,
+            data_dir: Some(env::temp_dir().join("satsconnect_mainnet_config_test")),
+            ..Default::default()
+        };
+
+        let config =
+            EngineConfig::load_layers(&args, vars(&[("CHAIN_SOURCE", "bitcoind")])).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("default Bitcoin RPC password"));
+
+        // With a real password only the `mainnet` feature decides
+        let config = EngineConfig::load_layers(
+            &args,
+            vars(&[
+                ("CHAIN_SOURCE", "bitcoind"),
+                ("BITCOIN_RPC_PASS", "hunter2-long"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "mainnet"));
+
+        // Esplora never uses the RPC password
+        let config = EngineConfig::load_layers(&args, vars(&[])).unwrap();
+        assert_eq!(config.lightning.esplora_url, "https://blockstream.info/api");
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "mainnet"));
+    }
+
+    #[test]
+    fn test_mainnet_requires_opt_in() {
+        let args = EngineArgs {
+            network: Some(Network::Bitcoin),
+            data_dir: Some(env::temp_dir().join("satsconnect_mainnet_opt_in_test")),
+            ..Default::default()
+        };
+        let config =
+            EngineConfig::load_layers(&args, vars(&[("BITCOIN_RPC_PASS", "hunter2-long")]))
+                .unwrap();
+        assert!(!config.lightning.allow_mainnet);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_lsp_is_refused() {
+        let mut config = EngineConfig::default();
+        config.lightning.data_dir = env::temp_dir().join("satsconnect_lsp_config_test");
+        config.lsps = vec![format!("{}@lsp.example.com:9735", LSP_NODE_ID)
+            .parse()
+            .unwrap()];
+        assert!(config.validate().is_ok());
+
+        config.lsps[0].address = "lsp.example.com".to_string();
+        assert!(config.validate().is_err());
+
+        config.lsps[0].address = "lsp.example.com:9735".to_string();
+        config.lsps[0].lsps1_url = Some("lsp.example.com/lsps1".to_string());
+        assert!(config.validate().is_err());
+
+        config.lsps[0].lsps1_url = None;
+        config.lsps[0].lsps2_url = Some("https://lsp.example.com/lsps2".to_string());
+        assert!(config.validate().is_ok());
+        assert_eq!(config.jit_lsp().unwrap().name, config.lsps[0].name);
+
+        // The node takes just-in-time channels from one LSP only
+        let mut second = config.lsps[0].clone();
+        second.name = "second-lsp".to_string();
+        config.lsps.push(second);
+        assert!(config.validate().is_err());
+
+        config.lsps = vec!["not-a-key@lsp.example.com:9735".parse().unwrap()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_tls_settings() {
+        let args = EngineArgs {
+            data_dir: Some(env::temp_dir().join("satsconnect_tls_config_test")),
+            tls_client_ca: Some(PathBuf::from("/etc/satsconnect/clients.pem")),
+            ..Default::default()
+        };
+        let vars = vars(&[("TLS_CERT_PATH", "/etc/satsconnect/engine.pem")]);
+
+        // Certificates without TLS enabled, and a certificate without its key
+        let config = EngineConfig::load_layers(&args, &vars).unwrap();
+        assert!(config.validate().is_err());
+        let args = EngineArgs { tls: true, ..args };
+        let config = EngineConfig::load_layers(&args, &vars).unwrap();
+        assert!(config.validate().is_err());
+
+        let mut config = config;
+        config.server.tls.key_path = Some(PathBuf::from("/etc/satsconnect/engine.key"));
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.server.tls.client_ca_path,
+            Some(PathBuf::from("/etc/satsconnect/clients.pem"))
+        );
+    }
+
+    #[test]
+    fn test_auth_settings() {
+        let data_dir = env::temp_dir().join("satsconnect_auth_config_test");
+        let args = EngineArgs {
+            data_dir: Some(data_dir.clone()),
+            ..Default::default()
+        };
+        let config = EngineConfig::load_layers(&args, vars(&[])).unwrap();
+        assert!(config.server.auth.enabled);
+        assert_eq!(
+            config.server.auth.root_key_path(&config.lightning.data_dir),
+            data_dir.join("auth").join("root.key")
+        );
+
+        let config = EngineConfig::load_layers(&args, vars(&[("AUTH_ENABLED", "false")])).unwrap();
+        assert!(!config.server.auth.enabled);
+
+        let args = EngineArgs {
+            disable_auth: true,
+            ..args
+        };
+        let config = EngineConfig::load_layers(&args, vars(&[("AUTH_ENABLED", "true")])).unwrap();
+        assert!(!config.server.auth.enabled);
+    }
+
+    #[test]
+    fn test_rate_limit_settings() {
+        let path = config_file(
+            "satsconnect_rate_limit_test.toml",
+            r#"
+            [server.rate_limit]
+            default = { burst = 100, per_minute = 1200 }
+
+            [server.rate_limit.rpc]
+            SendPayment = { burst = 2, per_minute = 4 }
+            "#,
+        );
+        let args = EngineArgs {
+            config: Some(path.clone()),
+            ..Default::default()
+        };
+        let config = EngineConfig::load_layers(&args, vars(&[])).unwrap();
+        let limits = &config.server.rate_limit;
+        assert!(limits.enabled);
+        assert_eq!(limits.limit("SendPayment").burst, 2);
+        // Built-in limits still apply to RPCs the file does not name
+        assert_eq!(limits.limit("CreateWallet").burst, 3);
+        assert_eq!(limits.limit("GetBalance").burst, 100);
+        assert!(config.validate().is_ok());
+
+        let mut config = config;
+        config.server.rate_limit.rpc.insert(
+            "NewInvoice".to_string(),
+            RateLimit {
+                burst: 0,
+                per_minute: 60,
+            },
+        );
+        assert!(config.validate().is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_channel_settings() {
+        let path = config_file(
+            "satsconnect_channels_test.toml",
+            r#"
+            [channels]
+            min_channel_size = 20000
+            max_channels_per_peer = 2
+            "#,
+        );
+        let args = EngineArgs {
+            config: Some(path.clone()),
+            ..Default::default()
+        };
+        let mut config = EngineConfig::load_layers(&args, vars(&[])).unwrap();
+        assert_eq!(config.channels.min_channel_size, 20_000);
+        assert_eq!(config.channels.max_channels_per_peer, 2);
+        // Unset limits keep their defaults
+        assert_eq!(config.channels.max_channel_size, 10_000_000);
+        assert!(config.validate().is_ok());
+
+        config.channels.min_channel_size = 20_000_000;
+        assert!(config.validate().is_err());
+        config.channels.min_channel_size = 20_000;
+        config.peers.max_backoff_secs = 1;
+        assert!(config.validate().is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_liquidity_settings() {
+        let path = config_file(
+            "satsconnect_liquidity_test.toml",
+            &format!(
+                r#"
+            [liquidity]
+            enabled = true
+            onchain_reserve_sats = 500000
+            preferred_peers = ["{}@127.0.0.1:9735"]
+
+            [liquidity.rebalance]
+            rebalance_threshold = 0.2
+            "#,
+                LSP_NODE_ID
+            ),
+        );
+        let args = EngineArgs {
+            config: Some(path.clone()),
+            ..Default::default()
+        };
+        let mut config = EngineConfig::load_layers(&args, vars(&[])).unwrap();
+        assert!(config.liquidity.enabled);
+        assert_eq!(config.liquidity.onchain_reserve_sats, 500_000);
+        assert_eq!(config.liquidity.rebalance.rebalance_threshold, 0.2);
+        // Unset policy keeps its defaults
+        assert_eq!(config.liquidity.channel_size_sats, 1_000_000);
+        assert_eq!(config.liquidity.rebalance.min_rebalance_amount, 10_000);
+        assert!(config.validate().is_ok());
+
+        // Channels the manager opens must be allowed under [channels]
+        config.liquidity.channel_size_sats = 50_000_000;
+        assert!(config.validate().is_err());
+        config.liquidity.channel_size_sats = 1_000_000;
+        config.liquidity.preferred_peers = vec!["127.0.0.1:9735".to_string()];
+        assert!(config.validate().is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_timeout() {
+        let config = EngineConfig::load_layers(&EngineArgs::default(), vars(&[])).unwrap();
+        assert_eq!(config.server.shutdown_timeout(), Duration::from_secs(30));
+
+        let vars = vars(&[("SHUTDOWN_TIMEOUT_SECS", "5")]);
+        let mut config = EngineConfig::load_layers(&EngineArgs::default(), vars).unwrap();
+        assert_eq!(config.server.shutdown_timeout(), Duration::from_secs(5));
+
+        config.server.shutdown_timeout_secs = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_display_redacts_secrets() {
+        let vars = vars(&[
+            ("BITCOIN_RPC_PASS", "rpc-secret"),
+            ("MPESA_CONSUMER_SECRET", "mpesa-secret"),
+            (
+                "LSP_NODES",
+                &format!("{}@lsp.example.com:9735", LSP_NODE_ID),
+            ),
+        ]);
+        let mut config = EngineConfig::load_layers(&EngineArgs::default(), vars).unwrap();
+        config.lsps[0].token = Some("lsp-token".to_string());
+
+        let rendered = config.to_string();
+        assert!(rendered.contains(REDACTED));
+        assert!(rendered.contains("lsp.example.com:9735"));
+        for secret in ["rpc-secret", "mpesa-secret", "lsp-token"] {
+            assert!(!rendered.contains(secret));
+        }
+    }
+}
@@ -0,0 +1,706 @@
+//! Mapping of engine errors onto gRPC statuses.
+//!
+//! Domain errors (`WalletError`, `PaymentError`, `LightningError`,
+//! `ChannelError`, `PeerError`, `Lsps1Error`, `Lsps2Error`, `FiatError`,
+//! `NetworkError`, `AuthError`, `RateLimitError` and LDK's `NodeError`) are
+//! reported with a matching status code and a `google.rpc.ErrorInfo` detail
+//! carrying a stable reason. Anything else is logged under an error id and
+//! reported to the client as a bare `INTERNAL`, so no internal text leaks out.
+
+use crate::lightning::{ChannelError, PeerError};
+use crate::lightning_engine::LightningError;
+use crate::lsp::{Lsps1Error, Lsps2Error};
+use crate::multi_currency::FiatError;
+use crate::network::NetworkError;
+use crate::payment::PaymentError;
+use crate::rate_limit::RateLimitError;
+use crate::security::AuthError;
+use crate::wallet::WalletError;
+use ldk_node::NodeError;
+use std::collections::HashMap;
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+use tracing::error;
+
+/// `ErrorInfo.domain` of every error the engine reports
+pub const ERROR_DOMAIN: &str = "engine.satsconnect";
+
+/// Reason reported for errors that are not part of the taxonomy
+pub const INTERNAL_REASON: &str = "INTERNAL";
+
+/// An error whose message is safe to return to API clients
+pub trait ClientError: std::error::Error {
+    /// gRPC status code; `Code::Internal` hides the message from the client
+    fn code(&self) -> Code;
+
+    /// Stable `UPPER_SNAKE_CASE` reason for `ErrorInfo`
+    fn reason(&self) -> &'static str;
+
+    /// Structured context for `ErrorInfo.metadata`
+    fn metadata(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+}
+
+/// Convert an engine error into the status returned to clients
+pub fn to_status(err: &anyhow::Error) -> Status {
+    match err.chain().find_map(client_error) {
+        Some(e) if e.code() != Code::Internal => Status::with_error_details(
+            e.code(),
+            e.to_string(),
+            ErrorDetails::with_error_info(e.reason(), ERROR_DOMAIN, e.metadata()),
+        ),
+        Some(e) => internal(err, e.reason()),
+        None => internal(err, INTERNAL_REASON),
+    }
+}
+
+/// `INVALID_ARGUMENT` for a malformed request field
+pub fn invalid_argument(field: &str, message: impl Into<String>) -> Status {
+    Status::with_error_details(
+        Code::InvalidArgument,
+        message,
+        ErrorDetails::with_error_info(
+            "INVALID_ARGUMENT",
+            ERROR_DOMAIN,
+            HashMap::from([("field".to_string(), field.to_string())]),
+        ),
+    )
+}
+
+/// `RESOURCE_EXHAUSTED` for a rate limited call, with the wait in whole
+/// seconds in the `retry-after` metadata and as a `RetryInfo` detail
+pub fn rate_limited(err: &RateLimitError) -> Status {
+    let mut details = ErrorDetails::with_error_info(err.reason(), ERROR_DOMAIN, err.metadata());
+    details.set_retry_info(Some(err.retry_after));
+    let mut status = Status::with_error_details(err.code(), err.to_string(), details);
+    status
+        .metadata_mut()
+        .insert("retry-after", err.retry_after_secs().into());
+    status
+}
+
+fn internal(err: &anyhow::Error, reason: &str) -> Status {
+    let error_id = uuid::Uuid::new_v4().to_string();
+    error!("Internal error {}: {:#}", error_id, err);
+
+    Status::with_error_details(
+        Code::Internal,
+        "Internal error",
+        ErrorDetails::with_error_info(
+            reason,
+            ERROR_DOMAIN,
+            HashMap::from([("error_id".to_string(), error_id)]),
+        ),
+    )
+}
+
+fn client_error<'a>(cause: &'a (dyn std::error::Error + 'static)) -> Option<&'a dyn ClientError> {
+    fn cast<'a, E: ClientError + 'static>(
+        cause: &'a (dyn std::error::Error + 'static),
+    ) -> Option<&'a dyn ClientError> {
+        cause.downcast_ref::<E>().map(|e| e as &dyn ClientError)
+    }
+
+    cast::<WalletError>(cause)
+        .or_else(|| cast::<PaymentError>(cause))
+        .or_else(|| cast::<LightningError>(cause))
+        .or_else(|| cast::<ChannelError>(cause))
+        .or_else(|| cast::<PeerError>(cause))
+        .or_else(|| cast::<Lsps1Error>(cause))
+        .or_else(|| cast::<Lsps2Error>(cause))
+        .or_else(|| cast::<FiatError>(cause))
+        .or_else(|| cast::<NetworkError>(cause))
+        .or_else(|| cast::<AuthError>(cause))
+        .or_else(|| cast::<RateLimitError>(cause))
+        .or_else(|| cast::<NodeError>(cause))
+}
+
+fn metadata<const N: usize>(entries: [(&str, String); N]) -> HashMap<String, String> {
+    entries
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+}
+
+impl ClientError for WalletError {
+    fn code(&self) -> Code {
+        match self {
+            WalletError::NotLoaded => Code::FailedPrecondition,
+            WalletError::NotFound => Code::NotFound,
+            WalletError::AlreadyExists(_) => Code::AlreadyExists,
+            WalletError::InvalidMnemonic(_) => Code::InvalidArgument,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            WalletError::NotLoaded => "WALLET_NOT_LOADED",
+            WalletError::NotFound => "WALLET_NOT_FOUND",
+            WalletError::AlreadyExists(_) => "WALLET_ALREADY_EXISTS",
+            WalletError::InvalidMnemonic(_) => "INVALID_MNEMONIC",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            WalletError::AlreadyExists(label) => metadata([("label", label.clone())]),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for PaymentError {
+    fn code(&self) -> Code {
+        match self {
+            PaymentError::PaymentIdConflict(_) => Code::AlreadyExists,
+            PaymentError::AlreadyPaid(_)
+            | PaymentError::NotRefundable(_)
+            | PaymentError::RefundExceedsRemaining { .. }
+            | PaymentError::LnurlNotConfigured => Code::FailedPrecondition,
+            PaymentError::NotFound | PaymentError::RefundNotFound => Code::NotFound,
+            PaymentError::InvalidRefund(_) => Code::InvalidArgument,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            PaymentError::PaymentIdConflict(_) => "PAYMENT_ID_CONFLICT",
+            PaymentError::AlreadyPaid(_) => "INVOICE_ALREADY_PAID",
+            PaymentError::NotFound => "PAYMENT_NOT_FOUND",
+            PaymentError::NotRefundable(_) => "PAYMENT_NOT_REFUNDABLE",
+            PaymentError::RefundExceedsRemaining { .. } => "REFUND_EXCEEDS_REMAINING",
+            PaymentError::RefundNotFound => "REFUND_NOT_FOUND",
+            PaymentError::InvalidRefund(_) => "INVALID_REFUND",
+            PaymentError::LnurlNotConfigured => "LNURL_NOT_CONFIGURED",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            PaymentError::PaymentIdConflict(payment_id) => {
+                metadata([("payment_id", payment_id.clone())])
+            }
+            PaymentError::AlreadyPaid(payment_hash) => {
+                metadata([("payment_hash", payment_hash.clone())])
+            }
+            PaymentError::RefundExceedsRemaining {
+                requested,
+                remaining,
+            } => metadata([
+                ("requested_sats", requested.to_string()),
+                ("remaining_sats", remaining.to_string()),
+            ]),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for LightningError {
+    fn code(&self) -> Code {
+        match self {
+            LightningError::NodeNotRunning => Code::Unavailable,
+            LightningError::AmountlessInvoice
+            | LightningError::InvalidAmount
+            | LightningError::InvalidPaymentId(_)
+            | LightningError::RefundAmountMismatch { .. } => Code::InvalidArgument,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            LightningError::NodeNotRunning => "NODE_NOT_RUNNING",
+            LightningError::AmountlessInvoice => "AMOUNTLESS_INVOICE",
+            LightningError::InvalidAmount => "INVALID_AMOUNT",
+            LightningError::InvalidPaymentId(_) => "INVALID_PAYMENT_ID",
+            LightningError::RefundAmountMismatch { .. } => "INVOICE_AMOUNT_MISMATCH",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            LightningError::RefundAmountMismatch {
+                invoice_msat,
+                amount_msat,
+            } => metadata([
+                ("invoice_msat", invoice_msat.to_string()),
+                ("amount_msat", amount_msat.to_string()),
+            ]),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for ChannelError {
+    fn code(&self) -> Code {
+        match self {
+            ChannelError::BelowMinimum { .. }
+            | ChannelError::AboveMaximum { .. }
+            | ChannelError::PushTooLarge { .. }
+            | ChannelError::InvalidPeerId(_)
+            | ChannelError::InvalidAddress(_) => Code::InvalidArgument,
+            ChannelError::TooManyChannels(_) | ChannelError::AlreadyClosed(_) => {
+                Code::FailedPrecondition
+            }
+            ChannelError::NotFound(_) => Code::NotFound,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            ChannelError::BelowMinimum { .. } => "CHANNEL_TOO_SMALL",
+            ChannelError::AboveMaximum { .. } => "CHANNEL_TOO_LARGE",
+            ChannelError::PushTooLarge { .. } => "PUSH_AMOUNT_TOO_LARGE",
+            ChannelError::TooManyChannels(_) => "TOO_MANY_CHANNELS",
+            ChannelError::InvalidPeerId(_) => "INVALID_NODE_ID",
+            ChannelError::InvalidAddress(_) => "INVALID_ADDRESS",
+            ChannelError::NotFound(_) => "CHANNEL_NOT_FOUND",
+            ChannelError::AlreadyClosed(_) => "CHANNEL_ALREADY_CLOSED",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            ChannelError::BelowMinimum { min_sats, .. } => {
+                metadata([("min_sats", min_sats.to_string())])
+            }
+            ChannelError::AboveMaximum { max_sats, .. } => {
+                metadata([("max_sats", max_sats.to_string())])
+            }
+            ChannelError::TooManyChannels(max) => metadata([("max_channels", max.to_string())]),
+            ChannelError::NotFound(channel_id) | ChannelError::AlreadyClosed(channel_id) => {
+                metadata([("channel_id", channel_id.clone())])
+            }
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for PeerError {
+    fn code(&self) -> Code {
+        match self {
+            PeerError::InvalidUri(_) | PeerError::InvalidNodeId(_) => Code::InvalidArgument,
+            PeerError::NotFound(_) => Code::NotFound,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            PeerError::InvalidUri(_) => "INVALID_PEER_URI",
+            PeerError::InvalidNodeId(_) => "INVALID_NODE_ID",
+            PeerError::NotFound(_) => "PEER_NOT_FOUND",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            PeerError::NotFound(node_id) => metadata([("node_id", node_id.clone())]),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for Lsps1Error {
+    fn code(&self) -> Code {
+        match self {
+            Lsps1Error::UnknownProvider(_) | Lsps1Error::OrderNotFound(_) => Code::NotFound,
+            Lsps1Error::NoProvider | Lsps1Error::PaymentUnavailable(_) => Code::FailedPrecondition,
+            Lsps1Error::OutOfRange { .. }
+            | Lsps1Error::ExpiryTooLong { .. }
+            | Lsps1Error::FeeTooHigh { .. } => Code::OutOfRange,
+            Lsps1Error::OrderMismatch(_)
+            | Lsps1Error::OrderTotalMismatch { .. }
+            | Lsps1Error::InvoiceAmountMismatch { .. }
+            | Lsps1Error::Rejected { .. } => Code::Aborted,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            Lsps1Error::NoProvider => "NO_LSP_AVAILABLE",
+            Lsps1Error::UnknownProvider(_) => "LSP_NOT_FOUND",
+            Lsps1Error::OutOfRange { .. } => "LSP_OPTION_MISMATCH",
+            Lsps1Error::ExpiryTooLong { .. } => "LSP_EXPIRY_TOO_LONG",
+            Lsps1Error::FeeTooHigh { .. } => "LSP_FEE_TOO_HIGH",
+            Lsps1Error::OrderMismatch(_) | Lsps1Error::OrderTotalMismatch { .. } => {
+                "LSP_ORDER_MISMATCH"
+            }
+            Lsps1Error::PaymentUnavailable(_) => "LSP_PAYMENT_UNAVAILABLE",
+            Lsps1Error::InvoiceAmountMismatch { .. } => "INVOICE_AMOUNT_MISMATCH",
+            Lsps1Error::Rejected { .. } => "LSP_REJECTED",
+            Lsps1Error::OrderNotFound(_) => "LSP_ORDER_NOT_FOUND",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            Lsps1Error::UnknownProvider(name) => metadata([("lsp", name.clone())]),
+            Lsps1Error::OutOfRange {
+                field, min, max, ..
+            } => metadata([
+                ("field", field.to_string()),
+                ("min_sats", min.to_string()),
+                ("max_sats", max.to_string()),
+            ]),
+            Lsps1Error::ExpiryTooLong { max, .. } => metadata([("max_blocks", max.to_string())]),
+            Lsps1Error::FeeTooHigh { fee_sat, .. } => metadata([("fee_sats", fee_sat.to_string())]),
+            Lsps1Error::OrderMismatch(order_id)
+            | Lsps1Error::OrderTotalMismatch { order_id, .. }
+            | Lsps1Error::PaymentUnavailable(order_id)
+            | Lsps1Error::OrderNotFound(order_id) => metadata([("order_id", order_id.clone())]),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for Lsps2Error {
+    fn code(&self) -> Code {
+        match self {
+            Lsps2Error::NoProvider | Lsps2Error::FeeNotAccepted { .. } => Code::FailedPrecondition,
+            Lsps2Error::AmountRequired => Code::InvalidArgument,
+            Lsps2Error::NoOffer { .. } => Code::OutOfRange,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            Lsps2Error::NoProvider => "NO_JIT_LSP_AVAILABLE",
+            Lsps2Error::AmountRequired => "JIT_AMOUNT_REQUIRED",
+            Lsps2Error::NoOffer { .. } => "JIT_CHANNEL_UNAVAILABLE",
+            Lsps2Error::FeeNotAccepted { .. } => "LSP_FEE_NOT_ACCEPTED",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            Lsps2Error::NoOffer { amount_msat } => {
+                metadata([("amount_msat", amount_msat.to_string())])
+            }
+            // Lets clients show the fee and ask the user again
+            Lsps2Error::FeeNotAccepted { fee_sat, .. } => {
+                metadata([("fee_sats", fee_sat.to_string())])
+            }
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for FiatError {
+    fn code(&self) -> Code {
+        match self {
+            FiatError::UnsupportedCurrency(_) => Code::InvalidArgument,
+            FiatError::AmountTooSmall { .. } | FiatError::AmountTooLarge { .. } => Code::OutOfRange,
+            FiatError::ProviderUnavailable(_) | FiatError::RateUnavailable(_) => Code::Unavailable,
+            FiatError::OrderNotFound(_) => Code::NotFound,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            FiatError::UnsupportedCurrency(_) => "UNSUPPORTED_CURRENCY",
+            FiatError::AmountTooSmall { .. } => "AMOUNT_TOO_SMALL",
+            FiatError::AmountTooLarge { .. } => "AMOUNT_TOO_LARGE",
+            FiatError::ProviderUnavailable(_) => "FIAT_PROVIDER_UNAVAILABLE",
+            FiatError::RateUnavailable(_) => "EXCHANGE_RATE_UNAVAILABLE",
+            FiatError::OrderNotFound(_) => "FIAT_ORDER_NOT_FOUND",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            FiatError::UnsupportedCurrency(code) => metadata([("currency", code.clone())]),
+            FiatError::AmountTooSmall { min } => metadata([("min_sats", min.to_string())]),
+            FiatError::AmountTooLarge { max } => metadata([("max_sats", max.to_string())]),
+            FiatError::ProviderUnavailable(provider) => {
+                metadata([("provider", provider.to_string())])
+            }
+            FiatError::RateUnavailable(currency) => metadata([("currency", currency.to_string())]),
+            FiatError::OrderNotFound(order_id) => metadata([("order_id", order_id.clone())]),
+        }
+    }
+}
+
+impl ClientError for NetworkError {
+    fn code(&self) -> Code {
+        match self {
+            NetworkError::InvalidInvoice(_)
+            | NetworkError::InvalidAddress { .. }
+            | NetworkError::InvoiceNetworkMismatch { .. }
+            | NetworkError::AddressNetworkMismatch { .. } => Code::InvalidArgument,
+            // Operator misconfiguration, not something the client can fix
+            NetworkError::DataNetworkMismatch { .. } | NetworkError::MainnetDisabled(_) => {
+                Code::Internal
+            }
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            NetworkError::InvalidInvoice(_) => "INVALID_INVOICE",
+            NetworkError::InvalidAddress { .. } => "INVALID_ADDRESS",
+            NetworkError::InvoiceNetworkMismatch { .. } => "INVOICE_NETWORK_MISMATCH",
+            NetworkError::AddressNetworkMismatch { .. } => "ADDRESS_NETWORK_MISMATCH",
+            NetworkError::DataNetworkMismatch { .. } => "DATA_NETWORK_MISMATCH",
+            NetworkError::MainnetDisabled(_) => "MAINNET_DISABLED",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            NetworkError::InvoiceNetworkMismatch { expected, .. }
+            | NetworkError::AddressNetworkMismatch { expected, .. } => {
+                metadata([("expected_network", expected.to_string())])
+            }
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for AuthError {
+    fn code(&self) -> Code {
+        match self {
+            AuthError::MissingToken | AuthError::InvalidToken | AuthError::TokenExpired => {
+                Code::Unauthenticated
+            }
+            AuthError::PermissionDenied(_)
+            | AuthError::AmountLimitExceeded { .. }
+            | AuthError::WalletNotAllowed(_) => Code::PermissionDenied,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            AuthError::MissingToken => "MISSING_TOKEN",
+            AuthError::InvalidToken => "INVALID_TOKEN",
+            AuthError::TokenExpired => "TOKEN_EXPIRED",
+            AuthError::PermissionDenied(_) => "PERMISSION_DENIED",
+            AuthError::AmountLimitExceeded { .. } => "TOKEN_AMOUNT_LIMIT_EXCEEDED",
+            AuthError::WalletNotAllowed(_) => "TOKEN_WALLET_NOT_ALLOWED",
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        match self {
+            AuthError::PermissionDenied(permission) => {
+                metadata([("permission", permission.clone())])
+            }
+            AuthError::AmountLimitExceeded { max_sats, .. } => {
+                metadata([("max_sats", max_sats.to_string())])
+            }
+            AuthError::WalletNotAllowed(wallet_id) => metadata([("wallet_id", wallet_id.clone())]),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl ClientError for RateLimitError {
+    fn code(&self) -> Code {
+        Code::ResourceExhausted
+    }
+
+    fn reason(&self) -> &'static str {
+        "RATE_LIMITED"
+    }
+
+    fn metadata(&self) -> HashMap<String, String> {
+        metadata([
+            ("method", self.method.clone()),
+            ("key", self.key.to_string()),
+            ("retry_after_secs", self.retry_after_secs().to_string()),
+        ])
+    }
+}
+
+impl ClientError for NodeError {
+    fn code(&self) -> Code {
+        match self {
+            NodeError::NotRunning
+            | NodeError::ConnectionFailed
+            | NodeError::TxSyncFailed
+            | NodeError::TxSyncTimeout
+            | NodeError::GossipUpdateFailed
+            | NodeError::GossipUpdateTimeout
+            | NodeError::FeerateEstimationUpdateFailed
+            | NodeError::FeerateEstimationUpdateTimeout
+            | NodeError::WalletOperationTimeout
+            | NodeError::LiquiditySourceUnavailable => Code::Unavailable,
+            NodeError::InsufficientFunds | NodeError::LiquidityFeeTooHigh => {
+                Code::FailedPrecondition
+            }
+            NodeError::DuplicatePayment => Code::AlreadyExists,
+            NodeError::InvalidAddress
+            | NodeError::InvalidSocketAddress
+            | NodeError::InvalidPublicKey
+            | NodeError::InvalidOfferId
+            | NodeError::InvalidNodeId
+            | NodeError::InvalidPaymentId
+            | NodeError::InvalidPaymentHash
+            | NodeError::InvalidPaymentPreimage
+            | NodeError::InvalidPaymentSecret
+            | NodeError::InvalidAmount
+            | NodeError::InvalidInvoice
+            | NodeError::InvalidOffer
+            | NodeError::InvalidRefund
+            | NodeError::InvalidChannelId
+            | NodeError::InvalidNetwork
+            | NodeError::InvalidUri
+            | NodeError::InvalidQuantity
+            | NodeError::InvalidNodeAlias
+            | NodeError::InvalidDateTime
+            | NodeError::InvalidFeeRate
+            | NodeError::InvalidCustomTlvs
+            | NodeError::UriParameterParsingFailed
+            | NodeError::UnsupportedCurrency => Code::InvalidArgument,
+            NodeError::PaymentSendingFailed
+            | NodeError::ProbeSendingFailed
+            | NodeError::ChannelCreationFailed
+            | NodeError::ChannelClosingFailed
+            | NodeError::LiquidityRequestFailed => Code::Aborted,
+            _ => Code::Internal,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            NodeError::NotRunning => "NODE_NOT_RUNNING",
+            NodeError::ConnectionFailed => "PEER_CONNECTION_FAILED",
+            NodeError::TxSyncFailed | NodeError::TxSyncTimeout => "CHAIN_SYNC_FAILED",
+            NodeError::GossipUpdateFailed | NodeError::GossipUpdateTimeout => "GOSSIP_SYNC_FAILED",
+            NodeError::FeerateEstimationUpdateFailed
+            | NodeError::FeerateEstimationUpdateTimeout => "FEE_ESTIMATION_UNAVAILABLE",
+            NodeError::LiquiditySourceUnavailable => "LIQUIDITY_SOURCE_UNAVAILABLE",
+            NodeError::LiquidityFeeTooHigh => "LIQUIDITY_FEE_TOO_HIGH",
+            NodeError::InsufficientFunds => "INSUFFICIENT_FUNDS",
+            NodeError::DuplicatePayment => "DUPLICATE_PAYMENT",
+            NodeError::InvalidInvoice => "INVALID_INVOICE",
+            NodeError::InvalidAddress => "INVALID_ADDRESS",
+            NodeError::InvalidAmount => "INVALID_AMOUNT",
+            NodeError::InvalidPublicKey | NodeError::InvalidNodeId => "INVALID_NODE_ID",
+            NodeError::InvalidOffer => "INVALID_OFFER",
+            NodeError::InvalidRefund => "INVALID_REFUND",
+            NodeError::PaymentSendingFailed => "PAYMENT_SENDING_FAILED",
+            NodeError::ProbeSendingFailed => "PROBE_SENDING_FAILED",
+            NodeError::ChannelCreationFailed => "CHANNEL_OPEN_FAILED",
+            NodeError::ChannelClosingFailed => "CHANNEL_CLOSE_FAILED",
+            NodeError::LiquidityRequestFailed => "LIQUIDITY_REQUEST_FAILED",
+            _ if self.code() == Code::InvalidArgument => "INVALID_ARGUMENT",
+            _ => "LIGHTNING_NODE_FAILURE",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_info(status: &Status) -> tonic_types::ErrorInfo {
+        status.get_details_error_info().expect("ErrorInfo detail")
+    }
+
+    #[test]
+    fn test_domain_errors_map_to_codes() {
+        let status = to_status(&PaymentError::NotFound.into());
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(error_info(&status).reason, "PAYMENT_NOT_FOUND");
+        assert_eq!(error_info(&status).domain, ERROR_DOMAIN);
+
+        let status = to_status(&WalletError::NotLoaded.into());
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(error_info(&status).reason, "WALLET_NOT_LOADED");
+
+        let status = to_status(&LightningError::NodeNotRunning.into());
+        assert_eq!(status.code(), Code::Unavailable);
+
+        let status = to_status(
+            &ChannelError::BelowMinimum {
+                capacity_sats: 50_000,
+                min_sats: 100_000,
+            }
+            .into(),
+        );
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(error_info(&status).reason, "CHANNEL_TOO_SMALL");
+        assert_eq!(error_info(&status).metadata["min_sats"], "100000");
+
+        let status = to_status(&PeerError::InvalidUri("peer".to_string()).into());
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(error_info(&status).reason, "INVALID_PEER_URI");
+
+        let status = to_status(
+            &Lsps1Error::FeeTooHigh {
+                fee_sat: 5_000,
+                max_fee_sat: 1_000,
+            }
+            .into(),
+        );
+        assert_eq!(status.code(), Code::OutOfRange);
+        assert_eq!(error_info(&status).reason, "LSP_FEE_TOO_HIGH");
+        assert_eq!(error_info(&status).metadata["fee_sats"], "5000");
+
+        let status = to_status(
+            &Lsps2Error::FeeNotAccepted {
+                fee_sat: 2_000,
+                max_fee_sat: 0,
+            }
+            .into(),
+        );
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(error_info(&status).reason, "LSP_FEE_NOT_ACCEPTED");
+        assert_eq!(error_info(&status).metadata["fee_sats"], "2000");
+
+        let status = to_status(&FiatError::AmountTooSmall { min: 100 }.into());
+        assert_eq!(status.code(), Code::OutOfRange);
+        assert_eq!(error_info(&status).metadata["min_sats"], "100");
+
+        let status = to_status(&NodeError::InsufficientFunds.into());
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(error_info(&status).reason, "INSUFFICIENT_FUNDS");
+    }
+
+    #[test]
+    fn test_rate_limited_status_carries_retry_after() {
+        let err = RateLimitError {
+            method: "SendPayment".to_string(),
+            key: "token",
+            retry_after: std::time::Duration::from_millis(2500),
+        };
+        let status = rate_limited(&err);
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "3");
+        assert_eq!(error_info(&status).reason, "RATE_LIMITED");
+        assert_eq!(
+            status.get_details_retry_info().unwrap().retry_delay,
+            Some(std::time::Duration::from_millis(2500))
+        );
+    }
+
+    #[test]
+    fn test_context_does_not_hide_domain_error() {
+        let err = anyhow::Error::from(PaymentError::RefundExceedsRemaining {
+            requested: 500,
+            remaining: 200,
+        })
+        .context("processing refund");
+
+        let status = to_status(&err);
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(error_info(&status).metadata["remaining_sats"], "200");
+    }
+
+    #[test]
+    fn test_internal_errors_are_not_leaked() {
+        let err = anyhow::anyhow!("database is locked: /var/lib/satsconnect/engine.sqlite");
+        let status = to_status(&err);
+        assert_eq!(status.code(), Code::Internal);
+        assert_eq!(status.message(), "Internal error");
+
+        let info = error_info(&status);
+        assert_eq!(info.reason, INTERNAL_REASON);
+        assert!(info.metadata.contains_key("error_id"));
+
+        let status = to_status(&NetworkError::MainnetDisabled("set allow_mainnet").into());
+        assert_eq!(status.code(), Code::Internal);
+        assert_eq!(status.message(), "Internal error");
+        assert_eq!(error_info(&status).reason, "MAINNET_DISABLED");
+    }
+}
@@ -0,0 +1,243 @@
+//! Bearer token authentication for the gRPC and HTTP/JSON APIs.
+//!
+//! Every RPC needs a permission (see [`required_permission`]), checked with
+//! `AuthenticationService::check_permission` before the call reaches the
+//! service. The caller's [`AuthToken`] is attached to the request so handlers
+//! can enforce the per-request caveats (amount limit, wallet) that depend on
+//! the message.
+
+use satsconnect_rust_engine::error;
+use satsconnect_rust_engine::security::scoped_token::permissions;
+use satsconnect_rust_engine::security::{AuthError, AuthResult, AuthToken, AuthenticationService};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{self, HeaderMap};
+use tonic::{Request, Status};
+use tower::{Layer, Service};
+
+/// gRPC method paths
+pub mod rpc {
+    pub const CREATE_WALLET: &str = "/satsconnect.wallet.v1.WalletService/CreateWallet";
+    pub const GET_BALANCE: &str = "/satsconnect.wallet.v1.WalletService/GetBalance";
+    pub const QUOTE_INVOICE: &str = "/satsconnect.wallet.v1.WalletService/QuoteInvoice";
+    pub const NEW_INVOICE: &str = "/satsconnect.wallet.v1.WalletService/NewInvoice";
+    pub const SEND_PAYMENT: &str = "/satsconnect.wallet.v1.WalletService/SendPayment";
+    pub const ESTIMATE_ROUTING_FEE: &str =
+        "/satsconnect.wallet.v1.WalletService/EstimateRoutingFee";
+    pub const PROCESS_PAYMENT: &str = "/satsconnect.payment.v1.PaymentService/ProcessPayment";
+    pub const GET_PAYMENT_STATUS: &str = "/satsconnect.payment.v1.PaymentService/GetPaymentStatus";
+    pub const PROCESS_REFUND: &str = "/satsconnect.payment.v1.PaymentService/ProcessRefund";
+    pub const GET_LNURL_WITHDRAW: &str = "/satsconnect.payment.v1.PaymentService/GetLnurlWithdraw";
+    pub const CLAIM_REFUND: &str = "/satsconnect.payment.v1.PaymentService/ClaimRefund";
+    pub const PAYMENT_STREAM: &str = "/satsconnect.payment.v1.PaymentService/PaymentStream";
+    pub const OPEN_CHANNEL: &str = "/satsconnect.channel.v1.ChannelService/OpenChannel";
+    pub const CLOSE_CHANNEL: &str = "/satsconnect.channel.v1.ChannelService/CloseChannel";
+    pub const LIST_CHANNELS: &str = "/satsconnect.channel.v1.ChannelService/ListChannels";
+    pub const GET_CHANNEL: &str = "/satsconnect.channel.v1.ChannelService/GetChannel";
+    pub const EXPORT_NETWORK_GRAPH: &str =
+        "/satsconnect.channel.v1.ChannelService/ExportNetworkGraph";
+    pub const CONNECT_PEER: &str = "/satsconnect.peer.v1.PeerService/ConnectPeer";
+    pub const DISCONNECT_PEER: &str = "/satsconnect.peer.v1.PeerService/DisconnectPeer";
+    pub const LIST_PEERS: &str = "/satsconnect.peer.v1.PeerService/ListPeers";
+}
+
+/// Services anyone may call: health checks and reflection
+const PUBLIC_SERVICES: &[&str] = &[
+    "/grpc.health.v1.Health/",
+    "/grpc.reflection.v1.ServerReflection/",
+    "/grpc.reflection.v1alpha.ServerReflection/",
+];
+
+/// Permission needed to call the RPC at `path`; `None` for public RPCs.
+/// RPCs not listed here need `admin`, so new ones are closed by default.
+pub fn required_permission(path: &str) -> Option<&'static str> {
+    if PUBLIC_SERVICES
+        .iter()
+        .any(|service| path.starts_with(service))
+    {
+        return None;
+    }
+
+    match path {
+        rpc::CREATE_WALLET
+        | rpc::OPEN_CHANNEL
+        | rpc::CLOSE_CHANNEL
+        | rpc::CONNECT_PEER
+        | rpc::DISCONNECT_PEER
+        | rpc::EXPORT_NETWORK_GRAPH => Some(permissions::ADMIN),
+        rpc::GET_BALANCE
+        | rpc::ESTIMATE_ROUTING_FEE
+        | rpc::GET_PAYMENT_STATUS
+        | rpc::PAYMENT_STREAM
+        | rpc::LIST_CHANNELS
+        | rpc::GET_CHANNEL
+        | rpc::LIST_PEERS => Some(permissions::READ),
+        rpc::QUOTE_INVOICE | rpc::NEW_INVOICE => Some(permissions::INVOICE),
+        rpc::SEND_PAYMENT | rpc::PROCESS_PAYMENT => Some(permissions::SEND),
+        rpc::PROCESS_REFUND => Some(permissions::REFUND),
+        // LNURL-withdraw is authorized by the k1 secret in the request
+        rpc::GET_LNURL_WITHDRAW | rpc::CLAIM_REFUND => None,
+        _ => Some(permissions::ADMIN),
+    }
+}
+
+/// Checks bearer tokens; a disabled authenticator lets everything through
+#[derive(Clone)]
+pub struct Authenticator {
+    auth: Option<Arc<AuthenticationService>>,
+}
+
+impl Authenticator {
+    pub fn new(auth: Arc<AuthenticationService>) -> Self {
+        Self { auth: Some(auth) }
+    }
+
+    pub fn disabled() -> Self {
+        Self { auth: None }
+    }
+
+    /// Check the `authorization: Bearer <token>` header for a call to the
+    /// RPC at `path`. Returns the caller's token, or `None` when no token is
+    /// needed.
+    pub async fn authorize(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<Option<AuthToken>, Status> {
+        let Some(auth) = &self.auth else {
+            return Ok(None);
+        };
+        let Some(permission) = required_permission(path) else {
+            return Ok(None);
+        };
+
+        let token = bearer_token(headers).ok_or_else(|| status(AuthError::MissingToken))?;
+        let auth_token = match auth
+            .validate_token(token)
+            .await
+            .map_err(|e| error::to_status(&e))?
+        {
+            AuthResult::Success(auth_token) => auth_token,
+            AuthResult::TokenExpired => return Err(status(AuthError::TokenExpired)),
+            _ => return Err(status(AuthError::InvalidToken)),
+        };
+
+        let allowed = auth
+            .check_permission(token, permission)
+            .await
+            .map_err(|e| error::to_status(&e))?;
+        if !allowed {
+            return Err(status(AuthError::PermissionDenied(permission.to_string())));
+        }
+
+        Ok(Some(auth_token))
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(http::header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+fn status(err: AuthError) -> Status {
+    error::to_status(&err.into())
+}
+
+/// Token of the caller, when authentication is enabled
+pub fn caller<T>(request: &Request<T>) -> Option<&AuthToken> {
+    request.extensions().get::<AuthToken>()
+}
+
+/// Refuse amounts above the caller's token limit
+pub fn check_amount<T>(request: &Request<T>, amount_sats: u64) -> Result<(), Status> {
+    match caller(request) {
+        Some(token) => token.check_amount(amount_sats).map_err(status),
+        None => Ok(()),
+    }
+}
+
+/// Refuse requests for a wallet the caller's token is not bound to
+pub fn check_wallet<T>(request: &Request<T>, wallet_id: &str) -> Result<(), Status> {
+    match caller(request) {
+        Some(token) => token.check_wallet(wallet_id).map_err(status),
+        None => Ok(()),
+    }
+}
+
+/// Whether the caller's token is bound to a wallet
+pub fn is_wallet_bound<T>(request: &Request<T>) -> bool {
+    caller(request).is_some_and(|token| token.wallet_id.is_some())
+}
+
+/// Tower layer running [`Authenticator::authorize`] in front of the gRPC services
+#[derive(Clone)]
+pub struct AuthLayer {
+    authenticator: Authenticator,
+}
+
+impl AuthLayer {
+    pub fn new(authenticator: Authenticator) -> Self {
+        Self { authenticator }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            authenticator: self.authenticator.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    authenticator: Authenticator,
+}
+
+impl<S, B> Service<http::Request<B>> for AuthService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        // Use the service that was polled ready and leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let authenticator = self.authenticator.clone();
+
+        Box::pin(async move {
+            match authenticator
+                .authorize(request.uri().path(), request.headers())
+                .await
+            {
+                Ok(token) => {
+                    if let Some(token) = token {
+                        request.extensions_mut().insert(token);
+                    }
+                    inner.call(request).await
+                }
+                Err(status) => Ok(status.into_http()),
+            }
+        })
+    }
+}
@@ -0,0 +1,86 @@
+use satsconnect_rust_engine::monitoring::health_check::SystemHealth;
+use satsconnect_rust_engine::monitoring::{HealthChecker, HealthStatus};
+use satsconnect_rust_engine::shutdown::Shutdown;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tracing::info;
+
+/// How often the health checks behind `grpc.health.v1.Health` are re-run
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A gRPC service and the health checks it needs to pass to be serving
+pub struct ServiceHealth {
+    pub service: &'static str,
+    pub checks: &'static [&'static str],
+}
+
+/// Periodically run the health checks and publish the results as
+/// per-service statuses; the empty service name reports overall health.
+/// Once `shutdown` triggers every service reports NOT_SERVING so load
+/// balancers and watchers move away while in-flight calls drain.
+pub async fn report_health(
+    checker: Arc<HealthChecker>,
+    mut reporter: HealthReporter,
+    services: Vec<ServiceHealth>,
+    shutdown: Shutdown,
+) {
+    let mut last: HashMap<&'static str, ServingStatus> = HashMap::new();
+    let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    let triggered = shutdown.triggered();
+    tokio::pin!(triggered);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = &mut triggered => {
+                let names = std::iter::once("").chain(services.iter().map(|s| s.service));
+                for service in names {
+                    reporter
+                        .set_service_status(service, ServingStatus::NotServing)
+                        .await;
+                }
+                info!("Shutting down, all services report NOT_SERVING");
+                return;
+            }
+        }
+        let health = checker.run_health_checks().await;
+
+        let statuses = std::iter::once(("", serving_status(&health.overall_status))).chain(
+            services
+                .iter()
+                .map(|s| (s.service, service_status(&health, s.checks))),
+        );
+
+        for (service, status) in statuses {
+            if last.insert(service, status) != Some(status) {
+                info!("Health of '{}' is now {:?}", service, status);
+            }
+            reporter.set_service_status(service, status).await;
+        }
+    }
+}
+
+fn serving_status(status: &HealthStatus) -> ServingStatus {
+    match status {
+        HealthStatus::Healthy | HealthStatus::Degraded => ServingStatus::Serving,
+        HealthStatus::Unhealthy | HealthStatus::Unknown => ServingStatus::NotServing,
+    }
+}
+
+/// Serving only if every check the service depends on is serving
+fn service_status(health: &SystemHealth, checks: &[&str]) -> ServingStatus {
+    let failing = health
+        .checks
+        .iter()
+        .filter(|check| checks.contains(&check.name.as_str()))
+        .any(|check| serving_status(&check.status) == ServingStatus::NotServing);
+
+    if failing {
+        ServingStatus::NotServing
+    } else {
+        ServingStatus::Serving
+    }
+}
@@ -0,0 +1,151 @@
+//! Rate limiting of the gRPC and HTTP/JSON APIs.
+//!
+//! Calls to the engine's services are charged to the caller's API token, its
+//! IP and, for wallet-bound tokens, the wallet (see [`RateLimiter`]). Handlers
+//! whose message names a wallet charge that wallet too with [`check_wallet`].
+//! Health checks and reflection are not limited.
+
+use crate::grpc_auth;
+use satsconnect_rust_engine::error;
+use satsconnect_rust_engine::rate_limit::{ClientKey, RateLimiter};
+use satsconnect_rust_engine::security::AuthToken;
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::http;
+use tonic::transport::server::TcpConnectInfo;
+use tonic::{Request, Status};
+use tower::{Layer, Service};
+
+/// Prefix of the RPCs that are rate limited
+const LIMITED_PACKAGE: &str = "/satsconnect.";
+
+/// Method name of a gRPC path, e.g. `SendPayment`
+pub fn method_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Charge a call to the RPC at `path` by the caller with `token` from `ip`.
+/// Attach the limiter to requests that pass so handlers can [`check_wallet`].
+pub async fn check(
+    limiter: &RateLimiter,
+    path: &str,
+    token: Option<&AuthToken>,
+    ip: Option<IpAddr>,
+) -> Result<(), Status> {
+    if !path.starts_with(LIMITED_PACKAGE) {
+        return Ok(());
+    }
+
+    let mut keys = Vec::with_capacity(3);
+    if let Some(token) = token {
+        keys.push(ClientKey::Token(token.user_id.clone()));
+        if let Some(wallet_id) = &token.wallet_id {
+            keys.push(ClientKey::Wallet(wallet_id.clone()));
+        }
+    }
+    if let Some(ip) = ip {
+        keys.push(ClientKey::Ip(ip));
+    }
+
+    limiter
+        .check(method_name(path), &keys)
+        .await
+        .map_err(|e| error::rate_limited(&e))
+}
+
+/// Charge the call to `wallet_id` from the message, unless the caller's token
+/// is bound to it and the call was charged to the wallet already
+pub async fn check_wallet<T>(
+    request: &Request<T>,
+    rpc: &str,
+    wallet_id: &str,
+) -> Result<(), Status> {
+    let Some(limiter) = request.extensions().get::<Arc<RateLimiter>>() else {
+        return Ok(());
+    };
+    let already_charged = grpc_auth::caller(request)
+        .is_some_and(|token| token.wallet_id.as_deref() == Some(wallet_id));
+    if already_charged || wallet_id.is_empty() {
+        return Ok(());
+    }
+
+    limiter
+        .check(
+            method_name(rpc),
+            &[ClientKey::Wallet(wallet_id.to_string())],
+        )
+        .await
+        .map_err(|e| error::rate_limited(&e))
+}
+
+/// Tower layer running [`check`] in front of the gRPC services; it must sit
+/// inside [`grpc_auth::AuthLayer`] to see the caller's token
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: Arc::clone(&self.limiter),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<http::Request<B>> for RateLimitService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = Arc::clone(&self.limiter);
+
+        Box::pin(async move {
+            let ip = request
+                .extensions()
+                .get::<TcpConnectInfo>()
+                .and_then(|info| info.remote_addr())
+                .map(|addr| addr.ip());
+            let token = request.extensions().get::<AuthToken>();
+
+            match check(&limiter, request.uri().path(), token, ip).await {
+                Ok(()) => {
+                    request.extensions_mut().insert(limiter);
+                    inner.call(request).await
+                }
+                Err(status) => Ok(status.into_http()),
+            }
+        })
+    }
+}
@@ -1,15 +1,45 @@
+use crate::grpc_auth;
+use crate::grpc_rate_limit;
 use anyhow::Result;
+use lightning_invoice::Bolt11Invoice;
+use satsconnect_rust_engine::lightning::route_estimator::EstimateTarget;
+use satsconnect_rust_engine::lightning::send_options::{PaymentFailureReason, SendPaymentOptions};
+use satsconnect_rust_engine::lightning::{
+    ChannelInfo, ChannelManager, ChannelState, PeerInfo, PeerManager, PeerState,
+};
+use satsconnect_rust_engine::proto::satsconnect::channel::v1::{
+    channel_service_server::ChannelService, Channel, ChannelState as ProtoChannelState,
+    CloseChannelRequest, CloseChannelResponse, ExportNetworkGraphRequest,
+    ExportNetworkGraphResponse, GetChannelRequest, GetChannelResponse, GraphFormat,
+    ListChannelsRequest, ListChannelsResponse, NodeCentrality as ProtoNodeCentrality,
+    OpenChannelRequest, OpenChannelResponse,
+};
 use satsconnect_rust_engine::proto::satsconnect::payment::v1::{
-    payment_service_server::PaymentService, PaymentRequest, PaymentResponse, PaymentStatusRequest,
-    PaymentStreamRequest, PaymentStreamResponse, RefundRequest,
+    payment_service_server::PaymentService, ClaimRefundRequest, LnurlWithdrawRequest,
+    LnurlWithdrawResponse, PaymentRequest, PaymentResponse, PaymentStatusRequest,
+    PaymentStreamRequest, PaymentStreamResponse, RefundInfo, RefundMethod as ProtoRefundMethod,
+    RefundRequest,
+};
+use satsconnect_rust_engine::proto::satsconnect::peer::v1::{
+    peer_service_server::PeerService, ConnectPeerRequest, ConnectPeerResponse,
+    DisconnectPeerRequest, DisconnectPeerResponse, ListPeersRequest, ListPeersResponse, Peer,
+    PeerState as ProtoPeerState,
 };
 use satsconnect_rust_engine::proto::satsconnect::wallet::v1::{
     wallet_service_server::WalletService, CreateWalletRequest, CreateWalletResponse,
-    GetBalanceRequest, GetBalanceResponse, NewInvoiceRequest, NewInvoiceResponse,
-    SendPaymentRequest, SendPaymentResponse,
+    EstimateRoutingFeeRequest, EstimateRoutingFeeResponse, GetBalanceRequest, GetBalanceResponse,
+    NewInvoiceRequest, NewInvoiceResponse, PaymentFailureReason as ProtoPaymentFailureReason,
+    QuoteInvoiceRequest, QuoteInvoiceResponse, SendPaymentRequest, SendPaymentResponse,
 };
-use satsconnect_rust_engine::{payment::PaymentHandler, wallet::WalletHandler};
+use satsconnect_rust_engine::{
+    error,
+    payment::{Payment, PaymentHandler},
+    refund::{self, RefundMethod},
+    wallet::WalletHandler,
+};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tonic::{Request, Response, Status};
 
 pub struct WalletServiceImpl {
@@ -20,6 +50,15 @@ impl WalletServiceImpl {
     pub fn new(wallet_handler: Arc<WalletHandler>) -> Self {
         Self { wallet_handler }
     }
+
+    /// Wallet-bound tokens only work while their wallet is the loaded one
+    async fn check_loaded_wallet<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        if grpc_auth::is_wallet_bound(request) {
+            let label = self.wallet_handler.current_wallet_label().await;
+            grpc_auth::check_wallet(request, &label.unwrap_or_default())?;
+        }
+        Ok(())
+    }
 }
 
 #[tonic::async_trait]
@@ -28,13 +67,14 @@ impl WalletService for WalletServiceImpl {
         &self,
         request: Request<CreateWalletRequest>,
     ) -> Result<Response<CreateWalletResponse>, Status> {
-        let req = request.into_inner();
-
-        let label = if req.label.is_empty() {
+        let label = if request.get_ref().label.is_empty() {
             "default".to_string()
         } else {
-            req.label
+            request.get_ref().label.clone()
         };
+        grpc_auth::check_wallet(&request, &label)?;
+        let req = request.into_inner();
+
         let mnemonic = if req.mnemonic.is_empty() {
             None
         } else {
@@ -46,14 +86,16 @@ impl WalletService for WalletServiceImpl {
                 let response = CreateWalletResponse { node_id, address };
                 Ok(Response::new(response))
             }
-            Err(e) => Err(Status::internal(e.to_string())),
+            Err(e) => Err(error::to_status(&e)),
         }
     }
 
     async fn get_balance(
         &self,
-        _request: Request<GetBalanceRequest>,
+        request: Request<GetBalanceRequest>,
     ) -> Result<Response<GetBalanceResponse>, Status> {
+        self.check_loaded_wallet(&request).await?;
+
         match self.wallet_handler.get_balance().await {
             Ok((confirmed_sats, lightning_sats)) => {
                 let response = GetBalanceResponse {
@@ -62,7 +104,34 @@ impl WalletService for WalletServiceImpl {
                 };
                 Ok(Response::new(response))
             }
-            Err(e) => Err(Status::internal(e.to_string())),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn quote_invoice(
+        &self,
+        request: Request<QuoteInvoiceRequest>,
+    ) -> Result<Response<QuoteInvoiceResponse>, Status> {
+        self.check_loaded_wallet(&request).await?;
+        let req = request.into_inner();
+
+        match self.wallet_handler.quote_invoice(req.amount_sats).await {
+            Ok(Some(quote)) => {
+                let lsp_fee_sats = quote.fee_msat.div_ceil(1000);
+                let response = QuoteInvoiceResponse {
+                    jit_channel: true,
+                    lsp_fee_sats,
+                    receive_sats: req.amount_sats.saturating_sub(lsp_fee_sats),
+                    lsp: quote.provider,
+                    fee_valid_until: quote.valid_until,
+                };
+                Ok(Response::new(response))
+            }
+            Ok(None) => Ok(Response::new(QuoteInvoiceResponse {
+                receive_sats: req.amount_sats,
+                ..Default::default()
+            })),
+            Err(e) => Err(error::to_status(&e)),
         }
     }
 
@@ -70,21 +139,24 @@ impl WalletService for WalletServiceImpl {
         &self,
         request: Request<NewInvoiceRequest>,
     ) -> Result<Response<NewInvoiceResponse>, Status> {
+        self.check_loaded_wallet(&request).await?;
         let req = request.into_inner();
 
         match self
             .wallet_handler
-            .generate_invoice(req.amount_sats, req.memo)
+            .new_invoice(req.amount_sats, req.memo, req.max_lsp_fee_sats)
             .await
         {
-            Ok((invoice, payment_hash)) => {
+            Ok(invoice) => {
                 let response = NewInvoiceResponse {
-                    invoice,
-                    payment_hash,
+                    invoice: invoice.invoice,
+                    payment_hash: invoice.payment_hash,
+                    jit_channel: invoice.lsp_fee_sats.is_some(),
+                    lsp_fee_sats: invoice.lsp_fee_sats.unwrap_or(0),
                 };
                 Ok(Response::new(response))
             }
-            Err(e) => Err(Status::internal(e.to_string())),
+            Err(e) => Err(error::to_status(&e)),
         }
     }
 
@@ -92,21 +164,170 @@ impl WalletService for WalletServiceImpl {
         &self,
         request: Request<SendPaymentRequest>,
     ) -> Result<Response<SendPaymentResponse>, Status> {
+        self.check_loaded_wallet(&request).await?;
+        if grpc_auth::caller(&request).is_some_and(|t| t.max_amount_sats.is_some()) {
+            let amount_sats = invoice_amount_sats(&request.get_ref().invoice)?;
+            grpc_auth::check_amount(&request, amount_sats)?;
+        }
         let req = request.into_inner();
+        let options = send_options_from_request(&req)?;
 
-        match self.wallet_handler.send_payment(req.invoice).await {
-            Ok((payment_hash, status)) => {
+        match self
+            .wallet_handler
+            .send_payment_with_options(req.invoice, options)
+            .await
+        {
+            Ok(outcome) => {
+                let failure_reason = outcome
+                    .failure_reason
+                    .map(failure_reason_to_proto)
+                    .unwrap_or(ProtoPaymentFailureReason::Unspecified);
                 let response = SendPaymentResponse {
-                    payment_hash,
-                    status,
+                    payment_hash: outcome.payment_hash,
+                    status: outcome.status,
+                    fee_paid_sats: outcome.fee_paid_sats,
+                    failure_reason: failure_reason as i32,
                 };
                 Ok(Response::new(response))
             }
-            Err(e) => Err(Status::internal(e.to_string())),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn estimate_routing_fee(
+        &self,
+        request: Request<EstimateRoutingFeeRequest>,
+    ) -> Result<Response<EstimateRoutingFeeResponse>, Status> {
+        self.check_loaded_wallet(&request).await?;
+        let req = request.into_inner();
+
+        let target = match (req.invoice.is_empty(), req.pubkey.is_empty()) {
+            (false, true) => EstimateTarget::Invoice(req.invoice),
+            (true, false) => {
+                if req.amount_sats == 0 {
+                    return Err(error::invalid_argument(
+                        "amount_sats",
+                        "amount_sats is required when estimating to a pubkey",
+                    ));
+                }
+                EstimateTarget::Node {
+                    pubkey: req.pubkey,
+                    amount_msat: req.amount_sats * 1000,
+                }
+            }
+            _ => {
+                return Err(error::invalid_argument(
+                    "invoice",
+                    "Exactly one of invoice or pubkey must be set",
+                ))
+            }
+        };
+
+        match self.wallet_handler.estimate_routing_fee(target).await {
+            Ok(estimate) => {
+                let response = EstimateRoutingFeeResponse {
+                    fee_sats: estimate.fee_msat.div_ceil(1000),
+                    fee_msat: estimate.fee_msat,
+                    hop_count: estimate.hop_count,
+                    success_probability: estimate.success_probability,
+                    cached: estimate.cached,
+                };
+                Ok(Response::new(response))
+            }
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+}
+
+/// Amount an invoice asks for, to check against token limits
+fn invoice_amount_sats(invoice: &str) -> Result<u64, Status> {
+    let invoice = Bolt11Invoice::from_str(invoice)
+        .map_err(|_| error::invalid_argument("invoice", "Invalid BOLT11 invoice"))?;
+    Ok(invoice.amount_milli_satoshis().unwrap_or(0).div_ceil(1000))
+}
+
+/// Build send options from the request; zero values mean "use the default"
+fn send_options_from_request(req: &SendPaymentRequest) -> Result<SendPaymentOptions, Status> {
+    let max_parts =
+        match req.max_parts {
+            0 => None,
+            parts => Some(u8::try_from(parts).map_err(|_| {
+                error::invalid_argument("max_parts", "max_parts must be at most 255")
+            })?),
+        };
+
+    let options = SendPaymentOptions {
+        fee_limit_sats: (req.fee_limit_sats > 0).then_some(req.fee_limit_sats),
+        fee_limit_ppm: (req.fee_limit_ppm > 0).then_some(req.fee_limit_ppm),
+        timeout: (req.timeout_seconds > 0).then(|| Duration::from_secs(req.timeout_seconds as u64)),
+        max_parts,
+        max_cltv_expiry_delta: (req.max_cltv_delta > 0).then_some(req.max_cltv_delta),
+    };
+
+    options
+        .validate()
+        .map_err(|e| error::invalid_argument("send_options", e.to_string()))?;
+
+    Ok(options)
+}
+
+fn payment_to_response(payment: Payment) -> PaymentResponse {
+    PaymentResponse {
+        refunded_sats: payment.refunded_sats(),
+        refunds: payment
+            .refunds
+            .iter()
+            .map(|refund| RefundInfo {
+                refund_id: refund.refund_id.clone(),
+                amount_sats: refund.amount_sats,
+                method: refund_method_to_proto(refund.method) as i32,
+                status: refund.status.as_str().to_string(),
+                lnurl: refund.lnurl.clone().unwrap_or_default(),
+                bolt12_refund: refund.bolt12_refund.clone().unwrap_or_default(),
+                payment_hash: refund.payment_hash.clone().unwrap_or_default(),
+                created_at: refund.created_at.clone(),
+                expires_at: refund.expires_at.clone().unwrap_or_default(),
+            })
+            .collect(),
+        payment_id: payment.payment_id,
+        status: payment.status,
+        message: payment.description,
+        amount_sats: payment.amount_sats,
+        payment_hash: payment.payment_hash,
+        timestamp: payment.timestamp,
+    }
+}
+
+fn refund_method_from_proto(method: ProtoRefundMethod) -> RefundMethod {
+    match method {
+        ProtoRefundMethod::Bolt11Invoice => RefundMethod::Bolt11Invoice,
+        ProtoRefundMethod::Bolt12Refund => RefundMethod::Bolt12Refund,
+        ProtoRefundMethod::LnurlWithdraw | ProtoRefundMethod::Unspecified => {
+            RefundMethod::LnurlWithdraw
         }
     }
 }
 
+fn refund_method_to_proto(method: RefundMethod) -> ProtoRefundMethod {
+    match method {
+        RefundMethod::LnurlWithdraw => ProtoRefundMethod::LnurlWithdraw,
+        RefundMethod::Bolt11Invoice => ProtoRefundMethod::Bolt11Invoice,
+        RefundMethod::Bolt12Refund => ProtoRefundMethod::Bolt12Refund,
+    }
+}
+
+fn failure_reason_to_proto(reason: PaymentFailureReason) -> ProtoPaymentFailureReason {
+    match reason {
+        PaymentFailureReason::NoRoute => ProtoPaymentFailureReason::NoRoute,
+        PaymentFailureReason::FeeTooHigh => ProtoPaymentFailureReason::FeeTooHigh,
+        PaymentFailureReason::Timeout => ProtoPaymentFailureReason::Timeout,
+        PaymentFailureReason::RecipientRejected => ProtoPaymentFailureReason::RecipientRejected,
+        PaymentFailureReason::InvoiceExpired => ProtoPaymentFailureReason::InvoiceExpired,
+        PaymentFailureReason::RetriesExhausted => ProtoPaymentFailureReason::RetriesExhausted,
+        PaymentFailureReason::Unknown => ProtoPaymentFailureReason::Unspecified,
+    }
+}
+
 pub struct PaymentServiceImpl {
     payment_handler: Arc<PaymentHandler>,
 }
@@ -115,6 +336,31 @@ impl PaymentServiceImpl {
     pub fn new(payment_handler: Arc<PaymentHandler>) -> Self {
         Self { payment_handler }
     }
+
+    /// Check a refund against the caller's wallet and amount caveats
+    async fn check_refund(&self, request: &Request<RefundRequest>) -> Result<(), Status> {
+        let Some(token) = grpc_auth::caller(request) else {
+            return Ok(());
+        };
+        if token.wallet_id.is_none() && token.max_amount_sats.is_none() {
+            return Ok(());
+        }
+
+        let req = request.get_ref();
+        let payment = self
+            .payment_handler
+            .get_payment_status(req.payment_id.clone())
+            .await
+            .map_err(|e| error::to_status(&e))?;
+        grpc_auth::check_wallet(request, &payment.wallet_id)?;
+
+        // Zero refunds whatever is left
+        let amount_sats = match req.amount_sats {
+            0 => refund::refundable_sats(payment.amount_sats, &payment.refunds),
+            amount_sats => amount_sats,
+        };
+        grpc_auth::check_amount(request, amount_sats)
+    }
 }
 
 #[tonic::async_trait]
@@ -123,6 +369,18 @@ impl PaymentService for PaymentServiceImpl {
         &self,
         request: Request<PaymentRequest>,
     ) -> Result<Response<PaymentResponse>, Status> {
+        grpc_auth::check_wallet(&request, &request.get_ref().wallet_id)?;
+        grpc_auth::check_amount(&request, request.get_ref().amount_sats)?;
+        if grpc_auth::caller(&request).is_some_and(|t| t.max_amount_sats.is_some()) {
+            let amount_sats = invoice_amount_sats(&request.get_ref().invoice)?;
+            grpc_auth::check_amount(&request, amount_sats)?;
+        }
+        grpc_rate_limit::check_wallet(
+            &request,
+            grpc_auth::rpc::PROCESS_PAYMENT,
+            &request.get_ref().wallet_id,
+        )
+        .await?;
         let req = request.into_inner();
 
         match self
@@ -133,21 +391,12 @@ impl PaymentService for PaymentServiceImpl {
                 req.amount_sats,
                 req.invoice,
                 req.description,
+                req.force,
             )
             .await
         {
-            Ok(payment) => {
-                let response = PaymentResponse {
-                    payment_id: payment.payment_id,
-                    status: payment.status,
-                    message: payment.description,
-                    amount_sats: payment.amount_sats,
-                    payment_hash: payment.payment_hash,
-                    timestamp: payment.timestamp,
-                };
-                Ok(Response::new(response))
-            }
-            Err(e) => Err(Status::internal(e.to_string())),
+            Ok(payment) => Ok(Response::new(payment_to_response(payment))),
+            Err(e) => Err(error::to_status(&e)),
         }
     }
 
@@ -155,25 +404,18 @@ impl PaymentService for PaymentServiceImpl {
         &self,
         request: Request<PaymentStatusRequest>,
     ) -> Result<Response<PaymentResponse>, Status> {
-        let req = request.into_inner();
+        let req = request.get_ref();
 
         match self
             .payment_handler
-            .get_payment_status(req.payment_id)
+            .get_payment_status(req.payment_id.clone())
             .await
         {
             Ok(payment) => {
-                let response = PaymentResponse {
-                    payment_id: payment.payment_id,
-                    status: payment.status,
-                    message: payment.description,
-                    amount_sats: payment.amount_sats,
-                    payment_hash: payment.payment_hash,
-                    timestamp: payment.timestamp,
-                };
-                Ok(Response::new(response))
+                grpc_auth::check_wallet(&request, &payment.wallet_id)?;
+                Ok(Response::new(payment_to_response(payment)))
             }
-            Err(e) => Err(Status::internal(e.to_string())),
+            Err(e) => Err(error::to_status(&e)),
         }
     }
 
@@ -181,25 +423,59 @@ impl PaymentService for PaymentServiceImpl {
         &self,
         request: Request<RefundRequest>,
     ) -> Result<Response<PaymentResponse>, Status> {
+        self.check_refund(&request).await?;
         let req = request.into_inner();
 
         match self
             .payment_handler
-            .process_refund(req.payment_id, req.amount_sats)
+            .process_refund(
+                req.payment_id,
+                req.amount_sats,
+                refund_method_from_proto(req.method()),
+                Some(req.invoice),
+            )
             .await
         {
-            Ok(payment) => {
-                let response = PaymentResponse {
-                    payment_id: payment.payment_id,
-                    status: payment.status,
-                    message: payment.description,
-                    amount_sats: payment.amount_sats,
-                    payment_hash: payment.payment_hash,
-                    timestamp: payment.timestamp,
+            Ok(payment) => Ok(Response::new(payment_to_response(payment))),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn get_lnurl_withdraw(
+        &self,
+        request: Request<LnurlWithdrawRequest>,
+    ) -> Result<Response<LnurlWithdrawResponse>, Status> {
+        let req = request.into_inner();
+
+        match self.payment_handler.lnurl_withdraw_request(&req.k1).await {
+            Ok(withdraw) => {
+                let response = LnurlWithdrawResponse {
+                    tag: withdraw.tag,
+                    callback: withdraw.callback,
+                    k1: withdraw.k1,
+                    min_withdrawable: withdraw.min_withdrawable,
+                    max_withdrawable: withdraw.max_withdrawable,
+                    default_description: withdraw.default_description,
                 };
                 Ok(Response::new(response))
             }
-            Err(e) => Err(Status::internal(e.to_string())),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn claim_refund(
+        &self,
+        request: Request<ClaimRefundRequest>,
+    ) -> Result<Response<PaymentResponse>, Status> {
+        let req = request.into_inner();
+
+        match self
+            .payment_handler
+            .claim_refund(&req.k1, req.invoice)
+            .await
+        {
+            Ok(payment) => Ok(Response::new(payment_to_response(payment))),
+            Err(e) => Err(error::to_status(&e)),
         }
     }
 
@@ -217,3 +493,268 @@ impl PaymentService for PaymentServiceImpl {
         Ok(Response::new(Box::pin(stream)))
     }
 }
+
+pub struct ChannelServiceImpl {
+    channel_manager: Arc<ChannelManager>,
+}
+
+impl ChannelServiceImpl {
+    pub fn new(channel_manager: Arc<ChannelManager>) -> Self {
+        Self { channel_manager }
+    }
+}
+
+#[tonic::async_trait]
+impl ChannelService for ChannelServiceImpl {
+    async fn open_channel(
+        &self,
+        request: Request<OpenChannelRequest>,
+    ) -> Result<Response<OpenChannelResponse>, Status> {
+        grpc_auth::check_amount(&request, request.get_ref().capacity_sats)?;
+        let req = request.into_inner();
+        if req.address.is_empty() {
+            return Err(error::invalid_argument(
+                "address",
+                "The peer's host:port is required",
+            ));
+        }
+
+        match self
+            .channel_manager
+            .open_channel(
+                &req.peer_id,
+                &req.address,
+                req.capacity_sats,
+                req.push_sats,
+                req.announce,
+            )
+            .await
+        {
+            Ok(channel) => Ok(Response::new(OpenChannelResponse {
+                channel: Some(channel_to_proto(channel)),
+            })),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn close_channel(
+        &self,
+        request: Request<CloseChannelRequest>,
+    ) -> Result<Response<CloseChannelResponse>, Status> {
+        let req = request.into_inner();
+
+        match self
+            .channel_manager
+            .close_channel(&req.channel_id, req.force)
+            .await
+        {
+            Ok(channel) => Ok(Response::new(CloseChannelResponse {
+                channel: Some(channel_to_proto(channel)),
+            })),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn list_channels(
+        &self,
+        request: Request<ListChannelsRequest>,
+    ) -> Result<Response<ListChannelsResponse>, Status> {
+        let req = request.into_inner();
+        let peer_id = (!req.peer_id.is_empty()).then_some(req.peer_id.as_str());
+
+        match self
+            .channel_manager
+            .list_channels(peer_id, req.include_closed)
+            .await
+        {
+            Ok(channels) => Ok(Response::new(ListChannelsResponse {
+                channels: channels.into_iter().map(channel_to_proto).collect(),
+            })),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn get_channel(
+        &self,
+        request: Request<GetChannelRequest>,
+    ) -> Result<Response<GetChannelResponse>, Status> {
+        let req = request.into_inner();
+
+        match self.channel_manager.channel(&req.channel_id).await {
+            Ok(channel) => Ok(Response::new(GetChannelResponse {
+                channel: Some(channel_to_proto(channel)),
+            })),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn export_network_graph(
+        &self,
+        request: Request<ExportNetworkGraphRequest>,
+    ) -> Result<Response<ExportNetworkGraphResponse>, Status> {
+        let req = request.into_inner();
+        let mut graph = self
+            .channel_manager
+            .network_graph()
+            .await
+            .map_err(|e| error::to_status(&e))?;
+
+        if !req.center_node_id.is_empty() {
+            let max_hops = match req.max_hops {
+                0 => DEFAULT_GRAPH_MAX_HOPS,
+                hops => hops as usize,
+            };
+            graph = graph
+                .subgraph(&req.center_node_id, max_hops)
+                .ok_or_else(|| {
+                    error::invalid_argument("center_node_id", "The node is not in the graph")
+                })?;
+        }
+
+        let rendered = match req.format() {
+            GraphFormat::Dot => graph.to_dot(),
+            GraphFormat::Unspecified | GraphFormat::Json => {
+                graph.to_json().map_err(|e| error::to_status(&e))?
+            }
+        };
+        let top_nodes = match req.top_nodes {
+            0 => DEFAULT_GRAPH_TOP_NODES,
+            n => n as usize,
+        };
+        let stats = graph.get_network_stats();
+        // Betweenness takes time in nodes times channels, so it runs off the
+        // async workers
+        let centrality = tokio::task::spawn_blocking(move || graph.centrality())
+            .await
+            .map_err(|e| error::to_status(&e.into()))?;
+
+        Ok(Response::new(ExportNetworkGraphResponse {
+            graph: rendered,
+            total_nodes: stats.total_nodes as u64,
+            total_channels: stats.total_channels as u64,
+            enabled_channels: stats.enabled_channels as u64,
+            total_capacity_sats: stats.total_capacity_sat,
+            top_nodes: centrality
+                .into_iter()
+                .take(top_nodes)
+                .map(|node| ProtoNodeCentrality {
+                    node_id: node.node_id,
+                    degree: node.degree as u32,
+                    capacity_sats: node.capacity_sat,
+                    betweenness: node.betweenness,
+                })
+                .collect(),
+        }))
+    }
+}
+
+/// Hops around `center_node_id` an export covers unless asked otherwise
+const DEFAULT_GRAPH_MAX_HOPS: usize = 2;
+/// Most central nodes an export lists unless asked otherwise
+const DEFAULT_GRAPH_TOP_NODES: usize = 10;
+
+fn channel_to_proto(channel: ChannelInfo) -> Channel {
+    let state = match channel.state {
+        ChannelState::Pending => ProtoChannelState::Pending,
+        ChannelState::Open => ProtoChannelState::Open,
+        ChannelState::Closing => ProtoChannelState::Closing,
+        ChannelState::Closed => ProtoChannelState::Closed,
+        ChannelState::Error => ProtoChannelState::Error,
+    };
+
+    Channel {
+        channel_id: channel.channel_id,
+        peer_id: channel.peer_id,
+        capacity_sats: channel.capacity_sats,
+        local_balance_sats: channel.local_balance_sats,
+        remote_balance_sats: channel.remote_balance_sats,
+        state: state as i32,
+        confirmations: channel.confirmations,
+        confirmations_required: channel.confirmations_required,
+        funding_txo: channel.funding_txo.unwrap_or_default(),
+        short_channel_id: channel
+            .short_channel_id
+            .map(|scid| scid.to_string())
+            .unwrap_or_default(),
+        created_at: channel.created_at.timestamp(),
+        updated_at: channel.updated_at.timestamp(),
+    }
+}
+
+pub struct PeerServiceImpl {
+    peer_manager: Arc<PeerManager>,
+}
+
+impl PeerServiceImpl {
+    pub fn new(peer_manager: Arc<PeerManager>) -> Self {
+        Self { peer_manager }
+    }
+}
+
+#[tonic::async_trait]
+impl PeerService for PeerServiceImpl {
+    async fn connect_peer(
+        &self,
+        request: Request<ConnectPeerRequest>,
+    ) -> Result<Response<ConnectPeerResponse>, Status> {
+        let req = request.into_inner();
+
+        match self.peer_manager.connect_peer(&req.uri, req.persist).await {
+            Ok(peer) => Ok(Response::new(ConnectPeerResponse {
+                peer: Some(peer_to_proto(peer)),
+            })),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn disconnect_peer(
+        &self,
+        request: Request<DisconnectPeerRequest>,
+    ) -> Result<Response<DisconnectPeerResponse>, Status> {
+        let req = request.into_inner();
+
+        match self.peer_manager.disconnect_peer(&req.node_id).await {
+            Ok(()) => Ok(Response::new(DisconnectPeerResponse {})),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+
+    async fn list_peers(
+        &self,
+        _request: Request<ListPeersRequest>,
+    ) -> Result<Response<ListPeersResponse>, Status> {
+        match self.peer_manager.list_peers().await {
+            Ok(peers) => Ok(Response::new(ListPeersResponse {
+                peers: peers.into_iter().map(peer_to_proto).collect(),
+            })),
+            Err(e) => Err(error::to_status(&e)),
+        }
+    }
+}
+
+fn peer_to_proto(peer: PeerInfo) -> Peer {
+    let state = match peer.state {
+        PeerState::Connected => ProtoPeerState::Connected,
+        PeerState::Connecting => ProtoPeerState::Connecting,
+        PeerState::Disconnected => ProtoPeerState::Disconnected,
+    };
+
+    Peer {
+        node_id: peer.node_id,
+        address: peer.address,
+        state: state as i32,
+        persisted: peer.persisted,
+        features: peer.features,
+        connect_latency_ms: peer.connect_latency_ms.unwrap_or_default(),
+        connected_since: peer
+            .connected_since
+            .map(|at| at.timestamp())
+            .unwrap_or_default(),
+        reconnect_attempts: peer.reconnect_attempts,
+        next_reconnect_at: peer
+            .next_reconnect_at
+            .map(|at| at.timestamp())
+            .unwrap_or_default(),
+        last_error: peer.last_error.unwrap_or_default(),
+    }
+}
@@ -0,0 +1,787 @@
+//! HTTP/JSON gateway mirroring `WalletService`, `PaymentService`,
+//! `ChannelService` and `PeerService`.
+//!
+//! Each route decodes the proto request message from JSON, authenticates and
+//! rate limits the caller exactly like the gRPC server would for the mirrored RPC, calls the
+//! same service implementation (with the HTTP headers passed on as gRPC
+//! metadata) and encodes the response message back to JSON. Errors
+//! keep their gRPC code and `ErrorInfo` details, rendered as a JSON error body.
+//! The LNURL-withdraw refund endpoints follow the LNURL wire format instead.
+
+use crate::grpc_auth::{rpc, Authenticator};
+use crate::grpc_rate_limit;
+use crate::grpc_services::{
+    ChannelServiceImpl, PaymentServiceImpl, PeerServiceImpl, WalletServiceImpl,
+};
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts, Path, Query, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use satsconnect_rust_engine::proto::satsconnect::channel::v1::{
+    channel_service_server::ChannelService, Channel, CloseChannelRequest, CloseChannelResponse,
+    ExportNetworkGraphRequest, ExportNetworkGraphResponse, GetChannelRequest, GetChannelResponse,
+    ListChannelsRequest, ListChannelsResponse, NodeCentrality, OpenChannelRequest,
+    OpenChannelResponse,
+};
+use satsconnect_rust_engine::proto::satsconnect::payment::v1::{
+    payment_service_server::PaymentService, ClaimRefundRequest, LnurlWithdrawRequest,
+    LnurlWithdrawResponse, PaymentRequest, PaymentResponse, PaymentStatusRequest, RefundInfo,
+    RefundRequest,
+};
+use satsconnect_rust_engine::proto::satsconnect::peer::v1::{
+    peer_service_server::PeerService, ConnectPeerRequest, ConnectPeerResponse,
+    DisconnectPeerRequest, DisconnectPeerResponse, ListPeersRequest, ListPeersResponse, Peer,
+};
+use satsconnect_rust_engine::proto::satsconnect::wallet::v1::{
+    wallet_service_server::WalletService, CreateWalletRequest, CreateWalletResponse,
+    EstimateRoutingFeeRequest, EstimateRoutingFeeResponse, GetBalanceRequest, GetBalanceResponse,
+    NewInvoiceRequest, NewInvoiceResponse, QuoteInvoiceRequest, QuoteInvoiceResponse,
+    SendPaymentRequest, SendPaymentResponse,
+};
+use satsconnect_rust_engine::rate_limit::RateLimiter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tonic::metadata::MetadataMap;
+use tonic::{Code, Status};
+use tonic_types::StatusExt;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityRequirement, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+/// OpenAPI description of the gateway, generated from the proto message types
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "SatsConnect Engine API", version = "v1"),
+    paths(
+        create_wallet,
+        get_balance,
+        quote_invoice,
+        new_invoice,
+        send_payment,
+        estimate_routing_fee,
+        process_payment,
+        get_payment_status,
+        process_refund,
+        open_channel,
+        list_channels,
+        get_channel,
+        close_channel,
+        export_network_graph,
+        connect_peer,
+        list_peers,
+        disconnect_peer,
+        lnurl_withdraw,
+        lnurl_withdraw_callback,
+    ),
+    components(schemas(
+        CreateWalletRequest,
+        CreateWalletResponse,
+        GetBalanceResponse,
+        QuoteInvoiceRequest,
+        QuoteInvoiceResponse,
+        NewInvoiceRequest,
+        NewInvoiceResponse,
+        SendPaymentRequest,
+        SendPaymentResponse,
+        EstimateRoutingFeeRequest,
+        EstimateRoutingFeeResponse,
+        PaymentRequest,
+        PaymentResponse,
+        RefundInfo,
+        RefundRequest,
+        Channel,
+        OpenChannelRequest,
+        OpenChannelResponse,
+        CloseChannelRequest,
+        CloseChannelResponse,
+        ListChannelsResponse,
+        GetChannelResponse,
+        NodeCentrality,
+        ExportNetworkGraphResponse,
+        Peer,
+        ConnectPeerRequest,
+        ConnectPeerResponse,
+        DisconnectPeerResponse,
+        ListPeersResponse,
+        LnurlWithdrawResponse,
+        LnurlStatus,
+        ErrorBody,
+        ErrorStatus,
+        ErrorInfoJson,
+    )),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+/// Every operation needs a bearer token unless it opts out
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+        openapi.security = Some(vec![SecurityRequirement::new(
+            "bearer",
+            Vec::<String>::new(),
+        )]);
+    }
+}
+
+#[derive(Clone)]
+struct Services {
+    wallet: Arc<WalletServiceImpl>,
+    payment: Arc<PaymentServiceImpl>,
+    channel: Arc<ChannelServiceImpl>,
+    peer: Arc<PeerServiceImpl>,
+    authenticator: Authenticator,
+    limiter: Arc<RateLimiter>,
+}
+
+/// Headers and address of the HTTP client
+struct Caller {
+    headers: HeaderMap,
+    ip: Option<IpAddr>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            headers: parts.headers.clone(),
+            ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+        })
+    }
+}
+
+impl Services {
+    /// Authenticate and rate limit the caller for `rpc` and wrap the message
+    /// for the service call, forwarding the HTTP headers as metadata
+    async fn request<T>(
+        &self,
+        rpc: &str,
+        caller: Caller,
+        message: T,
+    ) -> Result<tonic::Request<T>, Status> {
+        let token = self.authenticator.authorize(rpc, &caller.headers).await?;
+        grpc_rate_limit::check(&self.limiter, rpc, token.as_ref(), caller.ip).await?;
+
+        let mut request = tonic::Request::new(message);
+        *request.metadata_mut() = MetadataMap::from_headers(caller.headers);
+        request.extensions_mut().insert(Arc::clone(&self.limiter));
+        if let Some(token) = token {
+            request.extensions_mut().insert(token);
+        }
+        Ok(request)
+    }
+}
+
+/// Routes of the HTTP/JSON API, including `/openapi.json`. Serve them with
+/// `into_make_service_with_connect_info::<SocketAddr>()` so calls are also
+/// rate limited by client IP.
+pub fn router(
+    wallet: Arc<WalletServiceImpl>,
+    payment: Arc<PaymentServiceImpl>,
+    channel: Arc<ChannelServiceImpl>,
+    peer: Arc<PeerServiceImpl>,
+    authenticator: Authenticator,
+    limiter: Arc<RateLimiter>,
+) -> Router {
+    Router::new()
+        .route("/v1/wallet", post(create_wallet))
+        .route("/v1/wallet/balance", get(get_balance))
+        .route("/v1/wallet/invoices/quote", post(quote_invoice))
+        .route("/v1/wallet/invoices", post(new_invoice))
+        .route("/v1/wallet/payments", post(send_payment))
+        .route("/v1/wallet/routing-fee", post(estimate_routing_fee))
+        .route("/v1/payments", post(process_payment))
+        .route("/v1/payments/:payment_id", get(get_payment_status))
+        .route("/v1/payments/:payment_id/refunds", post(process_refund))
+        .route("/v1/channels", post(open_channel).get(list_channels))
+        .route("/v1/channels/:channel_id", get(get_channel))
+        .route("/v1/channels/:channel_id/close", post(close_channel))
+        .route("/v1/graph", get(export_network_graph))
+        .route("/v1/peers", post(connect_peer).get(list_peers))
+        .route("/v1/peers/:node_id", delete(disconnect_peer))
+        .route("/lnurl/withdraw/:k1", get(lnurl_withdraw))
+        .route("/lnurl/withdraw/:k1/callback", get(lnurl_withdraw_callback))
+        .route("/openapi.json", get(openapi))
+        .with_state(Services {
+            wallet,
+            payment,
+            channel,
+            peer,
+            authenticator,
+            limiter,
+        })
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// CORS policy for browser clients of the HTTP and gRPC-web APIs; an empty
+/// list allows any origin
+pub fn cors_layer(allowed_origins: &[String]) -> anyhow::Result<CorsLayer> {
+    let origins = if allowed_origins.is_empty() {
+        AllowOrigin::any()
+    } else {
+        let origins = allowed_origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|_| anyhow::anyhow!("Invalid CORS origin: {}", origin))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([
+            HeaderName::from_static("grpc-status"),
+            HeaderName::from_static("grpc-message"),
+            HeaderName::from_static("grpc-status-details-bin"),
+        ]))
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+fn reply<T>(result: Result<tonic::Response<T>, Status>) -> ApiResult<T> {
+    result
+        .map(|response| Json(response.into_inner()))
+        .map_err(ApiError)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/wallet",
+    request_body = CreateWalletRequest,
+    responses((status = 200, body = CreateWalletResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn create_wallet(
+    State(services): State<Services>,
+    caller: Caller,
+    Json(message): Json<CreateWalletRequest>,
+) -> ApiResult<CreateWalletResponse> {
+    let request = services
+        .request(rpc::CREATE_WALLET, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.wallet.create_wallet(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/wallet/balance",
+    responses((status = 200, body = GetBalanceResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn get_balance(
+    State(services): State<Services>,
+    caller: Caller,
+) -> ApiResult<GetBalanceResponse> {
+    let request = services
+        .request(rpc::GET_BALANCE, caller, GetBalanceRequest {})
+        .await
+        .map_err(ApiError)?;
+    reply(services.wallet.get_balance(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/wallet/invoices/quote",
+    request_body = QuoteInvoiceRequest,
+    responses((status = 200, body = QuoteInvoiceResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn quote_invoice(
+    State(services): State<Services>,
+    caller: Caller,
+    Json(message): Json<QuoteInvoiceRequest>,
+) -> ApiResult<QuoteInvoiceResponse> {
+    let request = services
+        .request(rpc::QUOTE_INVOICE, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.wallet.quote_invoice(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/wallet/invoices",
+    request_body = NewInvoiceRequest,
+    responses((status = 200, body = NewInvoiceResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn new_invoice(
+    State(services): State<Services>,
+    caller: Caller,
+    Json(message): Json<NewInvoiceRequest>,
+) -> ApiResult<NewInvoiceResponse> {
+    let request = services
+        .request(rpc::NEW_INVOICE, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.wallet.new_invoice(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/wallet/payments",
+    request_body = SendPaymentRequest,
+    responses((status = 200, body = SendPaymentResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn send_payment(
+    State(services): State<Services>,
+    caller: Caller,
+    Json(message): Json<SendPaymentRequest>,
+) -> ApiResult<SendPaymentResponse> {
+    let request = services
+        .request(rpc::SEND_PAYMENT, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.wallet.send_payment(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/wallet/routing-fee",
+    request_body = EstimateRoutingFeeRequest,
+    responses(
+        (status = 200, body = EstimateRoutingFeeResponse),
+        (status = "4XX", body = ErrorBody)
+    )
+)]
+async fn estimate_routing_fee(
+    State(services): State<Services>,
+    caller: Caller,
+    Json(message): Json<EstimateRoutingFeeRequest>,
+) -> ApiResult<EstimateRoutingFeeResponse> {
+    let request = services
+        .request(rpc::ESTIMATE_ROUTING_FEE, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.wallet.estimate_routing_fee(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/payments",
+    request_body = PaymentRequest,
+    responses((status = 200, body = PaymentResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn process_payment(
+    State(services): State<Services>,
+    caller: Caller,
+    Json(message): Json<PaymentRequest>,
+) -> ApiResult<PaymentResponse> {
+    let request = services
+        .request(rpc::PROCESS_PAYMENT, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.payment.process_payment(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/payments/{payment_id}",
+    params(("payment_id" = String, Path, description = "Payment identifier")),
+    responses((status = 200, body = PaymentResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn get_payment_status(
+    State(services): State<Services>,
+    caller: Caller,
+    Path(payment_id): Path<String>,
+) -> ApiResult<PaymentResponse> {
+    let message = PaymentStatusRequest { payment_id };
+    let request = services
+        .request(rpc::GET_PAYMENT_STATUS, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.payment.get_payment_status(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/payments/{payment_id}/refunds",
+    params(("payment_id" = String, Path, description = "Payment identifier")),
+    request_body = RefundRequest,
+    responses((status = 200, body = PaymentResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn process_refund(
+    State(services): State<Services>,
+    caller: Caller,
+    Path(payment_id): Path<String>,
+    Json(message): Json<RefundRequest>,
+) -> ApiResult<PaymentResponse> {
+    let message = RefundRequest {
+        payment_id,
+        ..message
+    };
+    let request = services
+        .request(rpc::PROCESS_REFUND, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.payment.process_refund(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/channels",
+    request_body = OpenChannelRequest,
+    responses((status = 200, body = OpenChannelResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn open_channel(
+    State(services): State<Services>,
+    caller: Caller,
+    Json(message): Json<OpenChannelRequest>,
+) -> ApiResult<OpenChannelResponse> {
+    let request = services
+        .request(rpc::OPEN_CHANNEL, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.channel.open_channel(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/channels",
+    params(
+        ("peerId" = Option<String>, Query, description = "Only channels with this peer"),
+        ("includeClosed" = Option<bool>, Query, description = "Also list closed channels")
+    ),
+    responses((status = 200, body = ListChannelsResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn list_channels(
+    State(services): State<Services>,
+    caller: Caller,
+    Query(message): Query<ListChannelsRequest>,
+) -> ApiResult<ListChannelsResponse> {
+    let request = services
+        .request(rpc::LIST_CHANNELS, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.channel.list_channels(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/channels/{channel_id}",
+    params(("channel_id" = String, Path, description = "Channel identifier")),
+    responses((status = 200, body = GetChannelResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn get_channel(
+    State(services): State<Services>,
+    caller: Caller,
+    Path(channel_id): Path<String>,
+) -> ApiResult<GetChannelResponse> {
+    let message = GetChannelRequest { channel_id };
+    let request = services
+        .request(rpc::GET_CHANNEL, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.channel.get_channel(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/channels/{channel_id}/close",
+    params(("channel_id" = String, Path, description = "Channel identifier")),
+    request_body = CloseChannelRequest,
+    responses((status = 200, body = CloseChannelResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn close_channel(
+    State(services): State<Services>,
+    caller: Caller,
+    Path(channel_id): Path<String>,
+    Json(message): Json<CloseChannelRequest>,
+) -> ApiResult<CloseChannelResponse> {
+    let message = CloseChannelRequest {
+        channel_id,
+        ..message
+    };
+    let request = services
+        .request(rpc::CLOSE_CHANNEL, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.channel.close_channel(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/graph",
+    params(
+        ("format" = Option<i32>, Query, description = "1 for JSON (the default), 2 for Graphviz DOT"),
+        ("centerNodeId" = Option<String>, Query, description = "Only the part of the graph around this node"),
+        ("maxHops" = Option<u32>, Query, description = "Hops around centerNodeId, 2 by default"),
+        ("topNodes" = Option<u32>, Query, description = "Most central nodes to list, 10 by default")
+    ),
+    responses((status = 200, body = ExportNetworkGraphResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn export_network_graph(
+    State(services): State<Services>,
+    caller: Caller,
+    Query(message): Query<ExportNetworkGraphRequest>,
+) -> ApiResult<ExportNetworkGraphResponse> {
+    let request = services
+        .request(rpc::EXPORT_NETWORK_GRAPH, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.channel.export_network_graph(request).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/peers",
+    request_body = ConnectPeerRequest,
+    responses((status = 200, body = ConnectPeerResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn connect_peer(
+    State(services): State<Services>,
+    caller: Caller,
+    Json(message): Json<ConnectPeerRequest>,
+) -> ApiResult<ConnectPeerResponse> {
+    let request = services
+        .request(rpc::CONNECT_PEER, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.peer.connect_peer(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/peers",
+    responses((status = 200, body = ListPeersResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn list_peers(
+    State(services): State<Services>,
+    caller: Caller,
+) -> ApiResult<ListPeersResponse> {
+    let request = services
+        .request(rpc::LIST_PEERS, caller, ListPeersRequest {})
+        .await
+        .map_err(ApiError)?;
+    reply(services.peer.list_peers(request).await)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/peers/{node_id}",
+    params(("node_id" = String, Path, description = "Hex encoded node public key")),
+    responses((status = 200, body = DisconnectPeerResponse), (status = "4XX", body = ErrorBody))
+)]
+async fn disconnect_peer(
+    State(services): State<Services>,
+    caller: Caller,
+    Path(node_id): Path<String>,
+) -> ApiResult<DisconnectPeerResponse> {
+    let message = DisconnectPeerRequest { node_id };
+    let request = services
+        .request(rpc::DISCONNECT_PEER, caller, message)
+        .await
+        .map_err(ApiError)?;
+    reply(services.peer.disconnect_peer(request).await)
+}
+
+/// LNURL `{"status": "OK"}` / `{"status": "ERROR", "reason": ...}` reply
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LnurlStatus {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl LnurlStatus {
+    fn error(status: Status) -> Response {
+        let reason = Some(status.message().to_string());
+        let body = Json(LnurlStatus {
+            status: "ERROR",
+            reason,
+        });
+        (http_status(status.code()), body).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LnurlCallbackQuery {
+    pr: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/lnurl/withdraw/{k1}",
+    params(("k1" = String, Path, description = "Refund withdraw secret")),
+    responses((status = 200, body = LnurlWithdrawResponse), (status = "4XX", body = LnurlStatus)),
+    security(())
+)]
+async fn lnurl_withdraw(
+    State(services): State<Services>,
+    caller: Caller,
+    Path(k1): Path<String>,
+) -> Response {
+    let message = LnurlWithdrawRequest { k1 };
+    let request = match services
+        .request(rpc::GET_LNURL_WITHDRAW, caller, message)
+        .await
+    {
+        Ok(request) => request,
+        Err(status) => return LnurlStatus::error(status),
+    };
+    match services.payment.get_lnurl_withdraw(request).await {
+        Ok(response) => Json(response.into_inner()).into_response(),
+        Err(status) => LnurlStatus::error(status),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/lnurl/withdraw/{k1}/callback",
+    params(("k1" = String, Path, description = "Refund withdraw secret"), ("pr" = String, Query, description = "BOLT11 invoice to pay")),
+    responses((status = 200, body = LnurlStatus), (status = "4XX", body = LnurlStatus)),
+    security(())
+)]
+async fn lnurl_withdraw_callback(
+    State(services): State<Services>,
+    caller: Caller,
+    Path(k1): Path<String>,
+    Query(query): Query<LnurlCallbackQuery>,
+) -> Response {
+    let message = ClaimRefundRequest {
+        k1,
+        invoice: query.pr,
+    };
+    let request = match services.request(rpc::CLAIM_REFUND, caller, message).await {
+        Ok(request) => request,
+        Err(status) => return LnurlStatus::error(status),
+    };
+    match services.payment.claim_refund(request).await {
+        Ok(_) => Json(LnurlStatus {
+            status: "OK",
+            reason: None,
+        })
+        .into_response(),
+        Err(status) => LnurlStatus::error(status),
+    }
+}
+
+/// JSON error body, in the shape Google APIs use for HTTP errors
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    error: ErrorStatus,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorStatus {
+    /// HTTP status code
+    code: u16,
+    /// gRPC status code name, e.g. `NOT_FOUND`
+    status: &'static str,
+    message: String,
+    details: Vec<ErrorInfoJson>,
+}
+
+/// `google.rpc.ErrorInfo` detail
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorInfoJson {
+    #[serde(rename = "@type")]
+    type_url: &'static str,
+    reason: String,
+    domain: String,
+    metadata: HashMap<String, String>,
+}
+
+/// A failed service call, rendered as an [`ErrorBody`]
+pub struct ApiError(Status);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let code = http_status(self.0.code());
+        let details = self
+            .0
+            .get_details_error_info()
+            .map(|info| ErrorInfoJson {
+                type_url: "type.googleapis.com/google.rpc.ErrorInfo",
+                reason: info.reason,
+                domain: info.domain,
+                metadata: info.metadata,
+            })
+            .into_iter()
+            .collect();
+
+        let body = ErrorBody {
+            error: ErrorStatus {
+                code: code.as_u16(),
+                status: code_name(self.0.code()),
+                message: self.0.message().to_string(),
+                details,
+            },
+        };
+        let mut response = (code, Json(body)).into_response();
+        if let Some(retry_after) = self
+            .0
+            .metadata()
+            .get("retry-after")
+            .and_then(|value| HeaderValue::from_bytes(value.as_bytes()).ok())
+        {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after);
+        }
+        response
+    }
+}
+
+/// HTTP status for a gRPC code, as mapped by grpc-gateway
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::Cancelled => StatusCode::from_u16(499).unwrap_or(StatusCode::BAD_REQUEST),
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "OK",
+        Code::Cancelled => "CANCELLED",
+        Code::Unknown => "UNKNOWN",
+        Code::InvalidArgument => "INVALID_ARGUMENT",
+        Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+        Code::NotFound => "NOT_FOUND",
+        Code::AlreadyExists => "ALREADY_EXISTS",
+        Code::PermissionDenied => "PERMISSION_DENIED",
+        Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+        Code::FailedPrecondition => "FAILED_PRECONDITION",
+        Code::Aborted => "ABORTED",
+        Code::OutOfRange => "OUT_OF_RANGE",
+        Code::Unimplemented => "UNIMPLEMENTED",
+        Code::Internal => "INTERNAL",
+        Code::Unavailable => "UNAVAILABLE",
+        Code::DataLoss => "DATA_LOSS",
+        Code::Unauthenticated => "UNAUTHENTICATED",
+    }
+}
@@ -1,4 +1,8 @@
 pub mod proto {
+    /// Encoded descriptors of the engine's services, for server reflection
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("satsconnect_descriptor");
+
     pub mod satsconnect {
         pub mod wallet {
             pub mod v1 {
@@ -10,27 +14,47 @@ pub mod proto {
                 include!("proto/satsconnect.payment.v1.rs");
             }
         }
+        pub mod channel {
+            pub mod v1 {
+                include!("proto/satsconnect.channel.v1.rs");
+            }
+        }
+        pub mod peer {
+            pub mod v1 {
+                include!("proto/satsconnect.peer.v1.rs");
+            }
+        }
     }
 }
 
 pub mod ai;
 pub mod bitcoin_client;
 pub mod config;
+pub mod engine_config;
+pub mod error;
 pub mod lightning;
 pub mod lightning_engine;
 pub mod lsp;
 pub mod monitoring;
 pub mod multi_currency;
+pub mod network;
 pub mod notifications;
 pub mod payment;
 pub mod performance;
 pub mod privacy;
+pub mod rate_limit;
+pub mod refund;
 pub mod secure_storage;
 pub mod security;
+pub mod shutdown;
+pub mod store;
+pub mod tls;
 pub mod wallet;
 
 #[cfg(test)]
 mod tests {
+    use crate::config::LightningConfig;
+    use crate::network::{self, default_network};
     use crate::payment::PaymentHandler;
     use crate::secure_storage::SecureStorage;
     use crate::wallet::WalletHandler;
@@ -47,8 +71,7 @@ mod tests {
 
         let (node_id, address) = result.unwrap();
         assert!(!node_id.is_empty());
-        assert!(!address.is_empty());
-        assert!(address.starts_with("tb1q"));
+        assert!(network::parse_address(&address, default_network()).is_ok());
     }
 
     #[tokio::test]
@@ -88,7 +111,7 @@ mod tests {
         assert!(result.is_ok());
 
         let (invoice, payment_hash) = result.unwrap();
-        assert!(invoice.starts_with("lnbc"));
+        assert!(network::parse_invoice(&invoice, default_network()).is_ok());
         assert!(!payment_hash.is_empty());
     }
 
@@ -102,8 +125,9 @@ mod tests {
                 Some("test_payment_123".to_string()),
                 "test_wallet".to_string(),
                 1000,
-                "lnbc1000u1p3k2v5cpp5test".to_string(),
+                test_invoice(4),
                 "Test payment".to_string(),
+                false,
             )
             .await;
 
@@ -115,6 +139,285 @@ mod tests {
         assert_eq!(payment.amount_sats, 1000);
     }
 
+    fn test_invoice(seed: u8) -> String {
+        use ldk_node::bitcoin::hashes::{sha256, Hash};
+        use ldk_node::bitcoin::secp256k1::{Secp256k1, SecretKey};
+        use ldk_node::lightning_invoice::{InvoiceBuilder, PaymentSecret};
+
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[42; 32]).unwrap();
+        InvoiceBuilder::new(network::invoice_currency(default_network()))
+            .description("Test payment".to_string())
+            .payment_hash(sha256::Hash::from_slice(&[seed; 32]).unwrap())
+            .payment_secret(PaymentSecret([seed; 32]))
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(144)
+            .amount_milli_satoshis(1_000_000)
+            .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &key))
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_payment_idempotency() {
+        use crate::payment::{Payment, PaymentError};
+        use std::collections::HashMap;
+
+        let temp_dir = std::env::temp_dir().join("satsconnect_idempotency_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        // Seed the ledger as if a previous run had already paid this invoice
+        let invoice = test_invoice(1);
+        let payment_hash = hex::encode([1u8; 32]);
+        let mut ledger = HashMap::new();
+        ledger.insert(
+            "pay_retry".to_string(),
+            Payment {
+                payment_id: "pay_retry".to_string(),
+                wallet_id: "test_wallet".to_string(),
+                amount_sats: 1000,
+                invoice: invoice.clone(),
+                description: "Test payment".to_string(),
+                status: "SUCCEEDED".to_string(),
+                payment_hash,
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                direction: "OUTBOUND".to_string(),
+                refunds: vec![],
+            },
+        );
+        std::fs::write(
+            temp_dir.join("payments.json"),
+            serde_json::to_string(&ledger).unwrap(),
+        )
+        .unwrap();
+
+        let payment_handler = PaymentHandler::with_data_dir(temp_dir.clone()).unwrap();
+
+        // Same id and invoice returns the stored record without paying again
+        let payment = payment_handler
+            .process_payment(
+                Some("pay_retry".to_string()),
+                "test_wallet".to_string(),
+                1000,
+                invoice.clone(),
+                "Test payment".to_string(),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(payment.status, "SUCCEEDED");
+
+        // Same id with a different invoice is a conflict
+        let err = payment_handler
+            .process_payment(
+                Some("pay_retry".to_string()),
+                "test_wallet".to_string(),
+                1000,
+                test_invoice(2),
+                "Test payment".to_string(),
+                false,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PaymentError>(),
+            Some(PaymentError::PaymentIdConflict(_))
+        ));
+
+        // A new id for an already-paid invoice is refused without force
+        let err = payment_handler
+            .process_payment(
+                Some("pay_other".to_string()),
+                "test_wallet".to_string(),
+                1000,
+                invoice,
+                "Test payment".to_string(),
+                false,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PaymentError>(),
+            Some(PaymentError::AlreadyPaid(_))
+        ));
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wrong_network_invoice_rejected() {
+        use crate::network::NetworkError;
+        use ldk_node::bitcoin::hashes::{sha256, Hash};
+        use ldk_node::bitcoin::secp256k1::{Secp256k1, SecretKey};
+        use ldk_node::lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+
+        let temp_dir = std::env::temp_dir().join("satsconnect_wrong_network_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let payment_handler = PaymentHandler::with_data_dir(temp_dir.clone()).unwrap();
+
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[42; 32]).unwrap();
+        let mainnet_invoice = InvoiceBuilder::new(Currency::Bitcoin)
+            .description("Mainnet payment".to_string())
+            .payment_hash(sha256::Hash::from_slice(&[5; 32]).unwrap())
+            .payment_secret(PaymentSecret([5; 32]))
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(144)
+            .amount_milli_satoshis(1_000_000)
+            .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &key))
+            .unwrap()
+            .to_string();
+
+        let err = payment_handler
+            .process_payment(
+                Some("pay_mainnet".to_string()),
+                "test_wallet".to_string(),
+                1000,
+                mainnet_invoice,
+                "Mainnet payment".to_string(),
+                false,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NetworkError>(),
+            Some(NetworkError::InvoiceNetworkMismatch { .. })
+        ));
+
+        // Nothing was recorded for the refused invoice
+        assert!(payment_handler
+            .get_payment_status("pay_mainnet".to_string())
+            .await
+            .is_err());
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_partial_lnurl_refund() {
+        use crate::payment::{Payment, PaymentError};
+        use crate::refund::{RefundMethod, RefundStatus};
+        use std::collections::HashMap;
+
+        let temp_dir = std::env::temp_dir().join("satsconnect_refund_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let mut ledger = HashMap::new();
+        ledger.insert(
+            "pay_received".to_string(),
+            Payment {
+                payment_id: "pay_received".to_string(),
+                wallet_id: "test_wallet".to_string(),
+                amount_sats: 1000,
+                invoice: test_invoice(3),
+                description: "Order 42".to_string(),
+                status: "SUCCEEDED".to_string(),
+                payment_hash: hex::encode([3u8; 32]),
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                direction: "INBOUND".to_string(),
+                refunds: vec![],
+            },
+        );
+        std::fs::write(
+            temp_dir.join("payments.json"),
+            serde_json::to_string(&ledger).unwrap(),
+        )
+        .unwrap();
+
+        let config = LightningConfig {
+            data_dir: temp_dir.clone(),
+            ..LightningConfig::default()
+        };
+
+        // Without a public gateway URL nobody could redeem the link
+        let payment_handler = PaymentHandler::with_config(config.clone(), None).unwrap();
+        let err = payment_handler
+            .process_refund(
+                "pay_received".to_string(),
+                400,
+                RefundMethod::LnurlWithdraw,
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PaymentError>(),
+            Some(PaymentError::LnurlNotConfigured)
+        ));
+        drop(payment_handler);
+
+        let payment_handler =
+            PaymentHandler::with_config(config, Some("https://pay.example.com/".to_string()))
+                .unwrap();
+
+        let payment = payment_handler
+            .process_refund(
+                "pay_received".to_string(),
+                400,
+                RefundMethod::LnurlWithdraw,
+                None,
+            )
+            .await
+            .unwrap();
+        let refund = &payment.refunds[0];
+        assert_eq!(refund.amount_sats, 400);
+        assert_eq!(refund.status, RefundStatus::AwaitingClaim);
+        assert!(refund.lnurl.as_ref().unwrap().starts_with("LNURL1"));
+
+        let withdraw = payment_handler
+            .lnurl_withdraw_request(refund.k1.as_ref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(withdraw.tag, "withdrawRequest");
+        assert_eq!(withdraw.max_withdrawable, 400_000);
+        assert_eq!(
+            withdraw.callback,
+            format!(
+                "https://pay.example.com/lnurl/withdraw/{}/callback",
+                refund.k1.as_ref().unwrap()
+            )
+        );
+
+        // Only 600 sats remain refundable
+        let err = payment_handler
+            .process_refund(
+                "pay_received".to_string(),
+                700,
+                RefundMethod::LnurlWithdraw,
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PaymentError>(),
+            Some(PaymentError::RefundExceedsRemaining { remaining: 600, .. })
+        ));
+
+        // The payer's invoice asks for 1000 sats; nothing is paid or recorded
+        let err = payment_handler
+            .process_refund(
+                "pay_received".to_string(),
+                0,
+                RefundMethod::Bolt11Invoice,
+                Some(test_invoice(5)),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PaymentError>(),
+            Some(PaymentError::InvalidRefund(_))
+        ));
+        let payment = payment_handler
+            .get_payment_status("pay_received".to_string())
+            .await
+            .unwrap();
+        assert_eq!(payment.refunds.len(), 1);
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
     #[tokio::test]
     async fn test_secure_storage() {
         let temp_dir = std::env::temp_dir().join("satsconnect_test");
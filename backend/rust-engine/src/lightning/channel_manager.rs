@@ -1,10 +1,36 @@
+use crate::lightning::network_graph::NetworkGraph;
+use crate::lightning_engine::LightningEngine;
+use crate::store::{channels, ChannelRecord, Store};
 use anyhow::Result;
+use bitcoin::secp256k1::PublicKey;
+use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::{ChannelDetails, UserChannelId};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
+#[derive(Debug, thiserror::Error)]
+pub enum ChannelError {
+    #[error("Channel size {capacity_sats} sats is below minimum {min_sats} sats")]
+    BelowMinimum { capacity_sats: u64, min_sats: u64 },
+    #[error("Channel size {capacity_sats} sats exceeds maximum {max_sats} sats")]
+    AboveMaximum { capacity_sats: u64, max_sats: u64 },
+    #[error("Push amount {push_sats} sats must be below the channel size")]
+    PushTooLarge { push_sats: u64 },
+    #[error("Maximum channels per peer ({0}) exceeded")]
+    TooManyChannels(u32),
+    #[error("Invalid peer id: {0}")]
+    InvalidPeerId(String),
+    #[error("Invalid peer address: {0}")]
+    InvalidAddress(String),
+    #[error("Channel {0} not found")]
+    NotFound(String),
+    #[error("Channel {0} is already closed")]
+    AlreadyClosed(String),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ChannelState {
     Pending,
@@ -16,17 +42,27 @@ pub enum ChannelState {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelInfo {
+    /// LDK's `user_channel_id`, which unlike the LDK channel id stays the same
+    /// from open to close
     pub channel_id: String,
     pub peer_id: String,
     pub capacity_sats: u64,
+    /// Spendable by us, excluding our channel reserve
     pub local_balance_sats: u64,
+    /// Receivable from the peer, excluding their channel reserve
     pub remote_balance_sats: u64,
     pub state: ChannelState,
+    /// `txid:vout` of the funding output once negotiated
+    pub funding_txo: Option<String>,
+    pub short_channel_id: Option<u64>,
+    pub confirmations: u32,
+    pub confirmations_required: u32,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ChannelConfig {
     pub min_channel_size: u64,
     pub max_channel_size: u64,
@@ -47,111 +83,344 @@ impl Default for ChannelConfig {
     }
 }
 
-/// Manages Lightning Network channels
+impl ChannelConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_channel_size > self.max_channel_size {
+            return Err(anyhow::anyhow!(
+                "Minimum channel size {} sats exceeds the maximum {} sats",
+                self.min_channel_size,
+                self.max_channel_size
+            ));
+        }
+        if self.max_channels_per_peer == 0 {
+            return Err(anyhow::anyhow!("max_channels_per_peer must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Refuse channel sizes outside the configured range
+    pub fn check_capacity(&self, capacity_sats: u64) -> Result<(), ChannelError> {
+        if capacity_sats < self.min_channel_size {
+            return Err(ChannelError::BelowMinimum {
+                capacity_sats,
+                min_sats: self.min_channel_size,
+            });
+        }
+        if capacity_sats > self.max_channel_size {
+            return Err(ChannelError::AboveMaximum {
+                capacity_sats,
+                max_sats: self.max_channel_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl ChannelState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "PENDING",
+            Self::Open => "OPEN",
+            Self::Closing => "CLOSING",
+            Self::Closed => "CLOSED",
+            Self::Error => "ERROR",
+        }
+    }
+
+    pub fn parse(state: &str) -> Result<Self> {
+        match state {
+            "PENDING" => Ok(Self::Pending),
+            "OPEN" => Ok(Self::Open),
+            "CLOSING" => Ok(Self::Closing),
+            "CLOSED" => Ok(Self::Closed),
+            "ERROR" => Ok(Self::Error),
+            other => Err(anyhow::anyhow!("Unknown channel state: {}", other)),
+        }
+    }
+}
+
+impl ChannelInfo {
+    /// Current view of a channel the node knows about
+    fn from_details(details: &ChannelDetails) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            channel_id: details.user_channel_id.0.to_string(),
+            peer_id: details.counterparty_node_id.to_string(),
+            capacity_sats: details.channel_value_sats,
+            local_balance_sats: details.outbound_capacity_msat / 1000,
+            remote_balance_sats: details.inbound_capacity_msat / 1000,
+            state: if details.is_channel_ready {
+                ChannelState::Open
+            } else {
+                ChannelState::Pending
+            },
+            funding_txo: details.funding_txo.map(|txo| txo.to_string()),
+            short_channel_id: details.short_channel_id,
+            confirmations: details.confirmations.unwrap_or(0),
+            confirmations_required: details.confirmations_required.unwrap_or(0),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn to_record(&self) -> ChannelRecord {
+        ChannelRecord {
+            channel_id: self.channel_id.clone(),
+            peer_id: self.peer_id.clone(),
+            capacity_sats: self.capacity_sats,
+            local_balance_sats: self.local_balance_sats,
+            remote_balance_sats: self.remote_balance_sats,
+            state: self.state.as_str().to_string(),
+            funding_txo: self.funding_txo.clone(),
+            short_channel_id: self.short_channel_id,
+            confirmations: self.confirmations,
+            confirmations_required: self.confirmations_required,
+            created_at: self.created_at.timestamp(),
+            updated_at: self.updated_at.timestamp(),
+        }
+    }
+
+    fn from_record(record: ChannelRecord) -> Result<Self> {
+        Ok(Self {
+            channel_id: record.channel_id,
+            peer_id: record.peer_id,
+            capacity_sats: record.capacity_sats,
+            local_balance_sats: record.local_balance_sats,
+            remote_balance_sats: record.remote_balance_sats,
+            state: ChannelState::parse(&record.state)?,
+            funding_txo: record.funding_txo,
+            short_channel_id: record.short_channel_id,
+            confirmations: record.confirmations,
+            confirmations_required: record.confirmations_required,
+            created_at: timestamp_to_datetime(record.created_at),
+            updated_at: timestamp_to_datetime(record.updated_at),
+        })
+    }
+}
+
+fn timestamp_to_datetime(timestamp: i64) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(timestamp, 0).unwrap_or_default()
+}
+
+/// Opens and closes channels through the LDK node and keeps a record of them,
+/// so channels are still listed after LDK has forgotten them on close
 #[derive(Debug)]
 pub struct ChannelManager {
-    channels: Arc<RwLock<HashMap<String, ChannelInfo>>>,
+    store: Store,
     config: ChannelConfig,
+    engine: Arc<LightningEngine>,
 }
 
 impl ChannelManager {
-    pub fn new(config: ChannelConfig) -> Self {
+    /// Create a manager backed by a non-persistent store
+    pub fn new(config: ChannelConfig, engine: Arc<LightningEngine>) -> Self {
+        let store = Store::open_in_memory().expect("in-memory store should always open");
+        Self::with_store(config, store, engine)
+    }
+
+    /// Create a manager that keeps channel records in `store`
+    pub fn with_store(config: ChannelConfig, store: Store, engine: Arc<LightningEngine>) -> Self {
         Self {
-            channels: Arc::new(RwLock::new(HashMap::new())),
+            store,
             config,
+            engine,
         }
     }
 
-    /// Create a new channel with a peer
-    pub async fn create_channel(&self, peer_id: String, capacity_sats: u64) -> Result<String> {
-        // Validate channel size
-        if capacity_sats < self.config.min_channel_size {
-            return Err(anyhow::anyhow!(
-                "Channel size {} sats is below minimum {} sats",
-                capacity_sats,
-                self.config.min_channel_size
-            ));
-        }
-
-        if capacity_sats > self.config.max_channel_size {
-            return Err(anyhow::anyhow!(
-                "Channel size {} sats exceeds maximum {} sats",
-                capacity_sats,
-                self.config.max_channel_size
-            ));
+    /// Open a channel to `peer_id` at `address` (`host:port`), funded from the
+    /// on-chain wallet and pushing `push_sats` to the peer
+    pub async fn open_channel(
+        &self,
+        peer_id: &str,
+        address: &str,
+        capacity_sats: u64,
+        push_sats: u64,
+        announce: bool,
+    ) -> Result<ChannelInfo> {
+        self.config.check_capacity(capacity_sats)?;
+        if push_sats >= capacity_sats {
+            return Err(ChannelError::PushTooLarge { push_sats }.into());
         }
-
-        // Check existing channels with this peer
-        let channels = self.channels.read().await;
-        let peer_channel_count = channels.values().filter(|ch| ch.peer_id == peer_id).count();
-
-        if peer_channel_count >= self.config.max_channels_per_peer as usize {
-            return Err(anyhow::anyhow!(
-                "Maximum channels per peer ({}) exceeded",
-                self.config.max_channels_per_peer
-            ));
+        let node_id = PublicKey::from_str(peer_id)
+            .map_err(|_| ChannelError::InvalidPeerId(peer_id.to_string()))?;
+        let address = SocketAddress::from_str(address)
+            .map_err(|_| ChannelError::InvalidAddress(address.to_string()))?;
+
+        let peer_id = node_id.to_string();
+        let active = self.list_channels(Some(&peer_id), false).await?.len();
+        if active >= self.config.max_channels_per_peer as usize {
+            return Err(ChannelError::TooManyChannels(self.config.max_channels_per_peer).into());
         }
-        drop(channels);
 
-        // Generate channel ID
-        let channel_id = format!("ch_{}", uuid::Uuid::new_v4());
-
-        // Create channel info
-        let channel_info = ChannelInfo {
+        let user_channel_id = self
+            .engine
+            .open_channel(
+                node_id,
+                address,
+                capacity_sats,
+                (push_sats > 0).then_some(push_sats * 1000),
+                announce,
+            )
+            .await?;
+
+        // Record the channel right away so it is listed even if the peer
+        // rejects it before the next refresh
+        let now = chrono::Utc::now();
+        let channel_id = user_channel_id.0.to_string();
+        let pending = ChannelInfo {
             channel_id: channel_id.clone(),
             peer_id: peer_id.clone(),
             capacity_sats,
             local_balance_sats: 0,
-            remote_balance_sats: capacity_sats,
+            remote_balance_sats: 0,
             state: ChannelState::Pending,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
+            funding_txo: None,
+            short_channel_id: None,
+            confirmations: 0,
+            confirmations_required: 0,
+            created_at: now,
+            updated_at: now,
         };
-
-        // Store channel
-        let mut channels = self.channels.write().await;
-        channels.insert(channel_id.clone(), channel_info);
+        self.store
+            .with_conn(|c| channels::upsert(c, &pending.to_record()))?;
 
         info!(
-            "Created channel {} with peer {} (capacity: {} sats)",
+            "Opening channel {} with peer {} (capacity: {} sats)",
             channel_id, peer_id, capacity_sats
         );
+        self.channel(&channel_id).await
+    }
+
+    /// On-chain funds available to open channels with
+    pub async fn spendable_onchain_sats(&self) -> Result<u64> {
+        self.engine.spendable_onchain_sats().await
+    }
+
+    /// Close a channel cooperatively, or force close it
+    pub async fn close_channel(&self, channel_id: &str, force: bool) -> Result<ChannelInfo> {
+        let channel = self.channel(channel_id).await?;
+        if channel.state == ChannelState::Closed {
+            return Err(ChannelError::AlreadyClosed(channel_id.to_string()).into());
+        }
+        // Records from before channels were opened through the node have no
+        // LDK counterpart
+        let user_channel_id = channel_id
+            .parse::<u128>()
+            .map(UserChannelId)
+            .map_err(|_| ChannelError::NotFound(channel_id.to_string()))?;
+        let counterparty = PublicKey::from_str(&channel.peer_id)
+            .map_err(|_| ChannelError::InvalidPeerId(channel.peer_id.clone()))?;
+
+        self.engine
+            .close_channel(user_channel_id, counterparty, force)
+            .await?;
+        self.modify_channel(channel_id, |channel| channel.state = ChannelState::Closing)?;
+        info!(
+            "{} channel {}",
+            if force { "Force closing" } else { "Closing" },
+            channel_id
+        );
+        self.channel(channel_id).await
+    }
+
+    /// Channels as the node currently reports them, optionally only those with
+    /// `peer_id`; closed channels are left out unless `include_closed`
+    pub async fn list_channels(
+        &self,
+        peer_id: Option<&str>,
+        include_closed: bool,
+    ) -> Result<Vec<ChannelInfo>> {
+        self.refresh().await?;
+        let channels = match peer_id {
+            Some(peer_id) => self.load_channels(|c| channels::list_by_peer(c, peer_id)),
+            None => self.load_channels(channels::list),
+        };
+        Ok(channels
+            .into_iter()
+            .filter(|ch| include_closed || ch.state != ChannelState::Closed)
+            .collect())
+    }
+
+    /// A channel as the node currently reports it
+    pub async fn channel(&self, channel_id: &str) -> Result<ChannelInfo> {
+        self.refresh().await?;
+        self.get_channel(channel_id)
+            .await?
+            .ok_or_else(|| ChannelError::NotFound(channel_id.to_string()).into())
+    }
 
-        Ok(channel_id)
+    /// The public graph the node learned from gossip
+    pub async fn network_graph(&self) -> Result<NetworkGraph> {
+        self.engine.network_graph().await
+    }
+
+    /// Update the records from the node's channel list
+    pub async fn refresh(&self) -> Result<()> {
+        let live = self.engine.list_channels().await?;
+        self.reconcile(live.iter().map(ChannelInfo::from_details).collect())
+    }
+
+    /// Store the node's view of its channels. Channels the node no longer
+    /// lists have closed; a cooperative close stays `Closing` until then.
+    fn reconcile(&self, live: Vec<ChannelInfo>) -> Result<()> {
+        self.store.transaction(|tx| {
+            let mut stored: HashMap<String, ChannelInfo> = HashMap::new();
+            for record in channels::list(tx)? {
+                stored.insert(record.channel_id.clone(), ChannelInfo::from_record(record)?);
+            }
+
+            let mut listed = HashSet::new();
+            for mut channel in live {
+                if let Some(previous) = stored.get(&channel.channel_id) {
+                    channel.created_at = previous.created_at;
+                    if previous.state == ChannelState::Closing {
+                        channel.state = ChannelState::Closing;
+                    }
+                    if previous.state != channel.state {
+                        info!("Channel {} is now {:?}", channel.channel_id, channel.state);
+                    }
+                }
+                listed.insert(channel.channel_id.clone());
+                channels::upsert(tx, &channel.to_record())?;
+            }
+
+            for mut channel in stored.into_values() {
+                if listed.contains(&channel.channel_id) || channel.state == ChannelState::Closed {
+                    continue;
+                }
+                info!("Channel {} is now Closed", channel.channel_id);
+                channel.state = ChannelState::Closed;
+                channel.local_balance_sats = 0;
+                channel.remote_balance_sats = 0;
+                channel.updated_at = chrono::Utc::now();
+                channels::upsert(tx, &channel.to_record())?;
+            }
+            Ok(())
+        })
     }
 
     /// Get channel information
     pub async fn get_channel(&self, channel_id: &str) -> Result<Option<ChannelInfo>> {
-        let channels = self.channels.read().await;
-        Ok(channels.get(channel_id).cloned())
+        self.store
+            .with_conn(|c| channels::get(c, channel_id))?
+            .map(ChannelInfo::from_record)
+            .transpose()
     }
 
     /// Get all channels
     pub async fn get_all_channels(&self) -> Vec<ChannelInfo> {
-        let channels = self.channels.read().await;
-        channels.values().cloned().collect()
+        self.load_channels(channels::list)
     }
 
     /// Get channels by peer
     pub async fn get_channels_by_peer(&self, peer_id: &str) -> Vec<ChannelInfo> {
-        let channels = self.channels.read().await;
-        channels
-            .values()
-            .filter(|ch| ch.peer_id == peer_id)
-            .cloned()
-            .collect()
+        self.load_channels(|c| channels::list_by_peer(c, peer_id))
     }
 
     /// Update channel state
     pub async fn update_channel_state(&self, channel_id: &str, state: ChannelState) -> Result<()> {
-        let mut channels = self.channels.write().await;
-        if let Some(channel) = channels.get_mut(channel_id) {
-            channel.state = state;
-            channel.updated_at = chrono::Utc::now();
-            info!("Updated channel {} state to {:?}", channel_id, state);
-        } else {
-            return Err(anyhow::anyhow!("Channel {} not found", channel_id));
-        }
+        self.modify_channel(channel_id, |channel| channel.state = state.clone())?;
+        info!("Updated channel {} state to {:?}", channel_id, state);
         Ok(())
     }
 
@@ -162,115 +431,99 @@ impl ChannelManager {
         local_balance_sats: u64,
         remote_balance_sats: u64,
     ) -> Result<()> {
-        let mut channels = self.channels.write().await;
-        if let Some(channel) = channels.get_mut(channel_id) {
+        self.modify_channel(channel_id, |channel| {
             channel.local_balance_sats = local_balance_sats;
             channel.remote_balance_sats = remote_balance_sats;
-            channel.updated_at = chrono::Utc::now();
-            info!(
-                "Updated channel {} balance: local={}, remote={}",
-                channel_id, local_balance_sats, remote_balance_sats
-            );
-        } else {
-            return Err(anyhow::anyhow!("Channel {} not found", channel_id));
-        }
-        Ok(())
-    }
-
-    /// Close a channel
-    pub async fn close_channel(&self, channel_id: &str) -> Result<()> {
-        let mut channels = self.channels.write().await;
-        if let Some(channel) = channels.get_mut(channel_id) {
-            channel.state = ChannelState::Closing;
-            channel.updated_at = chrono::Utc::now();
-            info!("Closing channel {}", channel_id);
-        } else {
-            return Err(anyhow::anyhow!("Channel {} not found", channel_id));
-        }
+        })?;
+        info!(
+            "Updated channel {} balance: local={}, remote={}",
+            channel_id, local_balance_sats, remote_balance_sats
+        );
         Ok(())
     }
 
     /// Get total channel capacity
     pub async fn get_total_capacity(&self) -> u64 {
-        let channels = self.channels.read().await;
-        channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Open)
-            .map(|ch| ch.capacity_sats)
-            .sum()
+        self.open_channels().iter().map(|ch| ch.capacity_sats).sum()
     }
 
     /// Get total local balance
     pub async fn get_total_local_balance(&self) -> u64 {
-        let channels = self.channels.read().await;
-        channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Open)
+        self.open_channels()
+            .iter()
             .map(|ch| ch.local_balance_sats)
             .sum()
     }
 
     /// Get total remote balance
     pub async fn get_total_remote_balance(&self) -> u64 {
-        let channels = self.channels.read().await;
-        channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Open)
+        self.open_channels()
+            .iter()
             .map(|ch| ch.remote_balance_sats)
             .sum()
     }
 
     /// Get channel statistics
     pub async fn get_channel_stats(&self) -> ChannelStats {
-        let channels = self.channels.read().await;
-
-        let total_channels = channels.len();
-        let open_channels = channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Open)
-            .count();
-        let pending_channels = channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Pending)
-            .count();
-        let closing_channels = channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Closing)
-            .count();
-        let closed_channels = channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Closed)
-            .count();
-
-        let total_capacity: u64 = channels
-            .values()
+        let channels = self.load_channels(channels::list);
+        let count = |state: ChannelState| channels.iter().filter(|ch| ch.state == state).count();
+        let open: Vec<&ChannelInfo> = channels
+            .iter()
             .filter(|ch| ch.state == ChannelState::Open)
-            .map(|ch| ch.capacity_sats)
-            .sum();
-
-        let total_local_balance: u64 = channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Open)
-            .map(|ch| ch.local_balance_sats)
-            .sum();
-
-        let total_remote_balance: u64 = channels
-            .values()
-            .filter(|ch| ch.state == ChannelState::Open)
-            .map(|ch| ch.remote_balance_sats)
-            .sum();
+            .collect();
 
         ChannelStats {
-            total_channels,
-            open_channels,
-            pending_channels,
-            closing_channels,
-            closed_channels,
-            total_capacity,
-            total_local_balance,
-            total_remote_balance,
+            total_channels: channels.len(),
+            open_channels: open.len(),
+            pending_channels: count(ChannelState::Pending),
+            closing_channels: count(ChannelState::Closing),
+            closed_channels: count(ChannelState::Closed),
+            total_capacity: open.iter().map(|ch| ch.capacity_sats).sum(),
+            total_local_balance: open.iter().map(|ch| ch.local_balance_sats).sum(),
+            total_remote_balance: open.iter().map(|ch| ch.remote_balance_sats).sum(),
         }
     }
+
+    fn open_channels(&self) -> Vec<ChannelInfo> {
+        self.load_channels(|c| channels::list_by_state(c, ChannelState::Open.as_str()))
+    }
+
+    /// Run a channel query, logging and skipping failures for the infallible getters
+    fn load_channels(
+        &self,
+        query: impl FnOnce(&rusqlite::Connection) -> Result<Vec<ChannelRecord>>,
+    ) -> Vec<ChannelInfo> {
+        let records = match self.store.with_conn(query) {
+            Ok(records) => records,
+            Err(e) => {
+                error!("Failed to load channels: {}", e);
+                return Vec::new();
+            }
+        };
+
+        records
+            .into_iter()
+            .filter_map(|record| match ChannelInfo::from_record(record) {
+                Ok(channel) => Some(channel),
+                Err(e) => {
+                    warn!("Skipping unreadable channel record: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Read-modify-write a stored channel in one transaction
+    fn modify_channel(&self, channel_id: &str, f: impl FnOnce(&mut ChannelInfo)) -> Result<()> {
+        self.store.transaction(|tx| {
+            let record = channels::get(tx, channel_id)?
+                .ok_or_else(|| ChannelError::NotFound(channel_id.to_string()))?;
+            let mut channel = ChannelInfo::from_record(record)?;
+            f(&mut channel);
+            channel.updated_at = chrono::Utc::now();
+            channels::upsert(tx, &channel.to_record())
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -288,59 +541,164 @@ pub struct ChannelStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lightning_engine::LightningError;
+    use bitcoin::Network;
 
-    #[tokio::test]
-    async fn test_create_channel() {
-        let config = ChannelConfig::default();
-        let manager = ChannelManager::new(config);
+    const PEER: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
 
-        let channel_id = manager
-            .create_channel("peer123".to_string(), 1_000_000)
-            .await
-            .unwrap();
-        assert!(!channel_id.is_empty());
+    fn manager_with_store(store: Store) -> ChannelManager {
+        // The node is never started; the tests feed channel lists directly
+        let engine = LightningEngine::new(
+            std::env::temp_dir().join("satsconnect_channel_manager_test"),
+            Network::Regtest,
+        );
+        ChannelManager::with_store(ChannelConfig::default(), store, Arc::new(engine))
+    }
+
+    fn manager() -> ChannelManager {
+        manager_with_store(Store::open_in_memory().unwrap())
+    }
+
+    fn live(channel_id: &str, capacity_sats: u64, state: ChannelState) -> ChannelInfo {
+        let now = chrono::Utc::now();
+        ChannelInfo {
+            channel_id: channel_id.to_string(),
+            peer_id: PEER.to_string(),
+            capacity_sats,
+            local_balance_sats: capacity_sats / 2,
+            remote_balance_sats: capacity_sats / 2,
+            state,
+            funding_txo: None,
+            short_channel_id: None,
+            confirmations: 0,
+            confirmations_required: 3,
+            created_at: now,
+            updated_at: now,
+        }
+    }
 
-        let channel = manager.get_channel(&channel_id).await.unwrap();
-        assert!(channel.is_some());
-        let channel = channel.unwrap();
-        assert_eq!(channel.peer_id, "peer123");
-        assert_eq!(channel.capacity_sats, 1_000_000);
-        assert_eq!(channel.state, ChannelState::Pending);
+    async fn open_error(
+        manager: &ChannelManager,
+        peer_id: &str,
+        address: &str,
+        capacity_sats: u64,
+        push_sats: u64,
+    ) -> anyhow::Error {
+        manager
+            .open_channel(peer_id, address, capacity_sats, push_sats, false)
+            .await
+            .unwrap_err()
     }
 
     #[tokio::test]
     async fn test_channel_validation() {
-        let config = ChannelConfig::default();
-        let manager = ChannelManager::new(config);
+        let manager = manager();
+        let addr = "127.0.0.1:9735";
+
+        let err = open_error(&manager, PEER, addr, 50_000, 0).await;
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ChannelError::BelowMinimum { .. })
+        ));
+        let err = open_error(&manager, PEER, addr, 20_000_000, 0).await;
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ChannelError::AboveMaximum { .. })
+        ));
+        let err = open_error(&manager, PEER, addr, 1_000_000, 1_000_000).await;
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ChannelError::PushTooLarge { .. })
+        ));
+        let err = open_error(&manager, "peer123", addr, 1_000_000, 0).await;
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ChannelError::InvalidPeerId(_))
+        ));
+        let err = open_error(&manager, PEER, "nowhere", 1_000_000, 0).await;
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ChannelError::InvalidAddress(_))
+        ));
+
+        // A valid request needs the node
+        let err = open_error(&manager, PEER, addr, 1_000_000, 0).await;
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(LightningError::NodeNotRunning)
+        ));
+    }
 
-        // Test minimum channel size
-        let result = manager.create_channel("peer123".to_string(), 50_000).await;
-        assert!(result.is_err());
+    #[tokio::test]
+    async fn test_reconcile_tracks_node_channels() {
+        let manager = manager();
+        manager
+            .reconcile(vec![
+                live("1", 1_000_000, ChannelState::Pending),
+                live("2", 2_000_000, ChannelState::Open),
+            ])
+            .unwrap();
 
-        // Test maximum channel size
-        let result = manager
-            .create_channel("peer123".to_string(), 20_000_000)
-            .await;
-        assert!(result.is_err());
+        let stats = manager.get_channel_stats().await;
+        assert_eq!(stats.total_channels, 2);
+        assert_eq!(stats.pending_channels, 1);
+        assert_eq!(stats.total_capacity, 2_000_000);
+        let created_at = manager.get_channel("1").await.unwrap().unwrap().created_at;
+
+        // The funding confirmed and channel 2 was closed by the peer
+        let mut confirmed = live("1", 1_000_000, ChannelState::Open);
+        confirmed.confirmations = 3;
+        confirmed.created_at = created_at + chrono::Duration::hours(1);
+        manager.reconcile(vec![confirmed]).unwrap();
+
+        let channel = manager.get_channel("1").await.unwrap().unwrap();
+        assert_eq!(channel.state, ChannelState::Open);
+        assert_eq!(channel.confirmations, 3);
+        assert_eq!(channel.created_at, created_at);
+
+        let closed = manager.get_channel("2").await.unwrap().unwrap();
+        assert_eq!(closed.state, ChannelState::Closed);
+        assert_eq!(closed.local_balance_sats, 0);
     }
 
     #[tokio::test]
-    async fn test_channel_stats() {
-        let config = ChannelConfig::default();
-        let manager = ChannelManager::new(config);
-
-        // Create some channels
+    async fn test_cooperative_close_stays_closing() {
+        let manager = manager();
         manager
-            .create_channel("peer1".to_string(), 1_000_000)
-            .await
+            .reconcile(vec![live("1", 1_000_000, ChannelState::Open)])
             .unwrap();
         manager
-            .create_channel("peer2".to_string(), 2_000_000)
+            .update_channel_state("1", ChannelState::Closing)
             .await
             .unwrap();
 
-        let stats = manager.get_channel_stats().await;
-        assert_eq!(stats.total_channels, 2);
-        assert_eq!(stats.pending_channels, 2);
+        // LDK lists the channel until the closing transaction is agreed
+        manager
+            .reconcile(vec![live("1", 1_000_000, ChannelState::Open)])
+            .unwrap();
+        let channel = manager.get_channel("1").await.unwrap().unwrap();
+        assert_eq!(channel.state, ChannelState::Closing);
+
+        manager.reconcile(Vec::new()).unwrap();
+        let channel = manager.get_channel("1").await.unwrap().unwrap();
+        assert_eq!(channel.state, ChannelState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_channels_survive_restart() {
+        let store = Store::open_in_memory().unwrap();
+
+        let manager = manager_with_store(store.clone());
+        let mut channel = live("1", 1_000_000, ChannelState::Open);
+        channel.funding_txo = Some(format!("{}:0", "ab".repeat(32)));
+        channel.short_channel_id = Some(123_456);
+        manager.reconcile(vec![channel]).unwrap();
+        drop(manager);
+
+        let manager = manager_with_store(store);
+        let channel = manager.get_channel("1").await.unwrap().unwrap();
+        assert_eq!(channel.state, ChannelState::Open);
+        assert_eq!(channel.short_channel_id, Some(123_456));
+        assert_eq!(manager.get_total_capacity().await, 1_000_000);
     }
 }
@@ -1,59 +1,135 @@
+//! Planning of circular rebalances between our own channels.
+//!
+//! A channel with too much local balance would pay a self-invoice that comes
+//! back in through a channel with too little. The LDK node picks the path of
+//! every payment itself and does not route to our own node, so the engine
+//! cannot make such a payment; the rebalancer works out which channels to
+//! pair and how much to move, and leaves moving it to the operator.
+
+use crate::lightning_engine::LightningEngine;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use ldk_node::ChannelDetails;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::{info, error, warn, instrument};
-use chrono::{DateTime, Utc};
+use tracing::{info, instrument, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RebalanceConfig {
+    /// Rebalance channels whose local balance is further than this from half
+    /// their capacity, relative to half the capacity (0.1 = 10%)
+    pub rebalance_threshold: f64,
+    /// Smallest amount worth moving, in sats
+    pub min_rebalance_amount: u64,
+    /// Largest amount moved by one operation, in sats
+    pub max_rebalance_amount: u64,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self {
+            rebalance_threshold: 0.1,        // 10% threshold
+            min_rebalance_amount: 10_000,    // 10k sats
+            max_rebalance_amount: 1_000_000, // 1M sats
+        }
+    }
+}
+
+impl RebalanceConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.rebalance_threshold > 0.0 && self.rebalance_threshold <= 1.0) {
+            return Err(anyhow::anyhow!(
+                "rebalance_threshold must be above 0 and at most 1, got {}",
+                self.rebalance_threshold
+            ));
+        }
+        if self.min_rebalance_amount > self.max_rebalance_amount {
+            return Err(anyhow::anyhow!(
+                "Minimum rebalance amount {} sats exceeds the maximum {} sats",
+                self.min_rebalance_amount,
+                self.max_rebalance_amount
+            ));
+        }
+        Ok(())
+    }
+}
 
 /// Channel rebalancing service for optimal Lightning Network liquidity
 #[derive(Debug)]
 pub struct ChannelRebalancer {
     channels: Arc<RwLock<HashMap<String, ChannelInfo>>>,
-    rebalance_threshold: f64, // 0.1 = 10% threshold
-    min_rebalance_amount: u64, // Minimum sats to rebalance
-    max_rebalance_amount: u64, // Maximum sats to rebalance
+    config: RebalanceConfig,
+    node: Arc<dyn RebalanceNode>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelInfo {
+    /// LDK's `user_channel_id`, as used by `ChannelManager`
     pub channel_id: String,
+    /// Hex-encoded node id of the channel peer
+    pub peer_id: String,
+    /// Spendable by us, in sats
     pub local_balance: u64,
+    /// Receivable from the peer, in sats
     pub remote_balance: u64,
     pub capacity: u64,
     pub is_active: bool,
-    pub last_rebalance: Option<DateTime<Utc>>,
-    pub rebalance_count: u32,
 }
 
+/// Liquidity to move from one of our channels to another
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RebalanceOperation {
     pub operation_id: String,
     pub from_channel: String,
     pub to_channel: String,
+    /// Amount to move, in sats
     pub amount: u64,
-    pub status: RebalanceStatus,
     pub created_at: DateTime<Utc>,
-    pub completed_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum RebalanceStatus {
-    Pending,
-    InProgress,
-    Completed,
-    Failed,
-    Cancelled,
+/// What the rebalancer needs from the Lightning node
+#[async_trait::async_trait]
+pub trait RebalanceNode: Send + Sync + std::fmt::Debug {
+    /// Our usable channels with their current balances
+    async fn channels(&self) -> Result<Vec<ChannelInfo>>;
+}
+
+#[async_trait::async_trait]
+impl RebalanceNode for LightningEngine {
+    async fn channels(&self) -> Result<Vec<ChannelInfo>> {
+        Ok(self
+            .list_channels()
+            .await?
+            .iter()
+            .filter(|channel| channel.is_usable)
+            .map(ChannelInfo::from_details)
+            .collect())
+    }
+}
+
+impl ChannelInfo {
+    fn from_details(details: &ChannelDetails) -> Self {
+        Self {
+            channel_id: details.user_channel_id.0.to_string(),
+            peer_id: details.counterparty_node_id.to_string(),
+            local_balance: details.outbound_capacity_msat / 1000,
+            remote_balance: details.inbound_capacity_msat / 1000,
+            capacity: details.channel_value_sats,
+            is_active: details.is_usable,
+        }
+    }
 }
 
 impl ChannelRebalancer {
-    /// Create a new channel rebalancer
-    pub fn new() -> Self {
+    /// Create a rebalancer that plans over the channels of `node`
+    pub fn new(config: RebalanceConfig, node: Arc<dyn RebalanceNode>) -> Self {
         Self {
             channels: Arc::new(RwLock::new(HashMap::new())),
-            rebalance_threshold: 0.1, // 10% threshold
-            min_rebalance_amount: 10000, // 10k sats
-            max_rebalance_amount: 1000000, // 1M sats
+            config,
+            node,
         }
     }
 
@@ -65,6 +141,17 @@ impl ChannelRebalancer {
         Ok(())
     }
 
+    /// Reload channel balances from the node
+    pub async fn refresh_channels(&self) -> Result<()> {
+        let live = self.node.channels().await?;
+        let mut channels = self.channels.write().await;
+        *channels = live
+            .into_iter()
+            .map(|channel| (channel.channel_id.clone(), channel))
+            .collect();
+        Ok(())
+    }
+
     /// Check if channels need rebalancing
     #[instrument(skip(self))]
     pub async fn check_rebalancing_needed(&self) -> Result<Vec<RebalanceOperation>> {
@@ -73,23 +160,29 @@ impl ChannelRebalancer {
 
         // Find channels that are imbalanced
         let mut imbalanced_channels = Vec::new();
-        for (channel_id, channel) in channels.iter() {
+        for channel in channels.values() {
             if !channel.is_active {
                 continue;
             }
 
             let balance_ratio = self.calculate_balance_ratio(channel);
-            if balance_ratio > self.rebalance_threshold {
-                imbalanced_channels.push((channel_id.clone(), channel.clone(), balance_ratio));
+            if balance_ratio > self.config.rebalance_threshold {
+                imbalanced_channels.push((channel, balance_ratio));
             }
         }
 
         // Sort by imbalance severity
-        imbalanced_channels.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+        imbalanced_channels.sort_by(|a, b| b.1.total_cmp(&a.1));
 
-        // Create rebalance operations
-        for (channel_id, channel, ratio) in imbalanced_channels {
-            if let Some(operation) = self.create_rebalance_operation(&channel, ratio).await? {
+        // Create rebalance operations, moving each channel at most once
+        let mut planned = HashSet::new();
+        for (channel, _) in imbalanced_channels {
+            if planned.contains(&channel.channel_id) {
+                continue;
+            }
+            if let Some(operation) = self.create_rebalance_operation(&channels, channel, &planned) {
+                planned.insert(operation.from_channel.clone());
+                planned.insert(operation.to_channel.clone());
                 rebalance_ops.push(operation);
             }
         }
@@ -100,166 +193,106 @@ impl ChannelRebalancer {
 
     /// Calculate balance ratio (0.0 = perfectly balanced, 1.0 = completely imbalanced)
     fn calculate_balance_ratio(&self, channel: &ChannelInfo) -> f64 {
-        if channel.capacity == 0 {
+        let ideal_balance = channel.capacity / 2;
+        if ideal_balance == 0 {
             return 0.0;
         }
 
-        let ideal_balance = channel.capacity / 2;
-        let actual_balance = channel.local_balance;
-        let difference = if actual_balance > ideal_balance {
-            actual_balance - ideal_balance
-        } else {
-            ideal_balance - actual_balance
-        };
-
+        let difference = channel.local_balance.abs_diff(ideal_balance);
         (difference as f64) / (ideal_balance as f64)
     }
 
-    /// Create a rebalance operation
-    async fn create_rebalance_operation(
+    /// Pair an imbalanced channel with one imbalanced the other way
+    fn create_rebalance_operation(
         &self,
+        channels: &HashMap<String, ChannelInfo>,
         channel: &ChannelInfo,
-        ratio: f64,
-    ) -> Result<Option<RebalanceOperation>> {
-        let rebalance_amount = self.calculate_rebalance_amount(channel, ratio);
-        
-        if rebalance_amount < self.min_rebalance_amount {
-            return Ok(None);
-        }
-
-        if rebalance_amount > self.max_rebalance_amount {
-            return Ok(None);
+        planned: &HashSet<String>,
+    ) -> Option<RebalanceOperation> {
+        let rebalance_amount = self
+            .calculate_rebalance_amount(channel)
+            .min(self.config.max_rebalance_amount);
+        if rebalance_amount < self.config.min_rebalance_amount {
+            return None;
         }
 
-        // Find a suitable target channel
-        let channels = self.channels.read().await;
-        let target_channel = self.find_target_channel(&channels, channel, rebalance_amount).await?;
-
-        if target_channel.is_none() {
-            warn!("No suitable target channel found for rebalancing");
-            return Ok(None);
-        }
+        // A channel with too much local balance sends; one with too little receives
+        let sends = channel.local_balance > channel.capacity / 2;
+        let Some(counterpart) =
+            self.find_counterpart_channel(channels, channel, sends, rebalance_amount, planned)
+        else {
+            warn!(
+                "No channel to rebalance {} with found for {} sats",
+                channel.channel_id, rebalance_amount
+            );
+            return None;
+        };
+        let (from, to) = if sends {
+            (channel, counterpart)
+        } else {
+            (counterpart, channel)
+        };
 
-        let operation = RebalanceOperation {
+        Some(RebalanceOperation {
             operation_id: format!("rebalance_{}", uuid::Uuid::new_v4()),
-            from_channel: channel.channel_id.clone(),
-            to_channel: target_channel.unwrap().channel_id.clone(),
+            from_channel: from.channel_id.clone(),
+            to_channel: to.channel_id.clone(),
             amount: rebalance_amount,
-            status: RebalanceStatus::Pending,
             created_at: Utc::now(),
-            completed_at: None,
-        };
-
-        Ok(Some(operation))
+        })
     }
 
     /// Calculate rebalance amount
-    fn calculate_rebalance_amount(&self, channel: &ChannelInfo, ratio: f64) -> u64 {
+    fn calculate_rebalance_amount(&self, channel: &ChannelInfo) -> u64 {
         let ideal_balance = channel.capacity / 2;
-        let current_balance = channel.local_balance;
-        
-        if current_balance > ideal_balance {
-            // Need to send out
-            let excess = current_balance - ideal_balance;
-            (excess as f64 * 0.8) as u64 // Rebalance 80% of excess
-        } else {
-            // Need to receive
-            let deficit = ideal_balance - current_balance;
-            (deficit as f64 * 0.8) as u64 // Rebalance 80% of deficit
-        }
+        // Rebalance 80% of the excess or deficit
+        (channel.local_balance.abs_diff(ideal_balance) as f64 * 0.8) as u64
     }
 
-    /// Find a suitable target channel for rebalancing
-    async fn find_target_channel(
+    /// Find a channel that can take the other side of moving `amount`: one
+    /// short of local balance if `channel` sends, one with excess otherwise
+    fn find_counterpart_channel<'a>(
         &self,
-        channels: &HashMap<String, ChannelInfo>,
-        source_channel: &ChannelInfo,
+        channels: &'a HashMap<String, ChannelInfo>,
+        channel: &ChannelInfo,
+        channel_sends: bool,
         amount: u64,
-    ) -> Result<Option<&ChannelInfo>> {
-        for (_, channel) in channels.iter() {
-            if channel.channel_id == source_channel.channel_id {
-                continue;
-            }
-
-            if !channel.is_active {
-                continue;
-            }
-
-            // Check if this channel can receive the rebalance
-            let ideal_balance = channel.capacity / 2;
-            let current_balance = channel.local_balance;
-            
-            if current_balance < ideal_balance {
-                let capacity = ideal_balance - current_balance;
-                if capacity >= amount {
-                    return Ok(Some(channel));
+        planned: &HashSet<String>,
+    ) -> Option<&'a ChannelInfo> {
+        channels
+            .values()
+            .filter(|c| {
+                c.channel_id != channel.channel_id
+                    && c.is_active
+                    && !planned.contains(&c.channel_id)
+            })
+            .filter(|c| {
+                let ideal_balance = c.capacity / 2;
+                if channel_sends {
+                    c.local_balance < ideal_balance && ideal_balance - c.local_balance >= amount
+                } else {
+                    c.local_balance > ideal_balance && c.local_balance - ideal_balance >= amount
                 }
-            }
-        }
-
-        Ok(None)
-    }
-
-    /// Execute a rebalance operation
-    #[instrument(skip(self))]
-    pub async fn execute_rebalance(&self, operation: &mut RebalanceOperation) -> Result<bool> {
-        operation.status = RebalanceStatus::InProgress;
-        
-        info!("Executing rebalance operation: {} ({} sats)", operation.operation_id, operation.amount);
-
-        // In a real implementation, this would:
-        // 1. Create a Lightning invoice on the target channel
-        // 2. Send payment from source channel to target channel
-        // 3. Update channel balances
-        // 4. Handle errors and retries
-
-        // Simulate rebalance execution
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-
-        // For now, assume success
-        operation.status = RebalanceStatus::Completed;
-        operation.completed_at = Some(Utc::now());
-
-        // Update channel information
-        self.update_channel_after_rebalance(operation).await?;
-
-        info!("Rebalance operation completed: {}", operation.operation_id);
-        Ok(true)
-    }
-
-    /// Update channel information after rebalance
-    async fn update_channel_after_rebalance(&self, operation: &RebalanceOperation) -> Result<()> {
-        let mut channels = self.channels.write().await;
-        
-        // Update source channel
-        if let Some(source_channel) = channels.get_mut(&operation.from_channel) {
-            source_channel.local_balance = source_channel.local_balance.saturating_sub(operation.amount);
-            source_channel.last_rebalance = Some(Utc::now());
-            source_channel.rebalance_count += 1;
-        }
-
-        // Update target channel
-        if let Some(target_channel) = channels.get_mut(&operation.to_channel) {
-            target_channel.local_balance += operation.amount;
-            target_channel.last_rebalance = Some(Utc::now());
-        }
-
-        Ok(())
+            })
+            .max_by_key(|c| c.local_balance.abs_diff(c.capacity / 2))
     }
 
     /// Get rebalancing statistics
     pub async fn get_rebalancing_stats(&self) -> Result<RebalancingStats> {
         let channels = self.channels.read().await;
-        
+
         let total_channels = channels.len();
         let active_channels = channels.values().filter(|c| c.is_active).count();
-        let imbalanced_channels = channels.values()
-            .filter(|c| c.is_active && self.calculate_balance_ratio(c) > self.rebalance_threshold)
+        let imbalanced_channels = channels
+            .values()
+            .filter(|c| {
+                c.is_active && self.calculate_balance_ratio(c) > self.config.rebalance_threshold
+            })
             .count();
 
-        let total_rebalances: u32 = channels.values().map(|c| c.rebalance_count).sum();
         let avg_balance_ratio = if active_channels > 0 {
-            let total_ratio: f64 = channels.values()
+            let total_ratio: f64 = channels
+                .values()
                 .filter(|c| c.is_active)
                 .map(|c| self.calculate_balance_ratio(c))
                 .sum();
@@ -272,9 +305,8 @@ impl ChannelRebalancer {
             total_channels,
             active_channels,
             imbalanced_channels,
-            total_rebalances,
             avg_balance_ratio,
-            rebalance_threshold: self.rebalance_threshold,
+            rebalance_threshold: self.config.rebalance_threshold,
         })
     }
 }
@@ -284,56 +316,96 @@ pub struct RebalancingStats {
     pub total_channels: usize,
     pub active_channels: usize,
     pub imbalanced_channels: usize,
-    pub total_rebalances: u32,
     pub avg_balance_ratio: f64,
     pub rebalance_threshold: f64,
 }
 
-impl Default for ChannelRebalancer {
-    fn default() -> Self {
-        Self::new()
+/// Fixtures for the rebalancer and the liquidity manager's tests
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestNode {
+        channels: Vec<ChannelInfo>,
+    }
+
+    #[async_trait::async_trait]
+    impl RebalanceNode for TestNode {
+        async fn channels(&self) -> Result<Vec<ChannelInfo>> {
+            Ok(self.channels.clone())
+        }
+    }
+
+    /// An active 1M sats channel with `local_balance` on our side
+    pub(crate) fn channel(channel_id: &str, peer_id: &str, local_balance: u64) -> ChannelInfo {
+        ChannelInfo {
+            channel_id: channel_id.to_string(),
+            peer_id: peer_id.to_string(),
+            local_balance,
+            remote_balance: 1_000_000 - local_balance,
+            capacity: 1_000_000,
+            is_active: true,
+        }
+    }
+
+    /// A rebalancer over a node with these channels
+    pub(crate) fn rebalancer(
+        config: RebalanceConfig,
+        channels: Vec<ChannelInfo>,
+    ) -> ChannelRebalancer {
+        ChannelRebalancer::new(config, Arc::new(TestNode { channels }))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::test_support::{channel, rebalancer};
     use super::*;
 
+    fn setup(channels: Vec<ChannelInfo>) -> ChannelRebalancer {
+        rebalancer(RebalanceConfig::default(), channels)
+    }
+
     #[tokio::test]
-    async fn test_channel_rebalancer() {
-        let rebalancer = ChannelRebalancer::new();
-        
-        let channel = ChannelInfo {
-            channel_id: "test_channel".to_string(),
-            local_balance: 1000000, // 1M sats
-            remote_balance: 0, // 0 sats (completely imbalanced)
-            capacity: 1000000, // 1M sats total
-            is_active: true,
-            last_rebalance: None,
-            rebalance_count: 0,
-        };
-        
-        rebalancer.update_channel(channel).await.unwrap();
-        
+    async fn test_plans_from_excess_to_deficit() {
+        let rebalancer = setup(vec![
+            channel("out", "a", 900_000),
+            channel("in", "b", 100_000),
+            channel("even", "c", 500_000),
+        ]);
+        rebalancer.refresh_channels().await.unwrap();
+
         let operations = rebalancer.check_rebalancing_needed().await.unwrap();
-        assert!(!operations.is_empty());
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].from_channel, "out");
+        assert_eq!(operations[0].to_channel, "in");
+        // 80% of the 400k sats excess
+        assert_eq!(operations[0].amount, 320_000);
+
+        let stats = rebalancer.get_rebalancing_stats().await.unwrap();
+        assert_eq!(stats.active_channels, 3);
+        assert_eq!(stats.imbalanced_channels, 2);
+    }
+
+    #[tokio::test]
+    async fn test_no_counterpart_no_operation() {
+        // Both channels have too much local balance
+        let rebalancer = setup(vec![channel("a", "a", 900_000), channel("b", "b", 800_000)]);
+        rebalancer.refresh_channels().await.unwrap();
+        assert!(rebalancer
+            .check_rebalancing_needed()
+            .await
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
     fn test_balance_ratio_calculation() {
-        let rebalancer = ChannelRebalancer::new();
-        
-        let channel = ChannelInfo {
-            channel_id: "test".to_string(),
-            local_balance: 100000,
-            remote_balance: 900000,
-            capacity: 1000000,
-            is_active: true,
-            last_rebalance: None,
-            rebalance_count: 0,
-        };
-        
+        let rebalancer = setup(Vec::new());
+
+        let channel = channel("test", "a", 100_000);
         let ratio = rebalancer.calculate_balance_ratio(&channel);
-        assert!(ratio > 0.0);
+        assert!((ratio - 0.8).abs() < 1e-9);
     }
 }
@@ -0,0 +1,336 @@
+//! Rapid Gossip Sync.
+//!
+//! With `gossip_source = "rgs"` the LDK node downloads graph snapshots from
+//! an RGS server instead of following gossip from its peers, which is much
+//! lighter on mobile. Analytics read the node's own graph; `GossipSync`
+//! watches the snapshot time the node reports, records it in the store so it
+//! survives restarts and reports how fresh the graph is.
+
+use crate::config::LightningConfig;
+use crate::lightning_engine::LightningEngine;
+use crate::shutdown::Shutdown;
+use crate::store::{self, meta, Store};
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How often the node's snapshot time is checked; the node itself pulls a
+/// snapshot about once an hour
+pub const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How fresh the Rapid Gossip Sync graph is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GossipFreshness {
+    /// Time of the newest gossip in the last snapshot applied
+    pub last_sync_timestamp: Option<u64>,
+    pub age_secs: Option<u64>,
+    /// No snapshot yet, or the last one is older than the configured age
+    pub is_stale: bool,
+}
+
+/// What `GossipSync` needs from the node
+#[async_trait::async_trait]
+pub trait GossipNode: Send + Sync {
+    /// Time of the newest gossip in the last snapshot the node applied, if it
+    /// is running and has applied one
+    async fn latest_rgs_snapshot_timestamp(&self) -> Option<u64>;
+}
+
+#[async_trait::async_trait]
+impl GossipNode for LightningEngine {
+    async fn latest_rgs_snapshot_timestamp(&self) -> Option<u64> {
+        self.node_status()
+            .await
+            .and_then(|status| status.latest_rgs_snapshot_timestamp)
+    }
+}
+
+pub struct GossipSync {
+    max_snapshot_age_secs: u64,
+    store: Store,
+    node: Arc<dyn GossipNode>,
+}
+
+impl GossipSync {
+    pub fn new(config: &LightningConfig, store: Store, node: Arc<dyn GossipNode>) -> Self {
+        Self {
+            max_snapshot_age_secs: config.rgs_max_snapshot_age_secs,
+            store,
+            node,
+        }
+    }
+
+    /// Record the node's latest snapshot time if it moved on, returning the
+    /// time recorded
+    pub async fn sync(&self) -> Result<Option<u64>> {
+        let Some(timestamp) = self.node.latest_rgs_snapshot_timestamp().await else {
+            return self.last_sync_timestamp();
+        };
+        let recorded = self.last_sync_timestamp()?;
+        if recorded.map_or(true, |recorded| timestamp > recorded) {
+            self.store.with_conn(|conn| {
+                meta::set(conn, meta::RGS_SNAPSHOT_TIMESTAMP, &timestamp.to_string())
+            })?;
+            info!(
+                "Node applied a Rapid Gossip Sync snapshot up to {}",
+                timestamp
+            );
+            return Ok(Some(timestamp));
+        }
+        Ok(recorded)
+    }
+
+    /// Check now and then every [`POLL_INTERVAL`] until shutdown
+    pub async fn run(self: Arc<Self>, shutdown: Shutdown) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        let triggered = shutdown.triggered();
+        tokio::pin!(triggered);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut triggered => return,
+            }
+            if let Err(e) = self.sync().await {
+                warn!("Failed to record the Rapid Gossip Sync time: {:#}", e);
+            }
+        }
+    }
+
+    /// Time of the newest gossip synced, kept across restarts
+    pub fn last_sync_timestamp(&self) -> Result<Option<u64>> {
+        let value = self
+            .store
+            .with_conn(|conn| meta::get(conn, meta::RGS_SNAPSHOT_TIMESTAMP))?;
+        Ok(value.and_then(|v| v.parse().ok()))
+    }
+
+    pub fn freshness(&self) -> Result<GossipFreshness> {
+        let last_sync_timestamp = self.last_sync_timestamp()?;
+        let age_secs = last_sync_timestamp.map(|t| (store::now().max(0) as u64).saturating_sub(t));
+        Ok(GossipFreshness {
+            last_sync_timestamp,
+            age_secs,
+            is_stale: age_secs.map_or(true, |age| age > self.max_snapshot_age_secs),
+        })
+    }
+}
+
+/// A node whose snapshot time the gossip tests set
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    pub(crate) struct TestNode {
+        snapshot_timestamp: Mutex<Option<u64>>,
+    }
+
+    impl TestNode {
+        pub(crate) fn set_snapshot_timestamp(&self, timestamp: Option<u64>) {
+            *self.snapshot_timestamp.lock().unwrap() = timestamp;
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GossipNode for TestNode {
+        async fn latest_rgs_snapshot_timestamp(&self) -> Option<u64> {
+            *self.snapshot_timestamp.lock().unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::TestNode;
+    use super::*;
+    use crate::config::GossipSource;
+    use crate::lightning::NetworkGraph;
+    use axum::extract::Path;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use bitcoin::blockdata::constants::ChainHash;
+    use bitcoin::Network;
+    use ldk_node::lightning::routing::gossip::NetworkGraph as LdkNetworkGraph;
+    use ldk_node::lightning::util::logger::{Logger, Record};
+    use lightning_rapid_gossip_sync::RapidGossipSync;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    struct TestLogger;
+
+    impl Logger for TestLogger {
+        fn log(&self, _record: Record) {}
+    }
+
+    /// Pulls snapshots from the configured RGS server the way ldk-node does,
+    /// asking for what changed since the last one it applied
+    struct RgsClientNode {
+        server_url: String,
+        graph: LdkNetworkGraph<Arc<TestLogger>>,
+        synced_to: AtomicU32,
+    }
+
+    impl RgsClientNode {
+        fn new(config: &LightningConfig) -> Self {
+            Self {
+                server_url: config.rgs_server_url().unwrap(),
+                graph: LdkNetworkGraph::new(config.network, Arc::new(TestLogger)),
+                synced_to: AtomicU32::new(0),
+            }
+        }
+
+        async fn fetch(&self) -> Option<u32> {
+            let since = self.synced_to.load(Ordering::SeqCst);
+            let url = format!("{}/{}", self.server_url, since);
+            let response = reqwest::get(&url).await.ok()?.error_for_status().ok()?;
+            let snapshot = response.bytes().await.ok()?;
+            RapidGossipSync::new(&self.graph, Arc::new(TestLogger))
+                .update_network_graph_no_std(&snapshot, None)
+                .ok()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GossipNode for RgsClientNode {
+        async fn latest_rgs_snapshot_timestamp(&self) -> Option<u64> {
+            if let Some(timestamp) = self.fetch().await {
+                self.synced_to.store(timestamp, Ordering::SeqCst);
+            }
+            match self.synced_to.load(Ordering::SeqCst) {
+                0 => None,
+                timestamp => Some(u64::from(timestamp)),
+            }
+        }
+    }
+
+    /// An RGS snapshot without channels, seen up to `timestamp`
+    fn empty_snapshot(timestamp: u32) -> Vec<u8> {
+        let mut snapshot = vec![76, 68, 75, 1];
+        snapshot.extend_from_slice(&ChainHash::using_genesis_block(Network::Regtest).to_bytes());
+        snapshot.extend_from_slice(&timestamp.to_be_bytes());
+        // No node ids, announcements or updates
+        snapshot.extend_from_slice(&[0; 12]);
+        snapshot
+    }
+
+    /// Serve `snapshot.bin` from a directory the way an RGS server does,
+    /// recording the timestamps asked for
+    async fn serve(dir: &TempDir) -> (String, Arc<Mutex<Vec<String>>>) {
+        let file = dir.path().join("snapshot.bin");
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+        let app = Router::new().route(
+            "/snapshot/:since",
+            get(move |Path(since): Path<String>| {
+                let file = file.clone();
+                let seen = Arc::clone(&seen);
+                async move {
+                    seen.lock().unwrap().push(since);
+                    std::fs::read(file).map_err(|_| StatusCode::NOT_FOUND)
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/snapshot", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, requests)
+    }
+
+    fn setup(store: Store) -> (GossipSync, Arc<TestNode>) {
+        let node = Arc::new(TestNode::default());
+        let gossip = GossipSync::new(
+            &LightningConfig::default(),
+            store,
+            Arc::clone(&node) as Arc<dyn GossipNode>,
+        );
+        (gossip, node)
+    }
+
+    #[tokio::test]
+    async fn test_records_node_snapshot_time() {
+        let store = Store::open_in_memory().unwrap();
+        let (gossip, node) = setup(store.clone());
+
+        // The node has not applied a snapshot yet
+        assert_eq!(gossip.sync().await.unwrap(), None);
+        let freshness = gossip.freshness().unwrap();
+        assert_eq!(freshness.last_sync_timestamp, None);
+        assert!(freshness.is_stale);
+
+        let now = store::now() as u64;
+        node.set_snapshot_timestamp(Some(now - 60));
+        assert_eq!(gossip.sync().await.unwrap(), Some(now - 60));
+        let freshness = gossip.freshness().unwrap();
+        assert_eq!(freshness.last_sync_timestamp, Some(now - 60));
+        assert!(!freshness.is_stale);
+
+        // Kept across a restart, before the node has synced again
+        let (gossip, _node) = setup(store);
+        assert_eq!(gossip.sync().await.unwrap(), Some(now - 60));
+        assert!(!gossip.freshness().unwrap().is_stale);
+    }
+
+    #[tokio::test]
+    async fn test_stale_snapshot_time() {
+        let (gossip, node) = setup(Store::open_in_memory().unwrap());
+
+        let old = store::now() as u64 - 3 * 24 * 3600;
+        node.set_snapshot_timestamp(Some(old));
+        gossip.sync().await.unwrap();
+        assert!(gossip.freshness().unwrap().is_stale);
+
+        // A stopped node leaves the recorded time alone
+        node.set_snapshot_timestamp(None);
+        assert_eq!(gossip.sync().await.unwrap(), Some(old));
+        assert_eq!(gossip.last_sync_timestamp().unwrap(), Some(old));
+    }
+
+    #[tokio::test]
+    async fn test_sync_from_file_served_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let (url, requests) = serve(&dir).await;
+        let config = LightningConfig {
+            network: Network::Regtest,
+            gossip_source: GossipSource::Rgs,
+            rgs_server_url: Some(url),
+            ..LightningConfig::default()
+        };
+        let store = Store::open_in_memory().unwrap();
+        let node = Arc::new(RgsClientNode::new(&config));
+        let gossip = GossipSync::new(&config, store.clone(), node);
+
+        // Nothing served yet
+        assert_eq!(gossip.sync().await.unwrap(), None);
+        assert!(gossip.freshness().unwrap().is_stale);
+
+        let now = store::now() as u32;
+        let file = dir.path().join("snapshot.bin");
+        std::fs::write(&file, empty_snapshot(now - 60)).unwrap();
+        assert_eq!(gossip.sync().await.unwrap(), Some(u64::from(now - 60)));
+        let freshness = gossip.freshness().unwrap();
+        assert_eq!(freshness.last_sync_timestamp, Some(u64::from(now - 60)));
+        assert!(!freshness.is_stale);
+        let snapshot = std::fs::read(&file).unwrap();
+        let graph = NetworkGraph::from_rapid_gossip_snapshot(&snapshot, Network::Regtest).unwrap();
+        assert_eq!(graph.get_all_channels().len(), 0);
+
+        // Later pulls only ask for what changed
+        std::fs::write(&file, empty_snapshot(now)).unwrap();
+        assert_eq!(gossip.sync().await.unwrap(), Some(u64::from(now)));
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec!["0".to_string(), "0".to_string(), (now - 60).to_string()]
+        );
+
+        // Recorded in the store, so a restart still knows how fresh the graph is
+        let (gossip, _node) = setup(store);
+        assert_eq!(gossip.last_sync_timestamp().unwrap(), Some(u64::from(now)));
+        assert!(!gossip.freshness().unwrap().is_stale);
+    }
+}
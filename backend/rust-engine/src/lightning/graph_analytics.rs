@@ -0,0 +1,343 @@
+//! Export and topology analytics for a `NetworkGraph`.
+//!
+//! Graphs can be dumped as JSON or Graphviz DOT, cut down to the part around
+//! a node, and ranked by centrality. Centrality looks at the routable
+//! topology only: enabled channels, with parallel channels between two nodes
+//! counting once for betweenness.
+
+use crate::lightning::network_graph::{NetworkChannelInfo, NetworkGraph, NodeInfo};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+
+/// How central a node is to the routable graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeCentrality {
+    pub node_id: String,
+    /// Enabled channels
+    pub degree: usize,
+    /// Capacity of the enabled channels, the capacity-weighted degree
+    pub capacity_sat: u64,
+    /// Share of the shortest paths between other nodes that pass through
+    /// the node, from 0 to 1
+    pub betweenness: f64,
+}
+
+/// The JSON export: nodes and channels sorted by id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphExport {
+    pub nodes: Vec<NodeInfo>,
+    pub channels: Vec<NetworkChannelInfo>,
+}
+
+impl NetworkGraph {
+    pub fn to_export(&self) -> GraphExport {
+        let mut nodes: Vec<NodeInfo> = self.get_all_nodes().into_iter().cloned().collect();
+        nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        let mut channels: Vec<NetworkChannelInfo> =
+            self.get_all_channels().into_iter().cloned().collect();
+        channels.sort_by(|a, b| a.channel_id.cmp(&b.channel_id));
+        GraphExport { nodes, channels }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.to_export())?)
+    }
+
+    /// Graphviz DOT, one undirected edge per channel labelled with its
+    /// capacity; disabled channels are dashed
+    pub fn to_dot(&self) -> String {
+        let export = self.to_export();
+        let mut dot = String::from("graph lightning {\n  node [shape=ellipse];\n");
+        for node in &export.nodes {
+            let label = node
+                .alias
+                .clone()
+                .unwrap_or_else(|| node.node_id.chars().take(12).collect());
+            let _ = writeln!(
+                dot,
+                "  \"{}\" [label=\"{}\"];",
+                escape(&node.node_id),
+                escape(&label)
+            );
+        }
+        for channel in &export.channels {
+            let style = if channel.is_enabled {
+                ""
+            } else {
+                ", style=dashed"
+            };
+            let _ = writeln!(
+                dot,
+                "  \"{}\" -- \"{}\" [label=\"{} sat\", tooltip=\"{}\"{}];",
+                escape(&channel.node1),
+                escape(&channel.node2),
+                channel.capacity_sat,
+                escape(&channel.channel_id),
+                style
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The nodes within `max_hops` channels of `center` and the channels
+    /// between them; `None` if the graph does not know `center`
+    pub fn subgraph(&self, center: &str, max_hops: usize) -> Option<NetworkGraph> {
+        let mut neighbours: HashMap<&str, Vec<&str>> = HashMap::new();
+        for channel in self.get_all_channels() {
+            neighbours
+                .entry(&channel.node1)
+                .or_default()
+                .push(&channel.node2);
+            neighbours
+                .entry(&channel.node2)
+                .or_default()
+                .push(&channel.node1);
+        }
+        if self.get_node(center).is_none() && !neighbours.contains_key(center) {
+            return None;
+        }
+
+        let mut within = HashSet::from([center]);
+        let mut queue = VecDeque::from([(center, 0)]);
+        while let Some((node, hops)) = queue.pop_front() {
+            if hops == max_hops {
+                continue;
+            }
+            for &next in neighbours.get(node).into_iter().flatten() {
+                if within.insert(next) {
+                    queue.push_back((next, hops + 1));
+                }
+            }
+        }
+
+        let mut subgraph = NetworkGraph::new();
+        for node in self.get_all_nodes() {
+            if within.contains(node.node_id.as_str()) {
+                subgraph.add_node(node.clone());
+            }
+        }
+        for channel in self.get_all_channels() {
+            if within.contains(channel.node1.as_str()) && within.contains(channel.node2.as_str()) {
+                subgraph.add_channel(channel.clone());
+            }
+        }
+        Some(subgraph)
+    }
+
+    /// Centrality of every node, most central first: by betweenness, then
+    /// capacity
+    pub fn centrality(&self) -> Vec<NodeCentrality> {
+        let mut ids: BTreeSet<&str> = self
+            .get_all_nodes()
+            .into_iter()
+            .map(|n| n.node_id.as_str())
+            .collect();
+        let mut degree: HashMap<&str, (usize, u64)> = HashMap::new();
+        let mut links: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for channel in self.get_enabled_channels() {
+            for (node, other) in [
+                (channel.node1.as_str(), channel.node2.as_str()),
+                (channel.node2.as_str(), channel.node1.as_str()),
+            ] {
+                ids.insert(node);
+                let entry = degree.entry(node).or_default();
+                entry.0 += 1;
+                entry.1 += channel.capacity_sat;
+                links.entry(node).or_default().insert(other);
+            }
+        }
+
+        let ids: Vec<&str> = ids.into_iter().collect();
+        let index: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let adjacency: Vec<Vec<usize>> = ids
+            .iter()
+            .map(|id| {
+                links
+                    .get(id)
+                    .into_iter()
+                    .flatten()
+                    .map(|other| index[other])
+                    .collect()
+            })
+            .collect();
+        let betweenness = betweenness(&adjacency);
+
+        let mut centrality: Vec<NodeCentrality> = ids
+            .iter()
+            .zip(betweenness)
+            .map(|(id, betweenness)| {
+                let (degree, capacity_sat) = degree.get(id).copied().unwrap_or_default();
+                NodeCentrality {
+                    node_id: id.to_string(),
+                    degree,
+                    capacity_sat,
+                    betweenness,
+                }
+            })
+            .collect();
+        centrality.sort_by(|a, b| {
+            b.betweenness
+                .total_cmp(&a.betweenness)
+                .then(b.capacity_sat.cmp(&a.capacity_sat))
+                .then(a.node_id.cmp(&b.node_id))
+        });
+        centrality
+    }
+}
+
+/// Normalised betweenness of each node of an undirected, unweighted graph
+/// (Brandes' algorithm)
+fn betweenness(adjacency: &[Vec<usize>]) -> Vec<f64> {
+    let n = adjacency.len();
+    let mut centrality = vec![0.0; n];
+
+    for source in 0..n {
+        let mut order = Vec::with_capacity(n);
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut paths = vec![0.0f64; n];
+        let mut distance = vec![usize::MAX; n];
+        paths[source] = 1.0;
+        distance[source] = 0;
+
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            for &next in &adjacency[node] {
+                if distance[next] == usize::MAX {
+                    distance[next] = distance[node] + 1;
+                    queue.push_back(next);
+                }
+                if distance[next] == distance[node] + 1 {
+                    paths[next] += paths[node];
+                    predecessors[next].push(node);
+                }
+            }
+        }
+
+        let mut dependency = vec![0.0f64; n];
+        for &node in order.iter().rev() {
+            for &previous in &predecessors[node] {
+                dependency[previous] += paths[previous] / paths[node] * (1.0 + dependency[node]);
+            }
+            if node != source {
+                centrality[node] += dependency[node];
+            }
+        }
+    }
+
+    // Every pair was counted from both ends
+    if n > 2 {
+        let pairs = ((n - 1) * (n - 2)) as f64;
+        for value in &mut centrality {
+            *value /= pairs;
+        }
+    }
+    centrality
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(id: &str, a: &str, b: &str, capacity_sat: u64) -> NetworkChannelInfo {
+        NetworkChannelInfo {
+            channel_id: id.to_string(),
+            node1: a.to_string(),
+            node2: b.to_string(),
+            capacity_sat,
+            is_enabled: true,
+            last_update: 0,
+            base_fee_msat: 1000,
+            fee_rate_ppm: 1,
+            node1_policy: None,
+            node2_policy: None,
+        }
+    }
+
+    /// A chain A - B - C - D with a spur E off C
+    fn test_graph() -> NetworkGraph {
+        let mut graph = NetworkGraph::new();
+        graph.add_channel(channel("1", "A", "B", 100_000));
+        graph.add_channel(channel("2", "B", "C", 200_000));
+        graph.add_channel(channel("3", "C", "D", 300_000));
+        graph.add_channel(channel("4", "C", "E", 400_000));
+        graph
+    }
+
+    #[test]
+    fn test_centrality() {
+        let mut graph = test_graph();
+        let mut disabled = channel("5", "A", "E", 1_000_000);
+        disabled.is_enabled = false;
+        graph.add_channel(disabled);
+
+        let centrality = graph.centrality();
+        let ids: Vec<&str> = centrality.iter().map(|c| c.node_id.as_str()).collect();
+        assert_eq!(ids, vec!["C", "B", "E", "D", "A"]);
+
+        // C lies on 5 of the 6 paths between the other nodes, B on 3
+        assert!((centrality[0].betweenness - 5.0 / 6.0).abs() < 1e-9);
+        assert!((centrality[1].betweenness - 3.0 / 6.0).abs() < 1e-9);
+        assert_eq!(centrality[0].degree, 3);
+        assert_eq!(centrality[0].capacity_sat, 900_000);
+        // The disabled channel counts for nothing
+        assert_eq!(centrality[4].degree, 1);
+        assert_eq!(centrality[4].betweenness, 0.0);
+
+        let stats = graph.get_network_stats();
+        assert_eq!(stats.enabled_channels, 4);
+    }
+
+    #[test]
+    fn test_subgraph() {
+        let graph = test_graph();
+
+        let around_b = graph.subgraph("B", 1).unwrap();
+        let mut channels: Vec<&str> = around_b
+            .get_all_channels()
+            .iter()
+            .map(|c| c.channel_id.as_str())
+            .collect();
+        channels.sort();
+        assert_eq!(channels, vec!["1", "2"]);
+
+        assert_eq!(graph.subgraph("B", 2).unwrap().get_all_channels().len(), 4);
+        assert_eq!(graph.subgraph("A", 0).unwrap().get_all_channels().len(), 0);
+        assert!(graph.subgraph("Z", 3).is_none());
+    }
+
+    #[test]
+    fn test_export() {
+        let mut graph = test_graph();
+        graph.add_node(NodeInfo {
+            node_id: "A".to_string(),
+            alias: Some("Alice \"the\" node".to_string()),
+            color: None,
+            last_seen: 0,
+            features: Vec::new(),
+            addresses: Vec::new(),
+        });
+
+        let export: GraphExport = serde_json::from_str(&graph.to_json().unwrap()).unwrap();
+        assert_eq!(export.nodes.len(), 1);
+        let ids: Vec<&str> = export
+            .channels
+            .iter()
+            .map(|c| c.channel_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["1", "2", "3", "4"]);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("graph lightning {"));
+        assert!(dot.contains("\"A\" [label=\"Alice \\\"the\\\" node\"];"));
+        assert!(dot.contains("\"C\" -- \"E\" [label=\"400000 sat\", tooltip=\"4\"];"));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+}
@@ -0,0 +1,525 @@
+//! Liquidity autopilot.
+//!
+//! Every `interval_secs` the manager reports the channels that drifted from
+//! an even split and the rebalances that would even them out; the node cannot
+//! pay itself, so moving the liquidity is up to the operator. Once on-chain
+//! funds exceed the reserve by a channel's size, it opens a channel to the
+//! first preferred peer or LSP we have no channel with. Every decision is
+//! appended to the store's liquidity events.
+
+use crate::lightning::channel_manager::ChannelManager;
+use crate::lightning::channel_rebalancer::{ChannelRebalancer, RebalanceConfig};
+use crate::lightning::peer_manager::PeerUri;
+use crate::lightning_engine::LightningError;
+use crate::shutdown::Shutdown;
+use crate::store::{self, liquidity_events, LiquidityEventRecord, Store};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiquidityConfig {
+    /// Off by default; the manager spends funds on its own
+    pub enabled: bool,
+    /// Seconds between runs
+    pub interval_secs: u64,
+    /// On-chain funds never put into channels, in sats
+    pub onchain_reserve_sats: u64,
+    /// Size of the channels the manager opens, in sats
+    pub channel_size_sats: u64,
+    /// Announce the channels the manager opens
+    pub announce_channels: bool,
+    /// Peers to open channels to as `pubkey@host:port`, most preferred first
+    pub preferred_peers: Vec<String>,
+    /// Also open channels to the configured LSPs, after the preferred peers
+    pub open_to_lsps: bool,
+    pub rebalance: RebalanceConfig,
+}
+
+impl Default for LiquidityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 3600,
+            onchain_reserve_sats: 100_000,
+            channel_size_sats: 1_000_000,
+            announce_channels: false,
+            preferred_peers: Vec::new(),
+            open_to_lsps: true,
+            rebalance: RebalanceConfig::default(),
+        }
+    }
+}
+
+impl LiquidityConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "Liquidity manager interval must be at least 1 second"
+            ));
+        }
+        if self.channel_size_sats == 0 {
+            return Err(anyhow::anyhow!(
+                "Liquidity manager channel size must be above 0"
+            ));
+        }
+        for peer in &self.preferred_peers {
+            peer.parse::<PeerUri>()?;
+        }
+        self.rebalance.validate()
+    }
+
+    fn preferred_peers(&self) -> Vec<PeerUri> {
+        // Validated with the rest of the configuration
+        self.preferred_peers
+            .iter()
+            .filter_map(|peer| peer.parse().ok())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum LiquidityEventKind {
+    /// Channels drifted from an even split; the operator should move the
+    /// amount between them
+    RebalanceNeeded,
+    ChannelOpened,
+    ChannelOpenFailed,
+    /// Funds were available but every candidate peer already has a channel
+    ChannelOpenDeferred,
+}
+
+impl LiquidityEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RebalanceNeeded => "REBALANCE_NEEDED",
+            Self::ChannelOpened => "CHANNEL_OPENED",
+            Self::ChannelOpenFailed => "CHANNEL_OPEN_FAILED",
+            Self::ChannelOpenDeferred => "CHANNEL_OPEN_DEFERRED",
+        }
+    }
+}
+
+impl fmt::Display for LiquidityEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the manager needs to open channels
+#[async_trait::async_trait]
+pub trait LiquidityNode: Send + Sync + fmt::Debug {
+    /// On-chain funds available to fund channels
+    async fn spendable_onchain_sats(&self) -> Result<u64>;
+
+    /// Node ids of the peers we have pending or open channels with
+    async fn channel_peers(&self) -> Result<HashSet<String>>;
+
+    /// Open a channel funded on-chain; returns its channel id
+    async fn open_channel(
+        &self,
+        peer: &PeerUri,
+        capacity_sats: u64,
+        announce: bool,
+    ) -> Result<String>;
+}
+
+#[async_trait::async_trait]
+impl LiquidityNode for ChannelManager {
+    async fn spendable_onchain_sats(&self) -> Result<u64> {
+        ChannelManager::spendable_onchain_sats(self).await
+    }
+
+    async fn channel_peers(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .list_channels(None, false)
+            .await?
+            .into_iter()
+            .map(|channel| channel.peer_id)
+            .collect())
+    }
+
+    async fn open_channel(
+        &self,
+        peer: &PeerUri,
+        capacity_sats: u64,
+        announce: bool,
+    ) -> Result<String> {
+        let channel = ChannelManager::open_channel(
+            self,
+            &peer.node_id.to_string(),
+            &peer.address.to_string(),
+            capacity_sats,
+            0,
+            announce,
+        )
+        .await?;
+        Ok(channel.channel_id)
+    }
+}
+
+/// Reports imbalanced channels and opens new ones on a schedule, within the
+/// policy of its `LiquidityConfig`
+#[derive(Debug)]
+pub struct LiquidityManager {
+    config: LiquidityConfig,
+    /// Peers to open channels to, in order of preference
+    targets: Vec<PeerUri>,
+    store: Store,
+    node: Arc<dyn LiquidityNode>,
+    rebalancer: Arc<ChannelRebalancer>,
+    /// A channel open is deferred until a candidate peer is free; recorded
+    /// once rather than on every run
+    deferred: AtomicBool,
+    /// Channel pairs reported as needing a rebalance on the last run; a pair
+    /// is reported again only once it was even in between
+    needed: Mutex<HashSet<(String, String)>>,
+}
+
+impl LiquidityManager {
+    pub fn new(
+        config: LiquidityConfig,
+        store: Store,
+        node: Arc<dyn LiquidityNode>,
+        rebalancer: Arc<ChannelRebalancer>,
+    ) -> Self {
+        Self {
+            targets: config.preferred_peers(),
+            config,
+            store,
+            node,
+            rebalancer,
+            deferred: AtomicBool::new(false),
+            needed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Open channels to these LSPs once the preferred peers have one, unless
+    /// `open_to_lsps` is off
+    pub fn with_lsp_peers(mut self, peers: impl IntoIterator<Item = PeerUri>) -> Self {
+        if self.config.open_to_lsps {
+            for peer in peers {
+                if !self.targets.iter().any(|t| t.node_id == peer.node_id) {
+                    self.targets.push(peer);
+                }
+            }
+        }
+        self
+    }
+
+    /// Run every `interval_secs` until shutdown; returns at once if the
+    /// manager is not enabled
+    pub async fn run(self: Arc<Self>, shutdown: Shutdown) {
+        if !self.config.enabled {
+            info!("Liquidity manager is disabled");
+            return;
+        }
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        let triggered = shutdown.triggered();
+        tokio::pin!(triggered);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut triggered => return,
+            }
+            if let Err(e) = self.run_once().await {
+                // Nothing to do until the node is up
+                if !matches!(e.downcast_ref(), Some(LightningError::NodeNotRunning)) {
+                    warn!("Liquidity manager run failed: {:#}", e);
+                }
+            }
+        }
+    }
+
+    /// Report what needs rebalancing, then open a channel if funds allow;
+    /// returns the events recorded
+    pub async fn run_once(&self) -> Result<Vec<LiquidityEventRecord>> {
+        let mut events = Vec::new();
+        self.rebalance(&mut events).await?;
+        self.open_channel(&mut events).await?;
+        Ok(events)
+    }
+
+    async fn rebalance(&self, events: &mut Vec<LiquidityEventRecord>) -> Result<()> {
+        self.rebalancer.refresh_channels().await?;
+        let operations = self.rebalancer.check_rebalancing_needed().await?;
+        let reported = self.needed.lock().unwrap().clone();
+        let mut pairs = HashSet::new();
+        for operation in operations {
+            let pair = (operation.from_channel.clone(), operation.to_channel.clone());
+            if !pairs.insert(pair.clone()) || reported.contains(&pair) {
+                continue;
+            }
+            events.push(self.record(
+                LiquidityEventKind::RebalanceNeeded,
+                &format!("{} -> {}", operation.from_channel, operation.to_channel),
+                operation.amount,
+                format!(
+                    "Move {} sats from {} to {}; the node cannot route a payment to itself",
+                    operation.amount, operation.from_channel, operation.to_channel
+                ),
+            )?);
+        }
+        *self.needed.lock().unwrap() = pairs;
+        Ok(())
+    }
+
+    async fn open_channel(&self, events: &mut Vec<LiquidityEventRecord>) -> Result<()> {
+        if self.targets.is_empty() {
+            return Ok(());
+        }
+        let spendable = self.node.spendable_onchain_sats().await?;
+        let available = spendable.saturating_sub(self.config.onchain_reserve_sats);
+        if available < self.config.channel_size_sats {
+            self.deferred.store(false, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let peers = self.node.channel_peers().await?;
+        let Some(target) = self
+            .targets
+            .iter()
+            .find(|target| !peers.contains(&target.node_id.to_string()))
+        else {
+            if self.deferred.swap(true, Ordering::Relaxed) {
+                return Ok(());
+            }
+            events.push(self.record(
+                LiquidityEventKind::ChannelOpenDeferred,
+                "preferred peers",
+                self.config.channel_size_sats,
+                format!(
+                    "{} sats available above the reserve, but every preferred peer has a channel",
+                    available
+                ),
+            )?);
+            return Ok(());
+        };
+        self.deferred.store(false, Ordering::Relaxed);
+
+        let opened = self
+            .node
+            .open_channel(
+                target,
+                self.config.channel_size_sats,
+                self.config.announce_channels,
+            )
+            .await;
+        let event = match opened {
+            Ok(channel_id) => self.record(
+                LiquidityEventKind::ChannelOpened,
+                &target.to_string(),
+                self.config.channel_size_sats,
+                format!(
+                    "Opened channel {} with {} sats on-chain and {} sats reserved",
+                    channel_id, spendable, self.config.onchain_reserve_sats
+                ),
+            )?,
+            Err(e) => self.record(
+                LiquidityEventKind::ChannelOpenFailed,
+                &target.to_string(),
+                self.config.channel_size_sats,
+                format!("{:#}", e),
+            )?,
+        };
+        events.push(event);
+        Ok(())
+    }
+
+    fn record(
+        &self,
+        kind: LiquidityEventKind,
+        subject: &str,
+        amount_sat: u64,
+        detail: String,
+    ) -> Result<LiquidityEventRecord> {
+        let mut event = LiquidityEventRecord {
+            id: 0,
+            kind: kind.as_str().to_string(),
+            subject: subject.to_string(),
+            amount_sat,
+            detail,
+            created_at: store::now(),
+        };
+        event.id = self
+            .store
+            .with_conn(|conn| liquidity_events::insert(conn, &event))?;
+        info!(
+            "Liquidity {} {} ({} sats): {}",
+            kind, event.subject, amount_sat, event.detail
+        );
+        Ok(event)
+    }
+
+    /// The `limit` most recent decisions, newest first
+    pub fn list_events(&self, limit: u32) -> Result<Vec<LiquidityEventRecord>> {
+        self.store
+            .with_conn(|conn| liquidity_events::list(conn, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lightning::channel_rebalancer::test_support::{channel, rebalancer};
+    use crate::lightning::channel_rebalancer::ChannelInfo;
+    use tokio::sync::RwLock;
+
+    const PEER_A: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const PEER_B: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+
+    #[derive(Debug, Default)]
+    struct TestNode {
+        spendable_sats: u64,
+        peers: HashSet<String>,
+        opened: RwLock<Vec<(String, u64)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LiquidityNode for TestNode {
+        async fn spendable_onchain_sats(&self) -> Result<u64> {
+            Ok(self.spendable_sats)
+        }
+
+        async fn channel_peers(&self) -> Result<HashSet<String>> {
+            Ok(self.peers.clone())
+        }
+
+        async fn open_channel(
+            &self,
+            peer: &PeerUri,
+            capacity_sats: u64,
+            _announce: bool,
+        ) -> Result<String> {
+            self.opened
+                .write()
+                .await
+                .push((peer.node_id.to_string(), capacity_sats));
+            Ok("42".to_string())
+        }
+    }
+
+    fn setup(config: LiquidityConfig, node: TestNode) -> (LiquidityManager, Arc<TestNode>) {
+        setup_with_channels(config, node, Vec::new())
+    }
+
+    fn setup_with_channels(
+        config: LiquidityConfig,
+        node: TestNode,
+        channels: Vec<ChannelInfo>,
+    ) -> (LiquidityManager, Arc<TestNode>) {
+        let node = Arc::new(node);
+        let store = Store::open_in_memory().unwrap();
+        let rebalancer = Arc::new(rebalancer(config.rebalance.clone(), channels));
+        let manager = LiquidityManager::new(
+            config,
+            store,
+            Arc::clone(&node) as Arc<dyn LiquidityNode>,
+            rebalancer,
+        );
+        (manager, node)
+    }
+
+    fn peer(node_id: &str) -> String {
+        format!("{}@127.0.0.1:9735", node_id)
+    }
+
+    #[tokio::test]
+    async fn test_opens_channel_above_reserve() {
+        let config = LiquidityConfig {
+            enabled: true,
+            preferred_peers: vec![peer(PEER_A)],
+            ..LiquidityConfig::default()
+        };
+        assert!(config.validate().is_ok());
+
+        // PEER_A already has a channel, the LSP is next
+        let node = TestNode {
+            spendable_sats: 1_100_000,
+            peers: HashSet::from([PEER_A.to_string()]),
+            ..TestNode::default()
+        };
+        let (manager, node) = setup(config, node);
+        let manager = manager.with_lsp_peers([peer(PEER_B).parse().unwrap()]);
+
+        let events = manager.run_once().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "CHANNEL_OPENED");
+        assert_eq!(events[0].amount_sat, 1_000_000);
+        assert_eq!(
+            *node.opened.read().await,
+            vec![(PEER_B.to_string(), 1_000_000)]
+        );
+        assert_eq!(manager.list_events(10).unwrap(), events);
+    }
+
+    #[tokio::test]
+    async fn test_keeps_reserve_and_skips_peers_with_channels() {
+        let config = LiquidityConfig {
+            preferred_peers: vec![peer(PEER_A)],
+            open_to_lsps: false,
+            ..LiquidityConfig::default()
+        };
+
+        // One sat short of a channel above the reserve
+        let node = TestNode {
+            spendable_sats: 1_099_999,
+            ..TestNode::default()
+        };
+        let (manager, node) = setup(config.clone(), node);
+        assert!(manager.run_once().await.unwrap().is_empty());
+        assert!(node.opened.read().await.is_empty());
+
+        let node = TestNode {
+            spendable_sats: 5_000_000,
+            peers: HashSet::from([PEER_A.to_string()]),
+            ..TestNode::default()
+        };
+        let (manager, node) = setup(config, node);
+        let manager = manager.with_lsp_peers([peer(PEER_B).parse().unwrap()]);
+        let events = manager.run_once().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "CHANNEL_OPEN_DEFERRED");
+        assert!(node.opened.read().await.is_empty());
+
+        // Still deferred on the next run, which records nothing new
+        assert!(manager.run_once().await.unwrap().is_empty());
+        assert_eq!(manager.list_events(10).unwrap(), events);
+    }
+
+    #[tokio::test]
+    async fn test_reports_needed_rebalances() {
+        let (manager, _node) = setup_with_channels(
+            LiquidityConfig::default(),
+            TestNode::default(),
+            vec![channel("out", "a", 900_000), channel("in", "b", 100_000)],
+        );
+
+        let events = manager.run_once().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "REBALANCE_NEEDED");
+        assert_eq!(events[0].subject, "out -> in");
+        assert_eq!(events[0].amount_sat, 320_000);
+        assert_eq!(manager.list_events(10).unwrap(), events);
+
+        // Still imbalanced on the next run, which records nothing new
+        assert!(manager.run_once().await.unwrap().is_empty());
+        assert_eq!(manager.list_events(10).unwrap(), events);
+    }
+
+    #[test]
+    fn test_rejects_invalid_preferred_peer() {
+        let config = LiquidityConfig {
+            preferred_peers: vec!["not-a-peer".to_string()],
+            ..LiquidityConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,13 +1,38 @@
 pub mod channel_manager;
+pub mod channel_rebalancer;
+pub mod gossip_sync;
+pub mod graph_analytics;
 pub mod invoice_handler;
+pub mod liquidity_manager;
 pub mod network_graph;
+pub mod pathfinding;
 pub mod payment_processor;
+pub mod peer_manager;
+pub mod route_estimator;
+pub mod send_options;
 pub mod testnet_checker;
 pub mod regtest_setup;
 
-pub use channel_manager::{ChannelInfo, ChannelManager, ChannelState};
+pub use channel_manager::{
+    ChannelConfig, ChannelError, ChannelInfo, ChannelManager, ChannelState,
+};
+pub use channel_rebalancer::{
+    ChannelInfo as RebalanceChannelInfo, ChannelRebalancer, RebalanceConfig, RebalanceNode,
+    RebalanceOperation,
+};
+pub use gossip_sync::{GossipFreshness, GossipNode, GossipSync};
+pub use graph_analytics::{GraphExport, NodeCentrality};
 pub use invoice_handler::{InvoiceHandler, InvoiceInfo, InvoiceState};
-pub use network_graph::{ChannelInfo as NetworkChannelInfo, NetworkGraph, NodeInfo};
+pub use liquidity_manager::{
+    LiquidityConfig, LiquidityEventKind, LiquidityManager, LiquidityNode,
+};
+pub use network_graph::{ChannelInfo as NetworkChannelInfo, ChannelPolicy, NetworkGraph, NodeInfo};
+pub use pathfinding::{
+    FeeScorer, PathHop, ProbabilityScorer, Route, RouteConstraints, RouteScorer,
+};
 pub use payment_processor::{PaymentInfo, PaymentProcessor, PaymentState};
+pub use peer_manager::{PeerConfig, PeerError, PeerInfo, PeerManager, PeerState, PeerUri};
+pub use route_estimator::{EstimateTarget, RouteEstimate, RouteEstimator};
+pub use send_options::{PaymentFailureReason, SendPaymentOptions, SendPaymentOutcome};
 pub use testnet_checker::{TestnetChecker, TestnetNode, TestnetNodeResult, NetworkStats};
-pub use regtest_setup::{RegtestSetup, RegtestNode, RegtestNetworkStats};
+pub use regtest_setup::{RegtestError, RegtestSetup, RegtestNode, RegtestNetworkStats};
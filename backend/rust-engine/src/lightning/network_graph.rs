@@ -1,6 +1,25 @@
+use anyhow::Result;
+use ldk_node::lightning::routing::gossip::ChannelUpdateInfo;
+use ldk_node::lightning::util::logger::{Logger, Record};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// CLTV delta assumed for channels built without directional policies
+pub const DEFAULT_CLTV_EXPIRY_DELTA: u16 = 40;
+
+/// The policy of an optional LDK `ChannelUpdateInfo`
+fn channel_policy(update: Option<&ChannelUpdateInfo>) -> Option<ChannelPolicy> {
+    update.map(|update| ChannelPolicy {
+        is_enabled: update.enabled,
+        base_fee_msat: update.fees.base_msat,
+        fee_rate_ppm: update.fees.proportional_millionths,
+        cltv_expiry_delta: update.cltv_expiry_delta,
+        htlc_minimum_msat: update.htlc_minimum_msat,
+        htlc_maximum_msat: update.htlc_maximum_msat,
+        last_update: update.last_update as u64,
+    })
+}
+
 /// Network graph for Lightning Network topology
 #[derive(Debug, Clone)]
 pub struct NetworkGraph {
@@ -30,6 +49,76 @@ pub struct NetworkChannelInfo {
     pub last_update: u64,
     pub base_fee_msat: u32,
     pub fee_rate_ppm: u32,
+    /// Policy for payments from node1 to node2, from its `channel_update`
+    #[serde(default)]
+    pub node1_policy: Option<ChannelPolicy>,
+    /// Policy for payments from node2 to node1
+    #[serde(default)]
+    pub node2_policy: Option<ChannelPolicy>,
+}
+
+/// How a node forwards payments over one direction of a channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelPolicy {
+    pub is_enabled: bool,
+    pub base_fee_msat: u32,
+    pub fee_rate_ppm: u32,
+    /// Blocks the forwarding node adds to the HTLC expiry
+    pub cltv_expiry_delta: u16,
+    pub htlc_minimum_msat: u64,
+    pub htlc_maximum_msat: u64,
+    pub last_update: u64,
+}
+
+impl ChannelPolicy {
+    /// Fee for forwarding `amount_msat`
+    pub fn fee_msat(&self, amount_msat: u64) -> u64 {
+        u64::from(self.base_fee_msat)
+            + (u128::from(amount_msat) * u128::from(self.fee_rate_ppm) / 1_000_000) as u64
+    }
+}
+
+impl NetworkChannelInfo {
+    /// The policy for payments leaving `node_id` over this channel.
+    ///
+    /// Channels without directional policies use their flat fields both
+    /// ways; otherwise a direction without a policy cannot be used.
+    pub fn policy_from(&self, node_id: &str) -> Option<ChannelPolicy> {
+        if self.node1_policy.is_none() && self.node2_policy.is_none() {
+            if node_id != self.node1 && node_id != self.node2 {
+                return None;
+            }
+            let htlc_maximum_msat = match self.capacity_sat {
+                0 => u64::MAX,
+                capacity_sat => capacity_sat * 1000,
+            };
+            return Some(ChannelPolicy {
+                is_enabled: self.is_enabled,
+                base_fee_msat: self.base_fee_msat,
+                fee_rate_ppm: self.fee_rate_ppm,
+                cltv_expiry_delta: DEFAULT_CLTV_EXPIRY_DELTA,
+                htlc_minimum_msat: 0,
+                htlc_maximum_msat,
+                last_update: self.last_update,
+            });
+        }
+        if node_id == self.node1 {
+            self.node1_policy.clone()
+        } else if node_id == self.node2 {
+            self.node2_policy.clone()
+        } else {
+            None
+        }
+    }
+
+    /// The node at the other end from `node_id`
+    pub fn other_node(&self, node_id: &str) -> &str {
+        if self.node1 == node_id {
+            &self.node2
+        } else {
+            &self.node1
+        }
+    }
 }
 
 impl NetworkGraph {
@@ -41,6 +130,138 @@ impl NetworkGraph {
         }
     }
 
+    /// Build a snapshot of LDK's gossip graph.
+    ///
+    /// Both directions' policies are kept. The flat fee fields hold the first
+    /// announced direction's policy, and the channel counts as enabled if
+    /// either direction is.
+    pub fn from_ldk_graph(graph: &ldk_node::graph::NetworkGraph) -> Self {
+        let mut snapshot = Self::new();
+
+        for node_id in graph.list_nodes() {
+            let Some(info) = graph.node(&node_id) else {
+                continue;
+            };
+            let announcement = info.announcement_info.as_ref();
+            snapshot.add_node(NodeInfo {
+                node_id: node_id.to_string(),
+                alias: announcement.map(|a| a.alias().to_string()),
+                color: None,
+                last_seen: announcement.map(|a| a.last_update() as u64).unwrap_or(0),
+                features: Vec::new(),
+                addresses: announcement
+                    .map(|a| a.addresses().iter().map(|addr| addr.to_string()).collect())
+                    .unwrap_or_default(),
+            });
+        }
+
+        for scid in graph.list_channels() {
+            let Some(info) = graph.channel(scid) else {
+                continue;
+            };
+            snapshot.add_directed_channel(
+                scid,
+                info.node_one.to_string(),
+                info.node_two.to_string(),
+                info.capacity_sats,
+                channel_policy(info.one_to_two.as_ref()),
+                channel_policy(info.two_to_one.as_ref()),
+            );
+        }
+
+        snapshot
+    }
+
+    /// Build a graph from a Rapid Gossip Sync snapshot, as served by an RGS
+    /// server for `network`. Snapshots carry channels and their policies but
+    /// no node announcements, so nodes are known by id only.
+    pub fn from_rapid_gossip_snapshot(snapshot: &[u8], network: bitcoin::Network) -> Result<Self> {
+        use ldk_node::bitcoin::Network as LdkNetwork;
+        use ldk_node::lightning::routing::gossip::NetworkGraph as LdkNetworkGraph;
+        use lightning_rapid_gossip_sync::RapidGossipSync;
+
+        let ldk_network = match network {
+            bitcoin::Network::Bitcoin => LdkNetwork::Bitcoin,
+            bitcoin::Network::Testnet => LdkNetwork::Testnet,
+            bitcoin::Network::Testnet4 => LdkNetwork::Testnet4,
+            bitcoin::Network::Signet => LdkNetwork::Signet,
+            bitcoin::Network::Regtest => LdkNetwork::Regtest,
+            other => return Err(anyhow::anyhow!("Unsupported network {}", other)),
+        };
+        let graph = LdkNetworkGraph::new(ldk_network, &GossipLogger);
+        let sync = RapidGossipSync::new(&graph, &GossipLogger);
+        // Old snapshots are fine for analysis, so their age is not checked
+        sync.update_network_graph_no_std(snapshot, None)
+            .map_err(|e| anyhow::anyhow!("Invalid Rapid Gossip Sync snapshot: {:?}", e))?;
+
+        let mut imported = Self::new();
+        let read_only = graph.read_only();
+        for (scid, info) in read_only.channels().unordered_iter() {
+            imported.add_directed_channel(
+                *scid,
+                info.node_one.to_string(),
+                info.node_two.to_string(),
+                info.capacity_sats,
+                channel_policy(info.one_to_two.as_ref()),
+                channel_policy(info.two_to_one.as_ref()),
+            );
+        }
+        let node_ids: Vec<String> = imported
+            .channels
+            .values()
+            .flat_map(|c| [c.node1.clone(), c.node2.clone()])
+            .collect();
+        for node_id in node_ids {
+            imported.nodes.entry(node_id.clone()).or_insert(NodeInfo {
+                node_id,
+                alias: None,
+                color: None,
+                last_seen: 0,
+                features: Vec::new(),
+                addresses: Vec::new(),
+            });
+        }
+
+        Ok(imported)
+    }
+
+    /// Add a gossiped channel with its policy per direction
+    fn add_directed_channel(
+        &mut self,
+        scid: u64,
+        node1: String,
+        node2: String,
+        capacity_sats: Option<u64>,
+        node1_policy: Option<ChannelPolicy>,
+        node2_policy: Option<ChannelPolicy>,
+    ) {
+        let Some(policy) = node1_policy.as_ref().or(node2_policy.as_ref()) else {
+            // Channels without any update cannot be routed through
+            return;
+        };
+        let is_enabled = node1_policy.as_ref().is_some_and(|p| p.is_enabled)
+            || node2_policy.as_ref().is_some_and(|p| p.is_enabled);
+        let last_update = node1_policy
+            .iter()
+            .chain(node2_policy.iter())
+            .map(|p| p.last_update)
+            .max()
+            .unwrap_or(0);
+
+        self.add_channel(NetworkChannelInfo {
+            channel_id: scid.to_string(),
+            node1,
+            node2,
+            capacity_sat: capacity_sats.unwrap_or(0),
+            is_enabled,
+            last_update,
+            base_fee_msat: policy.base_fee_msat,
+            fee_rate_ppm: policy.fee_rate_ppm,
+            node1_policy,
+            node2_policy,
+        });
+    }
+
     /// Add a node to the network graph
     pub fn add_node(&mut self, node_info: NodeInfo) {
         self.nodes.insert(node_info.node_id.clone(), node_info);
@@ -101,6 +322,22 @@ impl NetworkGraph {
             if let Some(fee_rate_ppm) = updates.fee_rate_ppm {
                 channel.fee_rate_ppm = fee_rate_ppm;
             }
+            // Without a direction, the update applies to both
+            let directions = channel
+                .node1_policy
+                .iter_mut()
+                .chain(channel.node2_policy.iter_mut());
+            for policy in directions {
+                if let Some(is_enabled) = updates.is_enabled {
+                    policy.is_enabled = is_enabled;
+                }
+                if let Some(base_fee_msat) = updates.base_fee_msat {
+                    policy.base_fee_msat = base_fee_msat;
+                }
+                if let Some(fee_rate_ppm) = updates.fee_rate_ppm {
+                    policy.fee_rate_ppm = fee_rate_ppm;
+                }
+            }
             channel.last_update = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
@@ -193,6 +430,15 @@ pub struct NetworkStats {
     pub total_capacity_sat: u64,
 }
 
+/// Routes LDK's gossip logging to `tracing`
+struct GossipLogger;
+
+impl Logger for GossipLogger {
+    fn log(&self, record: Record) {
+        tracing::debug!("{}", record.args);
+    }
+}
+
 impl Default for NetworkGraph {
     fn default() -> Self {
         Self::new()
@@ -244,6 +490,8 @@ mod tests {
             last_update: 1234567890,
             base_fee_msat: 1000,
             fee_rate_ppm: 1,
+            node1_policy: None,
+            node2_policy: None,
         };
 
         graph.add_channel(channel);
@@ -295,6 +543,8 @@ mod tests {
             last_update: 0,
             base_fee_msat: 1000,
             fee_rate_ppm: 1,
+            node1_policy: None,
+            node2_policy: None,
         });
 
         graph.add_channel(NetworkChannelInfo {
@@ -306,6 +556,8 @@ mod tests {
             last_update: 0,
             base_fee_msat: 1000,
             fee_rate_ppm: 1,
+            node1_policy: None,
+            node2_policy: None,
         });
 
         let path = graph.find_shortest_path("A", "C");
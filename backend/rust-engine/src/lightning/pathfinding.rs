@@ -0,0 +1,679 @@
+//! Fee-aware pathfinding over a `NetworkGraph`.
+//!
+//! Routes are searched backwards from the recipient, as LDK does, since what
+//! a node charges depends on the amount it forwards. A channel costs the fee
+//! its forwarding node charges plus the penalty of a `RouteScorer`. Channels
+//! disabled in the direction of the payment, too small for the amount, or
+//! that would take the route past the fee, CLTV or hop limits are skipped.
+//! The limits are checked while searching, so a route cut off by them is not
+//! traded for a costlier one that fits; `find_routes` recovers such routes
+//! among the k cheapest (Yen's algorithm).
+
+use crate::lightning::network_graph::{ChannelPolicy, NetworkChannelInfo, NetworkGraph};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// LDK's default limit on the CLTV deltas a route adds up to
+pub const DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA: u32 = 1008;
+
+/// Hops that fit in a payment onion
+pub const DEFAULT_MAX_HOPS: usize = 20;
+
+/// Limits a route must stay within
+#[derive(Debug, Clone)]
+pub struct RouteConstraints {
+    /// Amount delivered to the recipient
+    pub amount_msat: u64,
+    pub max_fee_msat: Option<u64>,
+    /// Limit on the CLTV deltas the forwarding nodes add
+    pub max_total_cltv_expiry_delta: u32,
+    pub max_hops: usize,
+    pub excluded_channels: HashSet<String>,
+    pub excluded_nodes: HashSet<String>,
+}
+
+impl RouteConstraints {
+    pub fn new(amount_msat: u64) -> Self {
+        Self {
+            amount_msat,
+            max_fee_msat: None,
+            max_total_cltv_expiry_delta: DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA,
+            max_hops: DEFAULT_MAX_HOPS,
+            excluded_channels: HashSet::new(),
+            excluded_nodes: HashSet::new(),
+        }
+    }
+
+    pub fn with_max_fee_msat(mut self, max_fee_msat: u64) -> Self {
+        self.max_fee_msat = Some(max_fee_msat);
+        self
+    }
+
+    pub fn with_max_total_cltv_expiry_delta(mut self, max_total_cltv_expiry_delta: u32) -> Self {
+        self.max_total_cltv_expiry_delta = max_total_cltv_expiry_delta;
+        self
+    }
+
+    pub fn with_max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    pub fn excluding_channel(mut self, channel_id: impl Into<String>) -> Self {
+        self.excluded_channels.insert(channel_id.into());
+        self
+    }
+
+    pub fn excluding_node(mut self, node_id: impl Into<String>) -> Self {
+        self.excluded_nodes.insert(node_id.into());
+        self
+    }
+}
+
+/// Extra cost of sending `amount_msat` over a channel, in msat, on top of
+/// the fee it charges. Closures with the same signature are scorers too.
+pub trait RouteScorer {
+    fn penalty_msat(
+        &self,
+        channel: &NetworkChannelInfo,
+        policy: &ChannelPolicy,
+        amount_msat: u64,
+    ) -> u64;
+}
+
+impl<F> RouteScorer for F
+where
+    F: Fn(&NetworkChannelInfo, &ChannelPolicy, u64) -> u64,
+{
+    fn penalty_msat(
+        &self,
+        channel: &NetworkChannelInfo,
+        policy: &ChannelPolicy,
+        amount_msat: u64,
+    ) -> u64 {
+        self(channel, policy, amount_msat)
+    }
+}
+
+/// Routes by fee alone
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeeScorer;
+
+impl RouteScorer for FeeScorer {
+    fn penalty_msat(&self, _: &NetworkChannelInfo, _: &ChannelPolicy, _: u64) -> u64 {
+        0
+    }
+}
+
+/// Also penalises channels by how unlikely they are to carry the amount,
+/// assuming their liquidity is uniformly distributed. Channels of unknown
+/// capacity count as certain.
+#[derive(Debug, Clone, Copy)]
+pub struct ProbabilityScorer {
+    /// Penalty for a channel that carries the amount half the time; it
+    /// doubles each time the chance halves
+    pub penalty_msat: u64,
+}
+
+impl Default for ProbabilityScorer {
+    fn default() -> Self {
+        Self {
+            penalty_msat: 10_000,
+        }
+    }
+}
+
+impl RouteScorer for ProbabilityScorer {
+    fn penalty_msat(
+        &self,
+        channel: &NetworkChannelInfo,
+        _: &ChannelPolicy,
+        amount_msat: u64,
+    ) -> u64 {
+        if channel.capacity_sat == 0 {
+            return 0;
+        }
+        let capacity_msat = channel.capacity_sat as f64 * 1000.0;
+        let probability = ((capacity_msat - amount_msat as f64 + 1.0) / (capacity_msat + 1.0))
+            .clamp(f64::MIN_POSITIVE, 1.0);
+        (self.penalty_msat as f64 * -probability.log2()) as u64
+    }
+}
+
+/// One channel of a route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathHop {
+    pub channel_id: String,
+    /// Node the channel leads to
+    pub node_id: String,
+    /// Amount sent over the channel
+    pub amount_msat: u64,
+    /// Fee the node at the near end charges to forward over the channel;
+    /// the sender charges itself nothing for the first hop
+    pub fee_msat: u64,
+    /// CLTV delta the node at the near end adds; none for the first hop
+    pub cltv_expiry_delta: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    pub hops: Vec<PathHop>,
+    /// Amount delivered to the recipient
+    pub amount_msat: u64,
+    pub total_fee_msat: u64,
+    pub total_cltv_expiry_delta: u32,
+    /// Fees plus scorer penalties; routes are ranked by it
+    pub cost_msat: u64,
+}
+
+impl Route {
+    pub fn channel_ids(&self) -> Vec<&str> {
+        self.hops
+            .iter()
+            .map(|hop| hop.channel_id.as_str())
+            .collect()
+    }
+}
+
+/// A channel in the direction of a payment
+#[derive(Debug)]
+struct Edge<'a> {
+    channel: &'a NetworkChannelInfo,
+    from: &'a str,
+    to: &'a str,
+    policy: ChannelPolicy,
+}
+
+impl Edge<'_> {
+    fn can_carry(&self, amount_msat: u64) -> bool {
+        amount_msat >= self.policy.htlc_minimum_msat
+            && amount_msat <= self.policy.htlc_maximum_msat
+            && (self.channel.capacity_sat == 0 || amount_msat <= self.channel.capacity_sat * 1000)
+    }
+}
+
+/// Best way found from a node to the recipient
+#[derive(Debug, Clone, Copy)]
+struct Label<'e, 'a> {
+    cost_msat: u64,
+    /// Amount that must reach the node
+    arriving_msat: u64,
+    cltv_expiry_delta: u32,
+    hops: usize,
+    next: Option<&'e Edge<'a>>,
+}
+
+struct Search<'a, 's> {
+    target: &'a str,
+    constraints: &'s RouteConstraints,
+    scorer: &'s dyn RouteScorer,
+    /// Usable edges by the node they lead to
+    incoming: HashMap<&'a str, Vec<Edge<'a>>>,
+}
+
+impl<'a, 's> Search<'a, 's> {
+    fn new(
+        graph: &'a NetworkGraph,
+        target: &'a str,
+        constraints: &'s RouteConstraints,
+        scorer: &'s dyn RouteScorer,
+    ) -> Self {
+        let mut incoming: HashMap<&'a str, Vec<Edge<'a>>> = HashMap::new();
+        for channel in graph.get_all_channels() {
+            if constraints.excluded_channels.contains(&channel.channel_id) {
+                continue;
+            }
+            for (from, to) in [
+                (channel.node1.as_str(), channel.node2.as_str()),
+                (channel.node2.as_str(), channel.node1.as_str()),
+            ] {
+                if constraints.excluded_nodes.contains(from)
+                    || constraints.excluded_nodes.contains(to)
+                {
+                    continue;
+                }
+                let Some(policy) = channel.policy_from(from).filter(|p| p.is_enabled) else {
+                    continue;
+                };
+                incoming.entry(to).or_default().push(Edge {
+                    channel,
+                    from,
+                    to,
+                    policy,
+                });
+            }
+        }
+        Self {
+            target,
+            constraints,
+            scorer,
+            incoming,
+        }
+    }
+
+    /// Cheapest path from `source`, avoiding the `(channel, from)` edges and
+    /// nodes given
+    fn shortest(
+        &self,
+        source: &str,
+        excluded_edges: &HashSet<(&str, &str)>,
+        excluded_nodes: &HashSet<&str>,
+    ) -> Option<Vec<&Edge<'a>>> {
+        let constraints = self.constraints;
+        let mut labels: HashMap<&str, Label> = HashMap::new();
+        let mut settled = HashSet::new();
+        let mut queue = BinaryHeap::new();
+
+        labels.insert(
+            self.target,
+            Label {
+                cost_msat: 0,
+                arriving_msat: constraints.amount_msat,
+                cltv_expiry_delta: 0,
+                hops: 0,
+                next: None,
+            },
+        );
+        queue.push(Reverse((0, self.target)));
+
+        while let Some(Reverse((_, node))) = queue.pop() {
+            if !settled.insert(node) {
+                continue;
+            }
+            if node == source {
+                break;
+            }
+            let label = labels[node];
+            if label.hops == constraints.max_hops {
+                continue;
+            }
+
+            for edge in self.incoming.get(node).into_iter().flatten() {
+                if settled.contains(edge.from)
+                    || excluded_nodes.contains(edge.from)
+                    || excluded_edges.contains(&(edge.channel.channel_id.as_str(), edge.from))
+                    || !edge.can_carry(label.arriving_msat)
+                {
+                    continue;
+                }
+                let penalty_msat =
+                    self.scorer
+                        .penalty_msat(edge.channel, &edge.policy, label.arriving_msat);
+                // The sender does not pay itself to forward
+                let (fee_msat, cltv_expiry_delta) = if edge.from == source {
+                    (0, 0)
+                } else {
+                    (
+                        edge.policy.fee_msat(label.arriving_msat),
+                        u32::from(edge.policy.cltv_expiry_delta),
+                    )
+                };
+                let candidate = Label {
+                    cost_msat: label
+                        .cost_msat
+                        .saturating_add(fee_msat)
+                        .saturating_add(penalty_msat),
+                    arriving_msat: label.arriving_msat.saturating_add(fee_msat),
+                    cltv_expiry_delta: label.cltv_expiry_delta + cltv_expiry_delta,
+                    hops: label.hops + 1,
+                    next: Some(edge),
+                };
+                let total_fee_msat = candidate.arriving_msat - constraints.amount_msat;
+                if candidate.cltv_expiry_delta > constraints.max_total_cltv_expiry_delta
+                    || constraints
+                        .max_fee_msat
+                        .is_some_and(|max| total_fee_msat > max)
+                {
+                    continue;
+                }
+                let better = labels
+                    .get(edge.from)
+                    .map_or(true, |known| candidate.cost_msat < known.cost_msat);
+                if better {
+                    labels.insert(edge.from, candidate);
+                    queue.push(Reverse((candidate.cost_msat, edge.from)));
+                }
+            }
+        }
+
+        if !settled.contains(source) || source == self.target {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = source;
+        while let Some(edge) = labels[node].next {
+            path.push(edge);
+            node = edge.to;
+        }
+        Some(path)
+    }
+
+    /// Price a path from the recipient back, checking it against the constraints
+    fn evaluate(&self, path: &[&Edge]) -> Option<Route> {
+        let constraints = self.constraints;
+        if path.is_empty() || path.len() > constraints.max_hops {
+            return None;
+        }
+
+        let mut hops = Vec::with_capacity(path.len());
+        let mut arriving_msat = constraints.amount_msat;
+        let mut total_cltv_expiry_delta = 0u32;
+        let mut cost_msat = 0u64;
+        for (index, edge) in path.iter().enumerate().rev() {
+            if !edge.can_carry(arriving_msat) {
+                return None;
+            }
+            let penalty_msat = self
+                .scorer
+                .penalty_msat(edge.channel, &edge.policy, arriving_msat);
+            let (fee_msat, cltv_expiry_delta) = match index {
+                0 => (0, 0),
+                _ => (
+                    edge.policy.fee_msat(arriving_msat),
+                    edge.policy.cltv_expiry_delta,
+                ),
+            };
+            hops.push(PathHop {
+                channel_id: edge.channel.channel_id.clone(),
+                node_id: edge.to.to_string(),
+                amount_msat: arriving_msat,
+                fee_msat,
+                cltv_expiry_delta,
+            });
+            arriving_msat = arriving_msat.saturating_add(fee_msat);
+            total_cltv_expiry_delta += u32::from(cltv_expiry_delta);
+            cost_msat = cost_msat
+                .saturating_add(fee_msat)
+                .saturating_add(penalty_msat);
+        }
+        hops.reverse();
+
+        let total_fee_msat = arriving_msat - constraints.amount_msat;
+        if total_cltv_expiry_delta > constraints.max_total_cltv_expiry_delta
+            || constraints
+                .max_fee_msat
+                .is_some_and(|max| total_fee_msat > max)
+        {
+            return None;
+        }
+        Some(Route {
+            hops,
+            amount_msat: constraints.amount_msat,
+            total_fee_msat,
+            total_cltv_expiry_delta,
+            cost_msat,
+        })
+    }
+}
+
+impl NetworkGraph {
+    /// Cheapest route from `source` to `target` within `constraints`, by fee
+    /// plus the scorer's penalties
+    pub fn find_route(
+        &self,
+        source: &str,
+        target: &str,
+        constraints: &RouteConstraints,
+        scorer: &dyn RouteScorer,
+    ) -> Option<Route> {
+        self.find_routes(source, target, 1, constraints, scorer)
+            .into_iter()
+            .next()
+    }
+
+    /// Up to `k` loopless routes from `source` to `target`, cheapest first
+    pub fn find_routes(
+        &self,
+        source: &str,
+        target: &str,
+        k: usize,
+        constraints: &RouteConstraints,
+        scorer: &dyn RouteScorer,
+    ) -> Vec<Route> {
+        let search = Search::new(self, target, constraints, scorer);
+        let mut found: Vec<(Vec<&Edge>, Route)> = Vec::new();
+        let mut candidates: Vec<(Vec<&Edge>, Route)> = Vec::new();
+
+        let first = search
+            .shortest(source, &HashSet::new(), &HashSet::new())
+            .and_then(|path| search.evaluate(&path).map(|route| (path, route)));
+        let Some(first) = first else {
+            return Vec::new();
+        };
+        found.push(first);
+
+        while found.len() < k {
+            let previous = found
+                .last()
+                .map(|(path, _)| path.clone())
+                .unwrap_or_default();
+            for spur in 0..previous.len() {
+                let root = &previous[..spur];
+                let spur_node = previous[spur].from;
+
+                // Leave the root the way no route found so far does
+                let excluded_edges: HashSet<(&str, &str)> = found
+                    .iter()
+                    .map(|(path, _)| path)
+                    .filter(|path| path.len() > spur && same_channels(&path[..spur], root))
+                    .map(|path| (path[spur].channel.channel_id.as_str(), spur_node))
+                    .collect();
+                let excluded_nodes: HashSet<&str> = root.iter().map(|edge| edge.from).collect();
+
+                let Some(spur_path) = search.shortest(spur_node, &excluded_edges, &excluded_nodes)
+                else {
+                    continue;
+                };
+                let path: Vec<&Edge> = root.iter().copied().chain(spur_path).collect();
+                let known = found
+                    .iter()
+                    .chain(candidates.iter())
+                    .any(|(other, _)| same_channels(other, &path));
+                if known {
+                    continue;
+                }
+                if let Some(route) = search.evaluate(&path) {
+                    candidates.push((path, route));
+                }
+            }
+
+            let Some(cheapest) = candidates
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (_, route))| route.cost_msat)
+                .map(|(index, _)| index)
+            else {
+                break;
+            };
+            found.push(candidates.swap_remove(cheapest));
+        }
+
+        found.into_iter().map(|(_, route)| route).collect()
+    }
+}
+
+fn same_channels(a: &[&Edge], b: &[&Edge]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(x, y)| x.channel.channel_id == y.channel.channel_id && x.from == y.from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(base_fee_msat: u32, fee_rate_ppm: u32, cltv_expiry_delta: u16) -> ChannelPolicy {
+        ChannelPolicy {
+            is_enabled: true,
+            base_fee_msat,
+            fee_rate_ppm,
+            cltv_expiry_delta,
+            htlc_minimum_msat: 1,
+            htlc_maximum_msat: 1_000_000_000,
+            last_update: 0,
+        }
+    }
+
+    /// A channel with the same policy both ways
+    fn channel(
+        id: &str,
+        a: &str,
+        b: &str,
+        capacity_sat: u64,
+        fees: ChannelPolicy,
+    ) -> NetworkChannelInfo {
+        NetworkChannelInfo {
+            channel_id: id.to_string(),
+            node1: a.to_string(),
+            node2: b.to_string(),
+            capacity_sat,
+            is_enabled: true,
+            last_update: 0,
+            base_fee_msat: fees.base_fee_msat,
+            fee_rate_ppm: fees.fee_rate_ppm,
+            node1_policy: Some(fees.clone()),
+            node2_policy: Some(fees),
+        }
+    }
+
+    /// S reaches T directly through expensive B, or through cheap C and D
+    fn test_graph() -> NetworkGraph {
+        let mut graph = NetworkGraph::new();
+        graph.add_channel(channel("SB", "S", "B", 1_000_000, policy(0, 0, 40)));
+        graph.add_channel(channel("BT", "B", "T", 1_000_000, policy(5_000, 1_000, 40)));
+        graph.add_channel(channel("SC", "S", "C", 1_000_000, policy(0, 0, 40)));
+        graph.add_channel(channel("CD", "C", "D", 1_000_000, policy(1_000, 100, 144)));
+        graph.add_channel(channel("DT", "D", "T", 1_000_000, policy(1_000, 100, 144)));
+        graph
+    }
+
+    #[test]
+    fn test_prefers_cheaper_longer_route() {
+        let graph = test_graph();
+        let constraints = RouteConstraints::new(100_000_000);
+        let route = graph
+            .find_route("S", "T", &constraints, &FeeScorer)
+            .unwrap();
+
+        assert_eq!(route.channel_ids(), vec!["SC", "CD", "DT"]);
+        // D charges 1 sat + 100 ppm of 100k sats; C the same on what D forwards
+        assert_eq!(route.hops[2].fee_msat, 11_000);
+        assert_eq!(route.hops[1].fee_msat, 11_001);
+        assert_eq!(route.hops[0].fee_msat, 0);
+        assert_eq!(route.hops[0].amount_msat, 100_022_001);
+        assert_eq!(route.total_fee_msat, 22_001);
+        assert_eq!(route.total_cltv_expiry_delta, 288);
+
+        // Fewest hops is still what the old search returns
+        assert_eq!(
+            graph.find_shortest_path("S", "T").unwrap(),
+            vec!["S", "B", "T"]
+        );
+    }
+
+    #[test]
+    fn test_respects_direction_capacity_and_cltv() {
+        let mut graph = test_graph();
+
+        // A CLTV limit rules out the cheap route
+        let constraints = RouteConstraints::new(100_000_000).with_max_total_cltv_expiry_delta(200);
+        let route = graph
+            .find_route("S", "T", &constraints, &FeeScorer)
+            .unwrap();
+        assert_eq!(route.channel_ids(), vec!["SB", "BT"]);
+
+        // More than CD can carry
+        graph.add_channel(channel("CD", "C", "D", 1_000, policy(1_000, 100, 144)));
+        let constraints = RouteConstraints::new(100_000_000);
+        let route = graph
+            .find_route("S", "T", &constraints, &FeeScorer)
+            .unwrap();
+        assert_eq!(route.channel_ids(), vec!["SB", "BT"]);
+
+        // D has disabled its side of DT; T can still pay through it
+        let mut graph = test_graph();
+        let mut dt = channel("DT", "D", "T", 1_000_000, policy(1_000, 100, 144));
+        dt.node1_policy.as_mut().unwrap().is_enabled = false;
+        graph.add_channel(dt);
+        let constraints = RouteConstraints::new(100_000_000);
+        let route = graph
+            .find_route("S", "T", &constraints, &FeeScorer)
+            .unwrap();
+        assert_eq!(route.channel_ids(), vec!["SB", "BT"]);
+        assert!(graph
+            .find_route("T", "S", &constraints, &FeeScorer)
+            .is_some());
+    }
+
+    #[test]
+    fn test_fee_limit_and_exclusions() {
+        let graph = test_graph();
+        let constraints = RouteConstraints::new(100_000_000).with_max_fee_msat(20_000);
+        assert!(graph
+            .find_route("S", "T", &constraints, &FeeScorer)
+            .is_none());
+
+        let constraints = RouteConstraints::new(100_000_000).excluding_node("C");
+        let route = graph
+            .find_route("S", "T", &constraints, &FeeScorer)
+            .unwrap();
+        assert_eq!(route.channel_ids(), vec!["SB", "BT"]);
+        let constraints = RouteConstraints::new(100_000_000).excluding_channel("DT");
+        let route = graph
+            .find_route("S", "T", &constraints, &FeeScorer)
+            .unwrap();
+        assert_eq!(route.channel_ids(), vec!["SB", "BT"]);
+    }
+
+    #[test]
+    fn test_k_cheapest_routes() {
+        let mut graph = test_graph();
+        graph.add_channel(channel("BD", "B", "D", 1_000_000, policy(0, 0, 40)));
+
+        let constraints = RouteConstraints::new(100_000_000);
+        let routes = graph.find_routes("S", "T", 5, &constraints, &FeeScorer);
+        let paths: Vec<Vec<&str>> = routes.iter().map(Route::channel_ids).collect();
+        assert_eq!(
+            paths,
+            vec![
+                vec!["SB", "BD", "DT"],
+                vec!["SC", "CD", "DT"],
+                vec!["SB", "BT"],
+                vec!["SC", "CD", "BD", "BT"],
+            ]
+        );
+        assert!(routes
+            .windows(2)
+            .all(|pair| pair[0].cost_msat <= pair[1].cost_msat));
+    }
+
+    #[test]
+    fn test_pluggable_scorer() {
+        let graph = test_graph();
+        let constraints = RouteConstraints::new(100_000_000);
+
+        // Steer away from C whatever it charges
+        let avoid_c = |channel: &NetworkChannelInfo, _: &ChannelPolicy, _: u64| {
+            if channel.node1 == "C" || channel.node2 == "C" {
+                1_000_000
+            } else {
+                0
+            }
+        };
+        let route = graph.find_route("S", "T", &constraints, &avoid_c).unwrap();
+        assert_eq!(route.channel_ids(), vec!["SB", "BT"]);
+        assert_eq!(route.cost_msat, route.total_fee_msat);
+
+        // Small channels are unlikely to carry a large share of their capacity
+        let scorer = ProbabilityScorer::default();
+        let small = channel("X", "A", "B", 200_000, policy(0, 0, 40));
+        let large = channel("Y", "A", "B", 2_000_000, policy(0, 0, 40));
+        let fees = policy(0, 0, 40);
+        assert!(
+            scorer.penalty_msat(&small, &fees, 100_000_000)
+                > scorer.penalty_msat(&large, &fees, 100_000_000)
+        );
+    }
+}
@@ -1,11 +1,13 @@
+use crate::store::{payments, PaymentQuery, PaymentRecord, Store};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tokio::sync::RwLock;
+
+/// Payment states, shared with every other writer of the payments table
+pub use crate::store::PaymentStatus as PaymentState;
 
 /// Payment processor for handling Lightning Network payments
 #[derive(Debug, Clone)]
 pub struct PaymentProcessor {
-    payments: RwLock<HashMap<String, PaymentInfo>>,
+    store: Store,
 }
 
 /// Information about a payment
@@ -20,21 +22,49 @@ pub struct PaymentInfo {
     pub failure_reason: Option<String>,
 }
 
-/// Payment state enumeration
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum PaymentState {
-    Pending,
-    InFlight,
-    Succeeded,
-    Failed,
+impl PaymentInfo {
+    fn to_record(&self) -> PaymentRecord {
+        PaymentRecord {
+            payment_id: self.payment_hash.clone(),
+            wallet_id: String::new(),
+            direction: "OUTBOUND".to_string(),
+            amount_msat: self.amount_msat,
+            invoice: String::new(),
+            destination: Some(self.destination.clone()),
+            description: String::new(),
+            status: self.state.as_str().to_string(),
+            payment_hash: self.payment_hash.clone(),
+            retry_count: 0,
+            error_message: self.failure_reason.clone(),
+            created_at: self.created_at as i64,
+            updated_at: self.completed_at.unwrap_or(self.created_at) as i64,
+            completed_at: self.completed_at.map(|t| t as i64),
+        }
+    }
+
+    fn from_record(record: PaymentRecord) -> Result<Self, String> {
+        Ok(Self {
+            payment_hash: record.payment_hash,
+            amount_msat: record.amount_msat,
+            destination: record.destination.unwrap_or_default(),
+            state: PaymentState::parse(&record.status).map_err(|e| e.to_string())?,
+            created_at: record.created_at as u64,
+            completed_at: record.completed_at.map(|t| t as u64),
+            failure_reason: record.error_message,
+        })
+    }
 }
 
 impl PaymentProcessor {
-    /// Create a new payment processor
+    /// Create a new payment processor backed by a non-persistent store
     pub fn new() -> Self {
-        Self {
-            payments: RwLock::new(HashMap::new()),
-        }
+        let store = Store::open_in_memory().expect("in-memory store should always open");
+        Self::with_store(store)
+    }
+
+    /// Create a payment processor that keeps payments in `store`
+    pub fn with_store(store: Store) -> Self {
+        Self { store }
     }
 
     /// Create a new payment
@@ -57,16 +87,20 @@ impl PaymentProcessor {
             failure_reason: None,
         };
 
-        let mut payments = self.payments.write().await;
-        payments.insert(payment_hash.clone(), payment_info.clone());
+        self.store
+            .with_conn(|c| payments::insert(c, &payment_info.to_record()))
+            .map_err(|e| e.to_string())?;
 
         Ok(payment_info)
     }
 
     /// Get payment information
     pub async fn get_payment(&self, payment_hash: &str) -> Option<PaymentInfo> {
-        let payments = self.payments.read().await;
-        payments.get(payment_hash).cloned()
+        self.store
+            .with_conn(|c| payments::get(c, payment_hash))
+            .ok()
+            .flatten()
+            .and_then(|record| PaymentInfo::from_record(record).ok())
     }
 
     /// Update payment state
@@ -76,40 +110,48 @@ impl PaymentProcessor {
         state: PaymentState,
         failure_reason: Option<String>,
     ) -> Result<(), String> {
-        let mut payments = self.payments.write().await;
-
-        if let Some(payment) = payments.get_mut(payment_hash) {
-            payment.state = state;
-            payment.failure_reason = failure_reason;
-
-            if state == PaymentState::Succeeded || state == PaymentState::Failed {
-                payment.completed_at = Some(
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs(),
-                );
-            }
-
-            Ok(())
-        } else {
-            Err("Payment not found".to_string())
-        }
+        self.store
+            .transaction(|tx| {
+                let record = payments::get(tx, payment_hash)?
+                    .ok_or_else(|| anyhow::anyhow!("Payment not found"))?;
+                let mut payment =
+                    PaymentInfo::from_record(record).map_err(|e| anyhow::anyhow!(e))?;
+
+                payment.failure_reason = failure_reason;
+                if state == PaymentState::Succeeded || state == PaymentState::Failed {
+                    payment.completed_at = Some(
+                        std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                    );
+                }
+                payment.state = state;
+
+                payments::update(tx, &payment.to_record())
+            })
+            .map_err(|e| e.to_string())
     }
 
     /// Get all payments
     pub async fn get_all_payments(&self) -> Vec<PaymentInfo> {
-        let payments = self.payments.read().await;
-        payments.values().cloned().collect()
+        self.query_payments(&PaymentQuery::default())
     }
 
     /// Get payments by state
     pub async fn get_payments_by_state(&self, state: PaymentState) -> Vec<PaymentInfo> {
-        let payments = self.payments.read().await;
-        payments
-            .values()
-            .filter(|p| p.state == state)
-            .cloned()
+        self.query_payments(&PaymentQuery {
+            status: Some(state.as_str().to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn query_payments(&self, query: &PaymentQuery) -> Vec<PaymentInfo> {
+        self.store
+            .with_conn(|c| payments::list(c, query))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|record| PaymentInfo::from_record(record).ok())
             .collect()
     }
 
@@ -0,0 +1,531 @@
+use crate::lightning_engine::{LightningEngine, LightningError};
+use crate::shutdown::Shutdown;
+use crate::store::{self, peers, PeerRecord, Store};
+use anyhow::Result;
+use bitcoin::secp256k1::PublicKey;
+use chrono::{DateTime, Utc};
+use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::lightning::types::features::NodeFeatures;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PeerError {
+    #[error("Invalid peer URI {0}, expected pubkey@host:port")]
+    InvalidUri(String),
+    #[error("Invalid node id: {0}")]
+    InvalidNodeId(String),
+    #[error("Peer {0} not found")]
+    NotFound(String),
+}
+
+/// A peer's node id and where to reach it, written `pubkey@host:port`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerUri {
+    pub node_id: PublicKey,
+    pub address: SocketAddress,
+}
+
+impl FromStr for PeerUri {
+    type Err = PeerError;
+
+    fn from_str(uri: &str) -> Result<Self, Self::Err> {
+        let invalid = || PeerError::InvalidUri(uri.to_string());
+        let (node_id, address) = uri.trim().split_once('@').ok_or_else(invalid)?;
+        Ok(Self {
+            node_id: PublicKey::from_str(node_id).map_err(|_| invalid())?,
+            address: SocketAddress::from_str(address).map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for PeerUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.node_id, self.address)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerConfig {
+    /// Seconds between checks for persisted peers that have disconnected
+    pub reconnect_interval_secs: u64,
+    /// Wait before the second attempt to reconnect a peer; doubles after
+    /// every failure
+    pub min_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+}
+
+impl Default for PeerConfig {
+    fn default() -> Self {
+        Self {
+            reconnect_interval_secs: 10,
+            min_backoff_secs: 5,
+            max_backoff_secs: 600, // 10 minutes
+        }
+    }
+}
+
+impl PeerConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.reconnect_interval_secs == 0 || self.min_backoff_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "Peer reconnect interval and backoff must be at least 1 second"
+            ));
+        }
+        if self.min_backoff_secs > self.max_backoff_secs {
+            return Err(anyhow::anyhow!(
+                "Minimum peer backoff {}s exceeds the maximum {}s",
+                self.min_backoff_secs,
+                self.max_backoff_secs
+            ));
+        }
+        Ok(())
+    }
+
+    /// Wait after `failures` failed attempts in a row
+    fn backoff(&self, failures: u32) -> Duration {
+        let secs = self
+            .min_backoff_secs
+            .saturating_mul(1u64 << failures.saturating_sub(1).min(32))
+            .min(self.max_backoff_secs);
+        Duration::from_secs(secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum PeerState {
+    Connected,
+    Connecting,
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub node_id: String,
+    pub address: String,
+    pub state: PeerState,
+    /// Reconnected after restarts and disconnects
+    pub persisted: bool,
+    /// Features the peer announced, e.g. `VariableLengthOnion`
+    pub features: Vec<String>,
+    /// Time the last connection took to set up, TCP and handshake included
+    pub connect_latency_ms: Option<u64>,
+    pub connected_since: Option<DateTime<Utc>>,
+    /// Failed reconnects since the peer was last connected
+    pub reconnect_attempts: u32,
+    pub next_reconnect_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// What we know about a connection beyond what the node reports
+#[derive(Debug, Default, Clone)]
+struct Connection {
+    connecting: bool,
+    latency: Option<Duration>,
+    connected_since: Option<DateTime<Utc>>,
+    failures: u32,
+    next_attempt: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+/// Connects to peers through the LDK node and keeps the persisted ones
+/// connected, retrying with exponential backoff
+#[derive(Debug)]
+pub struct PeerManager {
+    store: Store,
+    config: PeerConfig,
+    engine: Arc<LightningEngine>,
+    connections: RwLock<HashMap<String, Connection>>,
+}
+
+impl PeerManager {
+    /// Create a manager that keeps persisted peers in `store`
+    pub fn with_store(config: PeerConfig, store: Store, engine: Arc<LightningEngine>) -> Self {
+        Self {
+            store,
+            config,
+            engine,
+            connections: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Connect to the peer at `uri` (`pubkey@host:port`). Persisted peers are
+    /// reconnected after restarts and whenever the connection drops.
+    pub async fn connect_peer(&self, uri: &str, persist: bool) -> Result<PeerInfo> {
+        let peer: PeerUri = uri.parse()?;
+        let node_id = peer.node_id.to_string();
+
+        self.connect(&peer).await?;
+        if persist {
+            let now = store::now();
+            let record = PeerRecord {
+                node_id: node_id.clone(),
+                address: peer.address.to_string(),
+                created_at: now,
+                updated_at: now,
+            };
+            self.store.with_conn(|c| peers::upsert(c, &record))?;
+        }
+        self.peer(&node_id).await
+    }
+
+    /// Disconnect from a peer and stop reconnecting it
+    pub async fn disconnect_peer(&self, node_id: &str) -> Result<()> {
+        let key = PublicKey::from_str(node_id)
+            .map_err(|_| PeerError::InvalidNodeId(node_id.to_string()))?;
+        let node_id = key.to_string();
+
+        let connected = self
+            .engine
+            .list_peers()
+            .await?
+            .iter()
+            .any(|peer| peer.node_id == key && peer.is_connected);
+        let forgotten = self.store.with_conn(|c| peers::delete(c, &node_id))?;
+        if !forgotten && !connected {
+            return Err(PeerError::NotFound(node_id).into());
+        }
+
+        self.connections.write().await.remove(&node_id);
+        self.engine.disconnect_peer(key).await?;
+        info!("Disconnected peer {}", node_id);
+        Ok(())
+    }
+
+    /// Peers the node is connected to and the persisted ones it is not
+    pub async fn list_peers(&self) -> Result<Vec<PeerInfo>> {
+        // (address, connected) by node id, sorted so the listing is stable
+        let mut known: BTreeMap<String, (String, bool)> = BTreeMap::new();
+        for record in self.store.with_conn(peers::list)? {
+            known.insert(record.node_id, (record.address, false));
+        }
+        let persisted: Vec<String> = known.keys().cloned().collect();
+        for peer in self.engine.list_peers().await? {
+            if peer.is_connected || known.contains_key(&peer.node_id.to_string()) {
+                known.insert(
+                    peer.node_id.to_string(),
+                    (peer.address.to_string(), peer.is_connected),
+                );
+            }
+        }
+
+        let connections = self.connections.read().await.clone();
+        let mut listed = Vec::with_capacity(known.len());
+        for (node_id, (address, connected)) in known {
+            let features = match PublicKey::from_str(&node_id) {
+                Ok(key) => self.engine.node_features(&key).await?,
+                Err(_) => None,
+            };
+            listed.push(peer_info(
+                node_id.clone(),
+                address,
+                connected,
+                persisted.contains(&node_id),
+                features.as_ref(),
+                connections.get(&node_id),
+            ));
+        }
+        Ok(listed)
+    }
+
+    /// A single peer, see [`PeerManager::list_peers`]
+    pub async fn peer(&self, node_id: &str) -> Result<PeerInfo> {
+        self.list_peers()
+            .await?
+            .into_iter()
+            .find(|peer| peer.node_id == node_id)
+            .ok_or_else(|| PeerError::NotFound(node_id.to_string()).into())
+    }
+
+    /// Reconnect persisted peers until shutdown
+    pub async fn run(self: Arc<Self>, shutdown: Shutdown) {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.reconnect_interval_secs));
+        let triggered = shutdown.triggered();
+        tokio::pin!(triggered);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut triggered => return,
+            }
+            if let Err(e) = self.reconnect_due().await {
+                // Nothing to do until the node is up
+                if !matches!(e.downcast_ref(), Some(LightningError::NodeNotRunning)) {
+                    warn!("Failed to reconnect peers: {:#}", e);
+                }
+            }
+        }
+    }
+
+    /// Try once to reconnect every persisted peer that is disconnected and
+    /// whose backoff has passed
+    async fn reconnect_due(&self) -> Result<()> {
+        let connected: Vec<String> = self
+            .engine
+            .list_peers()
+            .await?
+            .into_iter()
+            .filter(|peer| peer.is_connected)
+            .map(|peer| peer.node_id.to_string())
+            .collect();
+        let now = Utc::now();
+
+        for record in self.store.with_conn(peers::list)? {
+            if connected.contains(&record.node_id) {
+                continue;
+            }
+            let due = self
+                .connections
+                .read()
+                .await
+                .get(&record.node_id)
+                .and_then(|connection| connection.next_attempt)
+                .is_none_or(|next| next <= now);
+            if !due {
+                continue;
+            }
+
+            let peer: PeerUri = match format!("{}@{}", record.node_id, record.address).parse() {
+                Ok(peer) => peer,
+                Err(e) => {
+                    warn!("Not reconnecting peer {}: {}", record.node_id, e);
+                    continue;
+                }
+            };
+            match self.connect(&peer).await {
+                Ok(()) => info!("Reconnected peer {}", peer),
+                Err(e) => warn!("Failed to reconnect peer {}: {:#}", peer, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Connect and record the outcome; failures schedule the next attempt
+    async fn connect(&self, peer: &PeerUri) -> Result<()> {
+        let node_id = peer.node_id.to_string();
+        self.connections
+            .write()
+            .await
+            .entry(node_id.clone())
+            .or_default()
+            .connecting = true;
+
+        let started = Instant::now();
+        let result = self
+            .engine
+            .connect_peer(peer.node_id, peer.address.clone())
+            .await;
+
+        let mut connections = self.connections.write().await;
+        let connection = connections.entry(node_id).or_default();
+        connection.connecting = false;
+        match &result {
+            Ok(()) => {
+                connection.latency = Some(started.elapsed());
+                connection.connected_since = Some(Utc::now());
+                connection.failures = 0;
+                connection.next_attempt = None;
+                connection.last_error = None;
+            }
+            Err(e) => {
+                connection.connected_since = None;
+                connection.failures += 1;
+                connection.next_attempt =
+                    chrono::Duration::from_std(self.config.backoff(connection.failures))
+                        .ok()
+                        .map(|backoff| Utc::now() + backoff);
+                connection.last_error = Some(format!("{:#}", e));
+            }
+        }
+        result
+    }
+}
+
+fn peer_info(
+    node_id: String,
+    address: String,
+    connected: bool,
+    persisted: bool,
+    features: Option<&NodeFeatures>,
+    connection: Option<&Connection>,
+) -> PeerInfo {
+    let connection = connection.cloned().unwrap_or_default();
+    let state = if connected {
+        PeerState::Connected
+    } else if connection.connecting {
+        PeerState::Connecting
+    } else {
+        PeerState::Disconnected
+    };
+    PeerInfo {
+        node_id,
+        address,
+        state,
+        persisted,
+        features: features.map(feature_names).unwrap_or_default(),
+        connect_latency_ms: connection.latency.map(|latency| latency.as_millis() as u64),
+        // A connection the node made on its own, e.g. an inbound one
+        connected_since: connection.connected_since.filter(|_| connected),
+        reconnect_attempts: connection.failures,
+        next_reconnect_at: connection.next_attempt.filter(|_| persisted && !connected),
+        last_error: connection.last_error,
+    }
+}
+
+/// Names of the features a node requires or supports
+fn feature_names(features: &NodeFeatures) -> Vec<String> {
+    // `Display` lists every known feature as `Name: required`, `Name: supported`
+    // or `Name: not supported`, then the unknown bits
+    features
+        .to_string()
+        .split(", ")
+        .filter_map(|entry| entry.split_once(": "))
+        .filter(|(name, status)| {
+            *name != "unknown flags" && matches!(*status, "required" | "supported")
+        })
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+
+    #[test]
+    fn test_parse_peer_uri() {
+        let peer: PeerUri = format!("{}@127.0.0.1:9735", PEER).parse().unwrap();
+        assert_eq!(peer.node_id.to_string(), PEER);
+        assert_eq!(peer.to_string(), format!("{}@127.0.0.1:9735", PEER));
+
+        let peer: PeerUri = format!("{}@node.example.com:9735", PEER).parse().unwrap();
+        assert_eq!(peer.address.to_string(), "node.example.com:9735");
+
+        for uri in [
+            PEER.to_string(),
+            format!("{}@127.0.0.1", PEER),
+            "peer123@127.0.0.1:9735".to_string(),
+        ] {
+            assert!(matches!(
+                uri.parse::<PeerUri>(),
+                Err(PeerError::InvalidUri(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_maximum() {
+        let config = PeerConfig::default();
+        assert_eq!(config.backoff(1), Duration::from_secs(5));
+        assert_eq!(config.backoff(2), Duration::from_secs(10));
+        assert_eq!(config.backoff(4), Duration::from_secs(40));
+        assert_eq!(config.backoff(10), Duration::from_secs(600));
+        assert_eq!(config.backoff(u32::MAX), Duration::from_secs(600));
+
+        assert!(PeerConfig {
+            min_backoff_secs: 700,
+            ..PeerConfig::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_peer_state() {
+        let failed = Connection {
+            latency: Some(Duration::from_millis(120)),
+            connected_since: Some(Utc::now()),
+            failures: 2,
+            next_attempt: Some(Utc::now()),
+            last_error: Some("Connection refused".to_string()),
+            ..Connection::default()
+        };
+        let peer = peer_info(
+            PEER.to_string(),
+            "127.0.0.1:9735".to_string(),
+            false,
+            true,
+            None,
+            Some(&failed),
+        );
+        assert_eq!(peer.state, PeerState::Disconnected);
+        assert_eq!(peer.connect_latency_ms, Some(120));
+        assert!(peer.connected_since.is_none());
+        assert!(peer.next_reconnect_at.is_some());
+
+        let connecting = Connection {
+            connecting: true,
+            ..failed
+        };
+        let peer = peer_info(
+            PEER.to_string(),
+            "127.0.0.1:9735".to_string(),
+            false,
+            true,
+            None,
+            Some(&connecting),
+        );
+        assert_eq!(peer.state, PeerState::Connecting);
+
+        // Inbound connections have no history of ours
+        let peer = peer_info(
+            PEER.to_string(),
+            "127.0.0.1:9735".to_string(),
+            true,
+            false,
+            None,
+            None,
+        );
+        assert_eq!(peer.state, PeerState::Connected);
+        assert!(peer.next_reconnect_at.is_none());
+    }
+
+    #[test]
+    fn test_feature_names() {
+        let mut features = NodeFeatures::empty();
+        features.set_static_remote_key_required();
+        features.set_variable_length_onion_optional();
+
+        let names = feature_names(&features);
+        assert!(names.contains(&"StaticRemoteKey".to_string()));
+        assert!(names.contains(&"VariableLengthOnion".to_string()));
+        assert!(!names.contains(&"DataLossProtect".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_persisted_peers_need_node() {
+        let engine = LightningEngine::new(
+            std::env::temp_dir().join("satsconnect_peer_manager_test"),
+            bitcoin::Network::Regtest,
+        );
+        let manager = PeerManager::with_store(
+            PeerConfig::default(),
+            Store::open_in_memory().unwrap(),
+            Arc::new(engine),
+        );
+
+        let err = manager
+            .connect_peer(&format!("{}@127.0.0.1:9735", PEER), true)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(LightningError::NodeNotRunning)
+        ));
+        // A failed connect is not persisted, but the failure is kept for
+        // the listing
+        assert!(manager.store.with_conn(peers::list).unwrap().is_empty());
+        let connections = manager.connections.read().await;
+        assert_eq!(connections[PEER].failures, 1);
+        assert!(connections[PEER].last_error.is_some());
+    }
+}
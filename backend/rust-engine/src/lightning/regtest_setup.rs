@@ -1,14 +1,83 @@
+//! Local regtest Lightning network for demos and integration tests.
+//!
+//! `RegtestSetup` launches `bitcoind -regtest` and, for the Esplora chain
+//! source, an electrs Esplora server in front of it, each on free local ports
+//! under the setup's data directory. It then starts LDK nodes against that
+//! chain, funds them by mining, and opens, pays over and closes real channels
+//! between them. `bitcoind` and `electrs` are taken from `BITCOIND_EXE` and
+//! `ELECTRS_EXE`, else from `PATH`; electrs must be Blockstream's Esplora
+//! fork, which serves the HTTP API the nodes use.
+
+use crate::config::ChainSource;
 use anyhow::Result;
-use bitcoin::{Network, Address};
-use ldk_node::{Builder, Node};
-use std::path::PathBuf;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{Address, Amount, Network};
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+use ldk_node::bitcoin::hashes::Hash;
+use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::lightning::types::payment::PaymentHash;
+use ldk_node::lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription, Description};
+use ldk_node::{Builder, Event, Node, UserChannelId};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
 use std::str::FromStr;
-use tracing::{info, warn, error};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{error, info};
+
+const RPC_USER: &str = "satsconnect";
+const RPC_PASSWORD: &str = "satsconnect";
+/// bitcoind wallet the harness mines to and funds nodes from
+const MINER_WALLET: &str = "miner";
+/// Confirmations LDK waits for before a channel is ready
+const CHANNEL_CONFIRMATIONS: u64 = 6;
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+const EVENT_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegtestError {
+    #[error("Could not launch {exe}: {source}; set {env} or put it on PATH")]
+    Launch {
+        exe: String,
+        env: &'static str,
+        source: std::io::Error,
+    },
+    #[error("{name} exited with {status}, see {log}")]
+    ProcessExited {
+        name: &'static str,
+        status: String,
+        log: String,
+    },
+    #[error("Data directory {0} is not empty; regtest nodes need a fresh one")]
+    DataDirInUse(String),
+    #[error("Regtest chain has not been started")]
+    NotStarted,
+    #[error("Timed out waiting for {0}")]
+    Timeout(String),
+    #[error("Node {0} not found")]
+    NodeNotFound(String),
+    #[error("Node {0} is not running")]
+    NodeNotRunning(String),
+    #[error("No channel between {0} and {1}")]
+    NoChannel(String, String),
+    #[error("Payment from {from} to {to} failed: {reason}")]
+    PaymentFailed {
+        from: String,
+        to: String,
+        reason: String,
+    },
+}
 
 /// Local regtest Lightning Network setup
 pub struct RegtestSetup {
     data_dir: PathBuf,
-    esplora_url: String,
+    chain_source: ChainSource,
+    bitcoind_exe: PathBuf,
+    electrs_exe: PathBuf,
+    bitcoind: Option<Bitcoind>,
+    electrs: Option<Electrs>,
     nodes: Vec<RegtestNode>,
 }
 
@@ -18,26 +87,270 @@ pub struct RegtestNode {
     pub data_dir: PathBuf,
     pub node: Option<Node>,
     pub node_id: Option<String>,
+    /// On-chain address the node is funded at
     pub address: Option<String>,
     pub port: u16,
 }
 
+struct Bitcoind {
+    process: Child,
+    log: PathBuf,
+    data_dir: PathBuf,
+    rpc: Client,
+    rpc_port: u16,
+    miner_address: Address,
+}
+
+struct Electrs {
+    process: Child,
+    log: PathBuf,
+    url: String,
+}
+
+impl RegtestNode {
+    /// The LDK node, if it is running
+    pub fn running(&self) -> Result<&Node> {
+        self.node
+            .as_ref()
+            .ok_or_else(|| RegtestError::NodeNotRunning(self.name.clone()).into())
+    }
+
+    pub fn public_key(&self) -> Result<PublicKey> {
+        Ok(self.running()?.node_id())
+    }
+
+    pub fn listening_address(&self) -> SocketAddress {
+        SocketAddress::TcpIpV4 {
+            addr: [127, 0, 0, 1],
+            port: self.port,
+        }
+    }
+
+    /// Handle the node's events until one matches, discarding the others
+    async fn wait_for_event(
+        &self,
+        what: &str,
+        mut matches: impl FnMut(&Event) -> bool,
+    ) -> Result<Event> {
+        let node = self.running()?;
+        let deadline = Instant::now() + EVENT_TIMEOUT;
+        loop {
+            let event = tokio::time::timeout_at(deadline, node.next_event_async())
+                .await
+                .map_err(|_| RegtestError::Timeout(format!("{} on {}", what, self.name)))?;
+            node.event_handled()?;
+            if matches(&event) {
+                return Ok(event);
+            }
+        }
+    }
+}
+
 impl RegtestSetup {
-    /// Create a new regtest setup
+    /// Create a new regtest setup; nothing is launched until [`Self::start`]
     pub fn new(base_data_dir: PathBuf) -> Self {
         Self {
             data_dir: base_data_dir,
-            esplora_url: "http://127.0.0.1:3000".to_string(),
+            chain_source: ChainSource::Esplora,
+            bitcoind_exe: executable("BITCOIND_EXE", "bitcoind"),
+            electrs_exe: executable("ELECTRS_EXE", "electrs"),
+            bitcoind: None,
+            electrs: None,
             nodes: Vec::new(),
         }
     }
 
-    /// Create multiple regtest nodes for testing
+    /// Have the nodes follow the chain through electrs (the default) or
+    /// bitcoind's RPC directly, in which case no electrs is launched
+    pub fn with_chain_source(mut self, chain_source: ChainSource) -> Self {
+        self.chain_source = chain_source;
+        self
+    }
+
+    pub fn with_bitcoind_exe(mut self, path: PathBuf) -> Self {
+        self.bitcoind_exe = path;
+        self
+    }
+
+    pub fn with_electrs_exe(mut self, path: PathBuf) -> Self {
+        self.electrs_exe = path;
+        self
+    }
+
+    /// Launch bitcoind and the chain source, and mine past coinbase maturity
+    /// so the miner wallet can fund nodes
+    pub async fn start(&mut self) -> Result<()> {
+        if self.bitcoind.is_some() {
+            return Ok(());
+        }
+        if std::fs::read_dir(&self.data_dir).is_ok_and(|mut dir| dir.next().is_some()) {
+            return Err(RegtestError::DataDirInUse(self.data_dir.display().to_string()).into());
+        }
+
+        self.bitcoind = Some(self.start_bitcoind().await?);
+        let bitcoind = self.bitcoind()?;
+        bitcoind
+            .rpc
+            .generate_to_address(101, &bitcoind.miner_address)?;
+
+        if self.chain_source == ChainSource::Esplora {
+            self.electrs = Some(self.start_electrs()?);
+        }
+        self.wait_for_chain_source().await?;
+
+        info!(
+            "Regtest chain started at {} with {:?} chain source",
+            self.data_dir.display(),
+            self.chain_source
+        );
+        Ok(())
+    }
+
+    async fn start_bitcoind(&self) -> Result<Bitcoind> {
+        let data_dir = self.data_dir.join("bitcoind");
+        std::fs::create_dir_all(&data_dir)?;
+        let rpc_port = free_port()?;
+        let log = self.data_dir.join("bitcoind.log");
+
+        let mut process = spawn(
+            &self.bitcoind_exe,
+            "BITCOIND_EXE",
+            &[
+                "-regtest".to_string(),
+                "-server".to_string(),
+                "-listen=0".to_string(),
+                "-txindex".to_string(),
+                "-fallbackfee=0.0002".to_string(),
+                format!("-datadir={}", data_dir.display()),
+                format!("-rpcport={}", rpc_port),
+                "-rpcbind=127.0.0.1".to_string(),
+                "-rpcallowip=127.0.0.1".to_string(),
+                format!("-rpcuser={}", RPC_USER),
+                format!("-rpcpassword={}", RPC_PASSWORD),
+            ],
+            &log,
+        )?;
+        let rpc = Client::new(
+            &format!("http://127.0.0.1:{}", rpc_port),
+            Auth::UserPass(RPC_USER.to_string(), RPC_PASSWORD.to_string()),
+        )?;
+        poll("bitcoind RPC", STARTUP_TIMEOUT, || {
+            check_running(&mut process, "bitcoind", &log)?;
+            Ok(rpc.get_blockchain_info().is_ok())
+        })
+        .await?;
+
+        rpc.create_wallet(MINER_WALLET, None, None, None, None)?;
+        let miner_address = rpc.get_new_address(None, None)?.assume_checked();
+        info!("bitcoind listening for RPC on port {}", rpc_port);
+
+        Ok(Bitcoind {
+            process,
+            log,
+            data_dir,
+            rpc,
+            rpc_port,
+            miner_address,
+        })
+    }
+
+    fn start_electrs(&self) -> Result<Electrs> {
+        let bitcoind = self.bitcoind()?;
+        let http_port = free_port()?;
+        let log = self.data_dir.join("electrs.log");
+
+        let process = spawn(
+            &self.electrs_exe,
+            "ELECTRS_EXE",
+            &[
+                "-vv".to_string(),
+                "--network".to_string(),
+                "regtest".to_string(),
+                "--jsonrpc-import".to_string(),
+                "--daemon-dir".to_string(),
+                bitcoind.data_dir.display().to_string(),
+                "--daemon-rpc-addr".to_string(),
+                format!("127.0.0.1:{}", bitcoind.rpc_port),
+                "--cookie".to_string(),
+                format!("{}:{}", RPC_USER, RPC_PASSWORD),
+                "--db-dir".to_string(),
+                self.data_dir.join("electrs").display().to_string(),
+                "--http-addr".to_string(),
+                format!("127.0.0.1:{}", http_port),
+                "--electrum-rpc-addr".to_string(),
+                format!("127.0.0.1:{}", free_port()?),
+            ],
+            &log,
+        )?;
+        Ok(Electrs {
+            process,
+            log,
+            url: format!("http://127.0.0.1:{}", http_port),
+        })
+    }
+
+    fn bitcoind(&self) -> Result<&Bitcoind> {
+        self.bitcoind
+            .as_ref()
+            .ok_or_else(|| RegtestError::NotStarted.into())
+    }
+
+    /// Wait until electrs has indexed bitcoind's tip; bitcoind itself is
+    /// always up to date
+    async fn wait_for_chain_source(&mut self) -> Result<()> {
+        let height = self.block_height()?;
+        let Some(electrs) = self.electrs.as_mut() else {
+            return Ok(());
+        };
+
+        let http = reqwest::Client::new();
+        let url = format!("{}/blocks/tip/height", electrs.url);
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            check_running(&mut electrs.process, "electrs", &electrs.log)?;
+            let tip = match http.get(&url).send().await {
+                Ok(response) => response.text().await.ok(),
+                Err(_) => None,
+            };
+            if tip.and_then(|tip| tip.trim().parse::<u64>().ok()) >= Some(height) {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(
+                    RegtestError::Timeout(format!("electrs to reach block {}", height)).into(),
+                );
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    pub fn block_height(&self) -> Result<u64> {
+        Ok(self.bitcoind()?.rpc.get_block_count()?)
+    }
+
+    /// Mine `count` blocks and sync every running node to the new tip,
+    /// returning the new height
+    pub async fn mine_blocks(&mut self, count: u64) -> Result<u64> {
+        let bitcoind = self.bitcoind()?;
+        bitcoind
+            .rpc
+            .generate_to_address(count, &bitcoind.miner_address)?;
+        self.wait_for_chain_source().await?;
+        for node in &self.nodes {
+            if let Some(node) = &node.node {
+                node.sync_wallets()?;
+            }
+        }
+        self.block_height()
+    }
+
+    /// Create and start regtest nodes, starting the chain first if needed
     pub async fn create_test_nodes(&mut self, count: usize) -> Result<()> {
+        self.start().await?;
         info!("Creating {} regtest Lightning nodes...", count);
 
-        for i in 0..count {
-            let node_name = format!("node_{}", i + 1);
+        for _ in 0..count {
+            let node_name = format!("node_{}", self.nodes.len() + 1);
             let node_data_dir = self.data_dir.join(&node_name);
             std::fs::create_dir_all(&node_data_dir)?;
 
@@ -47,69 +360,56 @@ impl RegtestSetup {
                 node: None,
                 node_id: None,
                 address: None,
-                port: 9735 + i as u16,
+                port: free_port()?,
             };
 
-            // Create and start the node
-            self.start_node(&mut regtest_node).await?;
+            self.start_node(&mut regtest_node)?;
             self.nodes.push(regtest_node);
-
-            info!("Created regtest node: {}", node_name);
         }
 
         info!("Successfully created {} regtest nodes", count);
         Ok(())
     }
 
-    /// Start a regtest node
-    async fn start_node(&self, regtest_node: &mut RegtestNode) -> Result<()> {
+    /// Start a node with a fresh wallet against the regtest chain
+    fn start_node(&self, regtest_node: &mut RegtestNode) -> Result<()> {
         info!("Starting regtest node: {}", regtest_node.name);
 
-        // Create node builder
-        let builder = Builder::new()
+        let mut builder = Builder::new();
+        builder
             .set_network(Network::Regtest)
-            .set_esplora_server(self.esplora_url.clone())
-            .set_storage_dir_path(regtest_node.data_dir.clone())
-            .set_network_graph_use_persisted(false);
+            .set_storage_dir_path(regtest_node.data_dir.to_string_lossy().into_owned());
+        builder.set_listening_addresses(vec![regtest_node.listening_address()])?;
+        match &self.electrs {
+            Some(electrs) => {
+                builder.set_chain_source_esplora(electrs.url.clone(), None);
+            }
+            None => {
+                builder.set_chain_source_bitcoind_rpc(
+                    "127.0.0.1".to_string(),
+                    self.bitcoind()?.rpc_port,
+                    RPC_USER.to_string(),
+                    RPC_PASSWORD.to_string(),
+                );
+            }
+        }
 
-        // Build and start the node
         let node = builder.build()?;
-        node.start().await?;
-
-        // Generate node information
-        let node_id = self.generate_node_id(&node)?;
-        let address = self.generate_funding_address(&node)?;
+        node.start()?;
 
+        regtest_node.node_id = Some(node.node_id().to_string());
+        regtest_node.address = Some(node.onchain_payment().new_address()?.to_string());
         regtest_node.node = Some(node);
-        regtest_node.node_id = Some(node_id);
-        regtest_node.address = Some(address);
 
         info!(
             "Regtest node {} started - Node ID: {}, Address: {}",
             regtest_node.name,
-            regtest_node.node_id.as_ref().unwrap(),
-            regtest_node.address.as_ref().unwrap()
+            regtest_node.node_id.as_deref().unwrap_or_default(),
+            regtest_node.address.as_deref().unwrap_or_default()
         );
-
         Ok(())
     }
 
-    /// Generate a node ID for the regtest node
-    fn generate_node_id(&self, node: &Node) -> Result<String> {
-        // In a real implementation, this would get the actual node ID
-        // For now, we'll generate a simulated one
-        let simulated_node_id = format!("03{:064x}", rand::random::<u64>());
-        Ok(simulated_node_id)
-    }
-
-    /// Generate a funding address for the regtest node
-    fn generate_funding_address(&self, node: &Node) -> Result<String> {
-        // In a real implementation, this would get the actual funding address
-        // For now, we'll generate a simulated one
-        let simulated_address = format!("bcrt1q{:040x}", rand::random::<u64>());
-        Ok(simulated_address)
-    }
-
     /// Get all regtest nodes
     pub fn get_nodes(&self) -> &[RegtestNode] {
         &self.nodes
@@ -120,62 +420,132 @@ impl RegtestSetup {
         self.nodes.iter().find(|node| node.name == name)
     }
 
-    /// Connect two nodes (simulate channel opening)
+    fn node(&self, name: &str) -> Result<&RegtestNode> {
+        self.get_node(name)
+            .ok_or_else(|| RegtestError::NodeNotFound(name.to_string()).into())
+    }
+
+    /// Send `amount_sats` from the miner wallet to a node and confirm it
+    pub async fn fund_node(&mut self, node_name: &str, amount_sats: u64) -> Result<()> {
+        let node = self.node(node_name)?;
+        let target = node
+            .running()?
+            .list_balances()
+            .spendable_onchain_balance_sats
+            + amount_sats;
+        let address = Address::from_str(node.address.as_deref().unwrap_or_default())?
+            .require_network(Network::Regtest)?;
+
+        self.bitcoind()?.rpc.send_to_address(
+            &address,
+            Amount::from_sat(amount_sats),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )?;
+        self.mine_blocks(1).await?;
+
+        let node = self.node(node_name)?.running()?;
+        poll(
+            &format!("{} sats on {}", amount_sats, node_name),
+            EVENT_TIMEOUT,
+            || {
+                node.sync_wallets()?;
+                Ok(node.list_balances().spendable_onchain_balance_sats >= target)
+            },
+        )
+        .await?;
+        info!("Funded {} with {} sats", node_name, amount_sats);
+        Ok(())
+    }
+
+    /// Connect `node1_name` to `node2_name` as peers; the connection is
+    /// kept across restarts
     pub async fn connect_nodes(&self, node1_name: &str, node2_name: &str) -> Result<()> {
-        let node1 = self.get_node(node1_name)
-            .ok_or_else(|| anyhow::anyhow!("Node {} not found", node1_name))?;
-        let node2 = self.get_node(node2_name)
-            .ok_or_else(|| anyhow::anyhow!("Node {} not found", node2_name))?;
+        let node1 = self.node(node1_name)?;
+        let node2 = self.node(node2_name)?;
 
         info!("Connecting nodes: {} <-> {}", node1_name, node2_name);
+        node1
+            .running()?
+            .connect(node2.public_key()?, node2.listening_address(), true)?;
+        Ok(())
+    }
+
+    /// Open a channel from one node to another, funded from the opener's
+    /// on-chain wallet, and mine until both sides report it ready
+    pub async fn open_channel(
+        &mut self,
+        from_node: &str,
+        to_node: &str,
+        capacity_sats: u64,
+        push_msat: Option<u64>,
+    ) -> Result<UserChannelId> {
+        let from = self.node(from_node)?;
+        let to = self.node(to_node)?;
+        let from_id = from.public_key()?;
 
-        // In a real implementation, this would open a channel between the nodes
-        // For now, we'll just log the connection
         info!(
-            "Simulated connection: {} ({}) <-> {} ({})",
-            node1_name,
-            node1.node_id.as_ref().unwrap_or(&"Unknown".to_string()),
-            node2_name,
-            node2.node_id.as_ref().unwrap_or(&"Unknown".to_string())
+            "Opening {} sat channel {} -> {}",
+            capacity_sats, from_node, to_node
         );
+        let user_channel_id = from.running()?.open_channel(
+            to.public_key()?,
+            to.listening_address(),
+            capacity_sats,
+            push_msat,
+            None,
+        )?;
+        from.wait_for_event("channel funding", |event| {
+            matches!(event, Event::ChannelPending { user_channel_id: id, .. } if *id == user_channel_id)
+        })
+        .await?;
+
+        self.mine_blocks(CHANNEL_CONFIRMATIONS).await?;
+        self.node(from_node)?
+            .wait_for_event("channel ready", |event| {
+                matches!(event, Event::ChannelReady { user_channel_id: id, .. } if *id == user_channel_id)
+            })
+            .await?;
+        self.node(to_node)?
+            .wait_for_event("channel ready", |event| {
+                matches!(event, Event::ChannelReady { counterparty_node_id, .. } if *counterparty_node_id == Some(from_id))
+            })
+            .await?;
 
-        Ok(())
+        info!("Channel {} -> {} is ready", from_node, to_node);
+        Ok(user_channel_id)
+    }
+
+    fn invoice(&self, node_name: &str, amount_sats: u64, memo: &str) -> Result<Bolt11Invoice> {
+        let description = Bolt11InvoiceDescription::Direct(Description::new(memo.to_string())?);
+        Ok(self.node(node_name)?.running()?.bolt11_payment().receive(
+            amount_sats * 1000,
+            &description,
+            3600,
+        )?)
     }
 
-    /// Create a test invoice on a specific node
+    /// Create an invoice on a node, returning it with its payment hash
     pub async fn create_test_invoice(
         &self,
         node_name: &str,
         amount_sats: u64,
         memo: &str,
     ) -> Result<(String, String)> {
-        let node = self.get_node(node_name)
-            .ok_or_else(|| anyhow::anyhow!("Node {} not found", node_name))?;
-
+        let invoice = self.invoice(node_name, amount_sats, memo)?;
         info!(
-            "Creating test invoice on {}: {} sats - {}",
+            "Created invoice on {}: {} sats - {}",
             node_name, amount_sats, memo
         );
-
-        // In a real implementation, this would create an actual invoice
-        // For now, we'll generate a simulated one
-        let simulated_invoice = format!(
-            "lnbc{}u1p{:x}pp{:x}",
-            amount_sats,
-            rand::random::<u32>(),
-            rand::random::<u32>()
-        );
-        let payment_hash = format!("{:064x}", rand::random::<u64>());
-
-        info!(
-            "Test invoice created: {} (Hash: {})",
-            simulated_invoice, payment_hash
-        );
-
-        Ok((simulated_invoice, payment_hash))
+        Ok((invoice.to_string(), invoice.payment_hash().to_string()))
     }
 
-    /// Send a test payment between nodes
+    /// Pay a fresh invoice of `to_node` from `from_node`, waiting until the
+    /// payment has arrived; returns the payment hash
     pub async fn send_test_payment(
         &self,
         from_node: &str,
@@ -187,58 +557,137 @@ impl RegtestSetup {
             from_node, to_node, amount_sats
         );
 
-        // Create invoice on destination node
-        let (invoice, payment_hash) = self
-            .create_test_invoice(to_node, amount_sats, "Test payment")
+        let invoice = self.invoice(to_node, amount_sats, "Test payment")?;
+        let hash = PaymentHash(invoice.payment_hash().to_byte_array());
+        let from = self.node(from_node)?;
+        let payment_id = from.running()?.bolt11_payment().send(&invoice, None)?;
+
+        let outcome = from
+            .wait_for_event("payment", |event| match event {
+                Event::PaymentSuccessful {
+                    payment_id: Some(id),
+                    ..
+                }
+                | Event::PaymentFailed {
+                    payment_id: Some(id),
+                    ..
+                } => *id == payment_id,
+                _ => false,
+            })
+            .await?;
+        if let Event::PaymentFailed { reason, .. } = outcome {
+            return Err(RegtestError::PaymentFailed {
+                from: from_node.to_string(),
+                to: to_node.to_string(),
+                reason: format!("{:?}", reason),
+            }
+            .into());
+        }
+        self.node(to_node)?
+            .wait_for_event("payment", |event| {
+                matches!(event, Event::PaymentReceived { payment_hash, .. } if *payment_hash == hash)
+            })
             .await?;
 
-        // Simulate payment processing
-        info!(
-            "Simulated payment sent: {} -> {} via invoice {}",
-            from_node, to_node, &invoice[..20]
-        );
+        info!("Payment {} -> {} arrived", from_node, to_node);
+        Ok(invoice.payment_hash().to_string())
+    }
+
+    /// Cooperatively close the channel between two nodes and confirm the
+    /// closing transaction
+    pub async fn close_channel(&mut self, node1_name: &str, node2_name: &str) -> Result<()> {
+        let node1 = self.node(node1_name)?;
+        let counterparty = self.node(node2_name)?.public_key()?;
+        let channel = node1
+            .running()?
+            .list_channels()
+            .into_iter()
+            .find(|channel| channel.counterparty_node_id == counterparty)
+            .ok_or_else(|| {
+                RegtestError::NoChannel(node1_name.to_string(), node2_name.to_string())
+            })?;
+
+        info!("Closing channel {} <-> {}", node1_name, node2_name);
+        node1
+            .running()?
+            .close_channel(&channel.user_channel_id, counterparty)?;
+        node1
+            .wait_for_event("channel close", |event| {
+                matches!(event, Event::ChannelClosed { user_channel_id, .. } if *user_channel_id == channel.user_channel_id)
+            })
+            .await?;
 
-        Ok(payment_hash)
+        let rpc = &self.bitcoind()?.rpc;
+        poll("closing transaction", EVENT_TIMEOUT, || {
+            Ok(!rpc.get_raw_mempool()?.is_empty())
+        })
+        .await?;
+        self.mine_blocks(1).await?;
+        info!("Channel {} <-> {} closed", node1_name, node2_name);
+        Ok(())
     }
 
     /// Get network statistics
     pub fn get_network_stats(&self) -> RegtestNetworkStats {
         let total_nodes = self.nodes.len();
-        let active_nodes = self
+        let active_nodes = self.nodes.iter().filter(|node| node.node.is_some()).count();
+        let channels = self
             .nodes
             .iter()
-            .filter(|node| node.node.is_some())
-            .count();
+            .filter_map(|node| node.node.as_ref())
+            .map(|node| node.list_channels().len())
+            .sum::<usize>()
+            / 2;
 
         RegtestNetworkStats {
             total_nodes,
             active_nodes,
+            channels,
+            block_height: self.block_height().ok(),
             network_type: "Regtest".to_string(),
-            esplora_url: self.esplora_url.clone(),
+            esplora_url: self.electrs.as_ref().map(|electrs| electrs.url.clone()),
         }
     }
 
     /// Print network status
     pub fn print_network_status(&self) {
         println!("\n🔗 Regtest Lightning Network Status");
-        println!("=" .repeat(50));
+        println!("{}", "=".repeat(50));
 
         let stats = self.get_network_stats();
         println!("Network Type: {}", stats.network_type);
-        println!("Esplora URL: {}", stats.esplora_url);
+        match &stats.esplora_url {
+            Some(url) => println!("Esplora URL: {}", url),
+            None => println!("Chain Source: bitcoind RPC"),
+        }
+        if let Some(height) = stats.block_height {
+            println!("Block Height: {}", height);
+        }
         println!("Total Nodes: {}", stats.total_nodes);
         println!("Active Nodes: {}", stats.active_nodes);
+        println!("Channels: {}", stats.channels);
 
         println!("\n📋 Node Details:");
         for node in &self.nodes {
-            let status = if node.node.is_some() { "🟢 Active" } else { "🔴 Inactive" };
+            let status = if node.node.is_some() {
+                "🟢 Active"
+            } else {
+                "🔴 Inactive"
+            };
             println!(
                 "  {} {} - Port: {} - Node ID: {}",
                 status,
                 node.name,
                 node.port,
-                node.node_id.as_ref().unwrap_or(&"Unknown".to_string())
+                node.node_id.as_deref().unwrap_or("Unknown")
             );
+            if let Some(lightning_node) = &node.node {
+                let balances = lightning_node.list_balances();
+                println!(
+                    "    On-chain: {} sats - Lightning: {} sats",
+                    balances.spendable_onchain_balance_sats, balances.total_lightning_balance_sats
+                );
+            }
             if let Some(address) = &node.address {
                 println!("    Funding Address: {}", address);
             }
@@ -262,6 +711,23 @@ impl RegtestSetup {
         info!("All regtest nodes stopped");
         Ok(())
     }
+
+    /// Stop the nodes, then electrs and bitcoind
+    pub async fn shutdown(&mut self) -> Result<()> {
+        self.stop_all_nodes().await?;
+        if let Some(mut electrs) = self.electrs.take() {
+            let _ = electrs.process.kill();
+            electrs.process.wait()?;
+        }
+        if let Some(mut bitcoind) = self.bitcoind.take() {
+            if bitcoind.rpc.stop().is_err() {
+                let _ = bitcoind.process.kill();
+            }
+            bitcoind.process.wait()?;
+        }
+        info!("Regtest chain stopped");
+        Ok(())
+    }
 }
 
 /// Regtest network statistics
@@ -269,18 +735,91 @@ impl RegtestSetup {
 pub struct RegtestNetworkStats {
     pub total_nodes: usize,
     pub active_nodes: usize,
+    /// Channels between the setup's nodes
+    pub channels: usize,
+    pub block_height: Option<u64>,
     pub network_type: String,
-    pub esplora_url: String,
+    /// Unset when the nodes use bitcoind's RPC
+    pub esplora_url: Option<String>,
 }
 
 impl Drop for RegtestSetup {
     fn drop(&mut self) {
-        // Ensure all nodes are stopped when the setup is dropped
+        // Ensure all nodes and processes are stopped when the setup is dropped
         for node in &mut self.nodes {
             if let Some(lightning_node) = node.node.take() {
                 let _ = lightning_node.stop();
             }
         }
+        if let Some(electrs) = &mut self.electrs {
+            let _ = electrs.process.kill();
+            let _ = electrs.process.wait();
+        }
+        if let Some(bitcoind) = &mut self.bitcoind {
+            let _ = bitcoind.process.kill();
+            let _ = bitcoind.process.wait();
+        }
+    }
+}
+
+fn executable(env: &str, name: &str) -> PathBuf {
+    std::env::var_os(env)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+fn free_port() -> Result<u16> {
+    Ok(std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port())
+}
+
+/// Launch `exe` with its output going to `log`
+fn spawn(exe: &Path, env: &'static str, args: &[String], log: &Path) -> Result<Child> {
+    let output = File::create(log)?;
+    Command::new(exe)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(output.try_clone()?)
+        .stderr(output)
+        .spawn()
+        .map_err(|source| {
+            RegtestError::Launch {
+                exe: exe.display().to_string(),
+                env,
+                source,
+            }
+            .into()
+        })
+}
+
+fn check_running(process: &mut Child, name: &'static str, log: &Path) -> Result<()> {
+    match process.try_wait()? {
+        Some(status) => Err(RegtestError::ProcessExited {
+            name,
+            status: status.to_string(),
+            log: log.display().to_string(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Check `ready` every [`POLL_INTERVAL`] until it holds or `timeout` passes
+async fn poll(
+    what: &str,
+    timeout: Duration,
+    mut ready: impl FnMut() -> Result<bool>,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if ready()? {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(RegtestError::Timeout(what.to_string()).into());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
     }
 }
 
@@ -290,35 +829,45 @@ mod tests {
     use tempfile::tempdir;
 
     #[tokio::test]
-    async fn test_regtest_setup_creation() {
+    async fn test_missing_bitcoind() {
         let temp_dir = tempdir().unwrap();
-        let mut setup = RegtestSetup::new(temp_dir.path().to_path_buf());
-        
-        setup.create_test_nodes(2).await.unwrap();
-        assert_eq!(setup.get_nodes().len(), 2);
+        let mut setup = RegtestSetup::new(temp_dir.path().join("regtest"))
+            .with_bitcoind_exe(temp_dir.path().join("no-such-bitcoind"));
+
+        let err = setup.create_test_nodes(1).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RegtestError>(),
+            Some(RegtestError::Launch {
+                env: "BITCOIND_EXE",
+                ..
+            })
+        ));
+        assert!(setup.get_nodes().is_empty());
     }
 
     #[tokio::test]
-    async fn test_node_connection() {
+    async fn test_refuses_used_data_dir() {
         let temp_dir = tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("leftover"), b"").unwrap();
         let mut setup = RegtestSetup::new(temp_dir.path().to_path_buf());
-        
-        setup.create_test_nodes(2).await.unwrap();
-        setup.connect_nodes("node_1", "node_2").await.unwrap();
+
+        let err = setup.start().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RegtestError>(),
+            Some(RegtestError::DataDirInUse(_))
+        ));
     }
 
     #[tokio::test]
-    async fn test_invoice_creation() {
+    async fn test_unknown_node() {
         let temp_dir = tempdir().unwrap();
-        let mut setup = RegtestSetup::new(temp_dir.path().to_path_buf());
-        
-        setup.create_test_nodes(1).await.unwrap();
-        let (invoice, hash) = setup
-            .create_test_invoice("node_1", 1000, "Test invoice")
-            .await
-            .unwrap();
-        
-        assert!(invoice.starts_with("lnbc"));
-        assert!(!hash.is_empty());
+        let setup = RegtestSetup::new(temp_dir.path().to_path_buf());
+
+        let err = setup.connect_nodes("node_1", "node_2").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RegtestError>(),
+            Some(RegtestError::NodeNotFound(name)) if name == "node_1"
+        ));
+        assert!(setup.get_network_stats().block_height.is_none());
     }
 }
@@ -0,0 +1,305 @@
+use crate::lightning::network_graph::{NetworkChannelInfo, NetworkGraph};
+use crate::lightning::pathfinding::{FeeScorer, RouteConstraints};
+use anyhow::Result;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, instrument};
+
+/// How long a route estimate stays valid for repeated requests
+pub const DEFAULT_ESTIMATE_TTL: Duration = Duration::from_secs(30);
+
+/// What to estimate a route to
+#[derive(Debug, Clone)]
+pub enum EstimateTarget {
+    /// BOLT11 invoice; amount and route hints are taken from the invoice
+    Invoice(String),
+    /// Node pubkey (hex) for a keysend-style payment of `amount_msat`
+    Node { pubkey: String, amount_msat: u64 },
+}
+
+/// Expected cost and reliability of paying a destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteEstimate {
+    pub destination: String,
+    pub amount_msat: u64,
+    pub fee_msat: u64,
+    pub hop_count: u32,
+    /// A-priori probability (0.0 - 1.0) that the payment succeeds
+    pub success_probability: f64,
+    /// Node ids from the sender to the destination
+    pub path: Vec<String>,
+    /// Whether LDK probes were dispatched along the route
+    pub probed: bool,
+    /// Whether this estimate was served from the cache
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone)]
+struct CachedEstimate {
+    estimate: RouteEstimate,
+    created_at: Instant,
+}
+
+/// Estimates routing fees over a graph snapshot and caches the results
+#[derive(Debug)]
+pub struct RouteEstimator {
+    cache: Arc<RwLock<LruCache<(String, u64), CachedEstimate>>>,
+    ttl: Duration,
+}
+
+impl RouteEstimator {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            cache: Arc::new(RwLock::new(LruCache::new(NonZeroUsize::new(256).unwrap()))),
+            ttl,
+        }
+    }
+
+    /// Return a still-fresh estimate for the destination and amount, if any
+    pub async fn cached(&self, destination: &str, amount_msat: u64) -> Option<RouteEstimate> {
+        let mut cache = self.cache.write().await;
+        let key = (destination.to_string(), amount_msat);
+
+        match cache.get(&key) {
+            Some(entry) if entry.created_at.elapsed() < self.ttl => {
+                let mut estimate = entry.estimate.clone();
+                estimate.cached = true;
+                Some(estimate)
+            }
+            Some(_) => {
+                cache.pop(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store an estimate for reuse within the TTL window
+    pub async fn store(&self, estimate: RouteEstimate) {
+        let key = (estimate.destination.clone(), estimate.amount_msat);
+        let mut cache = self.cache.write().await;
+        cache.put(
+            key,
+            CachedEstimate {
+                estimate,
+                created_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Compute an estimate for paying `destination` from `source` over `graph`.
+    ///
+    /// The estimate follows the cheapest route by fee, where each channel
+    /// charges what the policy of its forwarding node asks for the amount it
+    /// forwards; the first hop is our own channel and charges no fee.
+    #[instrument(skip(self, graph))]
+    pub fn estimate(
+        &self,
+        graph: &NetworkGraph,
+        source: &str,
+        destination: &str,
+        amount_msat: u64,
+    ) -> Result<RouteEstimate> {
+        let route = graph
+            .find_route(
+                source,
+                destination,
+                &RouteConstraints::new(amount_msat),
+                &FeeScorer,
+            )
+            .ok_or_else(|| anyhow::anyhow!("No route found to {}", destination))?;
+
+        let success_probability = route
+            .hops
+            .iter()
+            .map(|hop| {
+                graph.get_channel(&hop.channel_id).map_or(1.0, |channel| {
+                    Self::channel_success_probability(channel, hop.amount_msat)
+                })
+            })
+            .product();
+
+        let path = std::iter::once(source.to_string())
+            .chain(route.hops.iter().map(|hop| hop.node_id.clone()))
+            .collect();
+
+        info!(
+            "Estimated route to {}: {} hops, {} msat fee, p={:.3}",
+            destination,
+            route.hops.len(),
+            route.total_fee_msat,
+            success_probability
+        );
+
+        Ok(RouteEstimate {
+            destination: destination.to_string(),
+            amount_msat,
+            fee_msat: route.total_fee_msat,
+            hop_count: route.hops.len() as u32,
+            success_probability,
+            path,
+            probed: false,
+            cached: false,
+        })
+    }
+
+    /// Success probability of a single channel assuming uniformly distributed
+    /// liquidity. Channels with unknown capacity (e.g. route hints) count as certain.
+    fn channel_success_probability(channel: &NetworkChannelInfo, amount_msat: u64) -> f64 {
+        if channel.capacity_sat == 0 {
+            return 1.0;
+        }
+        let capacity_msat = channel.capacity_sat as f64 * 1000.0;
+        ((capacity_msat - amount_msat as f64) / capacity_msat).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for RouteEstimator {
+    fn default() -> Self {
+        Self::new(DEFAULT_ESTIMATE_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lightning::network_graph::{ChannelPolicy, NodeInfo};
+
+    fn node(id: &str) -> NodeInfo {
+        NodeInfo {
+            node_id: id.to_string(),
+            alias: None,
+            color: None,
+            last_seen: 0,
+            features: vec![],
+            addresses: vec![],
+        }
+    }
+
+    fn channel(
+        id: &str,
+        a: &str,
+        b: &str,
+        base_fee_msat: u32,
+        fee_rate_ppm: u32,
+    ) -> NetworkChannelInfo {
+        NetworkChannelInfo {
+            channel_id: id.to_string(),
+            node1: a.to_string(),
+            node2: b.to_string(),
+            capacity_sat: 1_000_000,
+            is_enabled: true,
+            last_update: 0,
+            base_fee_msat,
+            fee_rate_ppm,
+            node1_policy: None,
+            node2_policy: None,
+        }
+    }
+
+    fn test_graph() -> NetworkGraph {
+        let mut graph = NetworkGraph::new();
+        for id in ["us", "A", "B"] {
+            graph.add_node(node(id));
+        }
+        graph.add_channel(channel("usA", "us", "A", 0, 0));
+        graph.add_channel(channel("AB", "A", "B", 1_000, 100));
+        graph
+    }
+
+    #[test]
+    fn test_estimate_fee_and_hops() {
+        let estimator = RouteEstimator::default();
+        let estimate = estimator
+            .estimate(&test_graph(), "us", "B", 100_000_000)
+            .unwrap();
+
+        // A charges 1 sat base + 100 ppm of 100k sats = 11 sats
+        assert_eq!(estimate.fee_msat, 11_000);
+        assert_eq!(estimate.hop_count, 2);
+        assert_eq!(estimate.path, vec!["us", "A", "B"]);
+        assert!(estimate.success_probability > 0.0 && estimate.success_probability < 1.0);
+    }
+
+    fn policy(base_fee_msat: u32, fee_rate_ppm: u32) -> ChannelPolicy {
+        ChannelPolicy {
+            is_enabled: true,
+            base_fee_msat,
+            fee_rate_ppm,
+            cltv_expiry_delta: 40,
+            htlc_minimum_msat: 0,
+            htlc_maximum_msat: u64::MAX,
+            last_update: 0,
+        }
+    }
+
+    #[test]
+    fn test_estimate_uses_forwarding_node_policy() {
+        // A forwards to B for free, B charges to forward to A
+        let mut ab = channel("AB", "A", "B", 0, 0);
+        ab.node1_policy = Some(policy(0, 0));
+        ab.node2_policy = Some(policy(50_000, 5_000));
+
+        let mut graph = NetworkGraph::new();
+        graph.add_channel(channel("usA", "us", "A", 0, 0));
+        graph.add_channel(ab.clone());
+        let estimate = RouteEstimator::default()
+            .estimate(&graph, "us", "B", 100_000_000)
+            .unwrap();
+        assert_eq!(estimate.path, vec!["us", "A", "B"]);
+        assert_eq!(estimate.fee_msat, 0);
+
+        let mut graph = NetworkGraph::new();
+        graph.add_channel(channel("usB", "us", "B", 0, 0));
+        graph.add_channel(ab);
+        let estimate = RouteEstimator::default()
+            .estimate(&graph, "us", "A", 100_000_000)
+            .unwrap();
+        assert_eq!(estimate.path, vec!["us", "B", "A"]);
+        // 50 sats base + 5000 ppm of 100k sats
+        assert_eq!(estimate.fee_msat, 550_000);
+    }
+
+    #[test]
+    fn test_estimate_prefers_cheaper_longer_route() {
+        let mut graph = test_graph();
+        // A also reaches B through C, for less than the direct channel
+        graph.add_channel(channel("AB", "A", "B", 50_000, 1_000));
+        graph.add_channel(channel("AC", "A", "C", 0, 0));
+        graph.add_channel(channel("CB", "C", "B", 1_000, 100));
+
+        let estimate = RouteEstimator::default()
+            .estimate(&graph, "us", "B", 100_000_000)
+            .unwrap();
+        assert_eq!(estimate.path, vec!["us", "A", "C", "B"]);
+        assert_eq!(estimate.hop_count, 3);
+        // C charges 11 sats, A forwards to C for free
+        assert_eq!(estimate.fee_msat, 11_000);
+    }
+
+    #[test]
+    fn test_estimate_no_route() {
+        let estimator = RouteEstimator::default();
+        assert!(estimator
+            .estimate(&test_graph(), "us", "unknown", 1_000)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_estimate_cache_expiry() {
+        let estimator = RouteEstimator::new(Duration::from_millis(50));
+        let estimate = estimator.estimate(&test_graph(), "us", "B", 1_000).unwrap();
+        estimator.store(estimate).await;
+
+        let cached = estimator.cached("B", 1_000).await.unwrap();
+        assert!(cached.cached);
+        assert!(estimator.cached("B", 2_000).await.is_none());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(estimator.cached("B", 1_000).await.is_none());
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use ldk_node::lightning::events::PaymentFailureReason as LdkPaymentFailureReason;
+use ldk_node::payment::SendingParameters;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Default time to wait for an outgoing payment to resolve
+pub const DEFAULT_PAYMENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Caller-supplied constraints for an outgoing Lightning payment
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SendPaymentOptions {
+    /// Absolute routing fee ceiling in sats
+    pub fee_limit_sats: Option<u64>,
+    /// Routing fee ceiling in parts-per-million of the payment amount
+    pub fee_limit_ppm: Option<u32>,
+    /// How long to wait for the payment to resolve before reporting a timeout
+    pub timeout: Option<Duration>,
+    /// Maximum number of MPP parts
+    pub max_parts: Option<u8>,
+    /// Maximum total CLTV expiry delta across the route
+    pub max_cltv_expiry_delta: Option<u32>,
+}
+
+/// Structured reason for a payment that did not succeed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PaymentFailureReason {
+    NoRoute,
+    FeeTooHigh,
+    Timeout,
+    RecipientRejected,
+    InvoiceExpired,
+    RetriesExhausted,
+    Unknown,
+}
+
+/// Result of an outgoing payment attempt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendPaymentOutcome {
+    pub payment_hash: String,
+    /// SUCCEEDED / PENDING / FAILED
+    pub status: String,
+    pub fee_paid_sats: u64,
+    pub failure_reason: Option<PaymentFailureReason>,
+}
+
+impl SendPaymentOptions {
+    /// Validate option ranges before they are handed to LDK
+    pub fn validate(&self) -> Result<()> {
+        if let Some(ppm) = self.fee_limit_ppm {
+            if ppm > 1_000_000 {
+                return Err(anyhow::anyhow!(
+                    "fee_limit_ppm {} exceeds 1000000 (100%)",
+                    ppm
+                ));
+            }
+        }
+
+        if self.max_parts == Some(0) {
+            return Err(anyhow::anyhow!("max_parts must be at least 1"));
+        }
+
+        if let Some(timeout) = self.timeout {
+            if timeout.is_zero() {
+                return Err(anyhow::anyhow!("timeout must be greater than zero"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the caller constrained routing fees at all
+    pub fn has_fee_limit(&self) -> bool {
+        self.fee_limit_sats.is_some() || self.fee_limit_ppm.is_some()
+    }
+
+    /// Effective fee ceiling in msat for a payment of `amount_msat`.
+    /// When both limits are given the stricter one wins.
+    pub fn max_fee_msat(&self, amount_msat: u64) -> Option<u64> {
+        let absolute = self.fee_limit_sats.map(|sats| sats.saturating_mul(1000));
+        let proportional = self
+            .fee_limit_ppm
+            .map(|ppm| (amount_msat as u128 * ppm as u128 / 1_000_000) as u64);
+
+        match (absolute, proportional) {
+            (Some(a), Some(p)) => Some(a.min(p)),
+            (a, p) => a.or(p),
+        }
+    }
+
+    /// Payment timeout, falling back to [`DEFAULT_PAYMENT_TIMEOUT`]
+    pub fn timeout_or_default(&self) -> Duration {
+        self.timeout.unwrap_or(DEFAULT_PAYMENT_TIMEOUT)
+    }
+
+    /// Map the options onto ldk-node's route parameters
+    pub fn to_sending_parameters(&self, amount_msat: u64) -> SendingParameters {
+        SendingParameters {
+            max_total_routing_fee_msat: self.max_fee_msat(amount_msat).map(Some),
+            max_total_cltv_expiry_delta: self.max_cltv_expiry_delta,
+            max_path_count: self.max_parts,
+            max_channel_saturation_power_of_half: None,
+        }
+    }
+}
+
+impl PaymentFailureReason {
+    /// Translate LDK's failure reason.
+    ///
+    /// LDK reports an exhausted fee budget as a routing failure, so when the
+    /// caller set a fee limit we surface it as `FeeTooHigh` instead.
+    pub fn from_ldk(reason: Option<LdkPaymentFailureReason>, fee_limited: bool) -> Self {
+        match reason {
+            Some(LdkPaymentFailureReason::RecipientRejected) => Self::RecipientRejected,
+            Some(LdkPaymentFailureReason::PaymentExpired) => Self::InvoiceExpired,
+            Some(LdkPaymentFailureReason::RouteNotFound) if fee_limited => Self::FeeTooHigh,
+            Some(LdkPaymentFailureReason::RouteNotFound) => Self::NoRoute,
+            Some(LdkPaymentFailureReason::RetriesExhausted) => Self::RetriesExhausted,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoRoute => "NO_ROUTE",
+            Self::FeeTooHigh => "FEE_TOO_HIGH",
+            Self::Timeout => "TIMEOUT",
+            Self::RecipientRejected => "RECIPIENT_REJECTED",
+            Self::InvoiceExpired => "INVOICE_EXPIRED",
+            Self::RetriesExhausted => "RETRIES_EXHAUSTED",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl std::fmt::Display for PaymentFailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stricter_fee_limit_wins() {
+        let options = SendPaymentOptions {
+            fee_limit_sats: Some(10),
+            fee_limit_ppm: Some(5_000),
+            ..Default::default()
+        };
+
+        // 0.5% of 1_000_000 msat is 5_000 msat, below the 10 sat ceiling
+        assert_eq!(options.max_fee_msat(1_000_000), Some(5_000));
+        // 0.5% of 10_000_000 msat is 50_000 msat, above the 10 sat ceiling
+        assert_eq!(options.max_fee_msat(10_000_000), Some(10_000));
+        assert_eq!(SendPaymentOptions::default().max_fee_msat(1_000), None);
+    }
+
+    #[test]
+    fn test_options_validation() {
+        assert!(SendPaymentOptions::default().validate().is_ok());
+
+        let options = SendPaymentOptions {
+            fee_limit_ppm: Some(2_000_000),
+            ..Default::default()
+        };
+        assert!(options.validate().is_err());
+
+        let options = SendPaymentOptions {
+            max_parts: Some(0),
+            ..Default::default()
+        };
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_failure_reason_mapping() {
+        assert_eq!(
+            PaymentFailureReason::from_ldk(Some(LdkPaymentFailureReason::RouteNotFound), false),
+            PaymentFailureReason::NoRoute
+        );
+        assert_eq!(
+            PaymentFailureReason::from_ldk(Some(LdkPaymentFailureReason::RouteNotFound), true),
+            PaymentFailureReason::FeeTooHigh
+        );
+        assert_eq!(
+            PaymentFailureReason::from_ldk(Some(LdkPaymentFailureReason::RecipientRejected), true),
+            PaymentFailureReason::RecipientRejected
+        );
+        assert_eq!(
+            PaymentFailureReason::from_ldk(None, false),
+            PaymentFailureReason::Unknown
+        );
+    }
+}
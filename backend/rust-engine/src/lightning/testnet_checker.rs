@@ -1,7 +1,7 @@
+use crate::lightning::peer_manager::PeerUri;
 use anyhow::Result;
 use bitcoin::Network;
 use ldk_node::{Builder, Node};
-use std::str::FromStr;
 use std::time::Duration;
 use tokio::time::timeout;
 use tracing::{error, info, warn};
@@ -207,16 +207,11 @@ impl TestnetChecker {
 
     /// Connect to a specific Lightning node
     async fn connect_to_node(&self, test_node: &Node, target_node: &TestnetNode) -> Result<()> {
-        // Parse the node URI
-        let node_id = bitcoin::secp256k1::PublicKey::from_str(&target_node.node_id)?;
-        
-        // Try to connect to the node
-        // Note: This is a simplified version - in practice, you'd need to handle
-        // the actual Lightning protocol handshake
+        let peer: PeerUri = target_node.uri.parse()?;
         info!("Attempting to connect to node: {}", target_node.name);
-        
-        // For now, we'll just simulate a successful connection
-        // In a real implementation, you'd use the LDK node's connect method
+
+        // Returns once the handshake with the node has completed
+        test_node.connect(peer.node_id, peer.address, false)?;
         Ok(())
     }
 
@@ -1,22 +1,76 @@
-use crate::config::LightningConfig;
+use crate::config::{ChainSource, GossipSource, LightningConfig};
+use crate::lightning::network_graph::{ChannelPolicy, NetworkChannelInfo, NetworkGraph};
+use crate::lightning::route_estimator::{EstimateTarget, RouteEstimate, RouteEstimator};
+use crate::lightning::send_options::{
+    PaymentFailureReason, SendPaymentOptions, SendPaymentOutcome,
+};
+use crate::network;
 use anyhow::Result;
 use bip32::{DerivationPath, ExtendedPrivateKey};
 use bip39::{Language, Mnemonic};
 use bitcoin::secp256k1::{Secp256k1, SecretKey};
 use bitcoin::{Address, Network, PrivateKey, PublicKey};
-use ldk_node::{Builder, Node, NodeError};
-use lightning_invoice::{Currency, Invoice};
+use ldk_node::lightning::events::PaymentFailureReason as LdkPaymentFailureReason;
+use ldk_node::lightning::ln::channelmanager::PaymentId;
+use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::lightning::routing::gossip::NodeId;
+use ldk_node::lightning::types::features::NodeFeatures;
+use ldk_node::lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription, Description};
+use ldk_node::payment::{PaymentDirection, PaymentKind, PaymentStatus};
+use ldk_node::{
+    Builder, ChannelDetails, Event, Node, NodeError, NodeStatus, PeerDetails, UserChannelId,
+};
+use lru::LruCache;
+use std::num::NonZeroUsize;
 use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
 use tracing::{error, info, warn};
 
+/// Failure reasons kept for payments nobody waits on; older ones are dropped
+const MAX_PAYMENT_FAILURES: usize = 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LightningError {
+    #[error("Lightning node not initialized")]
+    NodeNotRunning,
+    #[error("Amountless invoices are not supported")]
+    AmountlessInvoice,
+    #[error("Amount must be greater than zero")]
+    InvalidAmount,
+    #[error("Invalid payment id: {0}")]
+    InvalidPaymentId(String),
+    #[error(
+        "Refund invoice amount {invoice_msat} msat does not match refund amount {amount_msat} msat"
+    )]
+    RefundAmountMismatch { invoice_msat: u64, amount_msat: u64 },
+}
+
 /// Lightning Network Engine for SatsConnect
 /// Handles all Lightning Network operations including wallet creation,
 /// invoice generation, and payment processing.
 pub struct LightningEngine {
     node: Arc<RwLock<Option<Node>>>,
+    /// Serializes `initialize` so concurrent callers start a single node
+    init_lock: Mutex<()>,
     config: LightningConfig,
+    /// Failure reasons reported by LDK for outgoing payments, keyed by payment id
+    payment_failures: Arc<RwLock<LruCache<PaymentId, Option<LdkPaymentFailureReason>>>>,
+    route_estimator: RouteEstimator,
+    /// LSP just-in-time channels are bought from over LSPS2
+    lsps2_source: Option<(bitcoin::secp256k1::PublicKey, SocketAddress, Option<String>)>,
+}
+
+// `ldk_node::Node` has no `Debug`, so components holding the engine can
+// still derive it
+impl std::fmt::Debug for LightningEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LightningEngine")
+            .field("network", &self.config.network)
+            .field("data_dir", &self.config.data_dir)
+            .finish_non_exhaustive()
+    }
 }
 
 impl LightningEngine {
@@ -26,20 +80,55 @@ impl LightningEngine {
         config.data_dir = data_dir;
         config.network = network;
 
-        Self {
-            node: Arc::new(RwLock::new(None)),
-            config,
-        }
+        Self::with_config(config)
     }
 
     /// Create a new Lightning Engine instance with configuration
     pub fn with_config(config: LightningConfig) -> Self {
         Self {
             node: Arc::new(RwLock::new(None)),
+            init_lock: Mutex::new(()),
             config,
+            payment_failures: Arc::new(RwLock::new(LruCache::new(
+                NonZeroUsize::new(MAX_PAYMENT_FAILURES).unwrap(),
+            ))),
+            route_estimator: RouteEstimator::default(),
+            lsps2_source: None,
         }
     }
 
+    /// Let the node buy just-in-time channels over LSPS2 from the LSP at
+    /// `node_id@address`, so it can receive without inbound liquidity
+    pub fn with_lsps2_source(
+        mut self,
+        node_id: bitcoin::secp256k1::PublicKey,
+        address: SocketAddress,
+        token: Option<String>,
+    ) -> Self {
+        self.lsps2_source = Some((node_id, address, token));
+        self
+    }
+
+    /// Network the engine runs on
+    pub fn network(&self) -> Network {
+        self.config.network
+    }
+
+    /// Configuration the engine was created with
+    pub fn config(&self) -> &LightningConfig {
+        &self.config
+    }
+
+    /// Status of the LDK node, or `None` while it has not been started
+    pub async fn node_status(&self) -> Option<NodeStatus> {
+        self.node.read().await.as_ref().map(|node| node.status())
+    }
+
+    /// Parse an invoice, refusing ones issued for another network
+    fn parse_invoice(&self, invoice: &str) -> Result<Bolt11Invoice> {
+        network::parse_invoice(invoice, self.config.network)
+    }
+
     /// Initialize the Lightning Node
     pub async fn initialize(&self) -> Result<()> {
         info!(
@@ -47,37 +136,105 @@ impl LightningEngine {
             self.config.network
         );
 
+        let _init = self.init_lock.lock().await;
+        if self.node.read().await.is_some() {
+            return Ok(());
+        }
+
         // Validate configuration
         self.config.validate()?;
 
         // Create the node builder
         let mut builder = Builder::new();
-
-        // Configure the node
-        builder = builder
+        builder
             .set_network(self.config.network)
-            .set_esplora_server(self.config.esplora_url.clone())
-            .set_storage_dir_path(self.config.data_dir.clone())
-            .set_network_graph_use_persisted(self.config.persist_network_graph);
+            .set_storage_dir_path(self.config.data_dir.to_string_lossy().into_owned());
+
+        // Configure the chain source
+        match self.config.chain_source {
+            ChainSource::Esplora => {
+                builder.set_chain_source_esplora(self.config.esplora_url.clone(), None);
+            }
+            ChainSource::Bitcoind => {
+                let rpc = &self.config.bitcoin_rpc;
+                let (host, port) = rpc.host_port()?;
+                builder.set_chain_source_bitcoind_rpc(
+                    host,
+                    port,
+                    rpc.username.clone(),
+                    rpc.password.clone(),
+                );
+            }
+        }
 
         // Configure gossip source
         if self.config.use_ldk_gossip {
-            builder = builder.set_gossip_source_ldk();
+            match self.config.gossip_source {
+                GossipSource::P2p => builder.set_gossip_source_p2p(),
+                GossipSource::Rgs => builder.set_gossip_source_rgs(self.config.rgs_server_url()?),
+            };
+        }
+
+        if let Some((node_id, address, token)) = &self.lsps2_source {
+            builder.set_liquidity_source_lsps2(*node_id, address.clone(), token.clone());
         }
 
         // Build and start the node
         let node = builder.build()?;
-        node.start().await?;
+        node.start()?;
 
         info!("Lightning Node started successfully");
 
         // Store the node
         let mut node_guard = self.node.write().await;
         *node_guard = Some(node);
+        drop(node_guard);
+
+        self.spawn_event_handler();
 
         Ok(())
     }
 
+    /// Drain LDK events in the background so payment outcomes can be observed
+    fn spawn_event_handler(&self) {
+        let node = Arc::clone(&self.node);
+        let payment_failures = Arc::clone(&self.payment_failures);
+
+        tokio::spawn(async move {
+            loop {
+                let event = {
+                    let node_guard = node.read().await;
+                    match node_guard.as_ref() {
+                        Some(node) => node.next_event(),
+                        // Node was stopped
+                        None => break,
+                    }
+                };
+
+                let Some(event) = event else {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                };
+
+                if let Event::PaymentFailed {
+                    payment_id: Some(payment_id),
+                    reason,
+                    ..
+                } = &event
+                {
+                    payment_failures.write().await.put(*payment_id, *reason);
+                }
+
+                let node_guard = node.read().await;
+                if let Some(node) = node_guard.as_ref() {
+                    if let Err(e) = node.event_handled() {
+                        error!("Failed to mark LDK event handled: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
     /// Generate a new wallet from mnemonic
     pub async fn create_wallet_from_mnemonic(
         &self,
@@ -99,9 +256,7 @@ impl LightningEngine {
         // Derive the Lightning node private key (m/84'/0'/0'/0/0 for mainnet, m/84'/1'/0'/0/0 for testnet)
         let derivation_path = match self.config.network {
             Network::Bitcoin => DerivationPath::from_str("m/84'/0'/0'/0/0")?,
-            Network::Testnet => DerivationPath::from_str("m/84'/1'/0'/0/0")?,
-            Network::Regtest => DerivationPath::from_str("m/84'/1'/0'/0/0")?,
-            Network::Signet => DerivationPath::from_str("m/84'/1'/0'/0/0")?,
+            _ => DerivationPath::from_str("m/84'/1'/0'/0/0")?,
         };
 
         let derived_key = master_key.derive_priv(&secp, &derivation_path)?;
@@ -125,9 +280,7 @@ impl LightningEngine {
     /// Get the current balance (on-chain + Lightning)
     pub async fn get_balance(&self) -> Result<(u64, u64)> {
         let node_guard = self.node.read().await;
-        let node = node_guard
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Lightning node not initialized"))?;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
 
         // Get on-chain balance
         let onchain_balance = node.on_chain_balance()?;
@@ -143,12 +296,18 @@ impl LightningEngine {
         Ok((onchain_balance, lightning_balance))
     }
 
+    /// On-chain funds the node can spend, excluding the reserve kept for
+    /// anchor channels
+    pub async fn spendable_onchain_sats(&self) -> Result<u64> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        Ok(node.list_balances().spendable_onchain_balance_sats)
+    }
+
     /// Generate a Lightning invoice
     pub async fn generate_invoice(&self, amount_sats: u64, memo: &str) -> Result<(String, String)> {
         let node_guard = self.node.read().await;
-        let node = node_guard
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Lightning node not initialized"))?;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
 
         info!(
             "Generating invoice for {} sats with memo: {}",
@@ -170,32 +329,424 @@ impl LightningEngine {
         Ok((invoice_string, payment_hash))
     }
 
-    /// Send a Lightning payment
+    /// Inbound capacity of the usable channels, the most a single invoice can
+    /// receive without a new channel
+    pub async fn inbound_capacity_msat(&self) -> Result<u64> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        Ok(node
+            .list_channels()
+            .iter()
+            .filter(|channel| channel.is_usable)
+            .map(|channel| channel.inbound_capacity_msat)
+            .sum())
+    }
+
+    /// Generate an invoice whose route hint points at the LSPS2 LSP. The LSP
+    /// opens a channel to us when the payment reaches it and deducts its fee,
+    /// which may be at most `max_lsp_fee_msat`, from the payment.
+    pub async fn receive_via_jit_channel(
+        &self,
+        amount_msat: u64,
+        memo: &str,
+        max_lsp_fee_msat: u64,
+    ) -> Result<(String, String)> {
+        if amount_msat == 0 {
+            return Err(LightningError::InvalidAmount.into());
+        }
+        let description = Bolt11InvoiceDescription::Direct(Description::new(memo.to_string())?);
+
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        // 1 hour expiry, as for regular invoices
+        let invoice = node.bolt11_payment().receive_via_jit_channel(
+            amount_msat,
+            &description,
+            3600,
+            Some(max_lsp_fee_msat),
+        )?;
+
+        let payment_hash = invoice.payment_hash().to_string();
+        info!(
+            "JIT channel invoice generated - Payment Hash: {}, max LSP fee {} msat",
+            payment_hash, max_lsp_fee_msat
+        );
+        Ok((invoice.to_string(), payment_hash))
+    }
+
+    /// Send a Lightning payment and wait up to [`DEFAULT_PAYMENT_TIMEOUT`]
+    /// for it to resolve. Returns the payment hash and SUCCEEDED, FAILED or,
+    /// if it is still in flight, PENDING.
+    ///
+    /// [`DEFAULT_PAYMENT_TIMEOUT`]: crate::lightning::send_options::DEFAULT_PAYMENT_TIMEOUT
     pub async fn send_payment(&self, invoice: &str) -> Result<(String, String)> {
+        let outcome = self
+            .send_payment_with_options(invoice, &SendPaymentOptions::default())
+            .await?;
+        Ok((outcome.payment_hash, outcome.status))
+    }
+
+    /// Status the node recorded for the outgoing BOLT11 payment of
+    /// `payment_hash`: SUCCEEDED, FAILED or PENDING, or `None` if the node
+    /// never sent it. ldk-node keys BOLT11 payments by their payment hash.
+    pub async fn outbound_payment_status(&self, payment_hash: &str) -> Result<Option<String>> {
+        let id = Self::parse_payment_id(payment_hash)?;
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        Ok(node
+            .payment(&id)
+            .filter(|details| details.direction == PaymentDirection::Outbound)
+            .map(|details| {
+                match details.status {
+                    PaymentStatus::Succeeded => "SUCCEEDED",
+                    PaymentStatus::Failed => "FAILED",
+                    PaymentStatus::Pending => "PENDING",
+                }
+                .to_string()
+            }))
+    }
+
+    /// Send a Lightning payment with routing constraints and wait for it to resolve
+    pub async fn send_payment_with_options(
+        &self,
+        invoice: &str,
+        options: &SendPaymentOptions,
+    ) -> Result<SendPaymentOutcome> {
+        options.validate()?;
+
+        let invoice = self.parse_invoice(invoice)?;
+        let payment_hash = invoice.payment_hash().to_string();
+
+        let amount_msat = invoice
+            .amount_milli_satoshis()
+            .ok_or(LightningError::AmountlessInvoice)?;
+        let sending_parameters = options.to_sending_parameters(amount_msat);
+
+        let payment_id = {
+            let node_guard = self.node.read().await;
+            let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+            info!(
+                "Sending payment {} ({} msat, max fee {:?} msat)",
+                payment_hash,
+                amount_msat,
+                options.max_fee_msat(amount_msat)
+            );
+
+            node.bolt11_payment()
+                .send(&invoice, Some(sending_parameters))?
+        };
+
+        let deadline = tokio::time::Instant::now() + options.timeout_or_default();
+        loop {
+            let details = {
+                let node_guard = self.node.read().await;
+                node_guard
+                    .as_ref()
+                    .ok_or(LightningError::NodeNotRunning)?
+                    .payment(&payment_id)
+            };
+
+            match details.map(|d| (d.status, d.fee_paid_msat)) {
+                Some((PaymentStatus::Succeeded, fee_paid_msat)) => {
+                    self.payment_failures.write().await.pop(&payment_id);
+                    let fee_paid_sats = fee_paid_msat.unwrap_or(0) / 1000;
+                    info!(
+                        "Payment {} succeeded (fee {} sats)",
+                        payment_hash, fee_paid_sats
+                    );
+                    return Ok(SendPaymentOutcome {
+                        payment_hash,
+                        status: "SUCCEEDED".to_string(),
+                        fee_paid_sats,
+                        failure_reason: None,
+                    });
+                }
+                Some((PaymentStatus::Failed, _)) => {
+                    let ldk_reason = self
+                        .payment_failures
+                        .write()
+                        .await
+                        .pop(&payment_id)
+                        .flatten();
+                    let reason =
+                        PaymentFailureReason::from_ldk(ldk_reason, options.has_fee_limit());
+                    warn!("Payment {} failed: {}", payment_hash, reason);
+                    return Ok(SendPaymentOutcome {
+                        payment_hash,
+                        status: "FAILED".to_string(),
+                        fee_paid_sats: 0,
+                        failure_reason: Some(reason),
+                    });
+                }
+                _ => {}
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                // The HTLCs may still settle; callers should poll for the final state
+                self.payment_failures.write().await.pop(&payment_id);
+                warn!(
+                    "Payment {} did not resolve before the timeout",
+                    payment_hash
+                );
+                return Ok(SendPaymentOutcome {
+                    payment_hash,
+                    status: "PENDING".to_string(),
+                    fee_paid_sats: 0,
+                    failure_reason: Some(PaymentFailureReason::Timeout),
+                });
+            }
+
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    }
+
+    /// Look up a succeeded inbound payment by its LDK payment id (hex).
+    ///
+    /// Returns the payment hash and the amount received in msat.
+    pub async fn received_payment(&self, payment_id: &str) -> Result<Option<(String, u64)>> {
+        let id = Self::parse_payment_id(payment_id)?;
         let node_guard = self.node.read().await;
-        let node = node_guard
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Lightning node not initialized"))?;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        let details = match node.payment(&id) {
+            Some(details)
+                if details.direction == PaymentDirection::Inbound
+                    && details.status == PaymentStatus::Succeeded =>
+            {
+                details
+            }
+            _ => return Ok(None),
+        };
 
-        info!("Sending payment for invoice: {}", &invoice[..50]);
+        let payment_hash = match &details.kind {
+            PaymentKind::Bolt11 { hash, .. }
+            | PaymentKind::Bolt11Jit { hash, .. }
+            | PaymentKind::Spontaneous { hash, .. } => hex::encode(hash.0),
+            _ => payment_id.to_string(),
+        };
 
-        // Parse the invoice
-        let invoice = Invoice::from_str(invoice)?;
+        Ok(Some((payment_hash, details.amount_msat.unwrap_or(0))))
+    }
 
-        // Send the payment
+    /// Pay a payer-supplied BOLT11 refund invoice without waiting for it to resolve.
+    ///
+    /// Amountless invoices are paid for `amount_msat`; invoices with an amount
+    /// must match it exactly. Returns the payment hash and LDK payment id (hex).
+    pub async fn pay_refund_invoice(
+        &self,
+        invoice: &str,
+        amount_msat: u64,
+    ) -> Result<(String, String)> {
+        let invoice = self.parse_invoice(invoice)?;
         let payment_hash = invoice.payment_hash().to_string();
-        let payment_id = node.send_payment(&invoice)?;
 
-        // Wait for payment completion (in a real implementation, this would be async)
-        // For now, we'll assume it succeeds
-        let status = "SUCCEEDED".to_string();
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        let payment_id = match invoice.amount_milli_satoshis() {
+            Some(invoice_msat) if invoice_msat != amount_msat => {
+                return Err(LightningError::RefundAmountMismatch {
+                    invoice_msat,
+                    amount_msat,
+                }
+                .into());
+            }
+            Some(_) => node.bolt11_payment().send(&invoice, None)?,
+            None => node
+                .bolt11_payment()
+                .send_using_amount(&invoice, amount_msat, None)?,
+        };
 
         info!(
-            "Payment sent successfully - Payment Hash: {}, Status: {}",
-            payment_hash, status
+            "Refund payment {} sent ({} msat)",
+            payment_hash, amount_msat
         );
 
-        Ok((payment_hash, status))
+        Ok((payment_hash, hex::encode(payment_id.0)))
+    }
+
+    /// Create a BOLT12 refund the payer's wallet can claim.
+    ///
+    /// `payer_note` is embedded in the refund and used to find the resulting
+    /// payment again in [`Self::bolt12_refund_status`].
+    pub async fn initiate_bolt12_refund(
+        &self,
+        amount_msat: u64,
+        expiry_secs: u32,
+        payer_note: &str,
+    ) -> Result<String> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        let refund = node.bolt12_payment().initiate_refund(
+            amount_msat,
+            expiry_secs,
+            None,
+            Some(payer_note.to_string()),
+        )?;
+
+        info!("BOLT12 refund issued for {} msat", amount_msat);
+        Ok(refund.to_string())
+    }
+
+    /// Status of the payment made for a BOLT12 refund, with its payment hash once claimed
+    pub async fn bolt12_refund_status(
+        &self,
+        payer_note: &str,
+    ) -> Result<Option<(PaymentStatus, Option<String>)>> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        let payment = node
+            .list_payments_with_filter(|p| {
+                p.direction == PaymentDirection::Outbound
+                    && matches!(
+                        &p.kind,
+                        PaymentKind::Bolt12Refund { payer_note: Some(note), .. } if note.0 == payer_note
+                    )
+            })
+            .into_iter()
+            .next();
+
+        Ok(payment.map(|p| {
+            let hash = match p.kind {
+                PaymentKind::Bolt12Refund { hash, .. } => hash.map(|h| hex::encode(h.0)),
+                _ => None,
+            };
+            (p.status, hash)
+        }))
+    }
+
+    /// Current status of an outgoing payment by LDK payment id (hex)
+    pub async fn outbound_payment_status(&self, payment_id: &str) -> Result<Option<PaymentStatus>> {
+        let id = Self::parse_payment_id(payment_id)?;
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        Ok(node.payment(&id).map(|details| details.status))
+    }
+
+    fn parse_payment_id(payment_id: &str) -> Result<PaymentId> {
+        let bytes: [u8; 32] = hex::decode(payment_id)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| LightningError::InvalidPaymentId(payment_id.to_string()))?;
+        Ok(PaymentId(bytes))
+    }
+
+    /// Estimate the routing fee and success likelihood for paying a target.
+    ///
+    /// Repeated estimates to the same destination and amount are served from
+    /// a short-lived cache. Fresh estimates also dispatch LDK probes so the
+    /// scorer has up-to-date liquidity data when the payment is sent.
+    pub async fn estimate_routing_fee(&self, target: EstimateTarget) -> Result<RouteEstimate> {
+        let (invoice, destination, amount_msat) = match target {
+            EstimateTarget::Invoice(invoice) => {
+                let invoice = self.parse_invoice(&invoice)?;
+                let amount_msat = invoice
+                    .amount_milli_satoshis()
+                    .ok_or(LightningError::AmountlessInvoice)?;
+                let destination = invoice.recover_payee_pub_key().to_string();
+                (Some(invoice), destination, amount_msat)
+            }
+            EstimateTarget::Node {
+                pubkey,
+                amount_msat,
+            } => (None, pubkey, amount_msat),
+        };
+
+        if amount_msat == 0 {
+            return Err(LightningError::InvalidAmount.into());
+        }
+
+        if let Some(estimate) = self.route_estimator.cached(&destination, amount_msat).await {
+            return Ok(estimate);
+        }
+
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        let our_node_id = node.node_id().to_string();
+        let mut graph = NetworkGraph::from_ldk_graph(&node.network_graph());
+
+        // Our own (possibly unannounced) channels form the first hop. They
+        // only carry payments away from us, free and up to what we can send.
+        for channel in node.list_channels().into_iter().filter(|c| c.is_usable) {
+            graph.add_channel(NetworkChannelInfo {
+                channel_id: channel.channel_id.to_string(),
+                node1: our_node_id.clone(),
+                node2: channel.counterparty_node_id.to_string(),
+                capacity_sat: channel.outbound_capacity_msat / 1000,
+                is_enabled: true,
+                last_update: 0,
+                base_fee_msat: 0,
+                fee_rate_ppm: 0,
+                node1_policy: Some(ChannelPolicy {
+                    is_enabled: true,
+                    base_fee_msat: 0,
+                    fee_rate_ppm: 0,
+                    cltv_expiry_delta: 0,
+                    htlc_minimum_msat: channel.next_outbound_htlc_minimum_msat,
+                    htlc_maximum_msat: channel.next_outbound_htlc_limit_msat,
+                    last_update: 0,
+                }),
+                node2_policy: None,
+            });
+        }
+
+        // Private channels towards the payee are only known from route hints,
+        // which give the policy of the hop forwarding towards the payee
+        if let Some(invoice) = &invoice {
+            for hint in invoice.route_hints() {
+                let mut next_node = destination.clone();
+                for hop in hint.0.iter().rev() {
+                    let hop_node = hop.src_node_id.to_string();
+                    graph.add_channel(NetworkChannelInfo {
+                        channel_id: hop.short_channel_id.to_string(),
+                        node1: hop_node.clone(),
+                        node2: next_node,
+                        capacity_sat: 0,
+                        is_enabled: true,
+                        last_update: 0,
+                        base_fee_msat: hop.fees.base_msat,
+                        fee_rate_ppm: hop.fees.proportional_millionths,
+                        node1_policy: Some(ChannelPolicy {
+                            is_enabled: true,
+                            base_fee_msat: hop.fees.base_msat,
+                            fee_rate_ppm: hop.fees.proportional_millionths,
+                            cltv_expiry_delta: hop.cltv_expiry_delta,
+                            htlc_minimum_msat: hop.htlc_minimum_msat.unwrap_or(0),
+                            htlc_maximum_msat: hop.htlc_maximum_msat.unwrap_or(u64::MAX),
+                            last_update: 0,
+                        }),
+                        node2_policy: None,
+                    });
+                    next_node = hop_node;
+                }
+            }
+        }
+
+        let mut estimate =
+            self.route_estimator
+                .estimate(&graph, &our_node_id, &destination, amount_msat)?;
+
+        let probe_result = match &invoice {
+            Some(invoice) => node.bolt11_payment().send_probes(invoice),
+            None => {
+                let pubkey = bitcoin::secp256k1::PublicKey::from_str(&destination)?;
+                node.spontaneous_payment().send_probes(amount_msat, pubkey)
+            }
+        };
+        match probe_result {
+            Ok(()) => estimate.probed = true,
+            Err(e) => warn!("Failed to send probes to {}: {}", destination, e),
+        }
+
+        self.route_estimator.store(estimate.clone()).await;
+        Ok(estimate)
     }
 
     /// Buy airtime using Lightning payment
@@ -232,6 +783,139 @@ impl LightningEngine {
         Ok((invoice, payment_hash, status))
     }
 
+    /// Open a channel to `node_id` at `address`, funded from the on-chain
+    /// wallet. Announced channels are gossiped so others can route through them.
+    pub async fn open_channel(
+        &self,
+        node_id: bitcoin::secp256k1::PublicKey,
+        address: SocketAddress,
+        capacity_sats: u64,
+        push_msat: Option<u64>,
+        announce: bool,
+    ) -> Result<UserChannelId> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        let user_channel_id = if announce {
+            node.open_announced_channel(node_id, address, capacity_sats, push_msat, None)?
+        } else {
+            node.open_channel(node_id, address, capacity_sats, push_msat, None)?
+        };
+        info!(
+            "Opening {} sat channel {} with {}",
+            capacity_sats, user_channel_id.0, node_id
+        );
+        Ok(user_channel_id)
+    }
+
+    /// Close a channel cooperatively, or force close it by broadcasting our
+    /// latest commitment transaction
+    pub async fn close_channel(
+        &self,
+        user_channel_id: UserChannelId,
+        counterparty: bitcoin::secp256k1::PublicKey,
+        force: bool,
+    ) -> Result<()> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+
+        if force {
+            node.force_close_channel(&user_channel_id, counterparty, None)?;
+        } else {
+            node.close_channel(&user_channel_id, counterparty)?;
+        }
+        info!(
+            "{} channel {} with {}",
+            if force { "Force closing" } else { "Closing" },
+            user_channel_id.0,
+            counterparty
+        );
+        Ok(())
+    }
+
+    /// Channels the node currently has, including ones still being funded or
+    /// closed cooperatively; fully closed channels are not listed
+    pub async fn list_channels(&self) -> Result<Vec<ChannelDetails>> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        Ok(node.list_channels())
+    }
+
+    /// Snapshot of the public graph the node learned from gossip
+    pub async fn network_graph(&self) -> Result<NetworkGraph> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        Ok(NetworkGraph::from_ldk_graph(&node.network_graph()))
+    }
+
+    /// Connect to a peer, waiting for the handshake to complete. The node
+    /// does not remember the peer; `PeerManager` keeps the ones to reconnect.
+    pub async fn connect_peer(
+        &self,
+        node_id: bitcoin::secp256k1::PublicKey,
+        address: SocketAddress,
+    ) -> Result<()> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        node.connect(node_id, address, false)?;
+        Ok(())
+    }
+
+    /// Drop the connection to a peer
+    pub async fn disconnect_peer(&self, node_id: bitcoin::secp256k1::PublicKey) -> Result<()> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        node.disconnect(node_id)?;
+        Ok(())
+    }
+
+    /// Peers the node is connected to or remembers from its channels
+    pub async fn list_peers(&self) -> Result<Vec<PeerDetails>> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        Ok(node.list_peers())
+    }
+
+    /// Public key of the running node
+    pub async fn node_id(&self) -> Result<bitcoin::secp256k1::PublicKey> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        Ok(node.node_id())
+    }
+
+    /// Send `amount_sats` from the on-chain wallet to `address`, returning the txid
+    pub async fn send_onchain(&self, address: &str, amount_sats: u64) -> Result<bitcoin::Txid> {
+        if amount_sats == 0 {
+            return Err(LightningError::InvalidAmount.into());
+        }
+        let address = network::parse_address(address, self.config.network)?;
+
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        let txid = node
+            .onchain_payment()
+            .send_to_address(&address, amount_sats, None)?;
+        info!(
+            "Sent {} sats on-chain to {} in {}",
+            amount_sats, address, txid
+        );
+        Ok(txid)
+    }
+
+    /// Features a node announced in the gossip we have, if any
+    pub async fn node_features(
+        &self,
+        node_id: &bitcoin::secp256k1::PublicKey,
+    ) -> Result<Option<NodeFeatures>> {
+        let node_guard = self.node.read().await;
+        let node = node_guard.as_ref().ok_or(LightningError::NodeNotRunning)?;
+        Ok(node
+            .network_graph()
+            .node(&NodeId::from_pubkey(node_id))
+            .and_then(|info| info.announcement_info)
+            .map(|announcement| announcement.features().clone()))
+    }
+
     /// Stop the Lightning node
     pub async fn stop(&self) -> Result<()> {
         let mut node_guard = self.node.write().await;
@@ -245,14 +929,18 @@ impl LightningEngine {
 
 impl Drop for LightningEngine {
     fn drop(&mut self) {
-        // Ensure the node is stopped when the engine is dropped
-        if let Some(node) = self
-            .node
-            .try_write()
-            .ok()
-            .and_then(|mut guard| guard.take())
-        {
-            let _ = node.stop();
+        // Last resort for engines that were not stopped; `engine_server` calls
+        // stop() during shutdown so the node is not left to this
+        match self.node.try_write() {
+            Ok(mut guard) => {
+                if let Some(node) = guard.take() {
+                    warn!("Lightning engine dropped without stop(), stopping node");
+                    if let Err(e) = node.stop() {
+                        warn!("Failed to stop Lightning node: {}", e);
+                    }
+                }
+            }
+            Err(_) => warn!("Lightning node busy while the engine was dropped, not stopped"),
         }
     }
 }
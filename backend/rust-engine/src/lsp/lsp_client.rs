@@ -1,114 +1,206 @@
+use crate::lightning::PeerUri;
+use crate::lightning_engine::{LightningEngine, LightningError};
+use crate::lsp::lsp_provider::{LspHealth, LspProviderInfo, LspRegistry};
+use crate::lsp::lsps0::{LspProtocol, Lsps0Client};
+use crate::lsp::lsps1::{
+    HttpTransport, Lsps1Client, Lsps1Error, Lsps1Options, Lsps1Order, OrderParams, PaymentState,
+};
+use crate::lsp::lsps2::{self, Lsps2Client, Lsps2Error, OpeningFeeParams};
+use crate::network;
+use crate::shutdown::Shutdown;
+use crate::store::{self, lsp_orders, LspOrderRecord, Store};
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{error, info, instrument, warn};
 
 /// Lightning Service Provider client for liquidity management
 #[derive(Debug)]
 pub struct LSPClient {
-    providers: Arc<RwLock<HashMap<String, LSPProvider>>>,
+    registry: Arc<RwLock<LspRegistry>>,
     active_provider: Arc<RwLock<Option<String>>>,
+    /// Provider the node was set up to take just-in-time channels from
+    jit_provider: Option<String>,
     config: LSPConfig,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LSPProvider {
-    pub name: String,
-    pub endpoint: String,
-    pub api_key: String,
-    pub is_active: bool,
-    pub min_channel_size: u64,
-    pub max_channel_size: u64,
-    pub fee_rate: f64,         // sats per 1000 sats
-    pub reputation_score: f64, // 0.0 to 1.0
-    pub last_used: Option<DateTime<Utc>>,
-    pub success_rate: f64,          // 0.0 to 1.0
-    pub average_response_time: u64, // milliseconds
+    store: Store,
+    node: Arc<dyn LspNode>,
+    http: reqwest::Client,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LSPConfig {
     pub auto_select_provider: bool,
-    pub max_fee_rate: f64,
     pub min_reputation_score: f64,
     pub max_response_time: u64,
     pub retry_attempts: u32,
-    pub retry_delay: u64, // milliseconds
+    pub retry_delay: u64,         // milliseconds
+    pub order_poll_interval: u64, // milliseconds
+    /// How often to rediscover protocols and fees of every provider, which
+    /// also lets unreachable ones recover
+    pub discovery_interval: u64, // milliseconds
+}
+
+/// How to pay for a channel order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LSPPaymentMethod {
+    /// Pay the order's BOLT11 invoice over Lightning
+    #[default]
+    Bolt11,
+    /// Send the order total to the LSP's address from the on-chain wallet
+    Onchain,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LSPChannel {
-    pub channel_id: String,
+pub struct LSPChannelRequest {
+    /// Inbound capacity to buy
+    pub lsp_balance_sat: u64,
+    /// Outbound balance the LSP pushes to us, paid for on top of the fee
+    pub client_balance_sat: u64,
+    /// How long the LSP must keep the channel open
+    pub channel_expiry_blocks: u32,
+    pub announce_channel: bool,
+    pub payment_method: LSPPaymentMethod,
+    /// Refuse orders whose fee is higher
+    pub max_fee_sat: Option<u64>,
+    pub preferred_provider: Option<String>,
+}
+
+/// What receiving a payment through a just-in-time channel costs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JitChannelQuote {
     pub provider: String,
-    pub capacity: u64,
-    pub local_balance: u64,
-    pub remote_balance: u64,
-    pub is_active: bool,
-    pub created_at: DateTime<Utc>,
-    pub fee_rate: f64,
+    pub amount_msat: u64,
+    /// Opening fee the LSP deducts from the payment
+    pub fee_msat: u64,
+    /// When the LSP stops honouring the fee, RFC 3339
+    pub valid_until: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LSPInvoice {
+/// An invoice that opens a just-in-time channel when paid
+#[derive(Debug, Clone, PartialEq)]
+pub struct JitInvoice {
     pub invoice: String,
-    pub amount: u64,
-    pub provider: String,
-    pub expires_at: DateTime<Utc>,
-    pub fee: u64,
     pub payment_hash: String,
+    pub quote: JitChannelQuote,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LSPChannelRequest {
-    pub capacity: u64,
-    pub push_amount: Option<u64>,
-    pub fee_rate: Option<f64>,
-    pub preferred_provider: Option<String>,
+/// What the LSP client needs from the Lightning node
+#[async_trait::async_trait]
+pub trait LspNode: Send + Sync + std::fmt::Debug {
+    /// Hex-encoded public key of the node
+    async fn node_id(&self) -> Result<String>;
+
+    /// Connect to the LSP so it can open the channel to us
+    async fn connect(&self, node_id: &str, address: &str) -> Result<()>;
+
+    /// Pay an order invoice, which must be for exactly `amount_msat`;
+    /// returns the payment hash
+    async fn pay_invoice(&self, invoice: &str, amount_msat: u64) -> Result<String>;
+
+    /// Send `amount_sats` on-chain; returns the txid
+    async fn pay_onchain(&self, address: &str, amount_sats: u64) -> Result<String>;
+
+    /// Invoice for `amount_msat` carrying the route hint of the node's LSPS2
+    /// LSP, which opens the channel when the payment arrives and may deduct at
+    /// most `max_lsp_fee_msat`; returns the invoice and its payment hash
+    async fn receive_via_jit_channel(
+        &self,
+        amount_msat: u64,
+        memo: &str,
+        max_lsp_fee_msat: u64,
+    ) -> Result<(String, String)>;
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LSPChannelResponse {
-    pub success: bool,
-    pub channel: Option<LSPChannel>,
-    pub invoice: Option<LSPInvoice>,
-    pub error: Option<String>,
-    pub provider: String,
+#[async_trait::async_trait]
+impl LspNode for LightningEngine {
+    async fn node_id(&self) -> Result<String> {
+        Ok(LightningEngine::node_id(self).await?.to_string())
+    }
+
+    async fn connect(&self, node_id: &str, address: &str) -> Result<()> {
+        let uri: PeerUri = format!("{}@{}", node_id, address).parse()?;
+        self.connect_peer(uri.node_id, uri.address).await
+    }
+
+    async fn pay_invoice(&self, invoice: &str, amount_msat: u64) -> Result<String> {
+        let found_msat = network::parse_invoice(invoice, self.network())?.amount_milli_satoshis();
+        if found_msat != Some(amount_msat) {
+            return Err(Lsps1Error::InvoiceAmountMismatch {
+                expected_msat: amount_msat,
+                found_msat,
+            }
+            .into());
+        }
+        let (payment_hash, _) = self.send_payment(invoice).await?;
+        Ok(payment_hash)
+    }
+
+    async fn pay_onchain(&self, address: &str, amount_sats: u64) -> Result<String> {
+        Ok(self.send_onchain(address, amount_sats).await?.to_string())
+    }
+
+    async fn receive_via_jit_channel(
+        &self,
+        amount_msat: u64,
+        memo: &str,
+        max_lsp_fee_msat: u64,
+    ) -> Result<(String, String)> {
+        LightningEngine::receive_via_jit_channel(self, amount_msat, memo, max_lsp_fee_msat).await
+    }
 }
 
 impl LSPClient {
-    /// Create a new LSP client
-    pub fn new(config: LSPConfig) -> Self {
+    /// Create a new LSP client that keeps its orders in `store` and pays them from `node`
+    pub fn new(config: LSPConfig, store: Store, node: Arc<dyn LspNode>) -> Self {
         Self {
-            providers: Arc::new(RwLock::new(HashMap::new())),
+            registry: Arc::new(RwLock::new(LspRegistry::new())),
             active_provider: Arc::new(RwLock::new(None)),
+            jit_provider: None,
             config,
+            store,
+            node,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .expect("HTTP client"),
         }
     }
 
+    /// Work with the providers of `registry`, usually loaded from the configuration
+    pub fn with_registry(mut self, registry: LspRegistry) -> Self {
+        self.registry = Arc::new(RwLock::new(registry));
+        self
+    }
+
+    /// Quote and create just-in-time channel invoices with the provider
+    /// named `name`, which must be the LSP the node takes such channels from
+    pub fn with_jit_provider(mut self, name: impl Into<String>) -> Self {
+        self.jit_provider = Some(name.into());
+        self
+    }
+
     /// Add an LSP provider
     #[instrument(skip(self))]
-    pub async fn add_provider(&self, provider: LSPProvider) -> Result<()> {
-        let mut providers = self.providers.write().await;
-        providers.insert(provider.name.clone(), provider);
-        info!("Added LSP provider: {}", providers.last().unwrap().0);
+    pub async fn add_provider(&self, provider: LspProviderInfo) -> Result<()> {
+        self.registry.write().await.add_provider(provider);
         Ok(())
     }
 
-    /// Get the best available LSP provider
+    /// Get the best available provider to buy channels from
     #[instrument(skip(self))]
-    pub async fn get_best_provider(&self) -> Result<Option<LSPProvider>> {
-        let providers = self.providers.read().await;
+    pub async fn get_best_provider(&self) -> Result<Option<LspProviderInfo>> {
+        let registry = self.registry.read().await;
 
-        let mut eligible_providers: Vec<&LSPProvider> = providers
-            .values()
+        let mut eligible_providers: Vec<&LspProviderInfo> = registry
+            .get_providers_by_protocol(LspProtocol::Lsps1)
+            .into_iter()
             .filter(|p| {
-                p.is_active
-                    && p.fee_rate <= self.config.max_fee_rate
-                    && p.reputation_score >= self.config.min_reputation_score
-                    && p.average_response_time <= self.config.max_response_time
+                p.reputation_score >= self.config.min_reputation_score
+                    && p.average_response_time_ms <= self.config.max_response_time
             })
             .collect();
 
@@ -116,7 +208,7 @@ impl LSPClient {
             return Ok(None);
         }
 
-        // Sort by score (combination of reputation, fee rate, and response time)
+        // Sort by score (combination of reputation and response time)
         eligible_providers.sort_by(|a, b| {
             let score_a = self.calculate_provider_score(a);
             let score_b = self.calculate_provider_score(b);
@@ -127,154 +219,455 @@ impl LSPClient {
     }
 
     /// Calculate provider score for selection
-    fn calculate_provider_score(&self, provider: &LSPProvider) -> f64 {
-        let reputation_weight = 0.4;
-        let fee_weight = 0.3;
-        let response_time_weight = 0.3;
+    fn calculate_provider_score(&self, provider: &LspProviderInfo) -> f64 {
+        let reputation_weight = 0.6;
+        let response_time_weight = 0.4;
 
         let reputation_score = provider.reputation_score;
-        let fee_score = 1.0 - (provider.fee_rate / self.config.max_fee_rate);
         let response_time_score =
-            1.0 - (provider.average_response_time as f64 / self.config.max_response_time as f64);
+            1.0 - (provider.average_response_time_ms as f64 / self.config.max_response_time as f64);
 
-        reputation_score * reputation_weight
-            + fee_score * fee_weight
-            + response_time_score * response_time_weight
+        reputation_score * reputation_weight + response_time_score * response_time_weight
     }
 
-    /// Request a new channel from LSP
+    /// Order limits of a provider, from LSPS1 `get_info`
+    pub async fn get_options(&self, provider_name: &str) -> Result<Lsps1Options> {
+        let provider = self
+            .get_provider(provider_name)
+            .await?
+            .filter(|p| p.supports(LspProtocol::Lsps1))
+            .ok_or_else(|| Lsps1Error::UnknownProvider(provider_name.to_string()))?;
+
+        let started = Instant::now();
+        let result = self.lsps1(&provider).get_info().await;
+        self.update_provider_stats(&provider.name, result.is_ok(), started.elapsed())
+            .await?;
+        let options = result?;
+        self.registry
+            .write()
+            .await
+            .update_channel_options(&provider.name, options.clone())?;
+        Ok(options)
+    }
+
+    /// Buy a channel over LSPS1: check the request against the provider's
+    /// options, place the order and pay it. The channel opens once the LSP
+    /// has the payment; [`LSPClient::run`] follows the order until then.
     #[instrument(skip(self))]
-    pub async fn request_channel(&self, request: LSPChannelRequest) -> Result<LSPChannelResponse> {
-        let provider = if let Some(preferred) = &request.preferred_provider {
-            self.get_provider(preferred).await?
-        } else {
-            self.get_best_provider().await?
+    pub async fn request_channel(&self, request: LSPChannelRequest) -> Result<LspOrderRecord> {
+        let provider = match &request.preferred_provider {
+            Some(preferred) => self
+                .get_provider(preferred)
+                .await?
+                .filter(|p| p.supports(LspProtocol::Lsps1) && p.is_available())
+                .ok_or_else(|| Lsps1Error::UnknownProvider(preferred.clone()))?,
+            None => self
+                .get_best_provider()
+                .await?
+                .ok_or(Lsps1Error::NoProvider)?,
+        };
+
+        info!("Requesting channel from LSP provider: {}", provider.name);
+
+        let started = Instant::now();
+        let result = self.buy_channel(&provider, &request).await;
+
+        // Our own validation failures say nothing about the provider
+        let provider_failed = result.as_ref().is_err_and(|e| {
+            !matches!(
+                e.downcast_ref::<Lsps1Error>(),
+                Some(
+                    Lsps1Error::OutOfRange { .. }
+                        | Lsps1Error::ExpiryTooLong { .. }
+                        | Lsps1Error::FeeTooHigh { .. }
+                )
+            ) && e.downcast_ref::<LightningError>().is_none()
+        });
+        self.update_provider_stats(&provider.name, !provider_failed, started.elapsed())
+            .await?;
+
+        result
+    }
+
+    async fn buy_channel(
+        &self,
+        provider: &LspProviderInfo,
+        request: &LSPChannelRequest,
+    ) -> Result<LspOrderRecord> {
+        let client = self.lsps1(provider);
+        let options = client.get_info().await?;
+        self.registry
+            .write()
+            .await
+            .update_channel_options(&provider.name, options.clone())?;
+
+        let params = OrderParams {
+            lsp_balance_sat: request.lsp_balance_sat,
+            client_balance_sat: request.client_balance_sat,
+            required_channel_confirmations: options.min_required_channel_confirmations,
+            funding_confirms_within_blocks: options.min_funding_confirms_within_blocks,
+            channel_expiry_blocks: request.channel_expiry_blocks,
+            token: provider.token.clone(),
+            refund_onchain_address: None,
+            announce_channel: request.announce_channel,
         };
+        options.check(&params)?;
 
-        let provider = match provider {
-            Some(p) => p,
-            None => {
-                return Ok(LSPChannelResponse {
-                    success: false,
-                    channel: None,
-                    invoice: None,
-                    error: Some("No suitable LSP provider available".to_string()),
-                    provider: "none".to_string(),
-                });
+        // The LSP opens the channel to us, so it needs a connection
+        self.node
+            .connect(&provider.node_id, &provider.address)
+            .await?;
+        let node_id = self.node.node_id().await?;
+        let order = client.create_order(&params, Some(&node_id)).await?;
+        if order.params.lsp_balance_sat != params.lsp_balance_sat
+            || order.params.client_balance_sat != params.client_balance_sat
+        {
+            return Err(Lsps1Error::OrderMismatch(order.order_id).into());
+        }
+
+        let mut record = order_record(&provider.name, &order, request.payment_method)?;
+        if let Some(max_fee_sat) = request.max_fee_sat {
+            if record.fee_total_sat > max_fee_sat {
+                return Err(Lsps1Error::FeeTooHigh {
+                    fee_sat: record.fee_total_sat,
+                    max_fee_sat,
+                }
+                .into());
+            }
+        }
+        self.store
+            .with_conn(|conn| lsp_orders::insert(conn, &record))?;
+        info!(
+            "Placed LSP order {} with {} for {} sats inbound, fee {} sats",
+            order.order_id, provider.name, record.lsp_balance_sat, record.fee_total_sat
+        );
+
+        let payment = match request.payment_method {
+            LSPPaymentMethod::Bolt11 => {
+                let bolt11 = order
+                    .payment
+                    .bolt11
+                    .as_ref()
+                    .expect("checked by order_record");
+                self.node
+                    .pay_invoice(&bolt11.invoice, bolt11.order_total_sat * 1000)
+                    .await
+            }
+            LSPPaymentMethod::Onchain => {
+                let onchain = order
+                    .payment
+                    .onchain
+                    .as_ref()
+                    .expect("checked by order_record");
+                self.node
+                    .pay_onchain(&onchain.address, onchain.order_total_sat)
+                    .await
             }
         };
 
-        info!("Requesting channel from LSP provider: {}", provider.name);
+        record.updated_at = store::now();
+        match payment {
+            Ok(payment_ref) => {
+                info!("Paid LSP order {} with {}", record.order_id, payment_ref);
+                record.payment_ref = Some(payment_ref);
+                self.store
+                    .with_conn(|conn| lsp_orders::update(conn, &record))?;
+                Ok(record)
+            }
+            Err(e) => {
+                error!("Failed to pay LSP order {}: {:#}", record.order_id, e);
+                record.error_message = Some(format!("{:#}", e));
+                self.store
+                    .with_conn(|conn| lsp_orders::update(conn, &record))?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether a provider can open just-in-time channels
+    pub async fn has_jit_provider(&self) -> bool {
+        self.jit_provider().await.is_some()
+    }
 
-        // Simulate LSP channel request
-        let response = self
-            .simulate_lsp_channel_request(&provider, &request)
+    /// Fee for receiving `amount_msat` through a just-in-time channel, from the
+    /// cheapest terms the provider currently offers for that amount
+    pub async fn quote_jit_channel(&self, amount_msat: u64) -> Result<JitChannelQuote> {
+        let provider = self.jit_provider().await.ok_or(Lsps2Error::NoProvider)?;
+
+        let menu = self.get_opening_fee_params(&provider).await?;
+        let (terms, fee_msat) = lsps2::cheapest(&menu, amount_msat, Utc::now())
+            .ok_or(Lsps2Error::NoOffer { amount_msat })?;
+        Ok(JitChannelQuote {
+            provider: provider.name,
+            amount_msat,
+            fee_msat,
+            valid_until: terms.valid_until.clone(),
+        })
+    }
+
+    /// The provider's current LSPS2 fee terms, from `lsps2.get_info`
+    async fn get_opening_fee_params(
+        &self,
+        provider: &LspProviderInfo,
+    ) -> Result<Vec<OpeningFeeParams>> {
+        let started = Instant::now();
+        let result = self
+            .lsps2(provider)
+            .get_info(provider.token.as_deref())
+            .await;
+        self.update_provider_stats(&provider.name, result.is_ok(), started.elapsed())
             .await?;
 
-        // Update provider statistics
-        self.update_provider_stats(&provider.name, response.success)
+        let menu = result?;
+        self.registry
+            .write()
+            .await
+            .update_opening_fee_params(&provider.name, menu.clone())?;
+        Ok(menu)
+    }
+
+    /// Ask a provider over LSPS0 which protocols it speaks, then fetch the
+    /// fee schedule of those we use with it
+    #[instrument(skip(self))]
+    pub async fn discover_provider(&self, provider_name: &str) -> Result<LspProviderInfo> {
+        let unknown = || Lsps1Error::UnknownProvider(provider_name.to_string());
+        let provider = self
+            .get_provider(provider_name)
+            .await?
+            .ok_or_else(unknown)?;
+        let url = provider.lsps0_url().ok_or_else(unknown)?;
+
+        let started = Instant::now();
+        let result = Lsps0Client::new(self.transport(&provider, url))
+            .list_protocols()
+            .await;
+        self.update_provider_stats(&provider.name, result.is_ok(), started.elapsed())
             .await?;
+        self.registry
+            .write()
+            .await
+            .update_provider_protocols(&provider.name, result?)?;
+
+        let provider = self
+            .get_provider(provider_name)
+            .await?
+            .ok_or_else(unknown)?;
+        if provider.supports(LspProtocol::Lsps1) {
+            self.get_options(provider_name).await?;
+        }
+        if provider.supports(LspProtocol::Lsps2) {
+            self.get_opening_fee_params(&provider).await?;
+        }
+        Ok(self
+            .get_provider(provider_name)
+            .await?
+            .ok_or_else(unknown)?)
+    }
 
-        Ok(response)
+    /// Rediscover every provider, including unreachable ones so they can
+    /// recover
+    pub async fn discover_providers(&self) {
+        let names: Vec<String> = self
+            .get_providers()
+            .await
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        for name in names {
+            if let Err(e) = self.discover_provider(&name).await {
+                warn!("Failed to discover LSP {}: {:#}", name, e);
+            }
+        }
     }
 
-    /// Simulate LSP channel request (in real implementation, this would call LSP API)
-    async fn simulate_lsp_channel_request(
+    /// Invoice for `amount_msat` whose payment makes the provider open a
+    /// channel to us. Refused if the fee went above `max_fee_msat`, the fee
+    /// the user accepted after seeing a quote.
+    #[instrument(skip(self))]
+    pub async fn create_jit_invoice(
         &self,
-        provider: &LSPProvider,
-        request: &LSPChannelRequest,
-    ) -> Result<LSPChannelResponse> {
-        // Simulate API call delay
-        tokio::time::sleep(tokio::time::Duration::from_millis(
-            provider.average_response_time,
-        ))
-        .await;
+        amount_msat: u64,
+        memo: &str,
+        max_fee_msat: u64,
+    ) -> Result<JitInvoice> {
+        let quote = self.quote_jit_channel(amount_msat).await?;
+        if quote.fee_msat > max_fee_msat {
+            return Err(Lsps2Error::FeeNotAccepted {
+                fee_sat: quote.fee_msat.div_ceil(1000),
+                max_fee_sat: max_fee_msat / 1000,
+            }
+            .into());
+        }
 
-        // Simulate success/failure based on provider reputation
-        let success_probability = provider.reputation_score;
-        let success = rand::random::<f64>() < success_probability;
-
-        if success {
-            let channel = LSPChannel {
-                channel_id: format!("lsp_{}_{}", provider.name, uuid::Uuid::new_v4()),
-                provider: provider.name.clone(),
-                capacity: request.capacity,
-                local_balance: request.push_amount.unwrap_or(0),
-                remote_balance: request.capacity - request.push_amount.unwrap_or(0),
-                is_active: true,
-                created_at: Utc::now(),
-                fee_rate: provider.fee_rate,
-            };
-
-            let invoice = LSPInvoice {
-                invoice: format!("lnbc{}n1...", request.capacity),
-                amount: request.capacity,
-                provider: provider.name.clone(),
-                expires_at: Utc::now() + chrono::Duration::hours(1),
-                fee: (request.capacity as f64 * provider.fee_rate / 1000.0) as u64,
-                payment_hash: format!("hash_{}", uuid::Uuid::new_v4()),
-            };
-
-            Ok(LSPChannelResponse {
-                success: true,
-                channel: Some(channel),
-                invoice: Some(invoice),
-                error: None,
-                provider: provider.name.clone(),
-            })
-        } else {
-            Ok(LSPChannelResponse {
-                success: false,
-                channel: None,
-                invoice: None,
-                error: Some("LSP channel request failed".to_string()),
-                provider: provider.name.clone(),
-            })
+        let (invoice, payment_hash) = self
+            .node
+            .receive_via_jit_channel(amount_msat, memo, max_fee_msat)
+            .await?;
+        info!(
+            "Created invoice {} for a just-in-time channel from {}, fee {} msat",
+            payment_hash, quote.provider, quote.fee_msat
+        );
+        Ok(JitInvoice {
+            invoice,
+            payment_hash,
+            quote,
+        })
+    }
+
+    /// Fetch the latest state of an order from its LSP and record it
+    pub async fn refresh_order(&self, order_id: &str) -> Result<LspOrderRecord> {
+        let mut record = self
+            .store
+            .with_conn(|conn| lsp_orders::get(conn, order_id))?
+            .ok_or_else(|| Lsps1Error::OrderNotFound(order_id.to_string()))?;
+        let provider = self
+            .get_provider(&record.provider)
+            .await?
+            .filter(|p| p.lsps1_url.is_some())
+            .ok_or_else(|| Lsps1Error::UnknownProvider(record.provider.clone()))?;
+
+        let order = self.lsps1(&provider).get_order(order_id).await?;
+        let (order_state, payment_state) = order_states(&order, &record.payment_method);
+        let channel = order.channel.as_ref();
+        let funding_outpoint = channel.map(|c| c.funding_outpoint.clone());
+        let channel_expires_at = channel.map(|c| c.expires_at.clone());
+
+        if record.order_state != order_state
+            || record.payment_state != payment_state
+            || record.funding_outpoint != funding_outpoint
+        {
+            record.order_state = order_state;
+            record.payment_state = payment_state;
+            record.funding_outpoint = funding_outpoint;
+            record.channel_expires_at = channel_expires_at;
+            record.updated_at = store::now();
+            self.store
+                .with_conn(|conn| lsp_orders::update(conn, &record))?;
+
+            if order.is_channel_open() {
+                info!(
+                    "LSP {} opened the channel for order {} at {}",
+                    record.provider,
+                    record.order_id,
+                    record.funding_outpoint.as_deref().unwrap_or_default()
+                );
+            } else {
+                info!(
+                    "LSP order {} is {} with payment {}",
+                    record.order_id, record.order_state, record.payment_state
+                );
+            }
+        }
+        Ok(record)
+    }
+
+    /// Orders placed with any provider, newest first
+    pub fn list_orders(&self) -> Result<Vec<LspOrderRecord>> {
+        self.store.with_conn(lsp_orders::list)
+    }
+
+    /// Follow open orders until their channels are open or the LSP gives up,
+    /// including orders placed before a restart, and keep the providers'
+    /// protocols, fees and health current
+    pub async fn run(self: Arc<Self>, shutdown: Shutdown) {
+        let mut orders =
+            tokio::time::interval(Duration::from_millis(self.config.order_poll_interval));
+        let mut discovery =
+            tokio::time::interval(Duration::from_millis(self.config.discovery_interval));
+        let triggered = shutdown.triggered();
+        tokio::pin!(triggered);
+
+        loop {
+            tokio::select! {
+                _ = discovery.tick() => self.discover_providers().await,
+                _ = orders.tick() => self.refresh_open_orders().await,
+                _ = &mut triggered => return,
+            }
         }
     }
 
+    async fn refresh_open_orders(&self) {
+        let open = match self.store.with_conn(lsp_orders::list_open) {
+            Ok(open) => open,
+            Err(e) => {
+                error!("Failed to load open LSP orders: {:#}", e);
+                return;
+            }
+        };
+        for order in open {
+            if let Err(e) = self.refresh_order(&order.order_id).await {
+                warn!("Failed to refresh LSP order {}: {:#}", order.order_id, e);
+            }
+        }
+    }
+
+    fn lsps1(&self, provider: &LspProviderInfo) -> Lsps1Client {
+        let url = provider.lsps1_url.as_deref().unwrap_or_default();
+        Lsps1Client::new(self.transport(provider, url))
+    }
+
+    fn lsps2(&self, provider: &LspProviderInfo) -> Lsps2Client {
+        let url = provider.lsps2_url.as_deref().unwrap_or_default();
+        Lsps2Client::new(self.transport(provider, url))
+    }
+
+    fn transport(&self, provider: &LspProviderInfo, url: &str) -> Arc<HttpTransport> {
+        Arc::new(HttpTransport::new(
+            self.http.clone(),
+            url,
+            provider.token.clone(),
+        ))
+    }
+
+    /// The provider just-in-time channels come from, if it is up; the node
+    /// takes them from a single one
+    async fn jit_provider(&self) -> Option<LspProviderInfo> {
+        let name = self.jit_provider.as_deref()?;
+        let registry = self.registry.read().await;
+        registry
+            .get_provider(name)
+            .filter(|p| p.supports(LspProtocol::Lsps2) && p.is_available())
+            .cloned()
+    }
+
     /// Get a specific provider
-    async fn get_provider(&self, name: &str) -> Result<Option<LSPProvider>> {
-        let providers = self.providers.read().await;
-        Ok(providers.get(name).cloned())
+    async fn get_provider(&self, name: &str) -> Result<Option<LspProviderInfo>> {
+        let registry = self.registry.read().await;
+        Ok(registry.get_provider(name).cloned())
     }
 
     /// Update provider statistics
-    async fn update_provider_stats(&self, provider_name: &str, success: bool) -> Result<()> {
-        let mut providers = self.providers.write().await;
-        if let Some(provider) = providers.get_mut(provider_name) {
-            provider.last_used = Some(Utc::now());
-
-            // Update success rate (simple moving average)
-            let alpha = 0.1; // Learning rate
-            provider.success_rate =
-                alpha * (success as f64) + (1.0 - alpha) * provider.success_rate;
-
-            // Update reputation score based on success rate
-            provider.reputation_score = (provider.reputation_score + provider.success_rate) / 2.0;
-        }
+    async fn update_provider_stats(
+        &self,
+        provider_name: &str,
+        success: bool,
+        response_time: Duration,
+    ) -> Result<()> {
+        let mut registry = self.registry.write().await;
+        registry.update_provider_success_rate(provider_name, success)?;
+        registry.update_provider_response_time(provider_name, response_time)?;
         Ok(())
     }
 
     /// Get all providers
-    pub async fn get_providers(&self) -> Vec<LSPProvider> {
-        let providers = self.providers.read().await;
-        providers.values().cloned().collect()
+    pub async fn get_providers(&self) -> Vec<LspProviderInfo> {
+        let registry = self.registry.read().await;
+        registry.get_providers().into_iter().cloned().collect()
     }
 
     /// Get provider statistics
     pub async fn get_provider_stats(&self) -> Result<LSPStats> {
-        let providers = self.providers.read().await;
+        let registry = self.registry.read().await;
+        let providers = registry.get_providers();
 
         let total_providers = providers.len();
-        let active_providers = providers.values().filter(|p| p.is_active).count();
-        let avg_fee_rate = if total_providers > 0 {
-            providers.values().map(|p| p.fee_rate).sum::<f64>() / total_providers as f64
-        } else {
-            0.0
-        };
+        let active_providers = registry.get_active_providers().len();
+        let unreachable_providers = providers
+            .iter()
+            .filter(|p| p.health == LspHealth::Unreachable)
+            .count();
         let avg_reputation = if total_providers > 0 {
-            providers.values().map(|p| p.reputation_score).sum::<f64>() / total_providers as f64
+            providers.iter().map(|p| p.reputation_score).sum::<f64>() / total_providers as f64
         } else {
             0.0
         };
@@ -282,16 +675,16 @@ impl LSPClient {
         Ok(LSPStats {
             total_providers,
             active_providers,
-            avg_fee_rate,
+            unreachable_providers,
             avg_reputation,
-            providers: providers.values().cloned().collect(),
+            providers: providers.into_iter().cloned().collect(),
         })
     }
 
     /// Set active provider
     pub async fn set_active_provider(&self, provider_name: &str) -> Result<()> {
-        let providers = self.providers.read().await;
-        if providers.contains_key(provider_name) {
+        let registry = self.registry.read().await;
+        if registry.get_provider(provider_name).is_some() {
             let mut active = self.active_provider.write().await;
             *active = Some(provider_name.to_string());
             info!("Set active LSP provider: {}", provider_name);
@@ -302,24 +695,91 @@ impl LSPClient {
     }
 }
 
+/// Order and payment state names as stored, for the payment we chose
+fn order_states(order: &Lsps1Order, payment_method: &str) -> (String, String) {
+    let payment_state = match payment_method {
+        "onchain" => order.payment.onchain.as_ref().map(|p| p.state),
+        _ => order.payment.bolt11.as_ref().map(|p| p.state),
+    }
+    .unwrap_or(PaymentState::ExpectPayment);
+
+    (
+        order.order_state.as_str().to_string(),
+        payment_state.as_str().to_string(),
+    )
+}
+
+/// Record for a new order, paid with `method`, if its total adds up
+fn order_record(
+    provider: &str,
+    order: &Lsps1Order,
+    method: LSPPaymentMethod,
+) -> Result<LspOrderRecord> {
+    let (payment_method, fee_total_sat, order_total_sat) = match method {
+        LSPPaymentMethod::Bolt11 => order
+            .payment
+            .bolt11
+            .as_ref()
+            .map(|p| ("bolt11", p.fee_total_sat, p.order_total_sat)),
+        LSPPaymentMethod::Onchain => order
+            .payment
+            .onchain
+            .as_ref()
+            .map(|p| ("onchain", p.fee_total_sat, p.order_total_sat)),
+    }
+    .ok_or_else(|| Lsps1Error::PaymentUnavailable(order.order_id.clone()))?;
+
+    // LSPS1 has the total be exactly the fee plus the balance pushed to us
+    let expected_sat = fee_total_sat.saturating_add(order.params.client_balance_sat);
+    if order_total_sat != expected_sat {
+        return Err(Lsps1Error::OrderTotalMismatch {
+            order_id: order.order_id.clone(),
+            order_total_sat,
+            expected_sat,
+        }
+        .into());
+    }
+
+    let (order_state, payment_state) = order_states(order, payment_method);
+    let now = store::now();
+    Ok(LspOrderRecord {
+        order_id: order.order_id.clone(),
+        provider: provider.to_string(),
+        lsp_balance_sat: order.params.lsp_balance_sat,
+        client_balance_sat: order.params.client_balance_sat,
+        fee_total_sat,
+        order_total_sat,
+        payment_method: payment_method.to_string(),
+        payment_ref: None,
+        order_state,
+        payment_state,
+        funding_outpoint: None,
+        channel_expires_at: None,
+        error_message: None,
+        created_at: now,
+        updated_at: now,
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LSPStats {
     pub total_providers: usize,
     pub active_providers: usize,
-    pub avg_fee_rate: f64,
+    pub unreachable_providers: usize,
     pub avg_reputation: f64,
-    pub providers: Vec<LSPProvider>,
+    pub providers: Vec<LspProviderInfo>,
 }
 
 impl Default for LSPConfig {
     fn default() -> Self {
         Self {
             auto_select_provider: true,
-            max_fee_rate: 0.1, // 0.1 sats per 1000 sats
             min_reputation_score: 0.7,
             max_response_time: 5000, // 5 seconds
             retry_attempts: 3,
-            retry_delay: 1000, // 1 second
+            retry_delay: 1000,          // 1 second
+            order_poll_interval: 30000, // 30 seconds
+            discovery_interval: 600000, // 10 minutes
         }
     }
 }
@@ -327,11 +787,116 @@ impl Default for LSPConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lsp::lsp_provider::UNREACHABLE_AFTER_FAILURES;
+    use crate::lsp::mock::{MockLsp, MOCK_ONCHAIN_ADDRESS};
+
+    const LSP_NODE_ID: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+    const OUR_NODE_ID: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    /// Node that pays by telling the mock LSP
+    #[derive(Debug)]
+    struct TestNode {
+        lsp: Option<MockLsp>,
+        connected: RwLock<Vec<String>>,
+        /// Fee limit of every JIT invoice created
+        jit_fee_limits: RwLock<Vec<u64>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LspNode for TestNode {
+        async fn node_id(&self) -> Result<String> {
+            Ok(OUR_NODE_ID.to_string())
+        }
+
+        async fn connect(&self, node_id: &str, address: &str) -> Result<()> {
+            self.connected
+                .write()
+                .await
+                .push(format!("{}@{}", node_id, address));
+            Ok(())
+        }
+
+        async fn pay_invoice(&self, invoice: &str, _amount_msat: u64) -> Result<String> {
+            let lsp = self.lsp.as_ref().ok_or(LightningError::NodeNotRunning)?;
+            lsp.receive_payment(invoice).await;
+            Ok("payment-hash".to_string())
+        }
+
+        async fn pay_onchain(&self, address: &str, _amount_sats: u64) -> Result<String> {
+            let lsp = self.lsp.as_ref().ok_or(LightningError::NodeNotRunning)?;
+            lsp.receive_payment(address).await;
+            Ok("txid".to_string())
+        }
+
+        async fn receive_via_jit_channel(
+            &self,
+            amount_msat: u64,
+            _memo: &str,
+            max_lsp_fee_msat: u64,
+        ) -> Result<(String, String)> {
+            self.jit_fee_limits.write().await.push(max_lsp_fee_msat);
+            Ok((
+                format!("lnbcrt-jit-{}", amount_msat),
+                "jit-payment-hash".to_string(),
+            ))
+        }
+    }
+
+    fn options() -> Lsps1Options {
+        Lsps1Options {
+            min_required_channel_confirmations: 0,
+            min_funding_confirms_within_blocks: 6,
+            supports_zero_channel_reserve: false,
+            max_channel_expiry_blocks: 20_160,
+            min_initial_client_balance_sat: 0,
+            max_initial_client_balance_sat: 100_000,
+            min_initial_lsp_balance_sat: 100_000,
+            max_initial_lsp_balance_sat: 5_000_000,
+            min_channel_balance_sat: 100_000,
+            max_channel_balance_sat: 5_000_000,
+        }
+    }
+
+    fn request(lsp_balance_sat: u64, payment_method: LSPPaymentMethod) -> LSPChannelRequest {
+        LSPChannelRequest {
+            lsp_balance_sat,
+            client_balance_sat: 0,
+            channel_expiry_blocks: 4_032,
+            announce_channel: false,
+            payment_method,
+            max_fee_sat: None,
+            preferred_provider: None,
+        }
+    }
+
+    fn client(lsp: Option<MockLsp>) -> LSPClient {
+        LSPClient::new(
+            LSPConfig::default(),
+            Store::open_in_memory().unwrap(),
+            Arc::new(TestNode {
+                lsp,
+                connected: RwLock::new(Vec::new()),
+                jit_fee_limits: RwLock::new(Vec::new()),
+            }),
+        )
+    }
+
+    async fn client_with_mock(fee_sat: u64) -> (LSPClient, MockLsp) {
+        let lsp = MockLsp::start(options(), fee_sat).await.unwrap();
+        let client = client(Some(lsp.clone()));
+        client
+            .add_provider(
+                LspProviderInfo::new("mock", LSP_NODE_ID, "127.0.0.1:9735", None)
+                    .with_lsps1_url(lsp.url()),
+            )
+            .await
+            .unwrap();
+        (client, lsp)
+    }
 
     #[tokio::test]
     async fn test_lsp_client_creation() {
-        let config = LSPConfig::default();
-        let client = LSPClient::new(config);
+        let client = client(None);
 
         let stats = client.get_provider_stats().await.unwrap();
         assert_eq!(stats.total_providers, 0);
@@ -339,25 +904,275 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_provider() {
-        let client = LSPClient::new(LSPConfig::default());
-
-        let provider = LSPProvider {
-            name: "test_provider".to_string(),
-            endpoint: "https://test.lsp.com".to_string(),
-            api_key: "test_key".to_string(),
-            is_active: true,
-            min_channel_size: 100000,
-            max_channel_size: 10000000,
-            fee_rate: 0.05,
-            reputation_score: 0.9,
-            last_used: None,
-            success_rate: 0.95,
-            average_response_time: 1000,
-        };
+        let client = client(None);
+
+        let provider = LspProviderInfo::new(
+            "test_provider",
+            LSP_NODE_ID,
+            "lsp.example.com:9735",
+            Some("test_key".to_string()),
+        )
+        .with_lsps1_url("https://test.lsp.com");
 
         client.add_provider(provider).await.unwrap();
 
         let stats = client.get_provider_stats().await.unwrap();
         assert_eq!(stats.total_providers, 1);
     }
+
+    #[tokio::test]
+    async fn test_buy_channel_until_open() {
+        let (client, lsp) = client_with_mock(2_500).await;
+        assert_eq!(client.get_options("mock").await.unwrap(), options());
+
+        let order = client
+            .request_channel(request(1_000_000, LSPPaymentMethod::Bolt11))
+            .await
+            .unwrap();
+        assert_eq!(order.fee_total_sat, 2_500);
+        assert_eq!(order.order_total_sat, 2_500);
+        assert_eq!(order.payment_ref.as_deref(), Some("payment-hash"));
+        assert_eq!(
+            lsp.public_key(&order.order_id).await.as_deref(),
+            Some(OUR_NODE_ID)
+        );
+
+        let order = client.refresh_order(&order.order_id).await.unwrap();
+        assert_eq!(order.order_state, "CREATED");
+        assert_eq!(order.payment_state, "PAID");
+
+        lsp.open_channel(&order.order_id, "abcd:0").await.unwrap();
+        let order = client.refresh_order(&order.order_id).await.unwrap();
+        assert_eq!(order.order_state, "COMPLETED");
+        assert_eq!(order.funding_outpoint.as_deref(), Some("abcd:0"));
+        assert_eq!(client.list_orders().unwrap(), vec![order]);
+    }
+
+    #[tokio::test]
+    async fn test_buy_channel_on_chain() {
+        let (client, lsp) = client_with_mock(1_000).await;
+
+        let mut request = request(500_000, LSPPaymentMethod::Onchain);
+        request.client_balance_sat = 20_000;
+        let order = client.request_channel(request).await.unwrap();
+        assert_eq!(order.payment_method, "onchain");
+        assert_eq!(order.order_total_sat, 21_000);
+
+        let placed = lsp.order(&order.order_id).await.unwrap();
+        assert_eq!(
+            placed.payment.onchain.unwrap().address,
+            MOCK_ONCHAIN_ADDRESS
+        );
+
+        lsp.fail_order(&order.order_id).await.unwrap();
+        let order = client.refresh_order(&order.order_id).await.unwrap();
+        assert_eq!(order.order_state, "FAILED");
+        assert_eq!(order.payment_state, "REFUNDED");
+    }
+
+    #[tokio::test]
+    async fn test_order_outside_options_is_refused() {
+        let (client, _lsp) = client_with_mock(1_000).await;
+
+        let err = client
+            .request_channel(request(10_000_000, LSPPaymentMethod::Bolt11))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Lsps1Error::OutOfRange {
+                field: "lsp_balance_sat",
+                ..
+            })
+        ));
+
+        let mut expensive = request(1_000_000, LSPPaymentMethod::Bolt11);
+        expensive.max_fee_sat = Some(500);
+        let err = client.request_channel(expensive).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Lsps1Error::FeeTooHigh { fee_sat: 1_000, .. })
+        ));
+
+        // Nothing was stored or paid for the refused orders
+        assert!(client.list_orders().unwrap().is_empty());
+        let stats = client.get_provider_stats().await.unwrap();
+        assert_eq!(stats.providers[0].success_rate, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_order_with_wrong_total_is_refused() {
+        let (client, lsp) = client_with_mock(1_000).await;
+        lsp.set_order_total_surcharge(500).await;
+
+        let mut request = request(500_000, LSPPaymentMethod::Bolt11);
+        request.client_balance_sat = 20_000;
+        let err = client.request_channel(request).await.unwrap_err();
+        let Some(Lsps1Error::OrderTotalMismatch {
+            order_id,
+            order_total_sat: 21_500,
+            expected_sat: 21_000,
+        }) = err.downcast_ref()
+        else {
+            panic!("unexpected error: {:#}", err);
+        };
+
+        // The order was neither stored nor paid
+        assert!(client.list_orders().unwrap().is_empty());
+        let placed = lsp.order(order_id).await.unwrap();
+        assert_eq!(
+            placed.payment.bolt11.unwrap().state,
+            PaymentState::ExpectPayment
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jit_invoice() {
+        let lsp = MockLsp::start(options(), 1_000).await.unwrap();
+        let valid_until = (Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+        lsp.set_opening_fee_params(vec![OpeningFeeParams {
+            min_fee_msat: 2_000_000,
+            proportional: 10_000,
+            valid_until: valid_until.clone(),
+            min_lifetime: 1_008,
+            max_client_to_self_delay: 2_016,
+            min_payment_size_msat: 1_000_000,
+            max_payment_size_msat: 1_000_000_000,
+            promise: "promise".to_string(),
+        }])
+        .await;
+
+        let node = Arc::new(TestNode {
+            lsp: None,
+            connected: RwLock::new(Vec::new()),
+            jit_fee_limits: RwLock::new(Vec::new()),
+        });
+        let client = LSPClient::new(
+            LSPConfig::default(),
+            Store::open_in_memory().unwrap(),
+            Arc::clone(&node) as Arc<dyn LspNode>,
+        )
+        .with_jit_provider("jit");
+        assert!(!client.has_jit_provider().await);
+        // Only the provider the node takes JIT channels from is used
+        client
+            .add_provider(
+                LspProviderInfo::new("other", OUR_NODE_ID, "127.0.0.1:9736", None)
+                    .with_lsps2_url("http://127.0.0.1:1/"),
+            )
+            .await
+            .unwrap();
+        assert!(!client.has_jit_provider().await);
+        client
+            .add_provider(
+                LspProviderInfo::new("jit", LSP_NODE_ID, "127.0.0.1:9735", None)
+                    .with_lsps2_url(lsp.url()),
+            )
+            .await
+            .unwrap();
+        assert!(client.has_jit_provider().await);
+
+        let quote = client.quote_jit_channel(500_000_000).await.unwrap();
+        assert_eq!(quote.provider, "jit");
+        assert_eq!(quote.fee_msat, 5_000_000);
+        assert_eq!(quote.valid_until, valid_until);
+
+        let jit = client
+            .create_jit_invoice(500_000_000, "first payment", 5_000_000)
+            .await
+            .unwrap();
+        assert_eq!(jit.invoice, "lnbcrt-jit-500000000");
+        assert_eq!(jit.quote, quote);
+        assert_eq!(*node.jit_fee_limits.read().await, vec![5_000_000]);
+
+        // A fee above what the user accepted creates no invoice
+        let err = client
+            .create_jit_invoice(500_000_000, "first payment", 4_000_000)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Lsps2Error::FeeNotAccepted {
+                fee_sat: 5_000,
+                max_fee_sat: 4_000
+            })
+        ));
+        let err = client.quote_jit_channel(500_000).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(Lsps2Error::NoOffer { .. })
+        ));
+        assert_eq!(node.jit_fee_limits.read().await.len(), 1);
+
+        // The JIT-only provider sells no LSPS1 channels
+        let err = client
+            .request_channel(request(1_000_000, LSPPaymentMethod::Bolt11))
+            .await
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(Lsps1Error::NoProvider)));
+    }
+
+    #[tokio::test]
+    async fn test_discover_provider() {
+        let lsp = MockLsp::start(options(), 1_000).await.unwrap();
+        lsp.set_protocols(vec![LspProtocol::Lsps2]).await;
+        let client = client(Some(lsp.clone()));
+        client
+            .add_provider(
+                LspProviderInfo::new("mock", LSP_NODE_ID, "127.0.0.1:9735", None)
+                    .with_lsps1_url(lsp.url())
+                    .with_lsps2_url(lsp.url()),
+            )
+            .await
+            .unwrap();
+
+        // Configured for both, but the LSP only opens just-in-time channels
+        let provider = client.discover_provider("mock").await.unwrap();
+        assert_eq!(provider.protocols, vec![LspProtocol::Lsps2]);
+        assert_eq!(provider.health, LspHealth::Healthy);
+        assert!(provider.fees.channel_options.is_none());
+        assert!(provider.fees.updated_at.is_some());
+        assert!(client.has_jit_provider().await);
+        let err = client
+            .request_channel(request(1_000_000, LSPPaymentMethod::Bolt11))
+            .await
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(Lsps1Error::NoProvider)));
+
+        lsp.set_protocols(vec![LspProtocol::Lsps1, LspProtocol::Lsps2])
+            .await;
+        let provider = client.discover_provider("mock").await.unwrap();
+        assert_eq!(provider.fees.channel_options, Some(options()));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_provider_is_skipped() {
+        let client = client(None);
+        client
+            .add_provider(
+                LspProviderInfo::new("down", LSP_NODE_ID, "127.0.0.1:9735", None)
+                    .with_lsps1_url("http://127.0.0.1:1/"),
+            )
+            .await
+            .unwrap();
+        assert!(client.get_best_provider().await.unwrap().is_some());
+
+        for _ in 0..UNREACHABLE_AFTER_FAILURES {
+            client.discover_providers().await;
+        }
+        let stats = client.get_provider_stats().await.unwrap();
+        assert_eq!(stats.unreachable_providers, 1);
+        assert!(stats.providers[0].success_rate < 1.0);
+        assert!(client.get_best_provider().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_no_provider() {
+        let client = client(None);
+        let err = client
+            .request_channel(request(1_000_000, LSPPaymentMethod::Bolt11))
+            .await
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(Lsps1Error::NoProvider)));
+    }
 }
@@ -1,297 +1,402 @@
+//! Registry of the Lightning Service Providers from the `[[lsps]]`
+//! configuration.
+//!
+//! Each entry holds where to reach the LSP, the LSPS protocols it reported
+//! over LSPS0, its last fetched fee schedule and how well it has been
+//! answering. Statistics and health follow the outcomes of real calls made by
+//! [`crate::lsp::LSPClient`].
+
+use crate::engine_config::LspEndpoint;
+use crate::lsp::lsps0::LspProtocol;
+use crate::lsp::lsps1::Lsps1Options;
+use crate::lsp::lsps2::OpeningFeeParams;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tracing::{error, info, warn};
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum LspProviderType {
-    AWS_CloudHSM,
-    Azure_KeyVault,
-    Google_CloudKMS,
-    HashiCorp_Vault,
-    Custom(String),
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Failed calls in a row after which an LSP is skipped until it answers again
+pub const UNREACHABLE_AFTER_FAILURES: u32 = 3;
+
+/// Weight of the latest outcome in the moving averages
+const OUTCOME_WEIGHT: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LspHealth {
+    /// Not called yet
+    #[default]
+    Unknown,
+    Healthy,
+    /// Failed its last [`UNREACHABLE_AFTER_FAILURES`] calls
+    Unreachable,
+}
+
+/// What an LSP charges, as last fetched
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LspFeeSchedule {
+    /// LSPS1 order limits; the fee itself is quoted per order
+    pub channel_options: Option<Lsps1Options>,
+    /// LSPS2 opening fee terms for just-in-time channels
+    pub opening_fee_params_menu: Vec<OpeningFeeParams>,
+    pub updated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LspProviderInfo {
     pub name: String,
-    pub provider_type: LspProviderType,
-    pub endpoint: String,
+    /// Hex-encoded node public key the LSP opens channels from
+    pub node_id: String,
+    /// `host:port` the LSP accepts peer connections on
+    pub address: String,
+    /// Access token some LSPs require
+    pub token: Option<String>,
+    /// LSPS1 JSON-RPC endpoint
+    pub lsps1_url: Option<String>,
+    /// LSPS2 JSON-RPC endpoint
+    pub lsps2_url: Option<String>,
+    /// Protocols reported over LSPS0; empty until discovered
+    pub protocols: Vec<LspProtocol>,
+    pub fees: LspFeeSchedule,
+    pub health: LspHealth,
+    pub consecutive_failures: u32,
+    pub last_checked: Option<DateTime<Utc>>,
+    pub last_used: Option<DateTime<Utc>>,
     pub is_active: bool,
-    pub min_channel_size: u64,
-    pub max_channel_size: u64,
-    pub fee_rate: f64,
-    pub reputation_score: f64,
-    pub success_rate: f64,
+    pub reputation_score: f64, // 0.0 to 1.0
+    pub success_rate: f64,     // 0.0 to 1.0
     pub average_response_time_ms: u64,
-    pub supported_features: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LspConfig {
-    pub providers: Vec<LspProviderInfo>,
-    pub default_provider: Option<String>,
-    pub fallback_providers: Vec<String>,
-    pub max_retries: u32,
-    pub timeout_ms: u64,
-}
-
-impl Default for LspConfig {
-    fn default() -> Self {
+impl LspProviderInfo {
+    /// An LSP reachable at `node_id@address` speaking none of the protocols
+    /// yet. Its statistics start out optimistic and follow real outcomes.
+    pub fn new(
+        name: impl Into<String>,
+        node_id: impl Into<String>,
+        address: impl Into<String>,
+        token: Option<String>,
+    ) -> Self {
         Self {
-            providers: vec![
-                LspProviderInfo {
-                    name: "AWS CloudHSM".to_string(),
-                    provider_type: LspProviderType::AWS_CloudHSM,
-                    endpoint: "https://hsm.aws.com".to_string(),
-                    is_active: true,
-                    min_channel_size: 100_000,
-                    max_channel_size: 10_000_000,
-                    fee_rate: 0.05,
-                    reputation_score: 0.95,
-                    success_rate: 0.98,
-                    average_response_time_ms: 500,
-                    supported_features: vec![
-                        "channel_management".to_string(),
-                        "payment_routing".to_string(),
-                        "liquidity_provision".to_string(),
-                    ],
-                },
-                LspProviderInfo {
-                    name: "Azure KeyVault".to_string(),
-                    provider_type: LspProviderType::Azure_KeyVault,
-                    endpoint: "https://vault.azure.com".to_string(),
-                    is_active: true,
-                    min_channel_size: 50_000,
-                    max_channel_size: 5_000_000,
-                    fee_rate: 0.03,
-                    reputation_score: 0.92,
-                    success_rate: 0.96,
-                    average_response_time_ms: 600,
-                    supported_features: vec![
-                        "channel_management".to_string(),
-                        "payment_routing".to_string(),
-                    ],
-                },
-            ],
-            default_provider: Some("AWS CloudHSM".to_string()),
-            fallback_providers: vec!["Azure KeyVault".to_string()],
-            max_retries: 3,
-            timeout_ms: 5000,
+            name: name.into(),
+            node_id: node_id.into(),
+            address: address.into(),
+            token: token.filter(|token| !token.is_empty()),
+            lsps1_url: None,
+            lsps2_url: None,
+            protocols: Vec::new(),
+            fees: LspFeeSchedule::default(),
+            health: LspHealth::Unknown,
+            consecutive_failures: 0,
+            last_checked: None,
+            last_used: None,
+            is_active: true,
+            reputation_score: 1.0,
+            success_rate: 1.0,
+            average_response_time_ms: 0,
         }
     }
+
+    /// Sells channels over LSPS1 at `url`
+    pub fn with_lsps1_url(mut self, url: impl Into<String>) -> Self {
+        self.lsps1_url = Some(url.into());
+        self
+    }
+
+    /// Opens just-in-time channels, quoting their fees over LSPS2 at `url`
+    pub fn with_lsps2_url(mut self, url: impl Into<String>) -> Self {
+        self.lsps2_url = Some(url.into());
+        self
+    }
+
+    /// Endpoint to ask for LSPS0 discovery, any the LSP takes JSON-RPC on
+    pub fn lsps0_url(&self) -> Option<&str> {
+        self.lsps1_url.as_deref().or(self.lsps2_url.as_deref())
+    }
+
+    /// Whether we can use `protocol` with the LSP: configured with an
+    /// endpoint for it and, once discovered, reported by the LSP
+    pub fn supports(&self, protocol: LspProtocol) -> bool {
+        let configured = match protocol {
+            LspProtocol::Lsps1 => self.lsps1_url.is_some(),
+            LspProtocol::Lsps2 => self.lsps2_url.is_some(),
+            LspProtocol::Other(_) => !self.protocols.is_empty(),
+        };
+        configured && (self.protocols.is_empty() || self.protocols.contains(&protocol))
+    }
+
+    /// Active and not known to be down
+    pub fn is_available(&self) -> bool {
+        self.is_active && self.health != LspHealth::Unreachable
+    }
 }
 
-/// LSP Provider management
-#[derive(Debug)]
-pub struct LspProvider {
-    config: LspConfig,
+impl From<&LspEndpoint> for LspProviderInfo {
+    fn from(lsp: &LspEndpoint) -> Self {
+        let mut provider = LspProviderInfo::new(
+            lsp.name.clone(),
+            lsp.node_id.clone(),
+            lsp.address.clone(),
+            lsp.token.clone(),
+        );
+        provider.lsps1_url = lsp.lsps1_url.clone();
+        provider.lsps2_url = lsp.lsps2_url.clone();
+        provider
+    }
+}
+
+/// LSPs by name
+#[derive(Debug, Default)]
+pub struct LspRegistry {
     provider_map: HashMap<String, LspProviderInfo>,
 }
 
-impl LspProvider {
-    pub fn new(config: LspConfig) -> Self {
-        let mut provider_map = HashMap::new();
-        for provider in &config.providers {
-            provider_map.insert(provider.name.clone(), provider.clone());
-        }
+impl LspRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        Self {
-            config,
-            provider_map,
+    /// Registry of the configured LSPs that have an LSPS endpoint; the others
+    /// are plain peers
+    pub fn from_config(lsps: &[LspEndpoint]) -> Self {
+        let mut registry = Self::new();
+        for lsp in lsps {
+            if lsp.lsps1_url.is_some() || lsp.lsps2_url.is_some() {
+                registry.add_provider(LspProviderInfo::from(lsp));
+            }
         }
+        registry
     }
 
     pub fn get_provider(&self, name: &str) -> Option<&LspProviderInfo> {
         self.provider_map.get(name)
     }
 
-    pub fn get_active_providers(&self) -> Vec<&LspProviderInfo> {
-        self.provider_map.values().filter(|p| p.is_active).collect()
+    pub fn get_providers(&self) -> Vec<&LspProviderInfo> {
+        self.provider_map.values().collect()
     }
 
-    pub fn get_best_provider(&self) -> Option<&LspProviderInfo> {
-        self.get_active_providers()
-            .iter()
-            .max_by(|a, b| {
-                // Sort by reputation score, then success rate, then response time
-                a.reputation_score
-                    .partial_cmp(&b.reputation_score)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-                    .then(
-                        a.success_rate
-                            .partial_cmp(&b.success_rate)
-                            .unwrap_or(std::cmp::Ordering::Equal),
-                    )
-                    .then(b.average_response_time_ms.cmp(&a.average_response_time_ms))
-            })
-            .copied()
+    pub fn get_active_providers(&self) -> Vec<&LspProviderInfo> {
+        self.provider_map
+            .values()
+            .filter(|p| p.is_available())
+            .collect()
     }
 
-    pub fn get_providers_by_feature(&self, feature: &str) -> Vec<&LspProviderInfo> {
+    /// Available providers we can use `protocol` with
+    pub fn get_providers_by_protocol(&self, protocol: LspProtocol) -> Vec<&LspProviderInfo> {
         self.get_active_providers()
             .into_iter()
-            .filter(|p| p.supported_features.contains(&feature.to_string()))
+            .filter(|p| p.supports(protocol))
             .collect()
     }
 
-    pub fn get_providers_by_capacity(
-        &self,
-        min_capacity: u64,
-        max_capacity: u64,
-    ) -> Vec<&LspProviderInfo> {
-        self.get_active_providers()
-            .into_iter()
-            .filter(|p| p.min_channel_size <= min_capacity && p.max_channel_size >= max_capacity)
-            .collect()
+    pub fn add_provider(&mut self, provider: LspProviderInfo) {
+        info!("Added LSP provider: {}", provider.name);
+        self.provider_map.insert(provider.name.clone(), provider);
     }
 
-    pub fn update_provider_reputation(&mut self, name: &str, reputation_score: f64) -> Result<()> {
-        if let Some(provider) = self.provider_map.get_mut(name) {
-            provider.reputation_score = reputation_score;
-            info!(
-                "Updated reputation score for provider {} to {}",
-                name, reputation_score
-            );
+    pub fn remove_provider(&mut self, name: &str) -> Result<()> {
+        if self.provider_map.remove(name).is_some() {
+            info!("Removed LSP provider: {}", name);
+            Ok(())
         } else {
-            return Err(anyhow::anyhow!("Provider {} not found", name));
+            Err(anyhow::anyhow!("Provider {} not found", name))
         }
-        Ok(())
     }
 
-    pub fn update_provider_success_rate(&mut self, name: &str, success_rate: f64) -> Result<()> {
-        if let Some(provider) = self.provider_map.get_mut(name) {
-            provider.success_rate = success_rate;
-            info!(
-                "Updated success rate for provider {} to {}",
-                name, success_rate
-            );
+    /// Fold the outcome of a call to the provider into its success rate,
+    /// reputation and health
+    pub fn update_provider_success_rate(&mut self, name: &str, success: bool) -> Result<()> {
+        let provider = self.provider_mut(name)?;
+        let now = Utc::now();
+        provider.last_used = Some(now);
+        provider.last_checked = Some(now);
+
+        let outcome = if success { 1.0 } else { 0.0 };
+        provider.success_rate =
+            OUTCOME_WEIGHT * outcome + (1.0 - OUTCOME_WEIGHT) * provider.success_rate;
+        provider.reputation_score = (provider.reputation_score + provider.success_rate) / 2.0;
+
+        if success {
+            if provider.health == LspHealth::Unreachable {
+                info!("LSP {} is reachable again", name);
+            }
+            provider.health = LspHealth::Healthy;
+            provider.consecutive_failures = 0;
         } else {
-            return Err(anyhow::anyhow!("Provider {} not found", name));
+            provider.consecutive_failures += 1;
+            if provider.consecutive_failures >= UNREACHABLE_AFTER_FAILURES
+                && provider.health != LspHealth::Unreachable
+            {
+                warn!(
+                    "LSP {} failed {} calls in a row, skipping it until it answers",
+                    name, provider.consecutive_failures
+                );
+                provider.health = LspHealth::Unreachable;
+            }
         }
         Ok(())
     }
 
-    pub fn update_provider_response_time(
+    /// Fold how long a call to the provider took into its average
+    pub fn update_provider_response_time(&mut self, name: &str, elapsed: Duration) -> Result<()> {
+        let provider = self.provider_mut(name)?;
+        let elapsed_ms = elapsed.as_millis() as f64;
+        provider.average_response_time_ms = (OUTCOME_WEIGHT * elapsed_ms
+            + (1.0 - OUTCOME_WEIGHT) * provider.average_response_time_ms as f64)
+            as u64;
+        Ok(())
+    }
+
+    /// Record the protocols the provider reported over LSPS0
+    pub fn update_provider_protocols(
         &mut self,
         name: &str,
-        response_time_ms: u64,
+        protocols: Vec<LspProtocol>,
     ) -> Result<()> {
-        if let Some(provider) = self.provider_map.get_mut(name) {
-            provider.average_response_time_ms = response_time_ms;
+        let provider = self.provider_mut(name)?;
+        if provider.protocols != protocols {
             info!(
-                "Updated response time for provider {} to {}ms",
-                name, response_time_ms
+                "LSP {} supports {}",
+                name,
+                protocols
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
             );
-        } else {
-            return Err(anyhow::anyhow!("Provider {} not found", name));
+            provider.protocols = protocols;
         }
         Ok(())
     }
 
-    pub fn add_provider(&mut self, provider: LspProviderInfo) {
-        self.provider_map.insert(provider.name.clone(), provider);
-        info!("Added new LSP provider: {}", self.provider_map.len());
+    /// Record the provider's current LSPS1 order limits
+    pub fn update_channel_options(&mut self, name: &str, options: Lsps1Options) -> Result<()> {
+        let fees = &mut self.provider_mut(name)?.fees;
+        fees.channel_options = Some(options);
+        fees.updated_at = Some(Utc::now());
+        Ok(())
     }
 
-    pub fn remove_provider(&mut self, name: &str) -> Result<()> {
-        if self.provider_map.remove(name).is_some() {
-            info!("Removed LSP provider: {}", name);
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Provider {} not found", name))
-        }
+    /// Record the provider's current LSPS2 opening fee terms
+    pub fn update_opening_fee_params(
+        &mut self,
+        name: &str,
+        menu: Vec<OpeningFeeParams>,
+    ) -> Result<()> {
+        let fees = &mut self.provider_mut(name)?.fees;
+        fees.opening_fee_params_menu = menu;
+        fees.updated_at = Some(Utc::now());
+        Ok(())
     }
 
-    pub fn get_provider_stats(&self) -> LspProviderStats {
-        let total_providers = self.provider_map.len();
-        let active_providers = self.get_active_providers().len();
-        let avg_reputation = self
-            .get_active_providers()
-            .iter()
-            .map(|p| p.reputation_score)
-            .sum::<f64>()
-            / active_providers.max(1) as f64;
-        let avg_success_rate = self
-            .get_active_providers()
-            .iter()
-            .map(|p| p.success_rate)
-            .sum::<f64>()
-            / active_providers.max(1) as f64;
-        let avg_response_time = self
-            .get_active_providers()
-            .iter()
-            .map(|p| p.average_response_time_ms)
-            .sum::<u64>()
-            / active_providers.max(1) as u64;
-
-        LspProviderStats {
-            total_providers,
-            active_providers,
-            avg_reputation,
-            avg_success_rate,
-            avg_response_time,
-        }
+    fn provider_mut(&mut self, name: &str) -> Result<&mut LspProviderInfo> {
+        self.provider_map
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("Provider {} not found", name))
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LspProviderStats {
-    pub total_providers: usize,
-    pub active_providers: usize,
-    pub avg_reputation: f64,
-    pub avg_success_rate: f64,
-    pub avg_response_time: u64,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_lsp_provider_creation() {
-        let config = LspConfig::default();
-        let provider = LspProvider::new(config);
+    const NODE_ID: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
 
-        assert!(provider.get_provider("AWS CloudHSM").is_some());
-        assert!(provider.get_provider("Azure KeyVault").is_some());
+    fn endpoint(name: &str, lsps1_url: Option<&str>, lsps2_url: Option<&str>) -> LspEndpoint {
+        LspEndpoint {
+            name: name.to_string(),
+            node_id: NODE_ID.to_string(),
+            address: "lsp.example.com:9735".to_string(),
+            token: None,
+            lsps1_url: lsps1_url.map(str::to_string),
+            lsps2_url: lsps2_url.map(str::to_string),
+        }
     }
 
-    #[test]
-    fn test_get_active_providers() {
-        let config = LspConfig::default();
-        let provider = LspProvider::new(config);
-
-        let active_providers = provider.get_active_providers();
-        assert_eq!(active_providers.len(), 2);
+    fn registry() -> LspRegistry {
+        LspRegistry::from_config(&[
+            endpoint("channels", Some("https://a.example.com/lsps1"), None),
+            endpoint(
+                "jit",
+                Some("https://b.example.com/lsps1"),
+                Some("https://b.example.com/lsps2"),
+            ),
+            endpoint("peer", None, None),
+        ])
     }
 
     #[test]
-    fn test_get_best_provider() {
-        let config = LspConfig::default();
-        let provider = LspProvider::new(config);
+    fn test_registry_from_config() {
+        let registry = registry();
+        assert_eq!(registry.get_providers().len(), 2);
+        assert!(registry.get_provider("peer").is_none());
 
-        let best_provider = provider.get_best_provider();
-        assert!(best_provider.is_some());
-        assert_eq!(best_provider.unwrap().name, "AWS CloudHSM");
+        let jit = registry.get_provider("jit").unwrap();
+        assert_eq!(jit.lsps0_url(), Some("https://b.example.com/lsps1"));
+        assert_eq!(jit.health, LspHealth::Unknown);
+        assert_eq!(
+            registry.get_providers_by_protocol(LspProtocol::Lsps1).len(),
+            2
+        );
+        assert_eq!(
+            registry.get_providers_by_protocol(LspProtocol::Lsps2)[0].name,
+            "jit"
+        );
     }
 
     #[test]
-    fn test_get_providers_by_feature() {
-        let config = LspConfig::default();
-        let provider = LspProvider::new(config);
+    fn test_discovered_protocols_limit_configured_ones() {
+        let mut registry = registry();
+        registry
+            .update_provider_protocols("jit", vec![LspProtocol::Lsps2])
+            .unwrap();
 
-        let providers = provider.get_providers_by_feature("channel_management");
-        assert_eq!(providers.len(), 2);
+        let jit = registry.get_provider("jit").unwrap();
+        assert!(!jit.supports(LspProtocol::Lsps1));
+        assert!(jit.supports(LspProtocol::Lsps2));
+        // Discovery does not make up endpoints we were not given
+        registry
+            .update_provider_protocols("channels", vec![LspProtocol::Lsps1, LspProtocol::Lsps2])
+            .unwrap();
+        assert!(!registry
+            .get_provider("channels")
+            .unwrap()
+            .supports(LspProtocol::Lsps2));
     }
 
     #[test]
-    fn test_provider_stats() {
-        let config = LspConfig::default();
-        let provider = LspProvider::new(config);
+    fn test_outcomes_update_statistics_and_health() {
+        let mut registry = registry();
+        registry
+            .update_provider_response_time("channels", Duration::from_millis(1_000))
+            .unwrap();
+        registry
+            .update_provider_success_rate("channels", true)
+            .unwrap();
+        let provider = registry.get_provider("channels").unwrap();
+        assert_eq!(provider.health, LspHealth::Healthy);
+        assert_eq!(provider.average_response_time_ms, 100);
+        assert_eq!(provider.success_rate, 1.0);
+
+        for _ in 0..UNREACHABLE_AFTER_FAILURES {
+            registry
+                .update_provider_success_rate("channels", false)
+                .unwrap();
+        }
+        let provider = registry.get_provider("channels").unwrap();
+        assert_eq!(provider.health, LspHealth::Unreachable);
+        assert!(provider.success_rate < 0.75);
+        assert!(provider.reputation_score < 1.0);
+        assert_eq!(registry.get_active_providers().len(), 1);
 
-        let stats = provider.get_provider_stats();
-        assert_eq!(stats.total_providers, 2);
-        assert_eq!(stats.active_providers, 2);
+        registry
+            .update_provider_success_rate("channels", true)
+            .unwrap();
+        assert_eq!(registry.get_active_providers().len(), 2);
+        assert!(registry.update_provider_success_rate("peer", true).is_err());
     }
 }
@@ -0,0 +1,94 @@
+//! LSPS0 protocol discovery.
+//!
+//! `lsps0.list_protocols` tells which LSPS protocols an LSP speaks, by number.
+//! It is called over the same JSON-RPC transport as LSPS1 and LSPS2.
+
+use crate::lsp::lsps1::Lsps1Transport;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+use std::sync::Arc;
+
+pub const LIST_PROTOCOLS: &str = "lsps0.list_protocols";
+
+/// An LSPS protocol, numbered as on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "u16", into = "u16")]
+pub enum LspProtocol {
+    /// Buying channels
+    Lsps1,
+    /// Just-in-time channels
+    Lsps2,
+    Other(u16),
+}
+
+impl From<u16> for LspProtocol {
+    fn from(number: u16) -> Self {
+        match number {
+            1 => LspProtocol::Lsps1,
+            2 => LspProtocol::Lsps2,
+            other => LspProtocol::Other(other),
+        }
+    }
+}
+
+impl From<LspProtocol> for u16 {
+    fn from(protocol: LspProtocol) -> Self {
+        match protocol {
+            LspProtocol::Lsps1 => 1,
+            LspProtocol::Lsps2 => 2,
+            LspProtocol::Other(number) => number,
+        }
+    }
+}
+
+impl fmt::Display for LspProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LSPS{}", u16::from(*self))
+    }
+}
+
+/// Typed LSPS0 calls over a transport
+#[derive(Debug, Clone)]
+pub struct Lsps0Client {
+    transport: Arc<dyn Lsps1Transport>,
+}
+
+impl Lsps0Client {
+    pub fn new(transport: Arc<dyn Lsps1Transport>) -> Self {
+        Self { transport }
+    }
+
+    /// Protocols the LSP supports
+    pub async fn list_protocols(&self) -> Result<Vec<LspProtocol>> {
+        #[derive(Deserialize)]
+        struct ListProtocols {
+            protocols: Vec<LspProtocol>,
+        }
+
+        let list: ListProtocols =
+            serde_json::from_value(self.transport.call(LIST_PROTOCOLS, json!({})).await?)?;
+        Ok(list.protocols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_numbers() {
+        let protocols: Vec<LspProtocol> = serde_json::from_value(json!([1, 2, 5])).unwrap();
+        assert_eq!(
+            protocols,
+            vec![
+                LspProtocol::Lsps1,
+                LspProtocol::Lsps2,
+                LspProtocol::Other(5)
+            ]
+        );
+        assert_eq!(serde_json::to_value(&protocols).unwrap(), json!([1, 2, 5]));
+        assert_eq!(LspProtocol::Lsps2.to_string(), "LSPS2");
+    }
+}
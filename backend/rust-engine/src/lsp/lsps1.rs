@@ -0,0 +1,479 @@
+//! LSPS1 (bLIP-51) client for buying channels from a Lightning Service Provider.
+//!
+//! Requests are LSPS0 JSON-RPC 2.0 messages posted to the LSP's HTTP endpoint.
+//! Satoshi amounts travel as decimal strings, as the spec requires.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+pub const GET_INFO: &str = "lsps1.get_info";
+pub const CREATE_ORDER: &str = "lsps1.create_order";
+pub const GET_ORDER: &str = "lsps1.get_order";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Lsps1Error {
+    #[error("No LSP is configured for channel purchases")]
+    NoProvider,
+    #[error("Unknown LSP: {0}")]
+    UnknownProvider(String),
+    #[error("{field} of {value} sats is outside the LSP's range of {min}..={max}")]
+    OutOfRange {
+        field: &'static str,
+        value: u64,
+        min: u64,
+        max: u64,
+    },
+    #[error("Channel expiry of {blocks} blocks exceeds the LSP's maximum of {max}")]
+    ExpiryTooLong { blocks: u32, max: u32 },
+    #[error("LSP fee of {fee_sat} sats exceeds the limit of {max_fee_sat} sats")]
+    FeeTooHigh { fee_sat: u64, max_fee_sat: u64 },
+    #[error("LSP returned order {0} with different terms than requested")]
+    OrderMismatch(String),
+    #[error(
+        "LSP order {order_id} totals {order_total_sat} sats, but its fee and client balance add up to {expected_sat} sats"
+    )]
+    OrderTotalMismatch {
+        order_id: String,
+        order_total_sat: u64,
+        expected_sat: u64,
+    },
+    #[error("Order {0} cannot be paid with the requested method")]
+    PaymentUnavailable(String),
+    #[error("Order invoice is for {found_msat:?} msat, expected {expected_msat} msat")]
+    InvoiceAmountMismatch {
+        expected_msat: u64,
+        found_msat: Option<u64>,
+    },
+    #[error("LSP rejected the request: {message} (code {code})")]
+    Rejected { code: i64, message: String },
+    #[error("LSP order not found: {0}")]
+    OrderNotFound(String),
+}
+
+/// Satoshi (and LSPS2 millisatoshi) amounts, sent as strings and accepted as
+/// strings or numbers
+pub(crate) mod sats {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Amount {
+            Text(String),
+            Number(u64),
+        }
+
+        match Amount::deserialize(deserializer)? {
+            Amount::Text(text) => text.parse().map_err(serde::de::Error::custom),
+            Amount::Number(number) => Ok(number),
+        }
+    }
+}
+
+/// Limits the LSP accepts orders within, from `lsps1.get_info`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lsps1Options {
+    pub min_required_channel_confirmations: u16,
+    pub min_funding_confirms_within_blocks: u16,
+    pub supports_zero_channel_reserve: bool,
+    pub max_channel_expiry_blocks: u32,
+    #[serde(with = "sats")]
+    pub min_initial_client_balance_sat: u64,
+    #[serde(with = "sats")]
+    pub max_initial_client_balance_sat: u64,
+    #[serde(with = "sats")]
+    pub min_initial_lsp_balance_sat: u64,
+    #[serde(with = "sats")]
+    pub max_initial_lsp_balance_sat: u64,
+    #[serde(with = "sats")]
+    pub min_channel_balance_sat: u64,
+    #[serde(with = "sats")]
+    pub max_channel_balance_sat: u64,
+}
+
+/// Channel terms of an order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderParams {
+    /// Inbound liquidity the LSP puts on its side of the channel
+    #[serde(with = "sats")]
+    pub lsp_balance_sat: u64,
+    /// Balance pushed to our side, paid for on top of the fee
+    #[serde(with = "sats")]
+    pub client_balance_sat: u64,
+    pub required_channel_confirmations: u16,
+    pub funding_confirms_within_blocks: u16,
+    /// How long the LSP keeps the channel open at least
+    pub channel_expiry_blocks: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refund_onchain_address: Option<String>,
+    pub announce_channel: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderState {
+    Created,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PaymentState {
+    ExpectPayment,
+    Hold,
+    Paid,
+    Refunded,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bolt11Payment {
+    pub state: PaymentState,
+    pub expires_at: String,
+    #[serde(with = "sats")]
+    pub fee_total_sat: u64,
+    #[serde(with = "sats")]
+    pub order_total_sat: u64,
+    pub invoice: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnchainPayment {
+    pub state: PaymentState,
+    pub expires_at: String,
+    #[serde(with = "sats")]
+    pub fee_total_sat: u64,
+    #[serde(with = "sats")]
+    pub order_total_sat: u64,
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_onchain_payment_confirmations: Option<u16>,
+}
+
+/// Ways the LSP accepts payment for an order; at least one is present
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaymentInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bolt11: Option<Bolt11Payment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onchain: Option<OnchainPayment>,
+}
+
+/// The channel opened for an order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelInfo {
+    pub funded_at: String,
+    /// `txid:vout`
+    pub funding_outpoint: String,
+    pub expires_at: String,
+}
+
+/// An order as reported by `lsps1.create_order` and `lsps1.get_order`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lsps1Order {
+    pub order_id: String,
+    #[serde(flatten)]
+    pub params: OrderParams,
+    pub created_at: String,
+    pub order_state: OrderState,
+    pub payment: PaymentInfo,
+    #[serde(default)]
+    pub channel: Option<ChannelInfo>,
+}
+
+impl Lsps1Options {
+    /// Refuse orders the LSP would reject anyway
+    pub fn check(&self, params: &OrderParams) -> Result<(), Lsps1Error> {
+        let in_range = |field, value, min, max| {
+            if value < min || value > max {
+                Err(Lsps1Error::OutOfRange {
+                    field,
+                    value,
+                    min,
+                    max,
+                })
+            } else {
+                Ok(())
+            }
+        };
+
+        in_range(
+            "lsp_balance_sat",
+            params.lsp_balance_sat,
+            self.min_initial_lsp_balance_sat,
+            self.max_initial_lsp_balance_sat,
+        )?;
+        in_range(
+            "client_balance_sat",
+            params.client_balance_sat,
+            self.min_initial_client_balance_sat,
+            self.max_initial_client_balance_sat,
+        )?;
+        in_range(
+            "channel balance",
+            params
+                .lsp_balance_sat
+                .saturating_add(params.client_balance_sat),
+            self.min_channel_balance_sat,
+            self.max_channel_balance_sat,
+        )?;
+        if params.channel_expiry_blocks > self.max_channel_expiry_blocks {
+            return Err(Lsps1Error::ExpiryTooLong {
+                blocks: params.channel_expiry_blocks,
+                max: self.max_channel_expiry_blocks,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl OrderState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderState::Created => "CREATED",
+            OrderState::Completed => "COMPLETED",
+            OrderState::Failed => "FAILED",
+        }
+    }
+}
+
+impl PaymentState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentState::ExpectPayment => "EXPECT_PAYMENT",
+            PaymentState::Hold => "HOLD",
+            PaymentState::Paid => "PAID",
+            PaymentState::Refunded => "REFUNDED",
+        }
+    }
+}
+
+impl Lsps1Order {
+    /// The LSP has funded the channel
+    pub fn is_channel_open(&self) -> bool {
+        self.order_state == OrderState::Completed && self.channel.is_some()
+    }
+}
+
+/// Carries JSON-RPC calls to an LSP
+#[async_trait::async_trait]
+pub trait Lsps1Transport: Send + Sync + std::fmt::Debug {
+    /// Call `method` and return its `result`; JSON-RPC errors come back as
+    /// [`Lsps1Error::Rejected`]
+    async fn call(&self, method: &str, params: Value) -> Result<Value>;
+}
+
+/// JSON-RPC over HTTP POST
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl HttpTransport {
+    pub fn new(client: reqwest::Client, url: impl Into<String>, token: Option<String>) -> Self {
+        Self {
+            client,
+            url: url.into(),
+            token,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+#[async_trait::async_trait]
+impl Lsps1Transport for HttpTransport {
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": uuid::Uuid::new_v4().to_string(),
+            "method": method,
+            "params": params,
+        });
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response: RpcResponse = request.send().await?.json().await?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(Lsps1Error::Rejected {
+                code: error.code,
+                message: error.message,
+            }
+            .into()),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(anyhow::anyhow!(
+                "LSP response to {} has neither result nor error",
+                method
+            )),
+        }
+    }
+}
+
+/// Typed LSPS1 calls over a transport
+#[derive(Debug, Clone)]
+pub struct Lsps1Client {
+    transport: Arc<dyn Lsps1Transport>,
+}
+
+impl Lsps1Client {
+    pub fn new(transport: Arc<dyn Lsps1Transport>) -> Self {
+        Self { transport }
+    }
+
+    /// The LSP's order limits
+    pub async fn get_info(&self) -> Result<Lsps1Options> {
+        #[derive(Deserialize)]
+        struct GetInfo {
+            options: Lsps1Options,
+        }
+
+        let info: GetInfo =
+            serde_json::from_value(self.transport.call(GET_INFO, json!({})).await?)?;
+        Ok(info.options)
+    }
+
+    /// Place an order. `public_key` tells the LSP which node to open the
+    /// channel to, since an HTTP request does not come from the node itself.
+    pub async fn create_order(
+        &self,
+        params: &OrderParams,
+        public_key: Option<&str>,
+    ) -> Result<Lsps1Order> {
+        let mut request = serde_json::to_value(params)?;
+        if let (Some(public_key), Some(object)) = (public_key, request.as_object_mut()) {
+            object.insert("public_key".to_string(), json!(public_key));
+        }
+        Ok(serde_json::from_value(
+            self.transport.call(CREATE_ORDER, request).await?,
+        )?)
+    }
+
+    pub async fn get_order(&self, order_id: &str) -> Result<Lsps1Order> {
+        Ok(serde_json::from_value(
+            self.transport
+                .call(GET_ORDER, json!({ "order_id": order_id }))
+                .await?,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> Lsps1Options {
+        Lsps1Options {
+            min_required_channel_confirmations: 0,
+            min_funding_confirms_within_blocks: 6,
+            supports_zero_channel_reserve: false,
+            max_channel_expiry_blocks: 20_160,
+            min_initial_client_balance_sat: 0,
+            max_initial_client_balance_sat: 100_000,
+            min_initial_lsp_balance_sat: 100_000,
+            max_initial_lsp_balance_sat: 1_000_000,
+            min_channel_balance_sat: 100_000,
+            max_channel_balance_sat: 1_000_000,
+        }
+    }
+
+    fn params(lsp_balance_sat: u64, client_balance_sat: u64) -> OrderParams {
+        OrderParams {
+            lsp_balance_sat,
+            client_balance_sat,
+            required_channel_confirmations: 0,
+            funding_confirms_within_blocks: 6,
+            channel_expiry_blocks: 4_032,
+            token: None,
+            refund_onchain_address: None,
+            announce_channel: false,
+        }
+    }
+
+    #[test]
+    fn test_options_check() {
+        let options = options();
+        assert!(options.check(&params(500_000, 0)).is_ok());
+        assert!(matches!(
+            options.check(&params(50_000, 0)),
+            Err(Lsps1Error::OutOfRange {
+                field: "lsp_balance_sat",
+                ..
+            })
+        ));
+        assert!(matches!(
+            options.check(&params(1_000_000, 50_000)),
+            Err(Lsps1Error::OutOfRange {
+                field: "channel balance",
+                ..
+            })
+        ));
+
+        let mut long = params(500_000, 0);
+        long.channel_expiry_blocks = 50_000;
+        assert!(matches!(
+            options.check(&long),
+            Err(Lsps1Error::ExpiryTooLong { max: 20_160, .. })
+        ));
+    }
+
+    #[test]
+    fn test_order_wire_format() {
+        let order: Lsps1Order = serde_json::from_value(json!({
+            "order_id": "bb4b5d0a-8334-49d8-9463-90a6d413af7c",
+            "lsp_balance_sat": "5000000",
+            "client_balance_sat": "2000000",
+            "required_channel_confirmations": 0,
+            "funding_confirms_within_blocks": 1,
+            "channel_expiry_blocks": 12,
+            "token": "",
+            "created_at": "2012-04-23T18:25:43.511Z",
+            "announce_channel": true,
+            "order_state": "CREATED",
+            "payment": {
+                "bolt11": {
+                    "state": "EXPECT_PAYMENT",
+                    "expires_at": "2025-01-01T00:00:00Z",
+                    "fee_total_sat": "8888",
+                    "order_total_sat": "2008888",
+                    "invoice": "lnbc252u1p3aht9ysp580g4633gd2x9lc5al0wd8wx0mpn9748jeyz46kqjrpxn52uhfpjqpp5qgf67tcqmuqehzgjm8mzya90h73deafvr4m5705l5u5l4r05l8cqdpud3h8ymm4w3jhytnpwpczqmt0de6xsmre2pkxzm3qydmkzdjrdev9s7zhgfaqxqyjw5qcqpjrzjqt6xptnd85lpqnu2lefq4cx070v5cdwzh2xlvmdgnu7gqp4zvkus5zapryqqx9qqqyqqqqqqqqqqqcsq9q9qyysgqen77vu8xqjelum24hgjpgfdgfgx4q0nehhalcmuggt32japhjuksq9jv6eksjfnppm4hrzsgyxt8y8xacxut9qv3fpyetz8t7tsymygq8yzn05"
+                }
+            },
+            "channel": null
+        }))
+        .unwrap();
+
+        assert_eq!(order.params.lsp_balance_sat, 5_000_000);
+        assert_eq!(order.order_state, OrderState::Created);
+        assert_eq!(order.payment.bolt11.as_ref().unwrap().fee_total_sat, 8_888);
+        assert!(order.payment.onchain.is_none());
+        assert!(!order.is_channel_open());
+
+        let value = serde_json::to_value(&order).unwrap();
+        assert_eq!(value["client_balance_sat"], "2000000");
+        assert_eq!(value["payment"]["bolt11"]["order_total_sat"], "2008888");
+    }
+}
@@ -0,0 +1,211 @@
+//! LSPS2 (bLIP-52) fee quotes for just-in-time channels.
+//!
+//! An LSP that supports LSPS2 opens a channel to us when a payment for one of
+//! our invoices reaches it, and deducts its opening fee from that payment. The
+//! fee terms come from `lsps2.get_info`, a menu of opening fee parameters,
+//! called over the same JSON-RPC transport as LSPS1. Millisatoshi amounts
+//! travel as decimal strings.
+
+use crate::lsp::lsps1::Lsps1Transport;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+
+pub const GET_INFO: &str = "lsps2.get_info";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Lsps2Error {
+    #[error("No LSP is configured for just-in-time channels")]
+    NoProvider,
+    #[error("Invoices received through a just-in-time channel need an amount")]
+    AmountRequired,
+    #[error("The LSP offers no just-in-time channel for a payment of {amount_msat} msat")]
+    NoOffer { amount_msat: u64 },
+    #[error("Receiving needs a new channel; the LSP fee of {fee_sat} sats exceeds the accepted {max_fee_sat} sats")]
+    FeeNotAccepted { fee_sat: u64, max_fee_sat: u64 },
+}
+
+/// One entry of the LSP's `opening_fee_params_menu`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpeningFeeParams {
+    #[serde(with = "crate::lsp::lsps1::sats")]
+    pub min_fee_msat: u64,
+    /// Parts per million of the payment
+    pub proportional: u32,
+    /// RFC 3339 time after which the LSP no longer honours these terms
+    pub valid_until: String,
+    /// Blocks the LSP keeps the channel open at least
+    pub min_lifetime: u32,
+    pub max_client_to_self_delay: u32,
+    #[serde(with = "crate::lsp::lsps1::sats")]
+    pub min_payment_size_msat: u64,
+    #[serde(with = "crate::lsp::lsps1::sats")]
+    pub max_payment_size_msat: u64,
+    /// LSP signature over the terms, handed back when buying
+    pub promise: String,
+}
+
+impl OpeningFeeParams {
+    /// Fee for opening a channel to receive `payment_size_msat`, rounded up
+    /// as the spec requires; `None` if the computation overflows
+    pub fn opening_fee_msat(&self, payment_size_msat: u64) -> Option<u64> {
+        let proportional_fee = payment_size_msat
+            .checked_mul(u64::from(self.proportional))?
+            .checked_add(999_999)?
+            / 1_000_000;
+        Some(proportional_fee.max(self.min_fee_msat))
+    }
+
+    /// Whether the terms are still valid at `now`; unreadable times never are
+    pub fn is_valid_at(&self, now: DateTime<Utc>) -> bool {
+        DateTime::parse_from_rfc3339(&self.valid_until).is_ok_and(|until| until > now)
+    }
+
+    /// Fee for a payment of `payment_size_msat`, if these terms accept it and
+    /// leave something for us after the fee
+    pub fn fee_for(&self, payment_size_msat: u64, now: DateTime<Utc>) -> Option<u64> {
+        if !self.is_valid_at(now)
+            || payment_size_msat < self.min_payment_size_msat
+            || payment_size_msat > self.max_payment_size_msat
+        {
+            return None;
+        }
+        self.opening_fee_msat(payment_size_msat)
+            .filter(|fee_msat| *fee_msat < payment_size_msat)
+    }
+}
+
+/// The cheapest terms in `menu` for a payment of `payment_size_msat`, with their fee
+pub fn cheapest(
+    menu: &[OpeningFeeParams],
+    payment_size_msat: u64,
+    now: DateTime<Utc>,
+) -> Option<(&OpeningFeeParams, u64)> {
+    menu.iter()
+        .filter_map(|params| {
+            params
+                .fee_for(payment_size_msat, now)
+                .map(|fee_msat| (params, fee_msat))
+        })
+        .min_by_key(|(_, fee_msat)| *fee_msat)
+}
+
+/// Typed LSPS2 calls over a transport
+#[derive(Debug, Clone)]
+pub struct Lsps2Client {
+    transport: Arc<dyn Lsps1Transport>,
+}
+
+impl Lsps2Client {
+    pub fn new(transport: Arc<dyn Lsps1Transport>) -> Self {
+        Self { transport }
+    }
+
+    /// The LSP's current opening fee terms
+    pub async fn get_info(&self, token: Option<&str>) -> Result<Vec<OpeningFeeParams>> {
+        #[derive(Deserialize)]
+        struct GetInfo {
+            opening_fee_params_menu: Vec<OpeningFeeParams>,
+        }
+
+        let params = match token {
+            Some(token) => json!({ "token": token }),
+            None => json!({}),
+        };
+        let info: GetInfo = serde_json::from_value(self.transport.call(GET_INFO, params).await?)?;
+        Ok(info.opening_fee_params_menu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(min_fee_msat: u64, proportional: u32, valid_until: &str) -> OpeningFeeParams {
+        OpeningFeeParams {
+            min_fee_msat,
+            proportional,
+            valid_until: valid_until.to_string(),
+            min_lifetime: 1_008,
+            max_client_to_self_delay: 2_016,
+            min_payment_size_msat: 1_000,
+            max_payment_size_msat: 100_000_000,
+            promise: "promise".to_string(),
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2025-01-01T00:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn test_opening_fee() {
+        let terms = params(2_000_000, 10_000, "2025-01-02T00:00:00Z");
+        // 1% of 500k sats beats the minimum
+        assert_eq!(terms.opening_fee_msat(500_000_000), Some(5_000_000));
+        // Rounded up
+        assert_eq!(terms.opening_fee_msat(300_000_001), Some(3_000_001));
+        // The minimum applies to small payments
+        assert_eq!(terms.opening_fee_msat(10_000_000), Some(2_000_000));
+        assert_eq!(terms.opening_fee_msat(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_fee_for_checks_terms() {
+        let terms = params(2_000_000, 10_000, "2025-01-02T00:00:00Z");
+        assert_eq!(terms.fee_for(50_000_000, now()), Some(2_000_000));
+        // Above the maximum payment size
+        assert_eq!(terms.fee_for(200_000_000, now()), None);
+        // The fee would eat the whole payment
+        assert_eq!(terms.fee_for(2_000_000, now()), None);
+
+        let expired = params(2_000_000, 10_000, "2024-12-31T23:59:59Z");
+        assert_eq!(expired.fee_for(50_000_000, now()), None);
+        let unreadable = params(2_000_000, 10_000, "tomorrow");
+        assert!(!unreadable.is_valid_at(now()));
+    }
+
+    #[test]
+    fn test_cheapest() {
+        let menu = vec![
+            params(2_000_000, 5_000, "2025-01-02T00:00:00Z"),
+            params(1_000_000, 25_000, "2025-01-02T00:00:00Z"),
+            params(0, 1_000, "2024-12-01T00:00:00Z"),
+        ];
+
+        // Small payments are cheaper with the lower minimum
+        let (terms, fee_msat) = cheapest(&menu, 30_000_000, now()).unwrap();
+        assert_eq!(fee_msat, 1_000_000);
+        assert_eq!(terms.min_fee_msat, 1_000_000);
+
+        // Large ones with the lower rate; the expired cheapest entry is ignored
+        let (terms, fee_msat) = cheapest(&menu, 100_000_000, now()).unwrap();
+        assert_eq!(fee_msat, 2_000_000);
+        assert_eq!(terms.proportional, 5_000);
+
+        assert!(cheapest(&menu, 500, now()).is_none());
+    }
+
+    #[test]
+    fn test_get_info_wire_format() {
+        let terms: OpeningFeeParams = serde_json::from_value(json!({
+            "min_fee_msat": "546000",
+            "proportional": 1200,
+            "valid_until": "2023-02-23T08:47:30.511Z",
+            "min_lifetime": 1008,
+            "max_client_to_self_delay": 2016,
+            "min_payment_size_msat": "1000",
+            "max_payment_size_msat": "1000000",
+            "promise": "abcdefghijklmnopqrstuvwxyz"
+        }))
+        .unwrap();
+        assert_eq!(terms.min_fee_msat, 546_000);
+        assert_eq!(terms.max_payment_size_msat, 1_000_000);
+
+        let value = serde_json::to_value(&terms).unwrap();
+        assert_eq!(value["min_fee_msat"], "546000");
+        assert_eq!(value["proportional"], 1200);
+    }
+}
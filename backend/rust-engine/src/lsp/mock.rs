@@ -0,0 +1,272 @@
+//! A minimal LSPS1 and LSPS2 service for tests and local development.
+//!
+//! It serves `lsps0.list_protocols`, `lsps1.*` and `lsps2.get_info` JSON-RPC
+//! calls over HTTP on a loopback port and keeps orders in memory. Payments and
+//! channel funding do not happen by themselves: callers report them with
+//! [`MockLsp::receive_payment`] and [`MockLsp::open_channel`], the way a real
+//! LSP would observe them.
+
+use crate::lsp::lsps0::{self, LspProtocol};
+use crate::lsp::lsps1::{
+    Bolt11Payment, ChannelInfo, Lsps1Options, Lsps1Order, OnchainPayment, OrderParams, OrderState,
+    PaymentInfo, PaymentState, CREATE_ORDER, GET_INFO, GET_ORDER,
+};
+use crate::lsp::lsps2::{self, OpeningFeeParams};
+use anyhow::Result;
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// JSON-RPC invalid params
+const INVALID_PARAMS: i64 = -32602;
+/// JSON-RPC method not found
+const METHOD_NOT_FOUND: i64 = -32601;
+/// LSPS1 order parameters outside the advertised options
+const OPTION_MISMATCH: i64 = 100;
+
+/// Regtest address the mock asks on-chain payments to go to
+pub const MOCK_ONCHAIN_ADDRESS: &str = "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw";
+
+#[derive(Debug)]
+struct MockState {
+    options: Lsps1Options,
+    fee_sat: u64,
+    /// Added to order totals on top of the fee and client balance
+    order_total_surcharge_sat: u64,
+    /// Reported by `lsps0.list_protocols`
+    protocols: Vec<LspProtocol>,
+    /// LSPS2 terms, offered until changed
+    opening_fee_params_menu: Vec<OpeningFeeParams>,
+    orders: HashMap<String, Lsps1Order>,
+    /// Node each order's channel is for
+    public_keys: HashMap<String, Option<String>>,
+}
+
+/// Handle on a running mock LSP; the server stops with the runtime
+#[derive(Debug, Clone)]
+pub struct MockLsp {
+    url: String,
+    state: Arc<RwLock<MockState>>,
+}
+
+impl MockLsp {
+    /// Serve on a free loopback port, charging `fee_sat` per order
+    pub async fn start(options: Lsps1Options, fee_sat: u64) -> Result<Self> {
+        let state = Arc::new(RwLock::new(MockState {
+            options,
+            fee_sat,
+            order_total_surcharge_sat: 0,
+            protocols: vec![LspProtocol::Lsps1, LspProtocol::Lsps2],
+            opening_fee_params_menu: Vec::new(),
+            orders: HashMap::new(),
+            public_keys: HashMap::new(),
+        }));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let app = Router::new()
+            .route("/", post(handle))
+            .with_state(Arc::clone(&state));
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("Mock LSP stopped: {}", e);
+            }
+        });
+
+        Ok(Self { url, state })
+    }
+
+    /// Endpoint to configure as the LSP's LSPS1 URL
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Report only `protocols` as supported
+    pub async fn set_protocols(&self, protocols: Vec<LspProtocol>) {
+        self.state.write().await.protocols = protocols;
+    }
+
+    /// Ask `surcharge_sat` more in order totals than the fee and client
+    /// balance add up to, as a misbehaving LSP would
+    pub async fn set_order_total_surcharge(&self, surcharge_sat: u64) {
+        self.state.write().await.order_total_surcharge_sat = surcharge_sat;
+    }
+
+    /// Offer `menu` from `lsps2.get_info`
+    pub async fn set_opening_fee_params(&self, menu: Vec<OpeningFeeParams>) {
+        self.state.write().await.opening_fee_params_menu = menu;
+    }
+
+    pub async fn order(&self, order_id: &str) -> Option<Lsps1Order> {
+        self.state.read().await.orders.get(order_id).cloned()
+    }
+
+    /// Node id the client asked the channel for `order_id` to be opened to
+    pub async fn public_key(&self, order_id: &str) -> Option<String> {
+        self.state
+            .read()
+            .await
+            .public_keys
+            .get(order_id)
+            .cloned()
+            .flatten()
+    }
+
+    /// Mark the unpaid order whose invoice or address was paid as paid; returns
+    /// the order id. Orders share one address, so on-chain payments settle the
+    /// oldest order expecting one.
+    pub async fn receive_payment(&self, invoice_or_address: &str) -> Option<String> {
+        let mut state = self.state.write().await;
+        let order = state
+            .orders
+            .values_mut()
+            .filter(|order| {
+                let bolt11 = order.payment.bolt11.as_ref().filter(|p| {
+                    p.state == PaymentState::ExpectPayment && p.invoice == invoice_or_address
+                });
+                let onchain = order.payment.onchain.as_ref().filter(|p| {
+                    p.state == PaymentState::ExpectPayment && p.address == invoice_or_address
+                });
+                order.order_state == OrderState::Created && (bolt11.is_some() || onchain.is_some())
+            })
+            .min_by(|a, b| a.created_at.cmp(&b.created_at))?;
+
+        if let Some(bolt11) = &mut order.payment.bolt11 {
+            bolt11.state = PaymentState::Paid;
+        }
+        if let Some(onchain) = &mut order.payment.onchain {
+            onchain.state = PaymentState::Paid;
+        }
+        Some(order.order_id.clone())
+    }
+
+    /// Fund the channel of a paid order and complete it
+    pub async fn open_channel(&self, order_id: &str, funding_outpoint: &str) -> Result<()> {
+        let mut state = self.state.write().await;
+        let order = state
+            .orders
+            .get_mut(order_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown order {}", order_id))?;
+        let now = Utc::now();
+        order.order_state = OrderState::Completed;
+        order.channel = Some(ChannelInfo {
+            funded_at: now.to_rfc3339(),
+            funding_outpoint: funding_outpoint.to_string(),
+            // Roughly ten minutes per block
+            expires_at: (now
+                + chrono::Duration::minutes(10 * i64::from(order.params.channel_expiry_blocks)))
+            .to_rfc3339(),
+        });
+        Ok(())
+    }
+
+    /// Give up on an order, refunding whatever was paid
+    pub async fn fail_order(&self, order_id: &str) -> Result<()> {
+        let mut state = self.state.write().await;
+        let order = state
+            .orders
+            .get_mut(order_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown order {}", order_id))?;
+        order.order_state = OrderState::Failed;
+        for payment_state in [
+            order.payment.bolt11.as_mut().map(|p| &mut p.state),
+            order.payment.onchain.as_mut().map(|p| &mut p.state),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if *payment_state != PaymentState::ExpectPayment {
+                *payment_state = PaymentState::Refunded;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn handle(
+    State(state): State<Arc<RwLock<MockState>>>,
+    Json(request): Json<Value>,
+) -> Json<Value> {
+    let id = request["id"].clone();
+    let params = request["params"].clone();
+    let result = {
+        let mut state = state.write().await;
+        match request["method"].as_str().unwrap_or_default() {
+            lsps0::LIST_PROTOCOLS => Ok(json!({ "protocols": state.protocols })),
+            GET_INFO => Ok(json!({ "options": state.options })),
+            CREATE_ORDER => state.create_order(params),
+            GET_ORDER => state.get_order(&params),
+            lsps2::GET_INFO => Ok(json!({
+                "opening_fee_params_menu": state.opening_fee_params_menu,
+            })),
+            method => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        }
+    };
+
+    Json(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    })
+}
+
+impl MockState {
+    fn create_order(&mut self, params: Value) -> Result<Value, (i64, String)> {
+        let public_key = params["public_key"].as_str().map(str::to_string);
+        let params: OrderParams =
+            serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+        self.options
+            .check(&params)
+            .map_err(|e| (OPTION_MISMATCH, e.to_string()))?;
+
+        let order_id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let expires_at = (now + chrono::Duration::hours(1)).to_rfc3339();
+        let order_total_sat =
+            self.fee_sat + params.client_balance_sat + self.order_total_surcharge_sat;
+        let order = Lsps1Order {
+            order_id: order_id.clone(),
+            params,
+            created_at: now.to_rfc3339(),
+            order_state: OrderState::Created,
+            payment: PaymentInfo {
+                bolt11: Some(Bolt11Payment {
+                    state: PaymentState::ExpectPayment,
+                    expires_at: expires_at.clone(),
+                    fee_total_sat: self.fee_sat,
+                    order_total_sat,
+                    invoice: format!("lnbcrt-mock-{}", order_id),
+                }),
+                onchain: Some(OnchainPayment {
+                    state: PaymentState::ExpectPayment,
+                    expires_at,
+                    fee_total_sat: self.fee_sat,
+                    order_total_sat,
+                    address: MOCK_ONCHAIN_ADDRESS.to_string(),
+                    min_onchain_payment_confirmations: Some(0),
+                }),
+            },
+            channel: None,
+        };
+
+        self.public_keys.insert(order_id.clone(), public_key);
+        self.orders.insert(order_id, order.clone());
+        Ok(json!(order))
+    }
+
+    fn get_order(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let order_id = params["order_id"].as_str().unwrap_or_default();
+        self.orders
+            .get(order_id)
+            .map(|order| json!(order))
+            .ok_or_else(|| (INVALID_PARAMS, format!("Order not found: {}", order_id)))
+    }
+}
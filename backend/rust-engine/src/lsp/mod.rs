@@ -1,5 +1,15 @@
 pub mod lsp_client;
 pub mod lsp_provider;
+pub mod lsps0;
+pub mod lsps1;
+pub mod lsps2;
+pub mod mock;
 
-pub use lsp_client::{LspClient, LspConfig, LspConnection};
-pub use lsp_provider::{LspProvider, LspProviderInfo, LspProviderType};
+pub use lsp_client::{
+    JitChannelQuote, JitInvoice, LSPChannelRequest, LSPClient, LSPConfig, LSPPaymentMethod,
+    LSPStats, LspNode,
+};
+pub use lsp_provider::{LspFeeSchedule, LspHealth, LspProviderInfo, LspRegistry};
+pub use lsps0::{LspProtocol, Lsps0Client};
+pub use lsps1::{Lsps1Client, Lsps1Error, Lsps1Options, Lsps1Order};
+pub use lsps2::{Lsps2Client, Lsps2Error, OpeningFeeParams};
@@ -1,52 +1,488 @@
-use anyhow::Result;
-use satsconnect_rust_engine::proto::satsconnect::payment::v1::payment_service_server::PaymentServiceServer;
-use satsconnect_rust_engine::proto::satsconnect::wallet::v1::wallet_service_server::WalletServiceServer;
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use satsconnect_rust_engine::engine_config::{EngineArgs, EngineConfig};
+use satsconnect_rust_engine::lightning::{
+    ChannelManager, ChannelRebalancer, GossipNode, GossipSync, LiquidityManager, LiquidityNode,
+    PeerManager, PeerUri, RebalanceNode,
+};
+use satsconnect_rust_engine::lightning_engine::LightningEngine;
+use satsconnect_rust_engine::lsp::{LSPClient, LSPConfig, LspNode, LspRegistry};
+use satsconnect_rust_engine::monitoring::{
+    GossipHealthCheck, HealthChecker, LdkNodeHealthCheck, MetricsCollector,
+};
+use satsconnect_rust_engine::proto::satsconnect::channel::v1::channel_service_server::{
+    self, ChannelServiceServer,
+};
+use satsconnect_rust_engine::proto::satsconnect::payment::v1::payment_service_server::{
+    self, PaymentServiceServer,
+};
+use satsconnect_rust_engine::proto::satsconnect::peer::v1::peer_service_server::{
+    self, PeerServiceServer,
+};
+use satsconnect_rust_engine::proto::satsconnect::wallet::v1::wallet_service_server::{
+    self, WalletServiceServer,
+};
+use satsconnect_rust_engine::proto::FILE_DESCRIPTOR_SET;
+use satsconnect_rust_engine::rate_limit::RateLimiter;
+use satsconnect_rust_engine::security::authentication::AuthConfig;
+use satsconnect_rust_engine::security::scoped_token::{self, load_or_create_root_key};
+use satsconnect_rust_engine::security::AuthenticationService;
+use satsconnect_rust_engine::shutdown::{self, Shutdown};
+use satsconnect_rust_engine::store::Store;
+use satsconnect_rust_engine::tls::TlsAcceptor;
 use satsconnect_rust_engine::{payment::PaymentHandler, wallet::WalletHandler};
+use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
 use tonic::transport::Server;
+use tonic_health::ServingStatus;
+use tonic_web::GrpcWebLayer;
 
+mod grpc_auth;
+mod grpc_health;
+mod grpc_rate_limit;
 mod grpc_services;
+mod http_gateway;
+
+use grpc_auth::{AuthLayer, Authenticator};
+use grpc_health::ServiceHealth;
+use grpc_rate_limit::RateLimitLayer;
+
+/// Delay between attempts to start the LDK node
+const NODE_START_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Run a server in the background; if it stops before shutdown was requested,
+/// that is a failure and takes the rest of the engine down with it
+fn spawn_server<F>(name: &'static str, server: F, shutdown: Shutdown) -> JoinHandle<Result<()>>
+where
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let result = server.await;
+        if shutdown.is_triggered() {
+            return result;
+        }
+        shutdown.trigger();
+        Err(match result {
+            Ok(()) => anyhow!("{} server stopped unexpectedly", name),
+            Err(e) => e.context(format!("{} server failed", name)),
+        })
+    })
+}
+
+/// Start shutdown on SIGINT or SIGTERM; a second signal exits immediately
+fn spawn_signal_handler(shutdown: Shutdown) {
+    tokio::spawn(async move {
+        let signal = shutdown::signal().await;
+        println!("🛑 Received {}, shutting down...", signal);
+        shutdown.trigger();
+
+        let signal = shutdown::signal().await;
+        eprintln!("Received {} again, exiting without draining", signal);
+        std::process::exit(1);
+    });
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let wallet_handler = Arc::new(WalletHandler::new()?);
-    let payment_handler = Arc::new(PaymentHandler::new()?);
+    let args = EngineArgs::parse();
+    let config = EngineConfig::load(&args)?;
+
+    println!("⚙️  Effective configuration:\n{}", config);
+    if args.print_config {
+        return Ok(());
+    }
+
+    let shutdown = Shutdown::new();
+    spawn_signal_handler(shutdown.clone());
+
+    let mut lightning_engine = LightningEngine::with_config(config.lightning.clone());
+    if let Some(lsp) = config.jit_lsp() {
+        let uri: PeerUri = format!("{}@{}", lsp.node_id, lsp.address).parse()?;
+        lightning_engine =
+            lightning_engine.with_lsps2_source(uri.node_id, uri.address, lsp.token.clone());
+    }
+    let lightning_engine = Arc::new(lightning_engine);
+    // One store for every service, checkpointed once on shutdown
+    let store = Store::open_in_dir(&config.lightning.data_dir)?;
+    store.bind_network(config.lightning.network)?;
+    let payment_handler = Arc::new(PaymentHandler::with_store(
+        Arc::clone(&lightning_engine),
+        store.clone(),
+        config.server.lnurl_base_url.clone(),
+    )?);
+    let channel_manager = Arc::new(ChannelManager::with_store(
+        config.channels.clone(),
+        store.clone(),
+        Arc::clone(&lightning_engine),
+    ));
+    let rebalancer = Arc::new(ChannelRebalancer::new(
+        config.liquidity.rebalance.clone(),
+        Arc::clone(&lightning_engine) as Arc<dyn RebalanceNode>,
+    ));
+    let lsp_peers = config.lsps.iter().filter_map(|lsp| {
+        format!("{}@{}", lsp.node_id, lsp.address)
+            .parse::<PeerUri>()
+            .ok()
+    });
+    let liquidity_manager = Arc::new(
+        LiquidityManager::new(
+            config.liquidity.clone(),
+            store.clone(),
+            Arc::clone(&channel_manager) as Arc<dyn LiquidityNode>,
+            rebalancer,
+        )
+        .with_lsp_peers(lsp_peers),
+    );
+    // Background loops that still use the store; shutdown waits for them
+    // before flushing it and stopping the node
+    let mut workers: Vec<(&'static str, JoinHandle<()>)> = Vec::new();
+    // Reports imbalanced channels and opens new ones on its own, if enabled
+    workers.push((
+        "liquidity manager",
+        tokio::spawn(liquidity_manager.run(shutdown.clone())),
+    ));
+    let peer_manager = Arc::new(PeerManager::with_store(
+        config.peers.clone(),
+        store.clone(),
+        Arc::clone(&lightning_engine),
+    ));
+    // Keeps persisted peers connected once the node is up
+    workers.push((
+        "peer manager",
+        tokio::spawn(Arc::clone(&peer_manager).run(shutdown.clone())),
+    ));
+
+    let mut lsp_client = LSPClient::new(
+        LSPConfig::default(),
+        store.clone(),
+        Arc::clone(&lightning_engine) as Arc<dyn LspNode>,
+    )
+    .with_registry(LspRegistry::from_config(&config.lsps));
+    // The same LSP the node was given as its LSPS2 source above
+    if let Some(lsp) = config.jit_lsp() {
+        lsp_client = lsp_client.with_jit_provider(lsp.name.clone());
+    }
+    let lsp_client = Arc::new(lsp_client);
+    // Follows LSPS1 orders until the LSP has opened their channels, and
+    // rediscovers the LSPs' protocols and fees
+    workers.push((
+        "LSP client",
+        tokio::spawn(Arc::clone(&lsp_client).run(shutdown.clone())),
+    ));
+
+    let wallet_handler = Arc::new(
+        WalletHandler::with_store(Arc::clone(&lightning_engine), store.clone())?
+            .with_lsp_client(Arc::clone(&lsp_client)),
+    );
+
+    // Start the node in the background; health reports NOT_SERVING until it
+    // is up and synced
+    let engine = Arc::clone(&lightning_engine);
+    let payments = Arc::clone(&payment_handler);
+    let node_shutdown = shutdown.clone();
+    let mut node_start = tokio::spawn(async move {
+        while let Err(e) = engine.initialize().await {
+            tracing::error!(
+                "Failed to start LDK node, retrying in {:?}: {:#}",
+                NODE_START_RETRY_INTERVAL,
+                e
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(NODE_START_RETRY_INTERVAL) => {}
+                _ = node_shutdown.triggered() => return,
+            }
+        }
+        // Payments a crash left PENDING are settled once the node is up
+        if let Err(e) = payments.reconcile_pending().await {
+            tracing::error!("Failed to reconcile pending payments: {:#}", e);
+        }
+    });
 
     println!("🚀 SatsConnect Rust Engine starting...");
     println!("⚡ Real Lightning Engine initialized with LDK-node");
 
-    // Create gRPC services
-    let wallet_service =
-        WalletServiceServer::new(grpc_services::WalletServiceImpl::new(wallet_handler));
-    let payment_service =
-        PaymentServiceServer::new(grpc_services::PaymentServiceImpl::new(payment_handler));
+    // Create gRPC services, shared with the HTTP/JSON gateway
+    let wallet_impl = Arc::new(grpc_services::WalletServiceImpl::new(Arc::clone(
+        &wallet_handler,
+    )));
+    let payment_impl = Arc::new(grpc_services::PaymentServiceImpl::new(Arc::clone(
+        &payment_handler,
+    )));
+    let channel_impl = Arc::new(grpc_services::ChannelServiceImpl::new(channel_manager));
+    let peer_impl = Arc::new(grpc_services::PeerServiceImpl::new(peer_manager));
+    let wallet_service = WalletServiceServer::from_arc(Arc::clone(&wallet_impl));
+    let payment_service = PaymentServiceServer::from_arc(Arc::clone(&payment_impl));
+    let channel_service = ChannelServiceServer::from_arc(Arc::clone(&channel_impl));
+    let peer_service = PeerServiceServer::from_arc(Arc::clone(&peer_impl));
+
+    // Standard health checking, driven by the health checker
+    let health_checker = Arc::new(HealthChecker::new());
+    let ldk_node_check = LdkNodeHealthCheck::new(Arc::clone(&lightning_engine));
+    health_checker.add_check(Box::new(ldk_node_check)).await;
+    if config.lightning.uses_rgs() {
+        // The node syncs its graph from the server; this records when it
+        // last did and reports how fresh the graph is
+        let gossip = Arc::new(GossipSync::new(
+            &config.lightning,
+            store.clone(),
+            Arc::clone(&lightning_engine) as Arc<dyn GossipNode>,
+        ));
+        workers.push((
+            "gossip sync",
+            tokio::spawn(Arc::clone(&gossip).run(shutdown.clone())),
+        ));
+        health_checker
+            .add_check(Box::new(GossipHealthCheck::new(gossip)))
+            .await;
+    }
+
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_service_status("", ServingStatus::NotServing)
+        .await;
+    let monitored = vec![
+        ServiceHealth {
+            service: wallet_service_server::SERVICE_NAME,
+            checks: &["ldk_node"],
+        },
+        ServiceHealth {
+            service: payment_service_server::SERVICE_NAME,
+            checks: &["ldk_node"],
+        },
+        ServiceHealth {
+            service: channel_service_server::SERVICE_NAME,
+            checks: &["ldk_node"],
+        },
+        ServiceHealth {
+            service: peer_service_server::SERVICE_NAME,
+            checks: &["ldk_node"],
+        },
+    ];
+    for service in &monitored {
+        health_reporter
+            .set_service_status(service.service, ServingStatus::NotServing)
+            .await;
+    }
+    tokio::spawn(grpc_health::report_health(
+        health_checker,
+        health_reporter,
+        monitored,
+        shutdown.clone(),
+    ));
+
+    // Server reflection for grpcurl and other tooling
+    let reflection_v1 = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build_v1()?;
+    let reflection_v1alpha = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build_v1alpha()?;
 
+    // Names only; reflection lists their methods
     println!("🔗 gRPC Services:");
-    println!("  WalletService - CreateWallet, GetBalance");
-    println!("  PaymentService - NewInvoice, SendPayment, BuyAirtime");
-    println!("🌐 gRPC Server starting on http://127.0.0.1:50051");
+    for service in [
+        wallet_service_server::SERVICE_NAME,
+        payment_service_server::SERVICE_NAME,
+        channel_service_server::SERVICE_NAME,
+        peer_service_server::SERVICE_NAME,
+        "grpc.health.v1.Health",
+        "grpc.reflection.v1.ServerReflection",
+        "grpc.reflection.v1alpha.ServerReflection",
+    ] {
+        println!("  {}", service);
+    }
+
+    let authenticator = if config.server.auth.enabled {
+        let root_key_path = config.server.auth.root_key_path(&config.lightning.data_dir);
+        let (root_key, created) = load_or_create_root_key(&root_key_path)?;
+        let auth = AuthenticationService::with_root_key(AuthConfig::default(), root_key);
+        if created {
+            let admin_token_path = root_key_path.with_file_name("admin.token");
+            scoped_token::save_token(&admin_token_path, &auth.mint_token(Vec::new()))?;
+            println!("🔑 Wrote admin API token to {}", admin_token_path.display());
+        }
+        Authenticator::new(Arc::new(auth))
+    } else {
+        println!("⚠️  API authentication is disabled, anyone who can connect has full access");
+        Authenticator::disabled()
+    };
+
+    let metrics = Arc::new(MetricsCollector::new());
+    let limiter =
+        Arc::new(RateLimiter::new(config.server.rate_limit.clone()).with_metrics(metrics));
+    if !config.server.rate_limit.enabled {
+        println!("⚠️  API rate limiting is disabled");
+    }
 
-    let addr = "127.0.0.1:50051".parse()?;
-    println!("🌐 Starting gRPC server on {}", addr);
+    let cors = http_gateway::cors_layer(&config.server.cors_allowed_origins)?;
 
-    let server = Server::builder()
+    let http_addr = config.server.http_listen_addr;
+    let http_listener = tokio::net::TcpListener::bind(http_addr).await?;
+    let http_router = http_gateway::router(
+        wallet_impl,
+        payment_impl,
+        channel_impl,
+        peer_impl,
+        authenticator.clone(),
+        Arc::clone(&limiter),
+    )
+    .layer(cors.clone());
+    let http_server = axum::serve(
+        http_listener,
+        http_router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown.triggered());
+    let mut http_task = spawn_server(
+        "HTTP",
+        async move { Ok(http_server.await?) },
+        shutdown.clone(),
+    );
+    println!(
+        "🌐 Serving HTTP/JSON API on {} (OpenAPI at /openapi.json)",
+        http_addr
+    );
+
+    let addr = config.server.listen_addr;
+    let tls = if config.server.tls.enabled {
+        let acceptor = Arc::new(TlsAcceptor::load(
+            &config.server.tls,
+            &config.lightning.data_dir,
+            addr,
+        )?);
+        tokio::spawn(Arc::clone(&acceptor).watch());
+        Some(acceptor)
+    } else {
+        None
+    };
+
+    match &tls {
+        Some(acceptor) => println!(
+            "🔒 Starting gRPC server on {} with TLS{} (certificate {})",
+            addr,
+            if acceptor.requires_client_cert() {
+                " and client certificates"
+            } else {
+                ""
+            },
+            acceptor.files().cert.display()
+        ),
+        None => println!("🌐 Starting gRPC server on {} (plaintext)", addr),
+    }
+
+    // HTTP/1.1 is needed for gRPC-web requests from browsers
+    let router = Server::builder()
+        .accept_http1(true)
+        .layer(cors)
+        .layer(GrpcWebLayer::new())
+        .layer(AuthLayer::new(authenticator))
+        .layer(RateLimitLayer::new(limiter))
+        .add_service(health_service)
+        .add_service(reflection_v1)
+        .add_service(reflection_v1alpha)
         .add_service(wallet_service)
         .add_service(payment_service)
-        .serve(addr);
+        .add_service(channel_service)
+        .add_service(peer_service);
+    // Once shutdown starts the servers stop accepting connections and wait
+    // for the calls in flight on the open ones
+    let grpc_shutdown = shutdown.triggered();
+    let server = async move {
+        match tls {
+            Some(acceptor) => {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                router
+                    .serve_with_incoming_shutdown(acceptor.incoming(listener), grpc_shutdown)
+                    .await?
+            }
+            None => router.serve_with_shutdown(addr, grpc_shutdown).await?,
+        }
+        anyhow::Ok(())
+    };
+    let mut grpc_task = spawn_server("gRPC", server, shutdown.clone());
 
     println!("✅ gRPC server is running! Press Ctrl+C to stop.");
 
-    // Keep the server running
-    tokio::select! {
-        _ = server => {
-            println!("Server stopped");
+    shutdown.triggered().await;
+    let timeout = config.server.shutdown_timeout();
+    let deadline = tokio::time::Instant::now() + timeout;
+    println!(
+        "⏳ Waiting up to {:?} for in-flight calls to finish...",
+        timeout
+    );
+
+    let mut errors = Vec::new();
+    let servers = tokio::time::timeout_at(deadline, async {
+        tokio::join!(&mut grpc_task, &mut http_task)
+    })
+    .await;
+    let mut drained = match servers {
+        Ok((grpc, http)) => {
+            for result in [grpc, http] {
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => errors.push(e),
+                    Err(e) => errors.push(anyhow!("Server task panicked: {}", e)),
+                }
+            }
+            true
         }
-        _ = tokio::signal::ctrl_c() => {
-            println!("Received Ctrl+C, shutting down...");
+        Err(_) => {
+            grpc_task.abort();
+            http_task.abort();
+            false
         }
+    };
+
+    // A node start in progress finishes first so stopping does not race it
+    if tokio::time::timeout_at(deadline, &mut node_start)
+        .await
+        .is_err()
+    {
+        node_start.abort();
+        drained = false;
+    }
+
+    // Background loops finish their current round before the store is
+    // flushed and the node they drive is stopped
+    for (name, mut worker) in workers {
+        match tokio::time::timeout_at(deadline, &mut worker).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => errors.push(anyhow!("The {} task panicked: {}", name, e)),
+            Err(_) => {
+                worker.abort();
+                drained = false;
+            }
+        }
+    }
+
+    println!("💾 Flushing the store and stopping the Lightning node...");
+    if let Err(e) = store.checkpoint() {
+        errors.push(e.context("Failed to flush the store"));
+    }
+    if let Err(e) = lightning_engine.stop().await {
+        errors.push(e.context("Failed to stop Lightning node"));
+    }
+
+    for e in &errors {
+        tracing::error!("{:#}", e);
+    }
+    if !drained {
+        return Err(anyhow!(
+            "In-flight work did not finish within {:?}",
+            timeout
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(anyhow!("Shutdown finished with {} error(s)", errors.len()));
     }
 
+    println!("👋 Shutdown complete");
     Ok(())
 }
@@ -1,3 +1,5 @@
+use crate::lightning::GossipSync;
+use crate::lightning_engine::LightningEngine;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -175,6 +177,108 @@ impl HealthCheckProvider for LightningHealthCheck {
     }
 }
 
+/// Health of the engine's LDK node: unhealthy while the node is starting and
+/// until it has completed its first chain sync
+pub struct LdkNodeHealthCheck {
+    lightning_engine: Arc<LightningEngine>,
+}
+
+impl LdkNodeHealthCheck {
+    pub fn new(lightning_engine: Arc<LightningEngine>) -> Self {
+        Self { lightning_engine }
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheckProvider for LdkNodeHealthCheck {
+    async fn check_health(&self) -> Result<HealthCheck> {
+        let start = std::time::Instant::now();
+
+        let (status, message) = match self.lightning_engine.node_status().await {
+            None => (HealthStatus::Unhealthy, "LDK node is starting".to_string()),
+            Some(node) if !node.is_running => (
+                HealthStatus::Unhealthy,
+                "LDK node is not running".to_string(),
+            ),
+            Some(node)
+                if node.latest_onchain_wallet_sync_timestamp.is_none()
+                    || node.latest_lightning_wallet_sync_timestamp.is_none() =>
+            {
+                (
+                    HealthStatus::Unhealthy,
+                    "LDK node is syncing to the chain tip".to_string(),
+                )
+            }
+            Some(node) => (
+                HealthStatus::Healthy,
+                format!(
+                    "LDK node synced to block {}",
+                    node.current_best_block.height
+                ),
+            ),
+        };
+
+        Ok(HealthCheck {
+            name: self.name().to_string(),
+            status,
+            message: Some(message),
+            timestamp: chrono::Utc::now(),
+            duration_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    fn name(&self) -> &str {
+        "ldk_node"
+    }
+}
+
+/// Freshness of the Rapid Gossip Sync graph: degraded until the first
+/// snapshot and once the last one is older than the configured age
+pub struct GossipHealthCheck {
+    gossip: Arc<GossipSync>,
+}
+
+impl GossipHealthCheck {
+    pub fn new(gossip: Arc<GossipSync>) -> Self {
+        Self { gossip }
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheckProvider for GossipHealthCheck {
+    async fn check_health(&self) -> Result<HealthCheck> {
+        let start = std::time::Instant::now();
+
+        let freshness = self.gossip.freshness()?;
+        let (status, message) = match freshness.age_secs {
+            None => (
+                HealthStatus::Degraded,
+                "No Rapid Gossip Sync snapshot yet".to_string(),
+            ),
+            Some(age) if freshness.is_stale => (
+                HealthStatus::Degraded,
+                format!("Network graph is stale, last synced {}s ago", age),
+            ),
+            Some(age) => (
+                HealthStatus::Healthy,
+                format!("Network graph synced {}s ago", age),
+            ),
+        };
+
+        Ok(HealthCheck {
+            name: self.name().to_string(),
+            status,
+            message: Some(message),
+            timestamp: chrono::Utc::now(),
+            duration_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    fn name(&self) -> &str {
+        "gossip"
+    }
+}
+
 /// API health check provider
 pub struct ApiHealthCheck {
     api_endpoint: String,
@@ -231,6 +335,42 @@ mod tests {
         assert_eq!(system_health.checks.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_ldk_node_unhealthy_until_started() {
+        let data_dir = std::env::temp_dir().join("satsconnect_ldk_health_test");
+        let engine = Arc::new(LightningEngine::new(data_dir, bitcoin::Network::Regtest));
+        let check = LdkNodeHealthCheck::new(engine);
+
+        let health = check.check_health().await.unwrap();
+        assert_eq!(health.name, "ldk_node");
+        assert_eq!(health.status, HealthStatus::Unhealthy);
+    }
+
+    #[tokio::test]
+    async fn test_gossip_degraded_until_synced() {
+        let store = crate::store::Store::open_in_memory().unwrap();
+        let gossip = Arc::new(GossipSync::new(
+            &Default::default(),
+            store.clone(),
+            Arc::new(crate::lightning::gossip_sync::test_support::TestNode::default()),
+        ));
+        let check = GossipHealthCheck::new(gossip);
+
+        let health = check.check_health().await.unwrap();
+        assert_eq!(health.name, "gossip");
+        assert_eq!(health.status, HealthStatus::Degraded);
+
+        // A sync recorded before a restart still counts
+        store
+            .with_conn(|conn| {
+                let now = crate::store::now().to_string();
+                crate::store::meta::set(conn, crate::store::meta::RGS_SNAPSHOT_TIMESTAMP, &now)
+            })
+            .unwrap();
+        let health = check.check_health().await.unwrap();
+        assert_eq!(health.status, HealthStatus::Healthy);
+    }
+
     #[tokio::test]
     async fn test_database_health_check() {
         let db_check = DatabaseHealthCheck::new("test://db".to_string());
@@ -4,6 +4,6 @@ pub mod metrics;
 pub mod performance_monitor;
 
 pub use alerts::{Alert, AlertLevel, AlertManager};
-pub use health_check::{HealthChecker, HealthStatus};
+pub use health_check::{GossipHealthCheck, HealthChecker, HealthStatus, LdkNodeHealthCheck};
 pub use metrics::{MetricType, MetricValue, MetricsCollector};
 pub use performance_monitor::{PerformanceMetrics, PerformanceMonitor};
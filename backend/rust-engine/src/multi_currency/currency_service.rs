@@ -1,13 +1,16 @@
+use crate::engine_config::FiatConfig;
 use crate::multi_currency::exchange_rates::{ExchangeRate, ExchangeRateProvider};
 use crate::multi_currency::fiat_providers::{
-    AirtelMoneyProvider, FiatProvider, MTNProvider, MpesaProvider,
+    AirtelMoneyProvider, FiatError, FiatProvider, MTNProvider, MpesaProvider, PaymentState,
 };
+use crate::store::{self, fiat_orders, FiatOrderRecord, Store};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
 
 /// Supported African currencies
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -112,30 +115,48 @@ pub struct CurrencyService {
     exchange_rates: Arc<RwLock<HashMap<Currency, ExchangeRate>>>,
     fiat_providers: HashMap<Currency, Box<dyn FiatProvider + Send + Sync>>,
     exchange_provider: Arc<dyn ExchangeRateProvider + Send + Sync>,
+    /// Fiat orders, kept across restarts
+    store: Store,
 }
 
 impl CurrencyService {
-    /// Create a new currency service
-    pub fn new() -> Self {
+    /// Create a currency service that records fiat orders in `store`
+    pub fn new(store: Store) -> Self {
+        Self::with_fiat_config(&FiatConfig::from_env(), store)
+    }
+
+    /// Create a currency service whose providers use the given credentials
+    pub fn with_fiat_config(config: &FiatConfig, store: Store) -> Self {
         let mut fiat_providers: HashMap<Currency, Box<dyn FiatProvider + Send + Sync>> =
             HashMap::new();
 
         // Initialize fiat providers for each currency
-        fiat_providers.insert(Currency::KES, Box::new(MpesaProvider::new()));
-        fiat_providers.insert(Currency::TZS, Box::new(AirtelMoneyProvider::new()));
-        fiat_providers.insert(Currency::UGX, Box::new(MTNProvider::new()));
-        fiat_providers.insert(Currency::NGN, Box::new(MTNProvider::new()));
-        fiat_providers.insert(Currency::ZAR, Box::new(MTNProvider::new()));
-        fiat_providers.insert(Currency::GHS, Box::new(MTNProvider::new()));
-        fiat_providers.insert(Currency::ETB, Box::new(MTNProvider::new()));
-        fiat_providers.insert(Currency::MWK, Box::new(MTNProvider::new()));
-        fiat_providers.insert(Currency::ZMW, Box::new(MTNProvider::new()));
-        fiat_providers.insert(Currency::BWP, Box::new(MTNProvider::new()));
+        fiat_providers.insert(
+            Currency::KES,
+            Box::new(MpesaProvider::from_config(&config.mpesa)),
+        );
+        fiat_providers.insert(
+            Currency::TZS,
+            Box::new(AirtelMoneyProvider::from_config(&config.airtel)),
+        );
+        for currency in [
+            Currency::UGX,
+            Currency::NGN,
+            Currency::ZAR,
+            Currency::GHS,
+            Currency::ETB,
+            Currency::MWK,
+            Currency::ZMW,
+            Currency::BWP,
+        ] {
+            fiat_providers.insert(currency, Box::new(MTNProvider::from_config(&config.mtn)));
+        }
 
         Self {
             exchange_rates: Arc::new(RwLock::new(HashMap::new())),
             fiat_providers,
             exchange_provider: Arc::new(ExchangeRateProvider::new()),
+            store,
         }
     }
 
@@ -186,16 +207,16 @@ impl CurrencyService {
 
         // Validate amount limits
         if sats < currency.min_sats() {
-            return Err(anyhow::anyhow!(
-                "Amount too small. Minimum: {} sats",
-                currency.min_sats()
-            ));
+            return Err(FiatError::AmountTooSmall {
+                min: currency.min_sats(),
+            }
+            .into());
         }
         if sats > currency.max_sats() {
-            return Err(anyhow::anyhow!(
-                "Amount too large. Maximum: {} sats",
-                currency.max_sats()
-            ));
+            return Err(FiatError::AmountTooLarge {
+                max: currency.max_sats(),
+            }
+            .into());
         }
 
         Ok(sats)
@@ -235,7 +256,7 @@ impl CurrencyService {
             "MWK" => Ok(Currency::MWK),
             "ZMW" => Ok(Currency::ZMW),
             "BWP" => Ok(Currency::BWP),
-            _ => Err(anyhow::anyhow!("Unsupported currency code: {}", code)),
+            _ => Err(FiatError::UnsupportedCurrency(code.to_string()).into()),
         }
     }
 
@@ -250,6 +271,125 @@ impl CurrencyService {
         }
     }
 
+    /// Place an order for `fiat_amount` of `currency` with that currency's
+    /// provider. The order is recorded before the provider is asked, so it is
+    /// never lost to a crash in between.
+    #[instrument(skip(self))]
+    pub async fn create_order(
+        &self,
+        wallet_id: &str,
+        currency: Currency,
+        fiat_amount: f64,
+        phone: &str,
+    ) -> Result<FiatOrderRecord> {
+        let provider = self
+            .get_fiat_provider(currency)
+            .ok_or_else(|| FiatError::UnsupportedCurrency(currency.code().to_string()))?;
+        let amount_sats = self.fiat_to_sats(fiat_amount, currency).await?;
+
+        let order_id = format!("fiat_{}", Uuid::new_v4());
+        let now = store::now();
+        let order = FiatOrderRecord {
+            order_id: order_id.clone(),
+            wallet_id: wallet_id.to_string(),
+            provider: provider.get_provider_name().to_string(),
+            currency: currency.code().to_string(),
+            fiat_amount,
+            amount_sats,
+            phone: phone.to_string(),
+            reference: order_id.clone(),
+            transaction_id: None,
+            status: PaymentState::Pending.as_str().to_string(),
+            error_message: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.store.with_conn(|c| fiat_orders::insert(c, &order))?;
+
+        let (status, transaction_id, error_message) = match provider
+            .initiate_payment(fiat_amount, phone, &order_id)
+            .await
+        {
+            Ok(response) if response.success => {
+                (PaymentState::Processing, response.transaction_id, None)
+            }
+            Ok(response) => (PaymentState::Failed, None, Some(response.message)),
+            Err(e) => {
+                warn!(
+                    "{} rejected fiat order {}: {:#}",
+                    order.provider, order_id, e
+                );
+                (PaymentState::Failed, None, Some(e.to_string()))
+            }
+        };
+        info!(
+            "Fiat order {} for {} {} is {}",
+            order_id,
+            fiat_amount,
+            currency.code(),
+            status.as_str()
+        );
+        self.store.with_conn(|c| {
+            fiat_orders::update_status(
+                c,
+                &order_id,
+                status.as_str(),
+                transaction_id.as_deref(),
+                error_message.as_deref(),
+            )
+        })?;
+        self.get_order(&order_id)
+    }
+
+    /// Ask the provider how an order still in progress is doing and record
+    /// the answer
+    #[instrument(skip(self))]
+    pub async fn refresh_order(&self, order_id: &str) -> Result<FiatOrderRecord> {
+        let order = self.get_order(order_id)?;
+        let in_progress = order.status == PaymentState::Pending.as_str()
+            || order.status == PaymentState::Processing.as_str();
+        let Some(transaction_id) = order.transaction_id.as_deref().filter(|_| in_progress) else {
+            return Ok(order);
+        };
+        let currency = Self::from_code(&order.currency)?;
+        let provider = self
+            .get_fiat_provider(currency)
+            .ok_or_else(|| FiatError::UnsupportedCurrency(order.currency.clone()))?;
+
+        let status = provider.verify_payment(transaction_id).await?;
+        if status.status.as_str() != order.status {
+            info!("Fiat order {} is {}", order_id, status.status.as_str());
+            self.store.with_conn(|c| {
+                fiat_orders::update_status(
+                    c,
+                    order_id,
+                    status.status.as_str(),
+                    None,
+                    status.error_message.as_deref(),
+                )
+            })?;
+        }
+        self.get_order(order_id)
+    }
+
+    pub fn get_order(&self, order_id: &str) -> Result<FiatOrderRecord> {
+        Ok(self
+            .store
+            .with_conn(|c| fiat_orders::get(c, order_id))?
+            .ok_or_else(|| FiatError::OrderNotFound(order_id.to_string()))?)
+    }
+
+    /// Orders placed for a wallet, optionally only those in `status`, newest
+    /// first
+    pub fn list_orders(
+        &self,
+        wallet_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<FiatOrderRecord>> {
+        self.store
+            .with_conn(|c| fiat_orders::list_by_wallet(c, wallet_id, status))
+    }
+
     /// Refresh all exchange rates
     #[instrument(skip(self))]
     pub async fn refresh_all_rates(&self) -> Result<()> {
@@ -283,19 +423,31 @@ pub struct CurrencyInfo {
     pub max_sats: u64,
 }
 
-impl Default for CurrencyService {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn service(store: Store) -> CurrencyService {
+        CurrencyService::with_fiat_config(&FiatConfig::default(), store)
+    }
+
+    /// Cache a rate so no test reaches out to a rate source
+    async fn set_rate(service: &CurrencyService, currency: Currency, rate: f64) {
+        service.exchange_rates.write().await.insert(
+            currency,
+            ExchangeRate {
+                currency,
+                rate,
+                timestamp: chrono::Utc::now().timestamp() as u64,
+                source: "test".to_string(),
+                ttl: 3600,
+            },
+        );
+    }
+
     #[tokio::test]
     async fn test_currency_service_creation() {
-        let service = CurrencyService::new();
+        let service = service(Store::open_in_memory().unwrap());
         let currencies = service.get_supported_currencies();
         assert_eq!(currencies.len(), 10);
     }
@@ -309,10 +461,48 @@ mod tests {
 
     #[test]
     fn test_currency_info() {
-        let service = CurrencyService::new();
+        let service = service(Store::open_in_memory().unwrap());
         let info = service.get_currency_info(Currency::KES);
         assert_eq!(info.code, "KES");
         assert_eq!(info.name, "Kenyan Shilling");
         assert_eq!(info.symbol, "KSh");
     }
+
+    #[tokio::test]
+    async fn test_orders_survive_restart() {
+        let store = Store::open_in_memory().unwrap();
+        let currencies = service(store.clone());
+        set_rate(&currencies, Currency::TZS, 0.5).await;
+
+        let order = currencies
+            .create_order("wallet_1", Currency::TZS, 2000.0, "+255700000000")
+            .await
+            .unwrap();
+        assert_eq!(order.provider, "Airtel Money");
+        assert_eq!(order.amount_sats, 1000);
+        assert_eq!(order.status, "PROCESSING");
+        assert!(order.transaction_id.is_some());
+
+        // A new service on the same store still has the order and can finish it
+        let currencies = service(store);
+        assert_eq!(
+            currencies.list_orders("wallet_1", None).unwrap(),
+            vec![order.clone()]
+        );
+        let order = currencies.refresh_order(&order.order_id).await.unwrap();
+        assert_eq!(order.status, "COMPLETED");
+        assert_eq!(
+            currencies
+                .list_orders("wallet_1", Some("PROCESSING"))
+                .unwrap()
+                .len(),
+            0
+        );
+
+        let err = currencies.get_order("fiat_missing").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FiatError>(),
+            Some(FiatError::OrderNotFound(_))
+        ));
+    }
 }
@@ -1,4 +1,5 @@
 use super::currency_service::Currency;
+use super::fiat_providers::FiatError;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -88,10 +89,7 @@ impl ExchangeRateProvider for MultiSourceExchangeRateProvider {
             }
         }
 
-        Err(anyhow::anyhow!(
-            "All exchange rate providers failed for {}",
-            currency.code()
-        ))
+        Err(FiatError::RateUnavailable(currency.code()).into())
     }
 
     async fn get_rates(
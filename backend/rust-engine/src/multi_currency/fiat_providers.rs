@@ -1,9 +1,28 @@
+use crate::engine_config::{AirtelConfig, FiatConfig, MpesaConfig, MtnConfig};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tracing::{error, info, instrument, warn};
 
+/// Failures converting between sats and fiat or talking to fiat providers
+#[derive(Debug, thiserror::Error)]
+pub enum FiatError {
+    #[error("Unsupported currency code: {0}")]
+    UnsupportedCurrency(String),
+    #[error("Amount too small. Minimum: {min} sats")]
+    AmountTooSmall { min: u64 },
+    #[error("Amount too large. Maximum: {max} sats")]
+    AmountTooLarge { max: u64 },
+    #[error("{0} is temporarily unavailable")]
+    ProviderUnavailable(&'static str),
+    #[error("No exchange rate available for {0}")]
+    RateUnavailable(&'static str),
+    #[error("Fiat order {0} not found")]
+    OrderNotFound(String),
+}
+
 /// Fiat provider trait for different payment methods
+#[async_trait::async_trait]
 pub trait FiatProvider: Send + Sync {
     async fn initiate_payment(
         &self,
@@ -51,6 +70,20 @@ pub enum PaymentState {
     Expired,
 }
 
+impl PaymentState {
+    /// Status recorded for fiat orders in this state
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "PENDING",
+            Self::Processing => "PROCESSING",
+            Self::Completed => "COMPLETED",
+            Self::Failed => "FAILED",
+            Self::Cancelled => "CANCELLED",
+            Self::Expired => "EXPIRED",
+        }
+    }
+}
+
 /// Payment limits
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaymentLimits {
@@ -74,18 +107,17 @@ pub struct MpesaProvider {
 
 impl MpesaProvider {
     pub fn new() -> Self {
+        Self::from_config(&FiatConfig::from_env().mpesa)
+    }
+
+    pub fn from_config(config: &MpesaConfig) -> Self {
         Self {
-            consumer_key: std::env::var("MPESA_CONSUMER_KEY")
-                .unwrap_or_else(|_| "test_key".to_string()),
-            consumer_secret: std::env::var("MPESA_CONSUMER_SECRET")
-                .unwrap_or_else(|_| "test_secret".to_string()),
-            business_short_code: std::env::var("MPESA_BUSINESS_SHORT_CODE")
-                .unwrap_or_else(|_| "174379".to_string()),
-            passkey: std::env::var("MPESA_PASSKEY").unwrap_or_else(|_| "test_passkey".to_string()),
-            callback_url: std::env::var("MPESA_CALLBACK_URL")
-                .unwrap_or_else(|_| "https://api.satsconnect.com/webhooks/mpesa".to_string()),
-            environment: std::env::var("MPESA_ENVIRONMENT")
-                .unwrap_or_else(|_| "sandbox".to_string()),
+            consumer_key: config.consumer_key.clone(),
+            consumer_secret: config.consumer_secret.clone(),
+            business_short_code: config.business_short_code.clone(),
+            passkey: config.passkey.clone(),
+            callback_url: config.callback_url.clone(),
+            environment: config.environment.clone(),
         }
     }
 
@@ -107,13 +139,14 @@ impl MpesaProvider {
             .await?;
 
         if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Failed to get MPesa access token"));
+            error!("MPesa token request failed: {}", response.status());
+            return Err(FiatError::ProviderUnavailable("MPesa").into());
         }
 
         let data: serde_json::Value = response.json().await?;
         let access_token = data["access_token"]
             .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid access token response"))?;
+            .ok_or(FiatError::ProviderUnavailable("MPesa"))?;
 
         Ok(access_token.to_string())
     }
@@ -254,13 +287,14 @@ pub struct AirtelMoneyProvider {
 
 impl AirtelMoneyProvider {
     pub fn new() -> Self {
+        Self::from_config(&FiatConfig::from_env().airtel)
+    }
+
+    pub fn from_config(config: &AirtelConfig) -> Self {
         Self {
-            client_id: std::env::var("AIRTEL_CLIENT_ID")
-                .unwrap_or_else(|_| "test_client_id".to_string()),
-            client_secret: std::env::var("AIRTEL_CLIENT_SECRET")
-                .unwrap_or_else(|_| "test_client_secret".to_string()),
-            environment: std::env::var("AIRTEL_ENVIRONMENT")
-                .unwrap_or_else(|_| "sandbox".to_string()),
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            environment: config.environment.clone(),
         }
     }
 }
@@ -330,11 +364,14 @@ pub struct MTNProvider {
 
 impl MTNProvider {
     pub fn new() -> Self {
+        Self::from_config(&FiatConfig::from_env().mtn)
+    }
+
+    pub fn from_config(config: &MtnConfig) -> Self {
         Self {
-            subscription_key: std::env::var("MTN_SUBSCRIPTION_KEY")
-                .unwrap_or_else(|_| "test_subscription_key".to_string()),
-            environment: std::env::var("MTN_ENVIRONMENT").unwrap_or_else(|_| "sandbox".to_string()),
-            country: std::env::var("MTN_COUNTRY").unwrap_or_else(|_| "UG".to_string()),
+            subscription_key: config.subscription_key.clone(),
+            environment: config.environment.clone(),
+            country: config.country.clone(),
         }
     }
 }
@@ -4,4 +4,6 @@ pub mod fiat_providers;
 
 pub use currency_service::CurrencyService;
 pub use exchange_rates::{ExchangeRate, ExchangeRateProvider};
-pub use fiat_providers::{AirtelMoneyProvider, FiatProvider, MTNProvider, MpesaProvider};
+pub use fiat_providers::{
+    AirtelMoneyProvider, FiatError, FiatProvider, MTNProvider, MpesaProvider,
+};
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use bitcoin::{Address, Network};
+use ldk_node::lightning_invoice::{Bolt11Invoice, Currency};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Invoices, addresses and data that can't be used on the node's network
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    #[error("Invalid invoice: {0}")]
+    InvalidInvoice(String),
+    #[error("Invalid address {address}: {reason}")]
+    InvalidAddress { address: String, reason: String },
+    #[error("Invoice is for {found:?}, but this node runs on {expected}")]
+    InvoiceNetworkMismatch { expected: Network, found: Currency },
+    #[error("Address {address} is not valid on {expected}")]
+    AddressNetworkMismatch { address: String, expected: Network },
+    #[error("Data directory belongs to {found}, but this node runs on {expected}")]
+    DataNetworkMismatch { expected: Network, found: String },
+    #[error("Mainnet is disabled: {0}")]
+    MainnetDisabled(&'static str),
+}
+
+/// Network used when none is configured, selected by the `regtest` / `testnet`
+/// Cargo features (testnet if neither is enabled)
+pub fn default_network() -> Network {
+    if cfg!(feature = "regtest") {
+        Network::Regtest
+    } else {
+        Network::Testnet
+    }
+}
+
+/// Refuse mainnet unless the binary was built with the `mainnet` feature and
+/// the operator opted in through configuration
+pub fn check_mainnet_allowed(network: Network, allow_mainnet: bool) -> Result<(), NetworkError> {
+    if network != Network::Bitcoin {
+        return Ok(());
+    }
+    if !cfg!(feature = "mainnet") {
+        return Err(NetworkError::MainnetDisabled(
+            "the engine was built without the `mainnet` feature",
+        ));
+    }
+    if !allow_mainnet {
+        return Err(NetworkError::MainnetDisabled(
+            "set allow_mainnet (ALLOW_MAINNET / --allow-mainnet) to run on mainnet",
+        ));
+    }
+    Ok(())
+}
+
+/// BOLT11 currency a node on `network` issues and pays invoices in
+pub fn invoice_currency(network: Network) -> Currency {
+    match network {
+        Network::Bitcoin => Currency::Bitcoin,
+        Network::Testnet | Network::Testnet4 => Currency::BitcoinTestnet,
+        Network::Signet => Currency::Signet,
+        Network::Regtest => Currency::Regtest,
+    }
+}
+
+/// Parse a BOLT11 invoice and make sure it can be paid from `network`
+pub fn parse_invoice(invoice: &str, network: Network) -> Result<Bolt11Invoice> {
+    let invoice = Bolt11Invoice::from_str(invoice)
+        .map_err(|e| NetworkError::InvalidInvoice(e.to_string()))?;
+
+    let expected = invoice_currency(network);
+    if invoice.currency() != expected {
+        return Err(NetworkError::InvoiceNetworkMismatch {
+            expected: network,
+            found: invoice.currency(),
+        }
+        .into());
+    }
+    Ok(invoice)
+}
+
+/// Parse an on-chain address and make sure it belongs to `network`
+pub fn parse_address(address: &str, network: Network) -> Result<Address> {
+    let unchecked = Address::from_str(address).map_err(|e| NetworkError::InvalidAddress {
+        address: address.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    unchecked.require_network(network).map_err(|_| {
+        NetworkError::AddressNetworkMismatch {
+            address: address.to_string(),
+            expected: network,
+        }
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ldk_node::bitcoin::hashes::{sha256, Hash};
+    use ldk_node::bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use ldk_node::lightning_invoice::{InvoiceBuilder, PaymentSecret};
+
+    fn invoice(currency: Currency) -> String {
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[42; 32]).unwrap();
+        InvoiceBuilder::new(currency)
+            .description("network test".to_string())
+            .payment_hash(sha256::Hash::from_slice(&[7; 32]).unwrap())
+            .payment_secret(PaymentSecret([7; 32]))
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(144)
+            .amount_milli_satoshis(1_000)
+            .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &key))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_invoice_network_must_match() {
+        let mainnet = invoice(Currency::Bitcoin);
+        assert!(mainnet.starts_with("lnbc"));
+        assert!(parse_invoice(&mainnet, Network::Bitcoin).is_ok());
+
+        let err = parse_invoice(&mainnet, Network::Testnet).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NetworkError>(),
+            Some(NetworkError::InvoiceNetworkMismatch { .. })
+        ));
+
+        let regtest = invoice(Currency::Regtest);
+        assert!(parse_invoice(&regtest, Network::Regtest).is_ok());
+        assert!(parse_invoice(&regtest, Network::Signet).is_err());
+    }
+
+    #[test]
+    fn test_address_network_must_match() {
+        let mainnet = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let testnet = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+
+        assert!(parse_address(mainnet, Network::Bitcoin).is_ok());
+        assert!(parse_address(testnet, Network::Testnet).is_ok());
+        // Testnet and signet share address encodings
+        assert!(parse_address(testnet, Network::Signet).is_ok());
+
+        let err = parse_address(mainnet, Network::Testnet).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NetworkError>(),
+            Some(NetworkError::AddressNetworkMismatch { .. })
+        ));
+        assert!(parse_address(testnet, Network::Bitcoin).is_err());
+    }
+
+    #[test]
+    fn test_mainnet_requires_opt_in() {
+        assert!(check_mainnet_allowed(Network::Testnet, false).is_ok());
+        assert!(check_mainnet_allowed(Network::Bitcoin, false).is_err());
+        assert_eq!(
+            check_mainnet_allowed(Network::Bitcoin, true).is_ok(),
+            cfg!(feature = "mainnet")
+        );
+    }
+}
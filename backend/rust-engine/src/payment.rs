@@ -1,14 +1,45 @@
-use crate::lightning_engine::LightningEngine;
+use crate::config::LightningConfig;
+use crate::lightning_engine::{LightningEngine, LightningError};
+use crate::network::{self, NetworkError};
+use crate::refund::{
+    self, LnurlWithdrawRequest, Refund, RefundMethod, RefundStatus, REFUND_CLAIM_EXPIRY_SECS,
+};
+use crate::store::{self, payments, PaymentQuery, PaymentRecord, Store};
 use anyhow::Result;
-use bitcoin::Network;
 use chrono::Utc;
 use directories::ProjectDirs;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use ldk_node::payment::PaymentStatus;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// JSON ledger used before payments moved into the store; imported once on startup
+const LEGACY_PAYMENTS_FILE: &str = "payments.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PaymentError {
+    #[error("Payment {0} already exists with a different invoice")]
+    PaymentIdConflict(String),
+    #[error("Invoice with payment hash {0} has already been paid")]
+    AlreadyPaid(String),
+    #[error("Payment not found")]
+    NotFound,
+    #[error("Payment cannot be refunded: {0}")]
+    NotRefundable(String),
+    #[error("Refund of {requested} sats exceeds the {remaining} sats still refundable")]
+    RefundExceedsRemaining { requested: u64, remaining: u64 },
+    #[error("Refund not found or no longer claimable")]
+    RefundNotFound,
+    #[error("Invalid refund: {0}")]
+    InvalidRefund(String),
+    #[error("LNURL-withdraw refunds need the gateway's public URL (lnurl_base_url)")]
+    LnurlNotConfigured,
+}
 
 // Simplified payment types for HTTP API (will be replaced with gRPC later)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Payment {
     pub payment_id: String,
     pub wallet_id: String,
@@ -18,27 +49,139 @@ pub struct Payment {
     pub status: String,
     pub payment_hash: String,
     pub timestamp: String,
+    /// OUTBOUND for payments we sent, INBOUND for payments we received
+    #[serde(default = "default_direction")]
+    pub direction: String,
+    #[serde(default)]
+    pub refunds: Vec<Refund>,
+}
+
+fn default_direction() -> String {
+    "OUTBOUND".to_string()
+}
+
+impl Payment {
+    /// Sats returned to the payer by succeeded refunds
+    pub fn refunded_sats(&self) -> u64 {
+        refund::refunded_sats(&self.refunds)
+    }
+
+    /// Move the payment to REFUNDED / PARTIALLY_REFUNDED once refunds settle
+    fn update_refund_status(&mut self) {
+        let refunded = self.refunded_sats();
+        if refunded >= self.amount_sats {
+            self.status = payments::PaymentStatus::Refunded.as_str().to_string();
+        } else if refunded > 0 {
+            self.status = payments::PaymentStatus::PartiallyRefunded
+                .as_str()
+                .to_string();
+        }
+    }
+
+    fn to_record(&self) -> PaymentRecord {
+        let timestamp = chrono::DateTime::parse_from_rfc3339(&self.timestamp)
+            .map(|t| t.timestamp())
+            .unwrap_or_else(|_| store::now());
+        let finished = self.status != "PENDING";
+
+        PaymentRecord {
+            payment_id: self.payment_id.clone(),
+            wallet_id: self.wallet_id.clone(),
+            direction: self.direction.clone(),
+            amount_msat: self.amount_sats * 1000,
+            invoice: self.invoice.clone(),
+            destination: None,
+            description: self.description.clone(),
+            status: self.status.clone(),
+            payment_hash: self.payment_hash.clone(),
+            retry_count: 0,
+            error_message: None,
+            created_at: timestamp,
+            updated_at: timestamp,
+            completed_at: finished.then_some(timestamp),
+        }
+    }
+
+    fn from_record(record: PaymentRecord, refunds: Vec<Refund>) -> Self {
+        Self {
+            payment_id: record.payment_id,
+            wallet_id: record.wallet_id,
+            amount_sats: record.amount_msat / 1000,
+            invoice: record.invoice,
+            description: record.description,
+            status: record.status,
+            payment_hash: record.payment_hash,
+            timestamp: chrono::DateTime::from_timestamp(record.updated_at, 0)
+                .unwrap_or_default()
+                .to_rfc3339(),
+            direction: record.direction,
+            refunds,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct PaymentHandler {
-    payments: Arc<RwLock<HashMap<String, Payment>>>,
+    store: Store,
     lightning_engine: Arc<LightningEngine>,
+    /// Public URL of the HTTP gateway; LNURL-withdraw refunds are refused
+    /// without one
+    lnurl_base_url: Option<String>,
+    /// Payments this process is sending; any other PENDING payment is
+    /// settled from the node's own record of it
+    sending: Mutex<HashSet<String>>,
 }
 
 impl PaymentHandler {
     pub fn new() -> Result<Self> {
         let dirs = ProjectDirs::from("com", "SatsConnect", "engine")
             .ok_or_else(|| anyhow::anyhow!("Failed to get project directories"))?;
-        let data_dir = dirs.data_dir().to_path_buf();
-        std::fs::create_dir_all(&data_dir)?;
+        Self::with_data_dir(dirs.data_dir().to_path_buf())
+    }
 
-        // Initialize Lightning engine with testnet for development
-        let lightning_engine = Arc::new(LightningEngine::new(data_dir, Network::Testnet));
+    /// Create a handler whose store and Lightning state live in `data_dir`
+    pub fn with_data_dir(data_dir: PathBuf) -> Result<Self> {
+        let config = LightningConfig {
+            data_dir,
+            ..LightningConfig::default()
+        };
+        Self::with_config(config, std::env::var("LNURL_BASE_URL").ok())
+    }
+
+    /// Create a handler for the network and data directory in `config`, serving
+    /// LNURL-withdraw refunds under `lnurl_base_url`
+    pub fn with_config(config: LightningConfig, lnurl_base_url: Option<String>) -> Result<Self> {
+        Self::with_engine(
+            Arc::new(LightningEngine::with_config(config)),
+            lnurl_base_url,
+        )
+    }
+
+    /// Create a handler on top of a Lightning engine shared with other services
+    pub fn with_engine(
+        lightning_engine: Arc<LightningEngine>,
+        lnurl_base_url: Option<String>,
+    ) -> Result<Self> {
+        let store = Store::open_in_dir(&lightning_engine.config().data_dir)?;
+        Self::with_store(lightning_engine, store, lnurl_base_url)
+    }
+
+    /// Create a handler that keeps its payments in `store`, shared with the
+    /// engine's other services
+    pub fn with_store(
+        lightning_engine: Arc<LightningEngine>,
+        store: Store,
+        lnurl_base_url: Option<String>,
+    ) -> Result<Self> {
+        let config = lightning_engine.config();
+        store.bind_network(config.network)?;
+        Self::import_legacy_ledger(&store, &config.data_dir.join(LEGACY_PAYMENTS_FILE))?;
 
         Ok(Self {
-            payments: Arc::new(RwLock::new(HashMap::new())),
+            store,
             lightning_engine,
+            lnurl_base_url,
+            sending: Mutex::new(HashSet::new()),
         })
     }
 
@@ -46,6 +189,63 @@ impl PaymentHandler {
         format!("pay_{}", &uuid::Uuid::new_v4().to_string()[..8])
     }
 
+    /// Move payments from the old JSON ledger into the store, then retire the file
+    fn import_legacy_ledger(store: &Store, path: &Path) -> Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let legacy: HashMap<String, Payment> = serde_json::from_str(&content)?;
+
+        store.transaction(|tx| {
+            for payment in legacy.values() {
+                if payments::get(tx, &payment.payment_id)?.is_none() {
+                    payments::insert(tx, &payment.to_record())?;
+                }
+                for refund in &payment.refunds {
+                    payments::save_refund(tx, &payment.payment_id, refund)?;
+                }
+            }
+            Ok(())
+        })?;
+
+        std::fs::rename(path, path.with_extension("json.imported"))?;
+        info!("Imported {} payments from {}", legacy.len(), path.display());
+        Ok(())
+    }
+
+    /// Payment hash of an invoice payable on this node's network
+    fn payment_hash_of(&self, invoice: &str) -> Result<String> {
+        let invoice = network::parse_invoice(invoice, self.lightning_engine.network())?;
+        Ok(invoice.payment_hash().to_string())
+    }
+
+    fn load_payment(conn: &rusqlite::Connection, payment_id: &str) -> Result<Option<Payment>> {
+        let Some(record) = payments::get(conn, payment_id)? else {
+            return Ok(None);
+        };
+        let refunds = payments::list_refunds(conn, payment_id)?;
+        Ok(Some(Payment::from_record(record, refunds)))
+    }
+
+    /// Update a payment's status in the store
+    fn set_status(&self, payment_id: &str, status: &str) -> Result<Payment> {
+        self.store.transaction(|tx| {
+            let mut payment = Self::load_payment(tx, payment_id)?.ok_or(PaymentError::NotFound)?;
+            payment.status = status.to_string();
+            payment.timestamp = Utc::now().to_rfc3339();
+            payments::update(tx, &payment.to_record())?;
+            Ok(payment)
+        })
+    }
+
+    /// Pay an invoice, idempotently keyed by `payment_id`.
+    ///
+    /// Re-submitting the same id with the same invoice returns the existing
+    /// record instead of paying again; the same id with another invoice is
+    /// rejected. An invoice whose payment hash was already paid (or is in
+    /// flight) is refused unless `force` is set.
     pub async fn process_payment(
         &self,
         payment_id: Option<String>,
@@ -53,63 +253,524 @@ impl PaymentHandler {
         amount_sats: u64,
         invoice: String,
         description: String,
+        force: bool,
     ) -> Result<Payment> {
-        let payment_id = if let Some(id) = payment_id {
-            id
-        } else {
-            Self::generate_id()
+        let payment_id = payment_id
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(Self::generate_id);
+        let payment_hash = self.payment_hash_of(&invoice)?;
+
+        // A PENDING payment an earlier run left behind would otherwise be
+        // returned for this id, and block the invoice, for good
+        let stale = self.store.with_conn(|conn| {
+            let mut ids: Vec<String> = payments::find_by_hash(conn, &payment_hash)?
+                .into_iter()
+                .map(|p| p.payment_id)
+                .collect();
+            ids.push(payment_id.clone());
+            Ok(ids)
+        })?;
+        for id in stale {
+            self.reconcile(&id).await?;
+        }
+
+        // Record the attempt before sending so a retry can't pay twice
+        let existing = {
+            let mut sending = self.sending.lock().unwrap();
+            let existing = self.store.transaction(|tx| {
+                if let Some(existing) = Self::load_payment(tx, &payment_id)? {
+                    if existing.invoice == invoice {
+                        return Ok(Some(existing));
+                    }
+                    return Err(PaymentError::PaymentIdConflict(payment_id.clone()).into());
+                }
+
+                let already_paid = payments::find_by_hash(tx, &payment_hash)?.iter().any(|p| {
+                    p.direction == "OUTBOUND" && (p.status == "SUCCEEDED" || p.status == "PENDING")
+                });
+                if already_paid && !force {
+                    return Err(PaymentError::AlreadyPaid(payment_hash.clone()).into());
+                }
+
+                let payment = Payment {
+                    payment_id: payment_id.clone(),
+                    wallet_id,
+                    amount_sats,
+                    invoice: invoice.clone(),
+                    description,
+                    status: "PENDING".to_string(),
+                    payment_hash: payment_hash.clone(),
+                    timestamp: Utc::now().to_rfc3339(),
+                    direction: default_direction(),
+                    refunds: Vec::new(),
+                };
+                payments::insert(tx, &payment.to_record())?;
+                Ok(None)
+            })?;
+            if existing.is_none() {
+                sending.insert(payment_id.clone());
+            }
+            existing
         };
 
-        // Initialize Lightning engine if not already done
-        self.lightning_engine.initialize().await?;
+        if let Some(existing) = existing {
+            return Ok(existing);
+        }
 
-        // Send payment using real Lightning engine
-        let (payment_hash, status) = self.lightning_engine.send_payment(&invoice).await?;
+        let result = async {
+            // Initialize Lightning engine if not already done
+            self.lightning_engine.initialize().await?;
 
-        let payment = Payment {
-            payment_id: payment_id.clone(),
-            wallet_id,
-            amount_sats,
-            invoice: invoice.clone(),
-            description,
-            status,
-            payment_hash,
-            timestamp: Utc::now().to_rfc3339(),
+            // Send payment using real Lightning engine
+            self.lightning_engine.send_payment(&invoice).await
+        }
+        .await;
+
+        let payment = match result {
+            Ok((_, status)) => self.set_status(&payment_id, &status),
+            Err(e) => {
+                warn!("Payment {} failed: {}", payment_id, e);
+                self.set_status(&payment_id, "FAILED").and(Err(e))
+            }
         };
+        self.sending.lock().unwrap().remove(&payment_id);
+        payment
+    }
 
+    /// Settle the outgoing payments an earlier run left PENDING from the
+    /// node's own records; returns how many there were
+    pub async fn reconcile_pending(&self) -> Result<usize> {
+        let pending = self.store.with_conn(|conn| {
+            payments::list(
+                conn,
+                &PaymentQuery {
+                    status: Some("PENDING".to_string()),
+                    direction: Some("OUTBOUND".to_string()),
+                    ..PaymentQuery::default()
+                },
+            )
+        })?;
+        for payment in &pending {
+            self.reconcile(&payment.payment_id).await?;
+        }
+        Ok(pending.len())
+    }
+
+    /// Settle a PENDING outgoing payment that no call in this process is
+    /// sending from the node's record of it. One the node never saw was
+    /// lost before it was sent, and failed.
+    async fn reconcile(&self, payment_id: &str) -> Result<()> {
+        let Some(payment) = self
+            .store
+            .with_conn(|conn| Self::load_payment(conn, payment_id))?
+        else {
+            return Ok(());
+        };
+        if payment.direction != "OUTBOUND"
+            || payment.status != "PENDING"
+            || self.sending.lock().unwrap().contains(payment_id)
         {
-            let mut payments = self.payments.write().await;
-            payments.insert(payment_id.clone(), payment.clone());
+            return Ok(());
         }
 
-        Ok(payment)
+        self.lightning_engine.initialize().await?;
+        let status = self
+            .lightning_engine
+            .outbound_payment_status(&payment.payment_hash)
+            .await?
+            .unwrap_or_else(|| "FAILED".to_string());
+        if status != payment.status {
+            info!(
+                "Payment {} left PENDING by an earlier run is {}",
+                payment_id, status
+            );
+            self.set_status(payment_id, &status)?;
+        }
+        Ok(())
     }
 
     pub async fn get_payment_status(&self, payment_id: String) -> Result<Payment> {
-        let payments = self.payments.read().await;
+        self.refresh_refunds(&payment_id).await?;
 
-        let payment = payments
-            .get(&payment_id)
-            .ok_or_else(|| anyhow::anyhow!("Payment not found"))?
-            .clone();
+        let payment = self
+            .store
+            .with_conn(|c| Self::load_payment(c, &payment_id))?
+            .ok_or(PaymentError::NotFound)?;
 
         Ok(payment)
     }
 
-    pub async fn process_refund(&self, payment_id: String, _amount_sats: u64) -> Result<Payment> {
-        let mut payments = self.payments.write().await;
+    /// Issue a (possibly partial) refund for a received payment.
+    ///
+    /// `amount_sats` of 0 refunds whatever is still refundable. With
+    /// [`RefundMethod::Bolt11Invoice`] the payer's invoice in `destination` is
+    /// paid straight away; the other methods hand out an LNURL-withdraw link or
+    /// BOLT12 refund for the payer to claim within [`REFUND_CLAIM_EXPIRY_SECS`].
+    pub async fn process_refund(
+        &self,
+        payment_id: String,
+        amount_sats: u64,
+        method: RefundMethod,
+        destination: Option<String>,
+    ) -> Result<Payment> {
+        if matches!(method, RefundMethod::LnurlWithdraw) {
+            self.lnurl_base_url()?;
+        }
+        self.import_received_payment(&payment_id).await?;
+        self.refresh_refunds(&payment_id).await?;
+
+        let invoice = match method {
+            RefundMethod::Bolt11Invoice => {
+                let invoice = destination.filter(|d| !d.is_empty()).ok_or_else(|| {
+                    PaymentError::InvalidRefund("a refund invoice is required".to_string())
+                })?;
+                let parsed = network::parse_invoice(&invoice, self.lightning_engine.network())
+                    .map_err(|e| PaymentError::InvalidRefund(e.to_string()))?;
+                Some((invoice, parsed.amount_milli_satoshis()))
+            }
+            RefundMethod::LnurlWithdraw | RefundMethod::Bolt12Refund => None,
+        };
+        let expires_at = Utc::now() + chrono::Duration::seconds(REFUND_CLAIM_EXPIRY_SECS as i64);
+
+        // Record the refund before paying anything out, in the transaction
+        // that checks the remaining amount, so concurrent refunds cannot
+        // together exceed the payment
+        let mut refund = self.store.transaction(|tx| {
+            let payment = Self::load_payment(tx, &payment_id)?.ok_or(PaymentError::NotFound)?;
+
+            if payment.direction != "INBOUND" {
+                return Err(PaymentError::NotRefundable(
+                    "only received payments can be refunded".to_string(),
+                )
+                .into());
+            }
+            if payment.status != "SUCCEEDED" && payment.status != "PARTIALLY_REFUNDED" {
+                return Err(
+                    PaymentError::NotRefundable(format!("payment is {}", payment.status)).into(),
+                );
+            }
+
+            let remaining = refund::refundable_sats(payment.amount_sats, &payment.refunds);
+            let amount_sats = if amount_sats == 0 {
+                remaining
+            } else {
+                amount_sats
+            };
+            if amount_sats == 0 || amount_sats > remaining {
+                return Err(PaymentError::RefundExceedsRemaining {
+                    requested: amount_sats,
+                    remaining,
+                }
+                .into());
+            }
+
+            if let Some((_, Some(invoice_msat))) = invoice {
+                if invoice_msat != amount_sats * 1000 {
+                    let mismatch = LightningError::RefundAmountMismatch {
+                        invoice_msat,
+                        amount_msat: amount_sats * 1000,
+                    };
+                    return Err(PaymentError::InvalidRefund(mismatch.to_string()).into());
+                }
+            }
+
+            let mut refund = Refund::new(amount_sats, method);
+            match method {
+                RefundMethod::LnurlWithdraw => {
+                    let k1 = hex::encode(rand::random::<[u8; 32]>());
+                    let url = format!("{}/lnurl/withdraw/{}", self.lnurl_base_url()?, k1);
+                    refund.lnurl = Some(refund::encode_lnurl(&url)?);
+                    refund.k1 = Some(k1);
+                    refund.expires_at = Some(expires_at.to_rfc3339());
+                }
+                RefundMethod::Bolt12Refund => refund.expires_at = Some(expires_at.to_rfc3339()),
+                RefundMethod::Bolt11Invoice => refund.status = RefundStatus::Pending,
+            }
+            refund.invoice = invoice.as_ref().map(|(invoice, _)| invoice.clone());
+            payments::save_refund(tx, &payment_id, &refund)?;
+            Ok(refund)
+        })?;
+
+        let amount_msat = refund.amount_sats * 1000;
+        let payout = async {
+            match method {
+                RefundMethod::LnurlWithdraw => {}
+                RefundMethod::Bolt12Refund => {
+                    self.lightning_engine.initialize().await?;
+                    let bolt12_refund = self
+                        .lightning_engine
+                        .initiate_bolt12_refund(
+                            amount_msat,
+                            REFUND_CLAIM_EXPIRY_SECS,
+                            &refund.refund_id,
+                        )
+                        .await?;
+                    refund.bolt12_refund = Some(bolt12_refund);
+                }
+                RefundMethod::Bolt11Invoice => {
+                    let invoice = refund.invoice.clone().unwrap_or_default();
+                    self.lightning_engine.initialize().await?;
+                    let (payment_hash, ldk_payment_id) = self
+                        .lightning_engine
+                        .pay_refund_invoice(&invoice, amount_msat)
+                        .await?;
+                    refund.payment_hash = Some(payment_hash);
+                    refund.ldk_payment_id = Some(ldk_payment_id);
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+
+        match &payout {
+            Ok(()) => info!(
+                "Issued {} refund {} of {} sats for payment {}",
+                method.as_str(),
+                refund.refund_id,
+                refund.amount_sats,
+                payment_id
+            ),
+            Err(e) => {
+                // Release the amount for another attempt
+                warn!("Refund {} failed: {:#}", refund.refund_id, e);
+                refund.status = RefundStatus::Failed;
+            }
+        }
+
+        let payment = self.store.transaction(|tx| {
+            payments::save_refund(tx, &payment_id, &refund)?;
+            Ok(Self::load_payment(tx, &payment_id)?.ok_or(PaymentError::NotFound)?)
+        })?;
+
+        payout.map_err(invalid_refund_invoice)?;
+        Ok(payment)
+    }
+
+    fn lnurl_base_url(&self) -> Result<&str, PaymentError> {
+        self.lnurl_base_url
+            .as_deref()
+            .map(|url| url.trim_end_matches('/'))
+            .ok_or(PaymentError::LnurlNotConfigured)
+    }
+
+    /// LUD-03 `withdrawRequest` for an unclaimed LNURL-withdraw refund
+    pub async fn lnurl_withdraw_request(&self, k1: &str) -> Result<LnurlWithdrawRequest> {
+        let (payment, refund) = self
+            .store
+            .with_conn(|c| Self::find_claimable_refund(c, k1))?
+            .ok_or(PaymentError::RefundNotFound)?;
+        let amount_msat = refund.amount_sats * 1000;
+
+        Ok(LnurlWithdrawRequest {
+            tag: "withdrawRequest".to_string(),
+            callback: format!("{}/lnurl/withdraw/{}/callback", self.lnurl_base_url()?, k1),
+            k1: k1.to_string(),
+            min_withdrawable: amount_msat,
+            max_withdrawable: amount_msat,
+            default_description: format!("Refund for {}", payment.description),
+        })
+    }
+
+    /// Pay out an LNURL-withdraw refund to the invoice the payer's wallet submitted
+    pub async fn claim_refund(&self, k1: &str, invoice: String) -> Result<Payment> {
+        // Claim the refund before paying so a second callback can't pay twice
+        let (payment_id, mut refund) = self.store.transaction(|tx| {
+            let (payment, mut refund) =
+                Self::find_claimable_refund(tx, k1)?.ok_or(PaymentError::RefundNotFound)?;
+            refund.status = RefundStatus::Pending;
+            refund.invoice = Some(invoice.clone());
+            payments::save_refund(tx, &payment.payment_id, &refund)?;
+            Ok((payment.payment_id, refund))
+        })?;
+
+        let result = async {
+            self.lightning_engine.initialize().await?;
+            self.lightning_engine
+                .pay_refund_invoice(&invoice, refund.amount_sats * 1000)
+                .await
+        }
+        .await;
+
+        let claim_result = match result {
+            Ok((payment_hash, ldk_payment_id)) => {
+                refund.payment_hash = Some(payment_hash);
+                refund.ldk_payment_id = Some(ldk_payment_id);
+                Ok(())
+            }
+            Err(e) => {
+                // Let the payer retry with another invoice
+                warn!("Refund {} claim failed: {}", refund.refund_id, e);
+                refund.status = RefundStatus::AwaitingClaim;
+                refund.invoice = None;
+                Err(invalid_refund_invoice(e))
+            }
+        };
 
-        let payment = payments
-            .get_mut(&payment_id)
-            .ok_or_else(|| anyhow::anyhow!("Payment not found"))?;
+        let payment = self.store.transaction(|tx| {
+            payments::save_refund(tx, &payment_id, &refund)?;
+            Ok(Self::load_payment(tx, &payment_id)?.ok_or(PaymentError::NotFound)?)
+        })?;
 
-        if payment.status != "COMPLETED" {
-            return Err(anyhow::anyhow!("Cannot refund non-completed payment"));
+        claim_result?;
+        Ok(payment)
+    }
+
+    /// Bring a payment received by the Lightning node into the store so it can be refunded
+    async fn import_received_payment(&self, payment_id: &str) -> Result<()> {
+        if self
+            .store
+            .with_conn(|c| payments::get(c, payment_id))?
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        self.lightning_engine.initialize().await?;
+        let (payment_hash, amount_msat) = self
+            .lightning_engine
+            .received_payment(payment_id)
+            .await
+            .ok()
+            .flatten()
+            .ok_or(PaymentError::NotFound)?;
+
+        let payment = Payment {
+            payment_id: payment_id.to_string(),
+            wallet_id: "default".to_string(),
+            amount_sats: amount_msat / 1000,
+            invoice: String::new(),
+            description: "Received payment".to_string(),
+            status: "SUCCEEDED".to_string(),
+            payment_hash,
+            timestamp: Utc::now().to_rfc3339(),
+            direction: "INBOUND".to_string(),
+            refunds: Vec::new(),
+        };
+        self.store.transaction(|tx| {
+            if payments::get(tx, payment_id)?.is_none() {
+                payments::insert(tx, &payment.to_record())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Expire unclaimed refunds and pick up the outcome of in-flight refund payments
+    async fn refresh_refunds(&self, payment_id: &str) -> Result<()> {
+        let refunds = self
+            .store
+            .with_conn(|c| payments::list_refunds(c, payment_id))?;
+        if refunds.is_empty() {
+            return Ok(());
         }
 
-        payment.status = "REFUNDED".to_string();
+        let now = Utc::now();
+        let mut updated = Vec::new();
+        for refund in refunds {
+            let update = match (refund.method, refund.status) {
+                (
+                    RefundMethod::Bolt12Refund,
+                    RefundStatus::AwaitingClaim | RefundStatus::Pending,
+                ) => {
+                    match self
+                        .lightning_engine
+                        .bolt12_refund_status(&refund.refund_id)
+                        .await
+                    {
+                        Ok(Some((PaymentStatus::Succeeded, hash))) => {
+                            Some((RefundStatus::Succeeded, hash))
+                        }
+                        Ok(Some((PaymentStatus::Failed, hash))) => {
+                            Some((RefundStatus::Failed, hash))
+                        }
+                        Ok(Some((PaymentStatus::Pending, Some(hash)))) => {
+                            Some((RefundStatus::Pending, Some(hash)))
+                        }
+                        _ if refund.is_expired(now) => Some((RefundStatus::Expired, None)),
+                        _ => None,
+                    }
+                }
+                (_, RefundStatus::AwaitingClaim) if refund.is_expired(now) => {
+                    Some((RefundStatus::Expired, None))
+                }
+                (_, RefundStatus::Pending) => {
+                    let Some(ldk_payment_id) = refund.ldk_payment_id.as_deref() else {
+                        continue;
+                    };
+                    match self
+                        .lightning_engine
+                        .outbound_payment_status(ldk_payment_id)
+                        .await
+                    {
+                        Ok(Some(PaymentStatus::Succeeded)) => Some((RefundStatus::Succeeded, None)),
+                        Ok(Some(PaymentStatus::Failed)) => Some((RefundStatus::Failed, None)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            if let Some((status, hash)) = update {
+                if status != refund.status || hash.is_some() {
+                    let mut refund = refund;
+                    refund.status = status;
+                    if hash.is_some() {
+                        refund.payment_hash = hash;
+                    }
+                    updated.push(refund);
+                }
+            }
+        }
+
+        if updated.is_empty() {
+            return Ok(());
+        }
+
+        self.store.transaction(|tx| {
+            for refund in &updated {
+                payments::save_refund(tx, payment_id, refund)?;
+            }
+            if let Some(mut payment) = Self::load_payment(tx, payment_id)? {
+                payment.update_refund_status();
+                payments::update(tx, &payment.to_record())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Unexpired LNURL-withdraw refund awaiting claim for `k1`, with its payment
+    fn find_claimable_refund(
+        conn: &rusqlite::Connection,
+        k1: &str,
+    ) -> Result<Option<(Payment, Refund)>> {
+        let Some((payment_id, refund)) = payments::find_refund_by_k1(conn, k1)? else {
+            return Ok(None);
+        };
+
+        if refund.method != RefundMethod::LnurlWithdraw
+            || refund.status != RefundStatus::AwaitingClaim
+            || refund.is_expired(Utc::now())
+        {
+            return Ok(None);
+        }
+
+        Ok(Self::load_payment(conn, &payment_id)?.map(|payment| (payment, refund)))
+    }
+}
 
-        Ok(payment.clone())
+/// Report problems with the payer's refund invoice as an invalid refund;
+/// anything else failing is ours
+fn invalid_refund_invoice(e: anyhow::Error) -> anyhow::Error {
+    let invalid = matches!(
+        e.downcast_ref::<NetworkError>(),
+        Some(NetworkError::InvalidInvoice(_) | NetworkError::InvoiceNetworkMismatch { .. })
+    ) || matches!(
+        e.downcast_ref::<LightningError>(),
+        Some(LightningError::RefundAmountMismatch { .. })
+    );
+    if invalid {
+        PaymentError::InvalidRefund(e.to_string()).into()
+    } else {
+        e
     }
 }
 
@@ -1,19 +1,21 @@
 use crate::performance::async_lightning_engine::AsyncLightningEngine;
+use crate::store::{payments, PaymentQuery, PaymentRecord, Store};
 use anyhow::Result;
 use bitcoin::Network;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::time::{timeout, Duration};
 use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 
+pub use crate::store::PaymentStatus;
+
 /// High-performance payment processor with async operations and retry logic
 #[derive(Debug)]
 pub struct PaymentProcessor {
-    payments: Arc<RwLock<HashMap<String, Payment>>>,
+    store: Store,
     lightning_engine: Arc<AsyncLightningEngine>,
     retry_queue: Arc<RwLock<Vec<RetryItem>>>,
     max_retries: u32,
@@ -35,14 +37,57 @@ pub struct Payment {
     pub error_message: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum PaymentStatus {
-    Pending,
-    Processing,
-    Succeeded,
-    Failed,
-    Retrying,
-    Cancelled,
+impl Payment {
+    fn to_record(&self) -> PaymentRecord {
+        let created_at = parse_timestamp(&self.created_at);
+        let updated_at = parse_timestamp(&self.updated_at);
+        let finished = self.status.is_final();
+
+        PaymentRecord {
+            payment_id: self.payment_id.clone(),
+            wallet_id: self.wallet_id.clone(),
+            direction: "OUTBOUND".to_string(),
+            amount_msat: self.amount_sats * 1000,
+            invoice: self.invoice.clone(),
+            destination: None,
+            description: self.description.clone(),
+            status: self.status.as_str().to_string(),
+            payment_hash: self.payment_hash.clone(),
+            retry_count: self.retry_count,
+            error_message: self.error_message.clone(),
+            created_at,
+            updated_at,
+            completed_at: finished.then_some(updated_at),
+        }
+    }
+
+    fn from_record(record: PaymentRecord) -> Result<Self> {
+        Ok(Self {
+            payment_id: record.payment_id,
+            wallet_id: record.wallet_id,
+            amount_sats: record.amount_msat / 1000,
+            invoice: record.invoice,
+            description: record.description,
+            status: PaymentStatus::parse(&record.status)?,
+            payment_hash: record.payment_hash,
+            created_at: format_timestamp(record.created_at),
+            updated_at: format_timestamp(record.updated_at),
+            retry_count: record.retry_count,
+            error_message: record.error_message,
+        })
+    }
+}
+
+fn parse_timestamp(timestamp: &str) -> i64 {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.timestamp())
+        .unwrap_or_else(|_| Utc::now().timestamp())
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .to_rfc3339()
 }
 
 #[derive(Debug, Clone)]
@@ -54,10 +99,16 @@ struct RetryItem {
 }
 
 impl PaymentProcessor {
-    /// Create a new high-performance payment processor
-    pub fn new(data_dir: std::path::PathBuf, network: Network) -> Self {
+    /// Create a new high-performance payment processor persisting to the store in `data_dir`
+    pub fn new(data_dir: std::path::PathBuf, network: Network) -> Result<Self> {
+        let store = Store::open_in_dir(&data_dir)?;
+        Ok(Self::with_store(data_dir, network, store))
+    }
+
+    /// Create a payment processor that keeps payments in `store`
+    pub fn with_store(data_dir: std::path::PathBuf, network: Network, store: Store) -> Self {
         Self {
-            payments: Arc::new(RwLock::new(HashMap::new())),
+            store,
             lightning_engine: Arc::new(AsyncLightningEngine::new(data_dir, network)),
             retry_queue: Arc::new(RwLock::new(Vec::new())),
             max_retries: 3,
@@ -113,10 +164,8 @@ impl PaymentProcessor {
         };
 
         // Store payment
-        {
-            let mut payments = self.payments.write().await;
-            payments.insert(payment_id.clone(), payment.clone());
-        }
+        self.store
+            .with_conn(|c| payments::insert(c, &payment.to_record()))?;
 
         // Process payment asynchronously
         let processor = self.clone();
@@ -210,65 +259,59 @@ impl PaymentProcessor {
     /// Get payment status
     #[instrument(skip(self))]
     pub async fn get_payment_status(&self, payment_id: &str) -> Result<Payment> {
-        let payments = self.payments.read().await;
-        payments
-            .get(payment_id)
+        self.store
+            .with_conn(|c| payments::get(c, payment_id))?
             .ok_or_else(|| anyhow::anyhow!("Payment not found"))
-            .map(|p| p.clone())
+            .and_then(Payment::from_record)
     }
 
     /// Get all payments for a wallet
     #[instrument(skip(self))]
     pub async fn get_wallet_payments(&self, wallet_id: &str) -> Result<Vec<Payment>> {
-        let payments = self.payments.read().await;
-        Ok(payments
-            .values()
-            .filter(|p| p.wallet_id == wallet_id)
-            .cloned()
-            .collect())
+        let query = PaymentQuery {
+            wallet_id: Some(wallet_id.to_string()),
+            ..Default::default()
+        };
+        self.store
+            .with_conn(|c| payments::list(c, &query))?
+            .into_iter()
+            .map(Payment::from_record)
+            .collect()
     }
 
     /// Cancel a payment
     #[instrument(skip(self))]
     pub async fn cancel_payment(&self, payment_id: &str) -> Result<Payment> {
-        let mut payments = self.payments.write().await;
-        let payment = payments
-            .get_mut(payment_id)
-            .ok_or_else(|| anyhow::anyhow!("Payment not found"))?;
+        self.store.transaction(|tx| {
+            let mut payment = payments::get(tx, payment_id)?
+                .ok_or_else(|| anyhow::anyhow!("Payment not found"))
+                .and_then(Payment::from_record)?;
 
-        if payment.status == PaymentStatus::Succeeded {
-            return Err(anyhow::anyhow!("Cannot cancel completed payment"));
-        }
+            if payment.status == PaymentStatus::Succeeded {
+                return Err(anyhow::anyhow!("Cannot cancel completed payment"));
+            }
 
-        payment.status = PaymentStatus::Cancelled;
-        payment.updated_at = Utc::now().to_rfc3339();
+            payment.status = PaymentStatus::Cancelled;
+            payment.updated_at = Utc::now().to_rfc3339();
+            payments::update(tx, &payment.to_record())?;
 
-        Ok(payment.clone())
+            Ok(payment)
+        })
     }
 
     /// Get payment metrics
     #[instrument(skip(self))]
     pub async fn get_metrics(&self) -> Result<PaymentMetrics> {
-        let payments = self.payments.read().await;
+        let counts = self.store.with_conn(payments::count_by_status)?;
+        let count =
+            |status: PaymentStatus| counts.get(status.as_str()).copied().unwrap_or(0) as usize;
         let retry_queue = self.retry_queue.read().await;
 
-        let total_payments = payments.len();
-        let pending_payments = payments
-            .values()
-            .filter(|p| p.status == PaymentStatus::Pending)
-            .count();
-        let processing_payments = payments
-            .values()
-            .filter(|p| p.status == PaymentStatus::Processing)
-            .count();
-        let succeeded_payments = payments
-            .values()
-            .filter(|p| p.status == PaymentStatus::Succeeded)
-            .count();
-        let failed_payments = payments
-            .values()
-            .filter(|p| p.status == PaymentStatus::Failed)
-            .count();
+        let total_payments = counts.values().sum::<u64>() as usize;
+        let pending_payments = count(PaymentStatus::Pending);
+        let processing_payments = count(PaymentStatus::Processing);
+        let succeeded_payments = count(PaymentStatus::Succeeded);
+        let failed_payments = count(PaymentStatus::Failed);
         let retry_queue_size = retry_queue.len();
 
         Ok(PaymentMetrics {
@@ -359,13 +402,10 @@ impl PaymentProcessor {
         status: PaymentStatus,
         error: Option<String>,
     ) -> Result<()> {
-        let mut payments = self.payments.write().await;
-        if let Some(payment) = payments.get_mut(payment_id) {
+        self.modify_payment(payment_id, |payment| {
             payment.status = status;
-            payment.updated_at = Utc::now().to_rfc3339();
             payment.error_message = error;
-        }
-        Ok(())
+        })
     }
 
     /// Update payment success
@@ -375,34 +415,41 @@ impl PaymentProcessor {
         payment_hash: String,
         status: String,
     ) -> Result<()> {
-        let mut payments = self.payments.write().await;
-        if let Some(payment) = payments.get_mut(payment_id) {
+        self.modify_payment(payment_id, |payment| {
             payment.status = PaymentStatus::Succeeded;
             payment.payment_hash = payment_hash;
-            payment.updated_at = Utc::now().to_rfc3339();
             payment.error_message = None;
-        }
-        Ok(())
+        })
     }
 
     /// Update payment failure
     async fn update_payment_failure(&self, payment_id: &str, error: String) -> Result<()> {
-        let mut payments = self.payments.write().await;
-        if let Some(payment) = payments.get_mut(payment_id) {
+        self.modify_payment(payment_id, |payment| {
             payment.status = PaymentStatus::Failed;
-            payment.updated_at = Utc::now().to_rfc3339();
             payment.error_message = Some(error);
-        }
-        Ok(())
+        })
+    }
+
+    /// Read-modify-write a stored payment in one transaction
+    fn modify_payment(&self, payment_id: &str, f: impl FnOnce(&mut Payment)) -> Result<()> {
+        self.store.transaction(|tx| {
+            let Some(record) = payments::get(tx, payment_id)? else {
+                return Ok(());
+            };
+            let mut payment = Payment::from_record(record)?;
+            f(&mut payment);
+            payment.updated_at = Utc::now().to_rfc3339();
+            payments::update(tx, &payment.to_record())
+        })
     }
 
     /// Get payment invoice
     async fn get_payment_invoice(&self, payment_id: &str) -> Result<String> {
-        let payments = self.payments.read().await;
-        let payment = payments
-            .get(payment_id)
+        let payment = self
+            .store
+            .with_conn(|c| payments::get(c, payment_id))?
             .ok_or_else(|| anyhow::anyhow!("Payment not found"))?;
-        Ok(payment.invoice.clone())
+        Ok(payment.invoice)
     }
 
     /// Generate unique payment ID
@@ -414,7 +461,7 @@ impl PaymentProcessor {
 impl Clone for PaymentProcessor {
     fn clone(&self) -> Self {
         Self {
-            payments: self.payments.clone(),
+            store: self.store.clone(),
             lightning_engine: self.lightning_engine.clone(),
             retry_queue: self.retry_queue.clone(),
             max_retries: self.max_retries,
@@ -442,7 +489,8 @@ mod tests {
     #[tokio::test]
     async fn test_payment_processor_creation() {
         let temp_dir = tempdir().unwrap();
-        let processor = PaymentProcessor::new(temp_dir.path().to_path_buf(), Network::Testnet);
+        let processor =
+            PaymentProcessor::new(temp_dir.path().to_path_buf(), Network::Testnet).unwrap();
 
         assert_eq!(processor.max_retries, 3);
         assert_eq!(processor.retry_delay, Duration::from_secs(5));
@@ -451,7 +499,8 @@ mod tests {
     #[tokio::test]
     async fn test_payment_creation() {
         let temp_dir = tempdir().unwrap();
-        let processor = PaymentProcessor::new(temp_dir.path().to_path_buf(), Network::Testnet);
+        let processor =
+            PaymentProcessor::new(temp_dir.path().to_path_buf(), Network::Testnet).unwrap();
 
         let payment = processor
             .process_payment(
@@ -1,4 +1,6 @@
 // This file is @generated by prost-build.
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PaymentRequest {
     #[prost(string, tag = "1")]
@@ -11,29 +13,105 @@ pub struct PaymentRequest {
     pub invoice: ::prost::alloc::string::String,
     #[prost(string, tag = "5")]
     pub description: ::prost::alloc::string::String,
+    /// pay even if an invoice with the same payment_hash was already paid
+    #[prost(bool, tag = "6")]
+    pub force: bool,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PaymentStatusRequest {
     #[prost(string, tag = "1")]
     pub payment_id: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RefundRequest {
     #[prost(string, tag = "1")]
     pub payment_id: ::prost::alloc::string::String,
+    /// 0 refunds the remaining amount
     #[prost(uint64, tag = "2")]
     pub amount_sats: u64,
+    #[prost(enumeration = "RefundMethod", tag = "3")]
+    pub method: i32,
+    /// payer's BOLT11 invoice for REFUND_METHOD_BOLT11_INVOICE
+    #[prost(string, tag = "4")]
+    pub invoice: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LnurlWithdrawRequest {
+    #[prost(string, tag = "1")]
+    pub k1: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LnurlWithdrawResponse {
+    #[prost(string, tag = "1")]
+    pub tag: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub callback: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub k1: ::prost::alloc::string::String,
+    /// msat
+    #[prost(uint64, tag = "4")]
+    pub min_withdrawable: u64,
+    /// msat
+    #[prost(uint64, tag = "5")]
+    pub max_withdrawable: u64,
+    #[prost(string, tag = "6")]
+    pub default_description: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClaimRefundRequest {
+    #[prost(string, tag = "1")]
+    pub k1: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub invoice: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RefundInfo {
+    #[prost(string, tag = "1")]
+    pub refund_id: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub amount_sats: u64,
+    #[prost(enumeration = "RefundMethod", tag = "3")]
+    pub method: i32,
+    /// AWAITING_CLAIM / PENDING / SUCCEEDED / FAILED / EXPIRED
+    #[prost(string, tag = "4")]
+    pub status: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub lnurl: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub bolt12_refund: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub payment_hash: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub created_at: ::prost::alloc::string::String,
+    #[prost(string, tag = "9")]
+    pub expires_at: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PaymentStreamRequest {
     #[prost(string, tag = "1")]
     pub wallet_id: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PaymentResponse {
     #[prost(string, tag = "1")]
     pub payment_id: ::prost::alloc::string::String,
-    /// COMPLETED / PENDING / FAILED / REFUNDED
+    /// SUCCEEDED / PENDING / FAILED / PARTIALLY_REFUNDED / REFUNDED
     #[prost(string, tag = "2")]
     pub status: ::prost::alloc::string::String,
     #[prost(string, tag = "3")]
@@ -44,7 +122,13 @@ pub struct PaymentResponse {
     pub payment_hash: ::prost::alloc::string::String,
     #[prost(string, tag = "6")]
     pub timestamp: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "7")]
+    pub refunded_sats: u64,
+    #[prost(message, repeated, tag = "8")]
+    pub refunds: ::prost::alloc::vec::Vec<RefundInfo>,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PaymentStreamResponse {
     #[prost(string, tag = "1")]
@@ -58,6 +142,39 @@ pub struct PaymentStreamResponse {
     #[prost(string, tag = "5")]
     pub timestamp: ::prost::alloc::string::String,
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum RefundMethod {
+    /// defaults to LNURL-withdraw
+    Unspecified = 0,
+    LnurlWithdraw = 1,
+    Bolt11Invoice = 2,
+    Bolt12Refund = 3,
+}
+impl RefundMethod {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "REFUND_METHOD_UNSPECIFIED",
+            Self::LnurlWithdraw => "REFUND_METHOD_LNURL_WITHDRAW",
+            Self::Bolt11Invoice => "REFUND_METHOD_BOLT11_INVOICE",
+            Self::Bolt12Refund => "REFUND_METHOD_BOLT12_REFUND",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "REFUND_METHOD_UNSPECIFIED" => Some(Self::Unspecified),
+            "REFUND_METHOD_LNURL_WITHDRAW" => Some(Self::LnurlWithdraw),
+            "REFUND_METHOD_BOLT11_INVOICE" => Some(Self::Bolt11Invoice),
+            "REFUND_METHOD_BOLT12_REFUND" => Some(Self::Bolt12Refund),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod payment_service_client {
     #![allow(
@@ -236,6 +353,65 @@ pub mod payment_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        /// LNURL-withdraw flow for refunds: serve the withdrawRequest, then pay the submitted invoice
+        pub async fn get_lnurl_withdraw(
+            &mut self,
+            request: impl tonic::IntoRequest<super::LnurlWithdrawRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::LnurlWithdrawResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/satsconnect.payment.v1.PaymentService/GetLnurlWithdraw",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "satsconnect.payment.v1.PaymentService",
+                        "GetLnurlWithdraw",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn claim_refund(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ClaimRefundRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::PaymentResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/satsconnect.payment.v1.PaymentService/ClaimRefund",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "satsconnect.payment.v1.PaymentService",
+                        "ClaimRefund",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn payment_stream(
             &mut self,
             request: impl tonic::IntoRequest<super::PaymentStreamRequest>,
@@ -292,6 +468,18 @@ pub mod payment_service_server {
             &self,
             request: tonic::Request<super::RefundRequest>,
         ) -> std::result::Result<tonic::Response<super::PaymentResponse>, tonic::Status>;
+        /// LNURL-withdraw flow for refunds: serve the withdrawRequest, then pay the submitted invoice
+        async fn get_lnurl_withdraw(
+            &self,
+            request: tonic::Request<super::LnurlWithdrawRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::LnurlWithdrawResponse>,
+            tonic::Status,
+        >;
+        async fn claim_refund(
+            &self,
+            request: tonic::Request<super::ClaimRefundRequest>,
+        ) -> std::result::Result<tonic::Response<super::PaymentResponse>, tonic::Status>;
         /// Server streaming response type for the PaymentStream method.
         type PaymentStreamStream: tonic::codegen::tokio_stream::Stream<
                 Item = std::result::Result<super::PaymentStreamResponse, tonic::Status>,
@@ -519,6 +707,97 @@ pub mod payment_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/satsconnect.payment.v1.PaymentService/GetLnurlWithdraw" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetLnurlWithdrawSvc<T: PaymentService>(pub Arc<T>);
+                    impl<
+                        T: PaymentService,
+                    > tonic::server::UnaryService<super::LnurlWithdrawRequest>
+                    for GetLnurlWithdrawSvc<T> {
+                        type Response = super::LnurlWithdrawResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::LnurlWithdrawRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as PaymentService>::get_lnurl_withdraw(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetLnurlWithdrawSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/satsconnect.payment.v1.PaymentService/ClaimRefund" => {
+                    #[allow(non_camel_case_types)]
+                    struct ClaimRefundSvc<T: PaymentService>(pub Arc<T>);
+                    impl<
+                        T: PaymentService,
+                    > tonic::server::UnaryService<super::ClaimRefundRequest>
+                    for ClaimRefundSvc<T> {
+                        type Response = super::PaymentResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ClaimRefundRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as PaymentService>::claim_refund(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ClaimRefundSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/satsconnect.payment.v1.PaymentService/PaymentStream" => {
                     #[allow(non_camel_case_types)]
                     struct PaymentStreamSvc<T: PaymentService>(pub Arc<T>);
@@ -1,4 +1,6 @@
 // This file is @generated by prost-build.
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CreateWalletRequest {
     /// optional mnemonic; if empty, server generates and returns one
@@ -8,6 +10,8 @@ pub struct CreateWalletRequest {
     #[prost(string, tag = "2")]
     pub label: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CreateWalletResponse {
     /// SECURITY: Do not return mnemonic/seed
@@ -19,8 +23,12 @@ pub struct CreateWalletResponse {
     #[prost(string, tag = "3")]
     pub address: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct GetBalanceRequest {}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct GetBalanceResponse {
     #[prost(uint64, tag = "1")]
@@ -29,6 +37,34 @@ pub struct GetBalanceResponse {
     #[prost(uint64, tag = "2")]
     pub lightning_sats: u64,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct QuoteInvoiceRequest {
+    #[prost(uint64, tag = "1")]
+    pub amount_sats: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QuoteInvoiceResponse {
+    /// receiving needs a just-in-time channel from the LSP
+    #[prost(bool, tag = "1")]
+    pub jit_channel: bool,
+    /// deducted by the LSP from the payment when it opens the channel
+    #[prost(uint64, tag = "2")]
+    pub lsp_fee_sats: u64,
+    /// amount left after the LSP fee
+    #[prost(uint64, tag = "3")]
+    pub receive_sats: u64,
+    #[prost(string, tag = "4")]
+    pub lsp: ::prost::alloc::string::String,
+    /// RFC 3339
+    #[prost(string, tag = "5")]
+    pub fee_valid_until: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct NewInvoiceRequest {
     /// 0 for amountless invoice
@@ -36,7 +72,13 @@ pub struct NewInvoiceRequest {
     pub amount_sats: u64,
     #[prost(string, tag = "2")]
     pub memo: ::prost::alloc::string::String,
+    /// highest LSP fee the user accepted from QuoteInvoice; a higher fee fails
+    /// with FAILED_PRECONDITION (LSP_FEE_NOT_ACCEPTED) and the fee in metadata
+    #[prost(uint64, tag = "3")]
+    pub max_lsp_fee_sats: u64,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct NewInvoiceResponse {
     /// BOLT11
@@ -44,12 +86,35 @@ pub struct NewInvoiceResponse {
     pub invoice: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub payment_hash: ::prost::alloc::string::String,
+    /// carries the LSP's route hint
+    #[prost(bool, tag = "3")]
+    pub jit_channel: bool,
+    #[prost(uint64, tag = "4")]
+    pub lsp_fee_sats: u64,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SendPaymentRequest {
     #[prost(string, tag = "1")]
     pub invoice: ::prost::alloc::string::String,
+    /// Routing fee ceiling; when both limits are set the stricter one applies
+    #[prost(uint64, tag = "2")]
+    pub fee_limit_sats: u64,
+    #[prost(uint32, tag = "3")]
+    pub fee_limit_ppm: u32,
+    /// 0 uses the engine default
+    #[prost(uint32, tag = "4")]
+    pub timeout_seconds: u32,
+    /// maximum number of MPP parts; 0 uses the LDK default
+    #[prost(uint32, tag = "5")]
+    pub max_parts: u32,
+    /// maximum total CLTV expiry delta; 0 uses the LDK default
+    #[prost(uint32, tag = "6")]
+    pub max_cltv_delta: u32,
 }
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SendPaymentResponse {
     #[prost(string, tag = "1")]
@@ -57,6 +122,84 @@ pub struct SendPaymentResponse {
     /// SUCCEEDED / PENDING / FAILED
     #[prost(string, tag = "2")]
     pub status: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "3")]
+    pub fee_paid_sats: u64,
+    /// set when status is FAILED or PENDING after a timeout
+    #[prost(enumeration = "PaymentFailureReason", tag = "4")]
+    pub failure_reason: i32,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EstimateRoutingFeeRequest {
+    /// exactly one of invoice or pubkey must be set
+    ///
+    /// BOLT11
+    #[prost(string, tag = "1")]
+    pub invoice: ::prost::alloc::string::String,
+    /// hex node id, requires amount_sats
+    #[prost(string, tag = "2")]
+    pub pubkey: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "3")]
+    pub amount_sats: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", default)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct EstimateRoutingFeeResponse {
+    #[prost(uint64, tag = "1")]
+    pub fee_sats: u64,
+    #[prost(uint64, tag = "2")]
+    pub fee_msat: u64,
+    #[prost(uint32, tag = "3")]
+    pub hop_count: u32,
+    /// 0.0 - 1.0
+    #[prost(double, tag = "4")]
+    pub success_probability: f64,
+    /// served from a recent estimate
+    #[prost(bool, tag = "5")]
+    pub cached: bool,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum PaymentFailureReason {
+    Unspecified = 0,
+    NoRoute = 1,
+    FeeTooHigh = 2,
+    Timeout = 3,
+    RecipientRejected = 4,
+    InvoiceExpired = 5,
+    RetriesExhausted = 6,
+}
+impl PaymentFailureReason {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "PAYMENT_FAILURE_REASON_UNSPECIFIED",
+            Self::NoRoute => "PAYMENT_FAILURE_REASON_NO_ROUTE",
+            Self::FeeTooHigh => "PAYMENT_FAILURE_REASON_FEE_TOO_HIGH",
+            Self::Timeout => "PAYMENT_FAILURE_REASON_TIMEOUT",
+            Self::RecipientRejected => "PAYMENT_FAILURE_REASON_RECIPIENT_REJECTED",
+            Self::InvoiceExpired => "PAYMENT_FAILURE_REASON_INVOICE_EXPIRED",
+            Self::RetriesExhausted => "PAYMENT_FAILURE_REASON_RETRIES_EXHAUSTED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "PAYMENT_FAILURE_REASON_UNSPECIFIED" => Some(Self::Unspecified),
+            "PAYMENT_FAILURE_REASON_NO_ROUTE" => Some(Self::NoRoute),
+            "PAYMENT_FAILURE_REASON_FEE_TOO_HIGH" => Some(Self::FeeTooHigh),
+            "PAYMENT_FAILURE_REASON_TIMEOUT" => Some(Self::Timeout),
+            "PAYMENT_FAILURE_REASON_RECIPIENT_REJECTED" => Some(Self::RecipientRejected),
+            "PAYMENT_FAILURE_REASON_INVOICE_EXPIRED" => Some(Self::InvoiceExpired),
+            "PAYMENT_FAILURE_REASON_RETRIES_EXHAUSTED" => Some(Self::RetriesExhausted),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod wallet_service_client {
@@ -204,6 +347,32 @@ pub mod wallet_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn quote_invoice(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QuoteInvoiceRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::QuoteInvoiceResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/satsconnect.wallet.v1.WalletService/QuoteInvoice",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("satsconnect.wallet.v1.WalletService", "QuoteInvoice"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn new_invoice(
             &mut self,
             request: impl tonic::IntoRequest<super::NewInvoiceRequest>,
@@ -256,6 +425,35 @@ pub mod wallet_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn estimate_routing_fee(
+            &mut self,
+            request: impl tonic::IntoRequest<super::EstimateRoutingFeeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::EstimateRoutingFeeResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/satsconnect.wallet.v1.WalletService/EstimateRoutingFee",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "satsconnect.wallet.v1.WalletService",
+                        "EstimateRoutingFee",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -285,6 +483,13 @@ pub mod wallet_service_server {
             tonic::Response<super::GetBalanceResponse>,
             tonic::Status,
         >;
+        async fn quote_invoice(
+            &self,
+            request: tonic::Request<super::QuoteInvoiceRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::QuoteInvoiceResponse>,
+            tonic::Status,
+        >;
         async fn new_invoice(
             &self,
             request: tonic::Request<super::NewInvoiceRequest>,
@@ -299,6 +504,13 @@ pub mod wallet_service_server {
             tonic::Response<super::SendPaymentResponse>,
             tonic::Status,
         >;
+        async fn estimate_routing_fee(
+            &self,
+            request: tonic::Request<super::EstimateRoutingFeeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::EstimateRoutingFeeResponse>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
     pub struct WalletServiceServer<T> {
@@ -466,6 +678,51 @@ pub mod wallet_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/satsconnect.wallet.v1.WalletService/QuoteInvoice" => {
+                    #[allow(non_camel_case_types)]
+                    struct QuoteInvoiceSvc<T: WalletService>(pub Arc<T>);
+                    impl<
+                        T: WalletService,
+                    > tonic::server::UnaryService<super::QuoteInvoiceRequest>
+                    for QuoteInvoiceSvc<T> {
+                        type Response = super::QuoteInvoiceResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QuoteInvoiceRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WalletService>::quote_invoice(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = QuoteInvoiceSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/satsconnect.wallet.v1.WalletService/NewInvoice" => {
                     #[allow(non_camel_case_types)]
                     struct NewInvoiceSvc<T: WalletService>(pub Arc<T>);
@@ -556,6 +813,52 @@ pub mod wallet_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/satsconnect.wallet.v1.WalletService/EstimateRoutingFee" => {
+                    #[allow(non_camel_case_types)]
+                    struct EstimateRoutingFeeSvc<T: WalletService>(pub Arc<T>);
+                    impl<
+                        T: WalletService,
+                    > tonic::server::UnaryService<super::EstimateRoutingFeeRequest>
+                    for EstimateRoutingFeeSvc<T> {
+                        type Response = super::EstimateRoutingFeeResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::EstimateRoutingFeeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WalletService>::estimate_routing_fee(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = EstimateRoutingFeeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         let mut response = http::Response::new(empty_body());
@@ -0,0 +1,254 @@
+//! Token bucket rate limiting for the engine API.
+//!
+//! Every call is charged to each client it can be attributed to (API token,
+//! client IP, wallet), with one bucket per client and RPC. A call goes through
+//! only if all of its buckets have a token left; a refused call is charged to
+//! none of them.
+
+use crate::engine_config::{RateLimit, RateLimitConfig};
+use crate::monitoring::MetricsCollector;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Counter incremented for every rejected call, labelled by `method` and `key`
+pub const REJECTED_METRIC: &str = "api_rate_limited_total";
+
+/// Buckets kept before idle ones are dropped
+const MAX_BUCKETS: usize = 10_000;
+
+/// A client a call is charged to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClientKey {
+    /// Id of the API token; attenuated copies share their parent's id
+    Token(String),
+    Ip(IpAddr),
+    Wallet(String),
+}
+
+impl ClientKey {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientKey::Token(_) => "token",
+            ClientKey::Ip(_) => "ip",
+            ClientKey::Wallet(_) => "wallet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Too many {method} calls for this {key}, retry in {}s", self.retry_after_secs())]
+pub struct RateLimitError {
+    pub method: String,
+    /// Kind of client that ran out, see [`ClientKey::kind`]
+    pub key: &'static str,
+    pub retry_after: Duration,
+}
+
+impl RateLimitError {
+    /// Whole seconds until the call would be accepted, at least one
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * refill_rate(limit)).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    /// Time until a token is available
+    fn wait(&self, limit: RateLimit) -> Duration {
+        Duration::from_secs_f64(((1.0 - self.tokens) / refill_rate(limit)).max(0.0))
+    }
+}
+
+/// Tokens added per second
+fn refill_rate(limit: RateLimit) -> f64 {
+    limit.per_minute as f64 / 60.0
+}
+
+/// Token buckets for every client and RPC seen recently
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(ClientKey, String), Bucket>>,
+    metrics: Option<Arc<MetricsCollector>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            metrics: None,
+        }
+    }
+
+    /// Count rejected calls in `metrics` as [`REJECTED_METRIC`]
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Charge a call of `method` to every client in `keys`, or refuse it
+    /// without charging anyone if one of them is out of tokens
+    pub async fn check(&self, method: &str, keys: &[ClientKey]) -> Result<(), RateLimitError> {
+        if !self.config.enabled || keys.is_empty() {
+            return Ok(());
+        }
+
+        let result = self.take(method, keys, Instant::now());
+        if let Err(e) = &result {
+            warn!("Rate limited {} call by {}", method, e.key);
+            if let Some(metrics) = &self.metrics {
+                let labels = HashMap::from([
+                    ("method".to_string(), method.to_string()),
+                    ("key".to_string(), e.key.to_string()),
+                ]);
+                if let Err(e) = metrics.increment_counter(REJECTED_METRIC, labels).await {
+                    warn!("Failed to record rate limit metric: {}", e);
+                }
+            }
+        }
+        result
+    }
+
+    fn take(&self, method: &str, keys: &[ClientKey], now: Instant) -> Result<(), RateLimitError> {
+        let limit = self.config.limit(method);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_BUCKETS {
+            self.drop_idle(&mut buckets, now);
+        }
+
+        let mut exhausted: Option<(&ClientKey, Duration)> = None;
+        for key in keys {
+            let bucket = buckets
+                .entry((key.clone(), method.to_string()))
+                .or_insert_with(|| Bucket::full(limit, now));
+            bucket.refill(limit, now);
+            if bucket.tokens < 1.0 {
+                let wait = bucket.wait(limit);
+                if exhausted.is_none_or(|(_, longest)| wait > longest) {
+                    exhausted = Some((key, wait));
+                }
+            }
+        }
+
+        if let Some((key, retry_after)) = exhausted {
+            return Err(RateLimitError {
+                method: method.to_string(),
+                key: key.kind(),
+                retry_after,
+            });
+        }
+
+        for key in keys {
+            if let Some(bucket) = buckets.get_mut(&(key.clone(), method.to_string())) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget buckets that have refilled completely; they behave exactly like
+    /// new ones
+    fn drop_idle(&self, buckets: &mut HashMap<(ClientKey, String), Bucket>, now: Instant) {
+        buckets.retain(|(_, method), bucket| {
+            let limit = self.config.limit(method);
+            bucket.refill(limit, now);
+            bucket.tokens < limit.burst as f64
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        let mut config = RateLimitConfig::default();
+        config.rpc.insert(
+            "SendPayment".to_string(),
+            RateLimit {
+                burst: 2,
+                per_minute: 60,
+            },
+        );
+        RateLimiter::new(config)
+    }
+
+    #[test]
+    fn test_burst_then_refill() {
+        let limiter = limiter();
+        let token = [ClientKey::Token("t1".to_string())];
+        let start = Instant::now();
+
+        assert!(limiter.take("SendPayment", &token, start).is_ok());
+        assert!(limiter.take("SendPayment", &token, start).is_ok());
+        let err = limiter.take("SendPayment", &token, start).unwrap_err();
+        assert_eq!(err.key, "token");
+        assert_eq!(err.retry_after_secs(), 1);
+
+        // Other RPCs have their own buckets
+        assert!(limiter.take("GetBalance", &token, start).is_ok());
+
+        // One token per second comes back
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.take("SendPayment", &token, later).is_ok());
+        assert!(limiter.take("SendPayment", &token, later).is_err());
+    }
+
+    #[test]
+    fn test_rejected_call_charges_nobody() {
+        let limiter = limiter();
+        let now = Instant::now();
+        let ip = ClientKey::Ip("203.0.113.7".parse().unwrap());
+        let first = ClientKey::Token("first".to_string());
+        let second = [ClientKey::Token("second".to_string())];
+
+        // Calls with the first token use up the shared IP's bucket
+        for _ in 0..2 {
+            assert!(limiter
+                .take("SendPayment", &[first.clone(), ip.clone()], now)
+                .is_ok());
+        }
+        let err = limiter
+            .take("SendPayment", &[second[0].clone(), ip], now)
+            .unwrap_err();
+        assert_eq!(err.key, "ip");
+
+        // The refused call did not spend the second token's budget
+        assert!(limiter.take("SendPayment", &second, now).is_ok());
+        assert!(limiter.take("SendPayment", &second, now).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_limiter_allows_everything() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            enabled: false,
+            ..RateLimitConfig::default()
+        });
+        let keys = [ClientKey::Wallet("w1".to_string())];
+        for _ in 0..10 {
+            assert!(limiter.check("CreateWallet", &keys).await.is_ok());
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::Result;
+use bitcoin::bech32::{self, Bech32, Hrp};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How long an issued refund can be claimed by the payer
+pub const REFUND_CLAIM_EXPIRY_SECS: u32 = 24 * 60 * 60;
+
+/// How the payer collects a refund
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RefundMethod {
+    /// Merchant issues an LNURL-withdraw link the payer's wallet claims
+    LnurlWithdraw,
+    /// Payer supplies a BOLT11 invoice that the merchant pays
+    Bolt11Invoice,
+    /// Merchant issues a BOLT12 refund the payer's wallet claims
+    Bolt12Refund,
+}
+
+/// Lifecycle of a single refund
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RefundStatus {
+    /// Issued, waiting for the payer to claim it
+    AwaitingClaim,
+    /// Refund payment is in flight
+    Pending,
+    Succeeded,
+    Failed,
+    /// Claim window elapsed without the payer claiming it
+    Expired,
+}
+
+/// A refund issued against a received payment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Refund {
+    pub refund_id: String,
+    pub amount_sats: u64,
+    pub method: RefundMethod,
+    pub status: RefundStatus,
+    /// LNURL-withdraw secret identifying the claim
+    pub k1: Option<String>,
+    /// Bech32 LNURL-withdraw link handed to the payer
+    pub lnurl: Option<String>,
+    /// BOLT12 refund string handed to the payer
+    pub bolt12_refund: Option<String>,
+    /// BOLT11 invoice the refund was paid to
+    pub invoice: Option<String>,
+    pub payment_hash: Option<String>,
+    /// LDK payment id (hex) of the outgoing refund payment
+    pub ldk_payment_id: Option<String>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+}
+
+/// LUD-03 `withdrawRequest` response served for an LNURL-withdraw refund
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LnurlWithdrawRequest {
+    pub tag: String,
+    pub callback: String,
+    pub k1: String,
+    pub min_withdrawable: u64,
+    pub max_withdrawable: u64,
+    pub default_description: String,
+}
+
+impl RefundMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LnurlWithdraw => "LNURL_WITHDRAW",
+            Self::Bolt11Invoice => "BOLT11_INVOICE",
+            Self::Bolt12Refund => "BOLT12_REFUND",
+        }
+    }
+}
+
+impl RefundStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AwaitingClaim => "AWAITING_CLAIM",
+            Self::Pending => "PENDING",
+            Self::Succeeded => "SUCCEEDED",
+            Self::Failed => "FAILED",
+            Self::Expired => "EXPIRED",
+        }
+    }
+}
+
+impl Refund {
+    pub fn new(amount_sats: u64, method: RefundMethod) -> Self {
+        Self {
+            refund_id: format!("ref_{}", &uuid::Uuid::new_v4().to_string()[..8]),
+            amount_sats,
+            method,
+            status: RefundStatus::AwaitingClaim,
+            k1: None,
+            lnurl: None,
+            bolt12_refund: None,
+            invoice: None,
+            payment_hash: None,
+            ldk_payment_id: None,
+            created_at: Utc::now().to_rfc3339(),
+            expires_at: None,
+        }
+    }
+
+    /// Whether the refund still counts against the refundable amount
+    pub fn is_active(&self) -> bool {
+        !matches!(self.status, RefundStatus::Failed | RefundStatus::Expired)
+    }
+
+    /// Whether an unclaimed refund has passed its claim window
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.status == RefundStatus::AwaitingClaim
+            && self
+                .expires_at
+                .as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+/// Sats of `amount_sats` not yet covered by succeeded or outstanding refunds
+pub fn refundable_sats(amount_sats: u64, refunds: &[Refund]) -> u64 {
+    let committed: u64 = refunds
+        .iter()
+        .filter(|r| r.is_active())
+        .map(|r| r.amount_sats)
+        .sum();
+    amount_sats.saturating_sub(committed)
+}
+
+/// Sats actually returned to the payer
+pub fn refunded_sats(refunds: &[Refund]) -> u64 {
+    refunds
+        .iter()
+        .filter(|r| r.status == RefundStatus::Succeeded)
+        .map(|r| r.amount_sats)
+        .sum()
+}
+
+/// Encode a URL as a bech32 LNURL (LUD-01)
+pub fn encode_lnurl(url: &str) -> Result<String> {
+    let hrp = Hrp::parse("lnurl")?;
+    Ok(bech32::encode_upper::<Bech32>(hrp, url.as_bytes())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refund(amount_sats: u64, status: RefundStatus) -> Refund {
+        Refund {
+            status,
+            ..Refund::new(amount_sats, RefundMethod::Bolt11Invoice)
+        }
+    }
+
+    #[test]
+    fn test_partial_refund_accounting() {
+        let refunds = vec![
+            refund(300, RefundStatus::Succeeded),
+            refund(200, RefundStatus::AwaitingClaim),
+            refund(400, RefundStatus::Failed),
+            refund(100, RefundStatus::Expired),
+        ];
+
+        // Failed and expired refunds free their amount up again
+        assert_eq!(refundable_sats(1_000, &refunds), 500);
+        assert_eq!(refunded_sats(&refunds), 300);
+        assert_eq!(refundable_sats(400, &refunds), 0);
+    }
+
+    #[test]
+    fn test_claim_expiry() {
+        let now = Utc::now();
+        let mut refund = refund(100, RefundStatus::AwaitingClaim);
+        assert!(!refund.is_expired(now));
+
+        refund.expires_at = Some((now - chrono::Duration::seconds(1)).to_rfc3339());
+        assert!(refund.is_expired(now));
+
+        refund.status = RefundStatus::Succeeded;
+        assert!(!refund.is_expired(now));
+    }
+
+    #[test]
+    fn test_encode_lnurl() {
+        // Example from LUD-01
+        let lnurl = encode_lnurl(
+            "https://service.com/api?q=3fc3645b439ce8e7f2553a69e5267081d96dcd340693afabe04be7b0ccd178df",
+        )
+        .unwrap();
+        assert_eq!(
+            lnurl,
+            "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS"
+        );
+    }
+}
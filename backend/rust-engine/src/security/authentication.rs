@@ -1,3 +1,4 @@
+use super::scoped_token::{Caveat, ScopedToken, ROOT_KEY_LEN, TOKEN_PREFIX};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -11,6 +12,52 @@ pub struct AuthToken {
     pub expires_at: SystemTime,
     pub permissions: Vec<String>,
     pub created_at: SystemTime,
+    /// Largest amount a single payment or refund may move
+    #[serde(default)]
+    pub max_amount_sats: Option<u64>,
+    /// Wallet the token is restricted to
+    #[serde(default)]
+    pub wallet_id: Option<String>,
+}
+
+/// Why a request was refused by API authentication
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Invalid token")]
+    InvalidToken,
+    #[error("Token expired")]
+    TokenExpired,
+    #[error("Token lacks the {0} permission")]
+    PermissionDenied(String),
+    #[error("Amount of {amount_sats} sats exceeds the token limit of {max_sats} sats")]
+    AmountLimitExceeded { amount_sats: u64, max_sats: u64 },
+    #[error("Token is not valid for wallet {0}")]
+    WalletNotAllowed(String),
+}
+
+impl AuthToken {
+    /// Refuse amounts above the token's limit
+    pub fn check_amount(&self, amount_sats: u64) -> Result<(), AuthError> {
+        match self.max_amount_sats {
+            Some(max_sats) if amount_sats > max_sats => Err(AuthError::AmountLimitExceeded {
+                amount_sats,
+                max_sats,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuse requests for wallets other than the one the token is bound to
+    pub fn check_wallet(&self, wallet_id: &str) -> Result<(), AuthError> {
+        match &self.wallet_id {
+            Some(allowed) if allowed != wallet_id => {
+                Err(AuthError::WalletNotAllowed(wallet_id.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,18 +113,31 @@ pub struct AuthenticationService {
     users: HashMap<String, User>,
     active_tokens: HashMap<String, AuthToken>,
     rate_limits: HashMap<String, (u32, SystemTime)>,
+    /// Key scoped tokens are signed with
+    root_key: [u8; ROOT_KEY_LEN],
 }
 
 impl AuthenticationService {
     pub fn new(config: AuthConfig) -> Self {
+        Self::with_root_key(config, rand::random())
+    }
+
+    /// Create a service that accepts scoped tokens signed with `root_key`
+    pub fn with_root_key(config: AuthConfig, root_key: [u8; ROOT_KEY_LEN]) -> Self {
         Self {
             config,
             users: HashMap::new(),
             active_tokens: HashMap::new(),
             rate_limits: HashMap::new(),
+            root_key,
         }
     }
 
+    /// Mint a scoped token; `caveats` restrict it, no caveats means full access
+    pub fn mint_token(&self, caveats: Vec<Caveat>) -> ScopedToken {
+        ScopedToken::mint(&self.root_key, uuid::Uuid::new_v4().to_string(), caveats)
+    }
+
     pub async fn register_user(
         &mut self,
         email: String,
@@ -174,6 +234,10 @@ impl AuthenticationService {
     }
 
     pub async fn validate_token(&self, token: &str) -> Result<AuthResult> {
+        if token.starts_with(TOKEN_PREFIX) {
+            return Ok(self.validate_scoped_token(token));
+        }
+
         let auth_token = match self.active_tokens.get(token) {
             Some(token) => token,
             None => return Ok(AuthResult::InvalidCredentials),
@@ -195,14 +259,42 @@ impl AuthenticationService {
     }
 
     pub async fn check_permission(&self, token: &str, permission: &str) -> Result<bool> {
-        let auth_token = match self.active_tokens.get(token) {
-            Some(token) => token,
-            None => return Ok(false),
+        let auth_token = match self.validate_token(token).await? {
+            AuthResult::Success(token) => token,
+            _ => return Ok(false),
         };
 
         Ok(auth_token.permissions.contains(&permission.to_string()))
     }
 
+    /// Verify a scoped token's signature and expiry; scoped tokens are
+    /// stateless, so they are never in `active_tokens`
+    fn validate_scoped_token(&self, token: &str) -> AuthResult {
+        let scoped = match ScopedToken::decode(token) {
+            Ok(scoped) if scoped.verify(&self.root_key).is_ok() => scoped,
+            _ => return AuthResult::InvalidCredentials,
+        };
+        let restrictions = match scoped.restrictions() {
+            Ok(restrictions) => restrictions,
+            Err(_) => return AuthResult::InvalidCredentials,
+        };
+
+        let expires_at = restrictions.expires_at.unwrap_or_else(far_future);
+        if SystemTime::now() > expires_at {
+            return AuthResult::TokenExpired;
+        }
+
+        AuthResult::Success(AuthToken {
+            token: token.to_string(),
+            user_id: scoped.id().to_string(),
+            expires_at,
+            permissions: restrictions.permissions,
+            created_at: UNIX_EPOCH,
+            max_amount_sats: restrictions.max_amount_sats,
+            wallet_id: restrictions.wallet_id,
+        })
+    }
+
     pub async fn logout_user(&mut self, token: &str) -> Result<()> {
         self.revoke_token(token).await
     }
@@ -228,6 +320,8 @@ impl AuthenticationService {
             expires_at,
             permissions: permissions.to_vec(),
             created_at: SystemTime::now(),
+            max_amount_sats: None,
+            wallet_id: None,
         })
     }
 
@@ -261,6 +355,11 @@ impl AuthenticationService {
     }
 }
 
+/// Expiry reported for scoped tokens without an expiry caveat
+fn far_future() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(u32::MAX as u64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -339,4 +438,56 @@ mod tests {
             _ => panic!("Should have failed with invalid credentials"),
         }
     }
+
+    #[tokio::test]
+    async fn test_scoped_token_permissions() {
+        use crate::security::scoped_token::permissions;
+
+        let auth_service = AuthenticationService::new(AuthConfig::default());
+        let token = auth_service
+            .mint_token(vec![
+                Caveat::Permissions(vec![
+                    permissions::READ.to_string(),
+                    permissions::SEND.to_string(),
+                ]),
+                Caveat::MaxAmountSats(1_000),
+            ])
+            .encode();
+
+        assert!(auth_service
+            .check_permission(&token, permissions::SEND)
+            .await
+            .unwrap());
+        assert!(!auth_service
+            .check_permission(&token, permissions::ADMIN)
+            .await
+            .unwrap());
+
+        match auth_service.validate_token(&token).await.unwrap() {
+            AuthResult::Success(auth_token) => {
+                assert!(auth_token.check_amount(1_000).is_ok());
+                assert!(auth_token.check_amount(1_001).is_err());
+                assert!(auth_token.check_wallet("any").is_ok());
+            }
+            _ => panic!("Scoped token should be valid"),
+        }
+
+        // Tokens from another engine are refused
+        let other = AuthenticationService::new(AuthConfig::default());
+        assert!(!other
+            .check_permission(&token, permissions::READ)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_expired_scoped_token() {
+        let auth_service = AuthenticationService::new(AuthConfig::default());
+        let token = auth_service.mint_token(vec![Caveat::ExpiresAt(1)]).encode();
+
+        assert!(matches!(
+            auth_service.validate_token(&token).await.unwrap(),
+            AuthResult::TokenExpired
+        ));
+    }
 }
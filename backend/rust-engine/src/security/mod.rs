@@ -1,7 +1,9 @@
 pub mod advanced;
 pub mod authentication;
 pub mod encryption;
+pub mod scoped_token;
 
 pub use advanced::{HsmConfig, HsmIntegration, HsmKey, HsmOperation};
-pub use authentication::{AuthResult, AuthToken, AuthenticationService};
+pub use authentication::{AuthError, AuthResult, AuthToken, AuthenticationService};
 pub use encryption::{EncryptionKey, EncryptionResult, EncryptionService};
+pub use scoped_token::{Caveat, ScopedToken};
@@ -0,0 +1,404 @@
+//! Macaroon-style bearer tokens.
+//!
+//! A token is an id plus a list of caveats, signed with an HMAC chain: the
+//! first link is keyed with the engine's root key, every caveat is then
+//! signed with the previous signature as key. Anyone holding a token can add
+//! caveats (attenuate it) without the root key, but nobody can remove one, so
+//! a token handed to a third-party app can only be narrowed further.
+
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Prefix of encoded tokens, to tell them apart from session tokens
+pub const TOKEN_PREFIX: &str = "sct1_";
+
+/// Size of the root key in bytes
+pub const ROOT_KEY_LEN: usize = 32;
+
+/// Permissions a token can carry
+pub mod permissions {
+    /// Balances, payment status, fee estimates
+    pub const READ: &str = "read";
+    /// Create invoices
+    pub const INVOICE: &str = "invoice";
+    /// Send payments
+    pub const SEND: &str = "send";
+    /// Refund received payments
+    pub const REFUND: &str = "refund";
+    /// Create and switch wallets
+    pub const ADMIN: &str = "admin";
+
+    pub const ALL: &[&str] = &[READ, INVOICE, SEND, REFUND, ADMIN];
+}
+
+/// A restriction on what a token may do; all caveats must hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caveat {
+    /// Only these permissions; several caveats intersect
+    Permissions(Vec<String>),
+    /// Unix timestamp after which the token is refused
+    ExpiresAt(u64),
+    /// Largest amount a single payment or refund may move
+    MaxAmountSats(u64),
+    /// Only requests for this wallet
+    WalletId(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    #[error("Malformed token")]
+    Malformed,
+    #[error("Invalid token signature")]
+    InvalidSignature,
+    #[error("Unknown caveat: {0}")]
+    UnknownCaveat(String),
+    #[error("Token is restricted to more than one wallet")]
+    ConflictingWallets,
+}
+
+impl fmt::Display for Caveat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Caveat::Permissions(permissions) => write!(f, "permissions={}", permissions.join(",")),
+            Caveat::ExpiresAt(timestamp) => write!(f, "expires_at={}", timestamp),
+            Caveat::MaxAmountSats(amount) => write!(f, "max_amount_sats={}", amount),
+            Caveat::WalletId(wallet_id) => write!(f, "wallet_id={}", wallet_id),
+        }
+    }
+}
+
+impl FromStr for Caveat {
+    type Err = TokenError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, TokenError> {
+        let unknown = || TokenError::UnknownCaveat(s.to_string());
+        let (key, value) = s.split_once('=').ok_or_else(unknown)?;
+        match key {
+            "permissions" => Ok(Caveat::Permissions(
+                value
+                    .split(',')
+                    .filter(|p| !p.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            )),
+            "expires_at" => value.parse().map(Caveat::ExpiresAt).map_err(|_| unknown()),
+            "max_amount_sats" => value
+                .parse()
+                .map(Caveat::MaxAmountSats)
+                .map_err(|_| unknown()),
+            "wallet_id" if !value.is_empty() => Ok(Caveat::WalletId(value.to_string())),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+impl Caveat {
+    /// Expiry `duration` from now
+    pub fn expires_in(duration: Duration) -> Self {
+        Caveat::ExpiresAt(
+            (SystemTime::now() + duration)
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        )
+    }
+}
+
+/// What a token allows once all its caveats are combined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Restrictions {
+    pub permissions: Vec<String>,
+    pub expires_at: Option<SystemTime>,
+    pub max_amount_sats: Option<u64>,
+    pub wallet_id: Option<String>,
+}
+
+/// A signed token with its caveats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopedToken {
+    id: String,
+    caveats: Vec<Caveat>,
+    signature: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize)]
+struct Encoded {
+    id: String,
+    caveats: Vec<String>,
+    signature: String,
+}
+
+impl ScopedToken {
+    /// Mint a token signed with the root key
+    pub fn mint(root_key: &[u8], id: impl Into<String>, caveats: Vec<Caveat>) -> Self {
+        let id = id.into();
+        let mut token = Self {
+            signature: sign(root_key, id.as_bytes()),
+            id,
+            caveats: Vec::new(),
+        };
+        for caveat in caveats {
+            token = token.attenuate(caveat);
+        }
+        token
+    }
+
+    /// Add a caveat; works without the root key
+    pub fn attenuate(mut self, caveat: Caveat) -> Self {
+        self.signature = sign(&self.signature, caveat.to_string().as_bytes());
+        self.caveats.push(caveat);
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn caveats(&self) -> &[Caveat] {
+        &self.caveats
+    }
+
+    /// Check the signature chain against the root key
+    pub fn verify(&self, root_key: &[u8]) -> std::result::Result<(), TokenError> {
+        let mut messages: Vec<Vec<u8>> = std::iter::once(self.id.clone().into_bytes())
+            .chain(self.caveats.iter().map(|c| c.to_string().into_bytes()))
+            .collect();
+        let last = messages.pop().expect("the id is always signed");
+        let key = messages.iter().fold(root_key.to_vec(), |key, message| {
+            sign(&key, message).to_vec()
+        });
+
+        // Compare the final link in constant time
+        let mut mac = HmacSha256::new_from_slice(&key).expect("HMAC accepts any key length");
+        mac.update(&last);
+        mac.verify_slice(&self.signature)
+            .map_err(|_| TokenError::InvalidSignature)
+    }
+
+    /// Combine the caveats; a token without a permissions caveat has all
+    /// permissions
+    pub fn restrictions(&self) -> std::result::Result<Restrictions, TokenError> {
+        let mut restrictions = Restrictions {
+            permissions: permissions::ALL.iter().map(|p| p.to_string()).collect(),
+            expires_at: None,
+            max_amount_sats: None,
+            wallet_id: None,
+        };
+
+        for caveat in &self.caveats {
+            match caveat {
+                Caveat::Permissions(allowed) => {
+                    restrictions.permissions.retain(|p| allowed.contains(p));
+                }
+                Caveat::ExpiresAt(timestamp) => {
+                    let expires_at = UNIX_EPOCH + Duration::from_secs(*timestamp);
+                    restrictions.expires_at = Some(
+                        restrictions
+                            .expires_at
+                            .map_or(expires_at, |e| e.min(expires_at)),
+                    );
+                }
+                Caveat::MaxAmountSats(amount) => {
+                    restrictions.max_amount_sats = Some(
+                        restrictions
+                            .max_amount_sats
+                            .map_or(*amount, |a| a.min(*amount)),
+                    );
+                }
+                Caveat::WalletId(wallet_id) => match &restrictions.wallet_id {
+                    Some(existing) if existing != wallet_id => {
+                        return Err(TokenError::ConflictingWallets)
+                    }
+                    _ => restrictions.wallet_id = Some(wallet_id.clone()),
+                },
+            }
+        }
+        Ok(restrictions)
+    }
+
+    pub fn encode(&self) -> String {
+        let encoded = Encoded {
+            id: self.id.clone(),
+            caveats: self.caveats.iter().map(|c| c.to_string()).collect(),
+            signature: hex::encode(self.signature),
+        };
+        let json = serde_json::to_vec(&encoded).expect("token serializes");
+        format!("{}{}", TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(json))
+    }
+
+    pub fn decode(token: &str) -> std::result::Result<Self, TokenError> {
+        let body = token
+            .strip_prefix(TOKEN_PREFIX)
+            .ok_or(TokenError::Malformed)?;
+        let json = URL_SAFE_NO_PAD
+            .decode(body)
+            .map_err(|_| TokenError::Malformed)?;
+        let encoded: Encoded = serde_json::from_slice(&json).map_err(|_| TokenError::Malformed)?;
+
+        let signature = hex::decode(&encoded.signature)
+            .ok()
+            .and_then(|s| <[u8; 32]>::try_from(s).ok())
+            .ok_or(TokenError::Malformed)?;
+        let caveats = encoded
+            .caveats
+            .iter()
+            .map(|c| c.parse())
+            .collect::<std::result::Result<_, _>>()?;
+
+        Ok(Self {
+            id: encoded.id,
+            caveats,
+            signature,
+        })
+    }
+}
+
+fn sign(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// Read the root key at `path`, creating a random one (readable only by the
+/// owner) if the file does not exist. Returns whether it was created.
+pub fn load_or_create_root_key(path: &Path) -> Result<([u8; ROOT_KEY_LEN], bool)> {
+    if path.exists() {
+        return Ok((load_root_key(path)?, false));
+    }
+
+    let key: [u8; ROOT_KEY_LEN] = rand::random();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_private(path, hex::encode(key).as_bytes())?;
+    Ok((key, true))
+}
+
+/// Read an existing root key
+pub fn load_root_key(path: &Path) -> Result<[u8; ROOT_KEY_LEN]> {
+    hex::decode(std::fs::read_to_string(path)?.trim())
+        .ok()
+        .and_then(|k| <[u8; ROOT_KEY_LEN]>::try_from(k).ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid root key in {}", path.display()))
+}
+
+/// Save an encoded token to a file readable only by the owner
+pub fn save_token(path: &Path, token: &ScopedToken) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_private(path, token.encode().as_bytes())?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT_KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_mint_encode_verify() {
+        let token = ScopedToken::mint(
+            ROOT_KEY,
+            "app-1",
+            vec![Caveat::Permissions(vec![permissions::READ.to_string()])],
+        );
+        let decoded = ScopedToken::decode(&token.encode()).unwrap();
+        assert_eq!(decoded, token);
+        assert!(decoded.verify(ROOT_KEY).is_ok());
+        assert!(decoded.verify(b"another root key").is_err());
+
+        let root = ScopedToken::mint(ROOT_KEY, "admin", vec![]);
+        assert!(root.verify(ROOT_KEY).is_ok());
+        assert_eq!(
+            root.restrictions().unwrap().permissions.len(),
+            permissions::ALL.len()
+        );
+    }
+
+    #[test]
+    fn test_attenuation_only_narrows() {
+        let token = ScopedToken::mint(
+            ROOT_KEY,
+            "shop",
+            vec![Caveat::Permissions(vec![
+                permissions::READ.to_string(),
+                permissions::SEND.to_string(),
+            ])],
+        )
+        .attenuate(Caveat::MaxAmountSats(5_000))
+        .attenuate(Caveat::Permissions(vec![
+            permissions::SEND.to_string(),
+            permissions::ADMIN.to_string(),
+        ]))
+        .attenuate(Caveat::MaxAmountSats(10_000));
+        assert!(token.verify(ROOT_KEY).is_ok());
+
+        let restrictions = token.restrictions().unwrap();
+        assert_eq!(
+            restrictions.permissions,
+            vec![permissions::SEND.to_string()]
+        );
+        assert_eq!(restrictions.max_amount_sats, Some(5_000));
+
+        // Dropping a caveat breaks the signature
+        let mut stripped = token.clone();
+        stripped.caveats.remove(1);
+        assert!(stripped.verify(ROOT_KEY).is_err());
+    }
+
+    #[test]
+    fn test_conflicting_wallets_are_refused() {
+        let token = ScopedToken::mint(ROOT_KEY, "w", vec![Caveat::WalletId("a".into())])
+            .attenuate(Caveat::WalletId("b".into()));
+        assert!(matches!(
+            token.restrictions(),
+            Err(TokenError::ConflictingWallets)
+        ));
+    }
+
+    #[test]
+    fn test_root_key_is_created_once() {
+        let path = std::env::temp_dir()
+            .join("satsconnect_root_key_test")
+            .join("root.key");
+        let _ = std::fs::remove_file(&path);
+
+        let (key, created) = load_or_create_root_key(&path).unwrap();
+        assert!(created);
+        let (again, created) = load_or_create_root_key(&path).unwrap();
+        assert!(!created);
+        assert_eq!(key, again);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+//! Coordinated shutdown of the engine.
+//!
+//! [`Shutdown`] is handed to the servers and background tasks; once it is
+//! triggered (by a signal or a server failing) they stop taking new work and
+//! wind down, and `engine_server` gives in-flight calls a bounded time to
+//! finish before it flushes the stores and stops the LDK node.
+
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Broadcasts the start of shutdown; clones share the same state
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Start shutting down; later calls do nothing
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once shutdown has been triggered
+    pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.sender.subscribe();
+        async move {
+            // The sender lives as long as `self`, so this only fails if every
+            // handle is gone, which also means shutting down
+            let _ = receiver.wait_for(|triggered| *triggered).await;
+        }
+    }
+}
+
+/// Resolves with the name of the first SIGINT (Ctrl+C) or SIGTERM received
+pub async fn signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = sigterm.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                tracing::warn!("Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_trigger_wakes_every_waiter() {
+        let shutdown = Shutdown::new();
+        let waiters: Vec<_> = (0..3)
+            .map(|_| tokio::spawn(shutdown.clone().triggered()))
+            .collect();
+        assert!(!shutdown.is_triggered());
+
+        shutdown.clone().trigger();
+        for waiter in waiters {
+            tokio::time::timeout(Duration::from_secs(1), waiter)
+                .await
+                .expect("waiter woke up")
+                .unwrap();
+        }
+
+        // Waiting after the fact resolves immediately
+        assert!(shutdown.is_triggered());
+        tokio::time::timeout(Duration::from_secs(1), shutdown.triggered())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelRecord {
+    pub channel_id: String,
+    pub peer_id: String,
+    pub capacity_sats: u64,
+    pub local_balance_sats: u64,
+    pub remote_balance_sats: u64,
+    pub state: String,
+    /// `txid:vout` once the funding transaction is negotiated
+    pub funding_txo: Option<String>,
+    pub short_channel_id: Option<u64>,
+    pub confirmations: u32,
+    pub confirmations_required: u32,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+const COLUMNS: &str = "channel_id, peer_id, capacity_sats, local_balance_sats, \
+     remote_balance_sats, state, funding_txo, short_channel_id, confirmations, \
+     confirmations_required, created_at, updated_at";
+
+impl ChannelRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            channel_id: row.get(0)?,
+            peer_id: row.get(1)?,
+            capacity_sats: row.get(2)?,
+            local_balance_sats: row.get(3)?,
+            remote_balance_sats: row.get(4)?,
+            state: row.get(5)?,
+            funding_txo: row.get(6)?,
+            short_channel_id: row.get(7)?,
+            confirmations: row.get(8)?,
+            confirmations_required: row.get(9)?,
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+        })
+    }
+}
+
+/// Insert a channel or replace the stored copy
+pub fn upsert(conn: &Connection, channel: &ChannelRecord) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO channels ({})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+             ON CONFLICT(channel_id) DO UPDATE SET
+                 peer_id = excluded.peer_id,
+                 capacity_sats = excluded.capacity_sats,
+                 local_balance_sats = excluded.local_balance_sats,
+                 remote_balance_sats = excluded.remote_balance_sats,
+                 state = excluded.state,
+                 funding_txo = excluded.funding_txo,
+                 short_channel_id = excluded.short_channel_id,
+                 confirmations = excluded.confirmations,
+                 confirmations_required = excluded.confirmations_required,
+                 updated_at = excluded.updated_at",
+            COLUMNS
+        ),
+        params![
+            channel.channel_id,
+            channel.peer_id,
+            channel.capacity_sats,
+            channel.local_balance_sats,
+            channel.remote_balance_sats,
+            channel.state,
+            channel.funding_txo,
+            channel.short_channel_id,
+            channel.confirmations,
+            channel.confirmations_required,
+            channel.created_at,
+            channel.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get(conn: &Connection, channel_id: &str) -> Result<Option<ChannelRecord>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {} FROM channels WHERE channel_id = ?1", COLUMNS),
+            params![channel_id],
+            ChannelRecord::from_row,
+        )
+        .optional()?)
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<ChannelRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM channels ORDER BY created_at",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map([], ChannelRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+pub fn list_by_peer(conn: &Connection, peer_id: &str) -> Result<Vec<ChannelRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM channels WHERE peer_id = ?1 ORDER BY created_at",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![peer_id], ChannelRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+pub fn list_by_state(conn: &Connection, state: &str) -> Result<Vec<ChannelRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM channels WHERE state = ?1 ORDER BY created_at",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![state], ChannelRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+/// A fiat on/off-ramp order placed through a provider such as M-Pesa
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiatOrderRecord {
+    pub order_id: String,
+    pub wallet_id: String,
+    pub provider: String,
+    pub currency: String,
+    pub fiat_amount: f64,
+    pub amount_sats: u64,
+    pub phone: String,
+    pub reference: String,
+    /// Provider-side transaction id, once the provider accepted the order
+    pub transaction_id: Option<String>,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+const COLUMNS: &str = "order_id, wallet_id, provider, currency, fiat_amount, amount_sats, phone, \
+     reference, transaction_id, status, error_message, created_at, updated_at";
+
+impl FiatOrderRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            order_id: row.get(0)?,
+            wallet_id: row.get(1)?,
+            provider: row.get(2)?,
+            currency: row.get(3)?,
+            fiat_amount: row.get(4)?,
+            amount_sats: row.get(5)?,
+            phone: row.get(6)?,
+            reference: row.get(7)?,
+            transaction_id: row.get(8)?,
+            status: row.get(9)?,
+            error_message: row.get(10)?,
+            created_at: row.get(11)?,
+            updated_at: row.get(12)?,
+        })
+    }
+}
+
+pub fn insert(conn: &Connection, order: &FiatOrderRecord) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO fiat_orders ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            COLUMNS
+        ),
+        params![
+            order.order_id,
+            order.wallet_id,
+            order.provider,
+            order.currency,
+            order.fiat_amount,
+            order.amount_sats,
+            order.phone,
+            order.reference,
+            order.transaction_id,
+            order.status,
+            order.error_message,
+            order.created_at,
+            order.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Record a provider status change for an order
+pub fn update_status(
+    conn: &Connection,
+    order_id: &str,
+    status: &str,
+    transaction_id: Option<&str>,
+    error_message: Option<&str>,
+) -> Result<()> {
+    let updated = conn.execute(
+        "UPDATE fiat_orders SET status = ?2, transaction_id = COALESCE(?3, transaction_id),
+             error_message = ?4, updated_at = ?5
+         WHERE order_id = ?1",
+        params![
+            order_id,
+            status,
+            transaction_id,
+            error_message,
+            super::now()
+        ],
+    )?;
+
+    if updated == 0 {
+        return Err(anyhow::anyhow!("Fiat order {} not found", order_id));
+    }
+    Ok(())
+}
+
+pub fn get(conn: &Connection, order_id: &str) -> Result<Option<FiatOrderRecord>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {} FROM fiat_orders WHERE order_id = ?1", COLUMNS),
+            params![order_id],
+            FiatOrderRecord::from_row,
+        )
+        .optional()?)
+}
+
+/// Look an order up by the provider's transaction id, e.g. from a webhook
+pub fn find_by_transaction(
+    conn: &Connection,
+    transaction_id: &str,
+) -> Result<Option<FiatOrderRecord>> {
+    Ok(conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM fiat_orders WHERE transaction_id = ?1",
+                COLUMNS
+            ),
+            params![transaction_id],
+            FiatOrderRecord::from_row,
+        )
+        .optional()?)
+}
+
+/// Orders for a wallet, optionally filtered by status, newest first
+pub fn list_by_wallet(
+    conn: &Connection,
+    wallet_id: &str,
+    status: Option<&str>,
+) -> Result<Vec<FiatOrderRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM fiat_orders WHERE wallet_id = ?1 AND (?2 IS NULL OR status = ?2)
+         ORDER BY created_at DESC, rowid DESC",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![wallet_id, status], FiatOrderRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+/// An invoice we issued, keyed by payment hash
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvoiceRecord {
+    pub payment_hash: String,
+    pub wallet_id: String,
+    pub bolt11: String,
+    pub amount_msat: Option<u64>,
+    pub description: String,
+    /// OPEN / PAID / EXPIRED
+    pub status: String,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub paid_at: Option<i64>,
+    /// Fee the LSP deducts from the payment when it opens a just-in-time
+    /// channel for it
+    pub lsp_fee_msat: Option<u64>,
+}
+
+const COLUMNS: &str = "payment_hash, wallet_id, bolt11, amount_msat, description, status, \
+     created_at, expires_at, paid_at, lsp_fee_msat";
+
+impl InvoiceRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            payment_hash: row.get(0)?,
+            wallet_id: row.get(1)?,
+            bolt11: row.get(2)?,
+            amount_msat: row.get(3)?,
+            description: row.get(4)?,
+            status: row.get(5)?,
+            created_at: row.get(6)?,
+            expires_at: row.get(7)?,
+            paid_at: row.get(8)?,
+            lsp_fee_msat: row.get(9)?,
+        })
+    }
+}
+
+pub fn insert(conn: &Connection, invoice: &InvoiceRecord) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO invoices ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            COLUMNS
+        ),
+        params![
+            invoice.payment_hash,
+            invoice.wallet_id,
+            invoice.bolt11,
+            invoice.amount_msat,
+            invoice.description,
+            invoice.status,
+            invoice.created_at,
+            invoice.expires_at,
+            invoice.paid_at,
+            invoice.lsp_fee_msat,
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get(conn: &Connection, payment_hash: &str) -> Result<Option<InvoiceRecord>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {} FROM invoices WHERE payment_hash = ?1", COLUMNS),
+            params![payment_hash],
+            InvoiceRecord::from_row,
+        )
+        .optional()?)
+}
+
+/// Mark an open invoice as paid; returns whether it was open
+pub fn mark_paid(conn: &Connection, payment_hash: &str, paid_at: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE invoices SET status = 'PAID', paid_at = ?2 WHERE payment_hash = ?1 AND status = 'OPEN'",
+        params![payment_hash, paid_at],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Move open invoices past their expiry to EXPIRED; returns how many changed
+pub fn expire(conn: &Connection, now: i64) -> Result<usize> {
+    Ok(conn.execute(
+        "UPDATE invoices SET status = 'EXPIRED' WHERE status = 'OPEN' AND expires_at <= ?1",
+        params![now],
+    )?)
+}
+
+/// Invoices for a wallet, optionally filtered by status, newest first
+pub fn list_by_wallet(
+    conn: &Connection,
+    wallet_id: &str,
+    status: Option<&str>,
+) -> Result<Vec<InvoiceRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM invoices WHERE wallet_id = ?1 AND (?2 IS NULL OR status = ?2)
+         ORDER BY created_at DESC, rowid DESC",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![wallet_id, status], InvoiceRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+
+    #[test]
+    fn test_invoice_lifecycle() {
+        let store = Store::open_in_memory().unwrap();
+        let invoice = |hash: &str, expires_at| InvoiceRecord {
+            payment_hash: hash.to_string(),
+            wallet_id: "w1".to_string(),
+            bolt11: format!("lnbcrt_{}", hash),
+            amount_msat: Some(1_000),
+            description: String::new(),
+            status: "OPEN".to_string(),
+            created_at: 100,
+            expires_at: Some(expires_at),
+            paid_at: None,
+            lsp_fee_msat: None,
+        };
+        let jit = InvoiceRecord {
+            lsp_fee_msat: Some(2_000_000),
+            ..invoice("h3", 200)
+        };
+        store
+            .transaction(|tx| {
+                insert(tx, &invoice("h1", 1_000))?;
+                insert(tx, &invoice("h2", 200))?;
+                insert(tx, &jit)
+            })
+            .unwrap();
+        assert_eq!(store.with_conn(|c| get(c, "h3")).unwrap(), Some(jit));
+
+        assert!(store.with_conn(|c| mark_paid(c, "h1", 150)).unwrap());
+        assert!(!store.with_conn(|c| mark_paid(c, "h1", 160)).unwrap());
+        assert_eq!(store.with_conn(|c| expire(c, 300)).unwrap(), 2);
+
+        let open = store
+            .with_conn(|c| list_by_wallet(c, "w1", Some("OPEN")))
+            .unwrap();
+        assert!(open.is_empty());
+        let paid = store.with_conn(|c| get(c, "h1")).unwrap().unwrap();
+        assert_eq!(paid.status, "PAID");
+        assert_eq!(paid.paid_at, Some(150));
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, Row};
+
+/// A decision of the liquidity manager, kept for audit
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquidityEventRecord {
+    /// Assigned by the store on insert
+    pub id: i64,
+    /// e.g. CHANNEL_OPENED or REBALANCE_NEEDED
+    pub kind: String,
+    /// Peer or pair of channels the decision is about
+    pub subject: String,
+    pub amount_sat: u64,
+    /// Why the decision was taken, or what went wrong
+    pub detail: String,
+    pub created_at: i64,
+}
+
+const COLUMNS: &str = "id, kind, subject, amount_sat, detail, created_at";
+
+impl LiquidityEventRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            subject: row.get(2)?,
+            amount_sat: row.get(3)?,
+            detail: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    }
+}
+
+/// Append an event; returns its id
+pub fn insert(conn: &Connection, event: &LiquidityEventRecord) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO liquidity_events (kind, subject, amount_sat, detail, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            event.kind,
+            event.subject,
+            event.amount_sat,
+            event.detail,
+            event.created_at
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// The `limit` most recent events, newest first
+pub fn list(conn: &Connection, limit: u32) -> Result<Vec<LiquidityEventRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM liquidity_events ORDER BY id DESC LIMIT ?1",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![limit], LiquidityEventRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+/// An LSPS1 channel order placed with a Lightning Service Provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct LspOrderRecord {
+    /// Order id assigned by the LSP
+    pub order_id: String,
+    /// Name of the LSP the order was placed with
+    pub provider: String,
+    pub lsp_balance_sat: u64,
+    pub client_balance_sat: u64,
+    pub fee_total_sat: u64,
+    pub order_total_sat: u64,
+    /// "bolt11" or "onchain"
+    pub payment_method: String,
+    /// Payment hash or txid of our payment, once it was sent
+    pub payment_ref: Option<String>,
+    /// LSPS1 order state: CREATED, COMPLETED or FAILED
+    pub order_state: String,
+    /// LSPS1 payment state: EXPECT_PAYMENT, HOLD, PAID or REFUNDED
+    pub payment_state: String,
+    /// `txid:vout` of the channel, once the LSP funded it
+    pub funding_outpoint: Option<String>,
+    /// When the LSP may close the channel, as reported by the LSP
+    pub channel_expires_at: Option<String>,
+    pub error_message: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+const COLUMNS: &str = "order_id, provider, lsp_balance_sat, client_balance_sat, fee_total_sat, \
+     order_total_sat, payment_method, payment_ref, order_state, payment_state, funding_outpoint, \
+     channel_expires_at, error_message, created_at, updated_at";
+
+impl LspOrderRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            order_id: row.get(0)?,
+            provider: row.get(1)?,
+            lsp_balance_sat: row.get(2)?,
+            client_balance_sat: row.get(3)?,
+            fee_total_sat: row.get(4)?,
+            order_total_sat: row.get(5)?,
+            payment_method: row.get(6)?,
+            payment_ref: row.get(7)?,
+            order_state: row.get(8)?,
+            payment_state: row.get(9)?,
+            funding_outpoint: row.get(10)?,
+            channel_expires_at: row.get(11)?,
+            error_message: row.get(12)?,
+            created_at: row.get(13)?,
+            updated_at: row.get(14)?,
+        })
+    }
+}
+
+pub fn insert(conn: &Connection, order: &LspOrderRecord) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO lsp_orders ({})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            COLUMNS
+        ),
+        params![
+            order.order_id,
+            order.provider,
+            order.lsp_balance_sat,
+            order.client_balance_sat,
+            order.fee_total_sat,
+            order.order_total_sat,
+            order.payment_method,
+            order.payment_ref,
+            order.order_state,
+            order.payment_state,
+            order.funding_outpoint,
+            order.channel_expires_at,
+            order.error_message,
+            order.created_at,
+            order.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Record our payment and the latest state reported by the LSP
+pub fn update(conn: &Connection, order: &LspOrderRecord) -> Result<()> {
+    let updated = conn.execute(
+        "UPDATE lsp_orders SET payment_ref = ?2, order_state = ?3, payment_state = ?4,
+             funding_outpoint = ?5, channel_expires_at = ?6, error_message = ?7, updated_at = ?8
+         WHERE order_id = ?1",
+        params![
+            order.order_id,
+            order.payment_ref,
+            order.order_state,
+            order.payment_state,
+            order.funding_outpoint,
+            order.channel_expires_at,
+            order.error_message,
+            order.updated_at,
+        ],
+    )?;
+    if updated == 0 {
+        return Err(anyhow::anyhow!("LSP order {} not found", order.order_id));
+    }
+    Ok(())
+}
+
+pub fn get(conn: &Connection, order_id: &str) -> Result<Option<LspOrderRecord>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {} FROM lsp_orders WHERE order_id = ?1", COLUMNS),
+            params![order_id],
+            LspOrderRecord::from_row,
+        )
+        .optional()?)
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<LspOrderRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM lsp_orders ORDER BY created_at DESC",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map([], LspOrderRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Orders the LSP has neither completed nor failed yet
+pub fn list_open(conn: &Connection) -> Result<Vec<LspOrderRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM lsp_orders WHERE order_state = 'CREATED' ORDER BY created_at",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map([], LspOrderRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
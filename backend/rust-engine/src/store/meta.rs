@@ -0,0 +1,28 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Key under which the data directory's network is recorded
+pub const NETWORK: &str = "network";
+
+/// Key under which the timestamp of the last Rapid Gossip Sync snapshot
+/// applied is recorded
+pub const RGS_SNAPSHOT_TIMESTAMP: &str = "rgs_snapshot_timestamp";
+
+pub fn get(conn: &Connection, key: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT value FROM meta WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+pub fn set(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
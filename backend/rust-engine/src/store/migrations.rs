@@ -0,0 +1,206 @@
+use anyhow::Result;
+use rusqlite::Connection;
+use tracing::info;
+
+/// Schema migrations, applied in order. The database's `user_version` records
+/// how many have run; append new entries, never edit released ones.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "
+    CREATE TABLE wallets (
+        wallet_id   TEXT PRIMARY KEY,
+        label       TEXT NOT NULL,
+        node_id     TEXT NOT NULL,
+        address     TEXT NOT NULL,
+        network     TEXT NOT NULL,
+        created_at  INTEGER NOT NULL
+    );
+    CREATE UNIQUE INDEX idx_wallets_label ON wallets(label);
+
+    CREATE TABLE payments (
+        payment_id    TEXT PRIMARY KEY,
+        wallet_id     TEXT NOT NULL DEFAULT '',
+        direction     TEXT NOT NULL,
+        amount_msat   INTEGER NOT NULL,
+        invoice       TEXT NOT NULL DEFAULT '',
+        destination   TEXT,
+        description   TEXT NOT NULL DEFAULT '',
+        status        TEXT NOT NULL,
+        payment_hash  TEXT NOT NULL DEFAULT '',
+        retry_count   INTEGER NOT NULL DEFAULT 0,
+        error_message TEXT,
+        created_at    INTEGER NOT NULL,
+        updated_at    INTEGER NOT NULL,
+        completed_at  INTEGER
+    );
+    CREATE INDEX idx_payments_wallet ON payments(wallet_id, created_at);
+    CREATE INDEX idx_payments_status ON payments(status, created_at);
+    CREATE INDEX idx_payments_hash ON payments(payment_hash);
+
+    CREATE TABLE refunds (
+        refund_id   TEXT PRIMARY KEY,
+        payment_id  TEXT NOT NULL REFERENCES payments(payment_id) ON DELETE CASCADE,
+        status      TEXT NOT NULL,
+        k1          TEXT,
+        data        TEXT NOT NULL,
+        created_at  INTEGER NOT NULL
+    );
+    CREATE INDEX idx_refunds_payment ON refunds(payment_id);
+    CREATE UNIQUE INDEX idx_refunds_k1 ON refunds(k1) WHERE k1 IS NOT NULL;
+
+    CREATE TABLE invoices (
+        payment_hash  TEXT PRIMARY KEY,
+        wallet_id     TEXT NOT NULL DEFAULT '',
+        bolt11        TEXT NOT NULL,
+        amount_msat   INTEGER,
+        description   TEXT NOT NULL DEFAULT '',
+        status        TEXT NOT NULL,
+        created_at    INTEGER NOT NULL,
+        expires_at    INTEGER,
+        paid_at       INTEGER
+    );
+    CREATE INDEX idx_invoices_wallet ON invoices(wallet_id, created_at);
+    CREATE INDEX idx_invoices_status ON invoices(status, created_at);
+
+    CREATE TABLE channels (
+        channel_id          TEXT PRIMARY KEY,
+        peer_id             TEXT NOT NULL,
+        capacity_sats       INTEGER NOT NULL,
+        local_balance_sats  INTEGER NOT NULL,
+        remote_balance_sats INTEGER NOT NULL,
+        state               TEXT NOT NULL,
+        created_at          INTEGER NOT NULL,
+        updated_at          INTEGER NOT NULL
+    );
+    CREATE INDEX idx_channels_peer ON channels(peer_id);
+    CREATE INDEX idx_channels_state ON channels(state, updated_at);
+
+    CREATE TABLE fiat_orders (
+        order_id       TEXT PRIMARY KEY,
+        wallet_id      TEXT NOT NULL DEFAULT '',
+        provider       TEXT NOT NULL,
+        currency       TEXT NOT NULL,
+        fiat_amount    REAL NOT NULL,
+        amount_sats    INTEGER NOT NULL,
+        phone          TEXT NOT NULL DEFAULT '',
+        reference      TEXT NOT NULL DEFAULT '',
+        transaction_id TEXT,
+        status         TEXT NOT NULL,
+        error_message  TEXT,
+        created_at     INTEGER NOT NULL,
+        updated_at     INTEGER NOT NULL
+    );
+    CREATE INDEX idx_fiat_orders_wallet ON fiat_orders(wallet_id, created_at);
+    CREATE INDEX idx_fiat_orders_status ON fiat_orders(status, created_at);
+    CREATE INDEX idx_fiat_orders_transaction ON fiat_orders(transaction_id);
+    ",
+    // 2: store-wide settings such as the network the data directory belongs to
+    "
+    CREATE TABLE meta (
+        key    TEXT PRIMARY KEY,
+        value  TEXT NOT NULL
+    );
+    ",
+    // 3: funding details of channels opened through the LDK node
+    "
+    ALTER TABLE channels ADD COLUMN funding_txo TEXT;
+    ALTER TABLE channels ADD COLUMN short_channel_id INTEGER;
+    ALTER TABLE channels ADD COLUMN confirmations INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE channels ADD COLUMN confirmations_required INTEGER NOT NULL DEFAULT 0;
+    ",
+    // 4: peers to stay connected to across restarts
+    "
+    CREATE TABLE peers (
+        node_id     TEXT PRIMARY KEY,
+        address     TEXT NOT NULL,
+        created_at  INTEGER NOT NULL,
+        updated_at  INTEGER NOT NULL
+    );
+    ",
+    // 5: channels bought from LSPs over LSPS1
+    "
+    CREATE TABLE lsp_orders (
+        order_id            TEXT PRIMARY KEY,
+        provider            TEXT NOT NULL,
+        lsp_balance_sat     INTEGER NOT NULL,
+        client_balance_sat  INTEGER NOT NULL,
+        fee_total_sat       INTEGER NOT NULL,
+        order_total_sat     INTEGER NOT NULL,
+        payment_method      TEXT NOT NULL,
+        payment_ref         TEXT,
+        order_state         TEXT NOT NULL,
+        payment_state       TEXT NOT NULL,
+        funding_outpoint    TEXT,
+        channel_expires_at  TEXT,
+        error_message       TEXT,
+        created_at          INTEGER NOT NULL,
+        updated_at          INTEGER NOT NULL
+    );
+    CREATE INDEX idx_lsp_orders_state ON lsp_orders(order_state, created_at);
+    ",
+    // 6: LSP fee of invoices received through just-in-time channels
+    "
+    ALTER TABLE invoices ADD COLUMN lsp_fee_msat INTEGER;
+    ",
+    // 7: decisions of the liquidity manager
+    "
+    CREATE TABLE liquidity_events (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind        TEXT NOT NULL,
+        subject     TEXT NOT NULL,
+        amount_sat  INTEGER NOT NULL DEFAULT 0,
+        detail      TEXT NOT NULL DEFAULT '',
+        created_at  INTEGER NOT NULL
+    );
+    CREATE INDEX idx_liquidity_events_created ON liquidity_events(created_at);
+    ",
+];
+
+/// Bring the schema up to date
+pub fn run(conn: &mut Connection) -> Result<()> {
+    let current: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    if current > MIGRATIONS.len() {
+        return Err(anyhow::anyhow!(
+            "Database schema version {} is newer than this engine supports ({})",
+            current,
+            MIGRATIONS.len()
+        ));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = index + 1;
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version)?;
+        tx.commit()?;
+        info!("Applied store migration {}", version);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_idempotent() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+        run(&mut conn).unwrap();
+
+        let version: usize = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_rejects_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(run(&mut conn).is_err());
+    }
+}
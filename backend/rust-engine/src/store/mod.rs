@@ -0,0 +1,205 @@
+pub mod channels;
+pub mod fiat_orders;
+pub mod invoices;
+pub mod liquidity_events;
+pub mod lsp_orders;
+pub mod meta;
+pub mod migrations;
+pub mod payments;
+pub mod peers;
+pub mod wallets;
+
+pub use channels::ChannelRecord;
+pub use fiat_orders::FiatOrderRecord;
+pub use invoices::InvoiceRecord;
+pub use liquidity_events::LiquidityEventRecord;
+pub use lsp_orders::LspOrderRecord;
+pub use payments::{PaymentQuery, PaymentRecord, PaymentStatus};
+pub use peers::PeerRecord;
+pub use wallets::WalletRecord;
+
+use crate::network::NetworkError;
+use anyhow::Result;
+use bitcoin::Network;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Database file inside the engine data directory
+pub const DB_FILE: &str = "satsconnect.db";
+
+/// Embedded SQLite store shared by the engine's handlers.
+///
+/// Each table module exposes plain functions over a [`Connection`] so several
+/// updates can be grouped with [`Store::transaction`].
+#[derive(Debug, Clone)]
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Store {
+    /// Open (or create) the database at `path` and apply pending migrations
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        info!("Opened store at {}", path.display());
+        Self::from_connection(conn)
+    }
+
+    /// Open the store in `data_dir` under [`DB_FILE`]
+    pub fn open_in_dir(data_dir: &Path) -> Result<Self> {
+        Self::open(&data_dir.join(DB_FILE))
+    }
+
+    /// Non-persistent store, used by tests and components created without a data directory
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        migrations::run(&mut conn)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Tie the store to `network` on first use and refuse it afterwards on any
+    /// other network, e.g. when a testnet backup is restored into a mainnet node
+    pub fn bind_network(&self, network: Network) -> Result<()> {
+        let name = network.to_string();
+        self.transaction(|tx| {
+            let found = match meta::get(tx, meta::NETWORK)? {
+                Some(bound) => Some(bound),
+                // Stores created before the network was recorded still name it per wallet
+                None => tx
+                    .query_row(
+                        "SELECT network FROM wallets WHERE network != ?1 LIMIT 1",
+                        params![name],
+                        |row| row.get(0),
+                    )
+                    .optional()?,
+            };
+
+            match found {
+                Some(found) if found != name => Err(NetworkError::DataNetworkMismatch {
+                    expected: network,
+                    found,
+                }
+                .into()),
+                Some(_) => Ok(()),
+                None => meta::set(tx, meta::NETWORK, &name),
+            }
+        })
+    }
+
+    /// Run `f` against the connection
+    pub fn with_conn<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Store connection poisoned"))?;
+        f(&conn)
+    }
+
+    /// Run `f` in a transaction, committing only if it returns `Ok`
+    pub fn transaction<T>(&self, f: impl FnOnce(&Transaction) -> Result<T>) -> Result<T> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Store connection poisoned"))?;
+        let tx = conn.transaction()?;
+        let result = f(&tx)?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Write the WAL back into the database file and truncate it, so a clean
+    /// shutdown leaves a single self-contained file behind
+    pub fn checkpoint(&self) -> Result<()> {
+        let busy: i64 = self.with_conn(|c| {
+            Ok(c.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?)
+        })?;
+        if busy != 0 {
+            return Err(anyhow::anyhow!(
+                "Store checkpoint blocked by another connection"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Current time as unix seconds, the timestamp format used by every table
+pub fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_rolls_back_on_error() {
+        let store = Store::open_in_memory().unwrap();
+        let record = WalletRecord::new("wallet_1", "default", "node", "addr", "regtest");
+
+        let result: Result<()> = store.transaction(|tx| {
+            wallets::insert(tx, &record)?;
+            Err(anyhow::anyhow!("abort"))
+        });
+        assert!(result.is_err());
+
+        let wallet = store.with_conn(|c| wallets::get(c, "wallet_1")).unwrap();
+        assert!(wallet.is_none());
+    }
+
+    #[test]
+    fn test_reopen_keeps_data() {
+        let dir = std::env::temp_dir().join("satsconnect_store_reopen_test");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        {
+            let store = Store::open_in_dir(&dir).unwrap();
+            let record = WalletRecord::new("wallet_1", "default", "node", "addr", "regtest");
+            store.with_conn(|c| wallets::insert(c, &record)).unwrap();
+        }
+
+        let store = Store::open_in_dir(&dir).unwrap();
+        let wallet = store.with_conn(|c| wallets::get(c, "wallet_1")).unwrap();
+        assert_eq!(wallet.unwrap().label, "default");
+
+        // A checkpoint folds the WAL into the database file
+        store.checkpoint().unwrap();
+        let wal = std::fs::metadata(dir.join(format!("{}-wal", DB_FILE))).unwrap();
+        assert_eq!(wal.len(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bind_network() {
+        let store = Store::open_in_memory().unwrap();
+        store.bind_network(Network::Regtest).unwrap();
+        store.bind_network(Network::Regtest).unwrap();
+
+        let err = store.bind_network(Network::Bitcoin).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NetworkError>(),
+            Some(NetworkError::DataNetworkMismatch { .. })
+        ));
+
+        // Wallets from before the network was recorded also pin it
+        let legacy = Store::open_in_memory().unwrap();
+        let record = WalletRecord::new("wallet_1", "default", "node", "addr", "testnet");
+        legacy.with_conn(|c| wallets::insert(c, &record)).unwrap();
+        assert!(legacy.bind_network(Network::Bitcoin).is_err());
+        legacy.bind_network(Network::Testnet).unwrap();
+    }
+}
@@ -0,0 +1,390 @@
+use crate::refund::Refund;
+use anyhow::Result;
+use rusqlite::types::ToSql;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A payment row, shared by every component that tracks payments
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentRecord {
+    pub payment_id: String,
+    pub wallet_id: String,
+    /// OUTBOUND / INBOUND
+    pub direction: String,
+    pub amount_msat: u64,
+    pub invoice: String,
+    pub destination: Option<String>,
+    pub description: String,
+    pub status: String,
+    pub payment_hash: String,
+    pub retry_count: u32,
+    pub error_message: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub completed_at: Option<i64>,
+}
+
+/// Filters for [`list`]; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct PaymentQuery {
+    pub wallet_id: Option<String>,
+    pub status: Option<String>,
+    pub direction: Option<String>,
+    /// Inclusive lower bound on `created_at`
+    pub since: Option<i64>,
+    /// Exclusive upper bound on `created_at`
+    pub until: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+/// Values of the `status` column. The payment handler and both payment
+/// processors share the table, so each of them must read every status any of
+/// them writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PaymentStatus {
+    Pending,
+    InFlight,
+    Processing,
+    Retrying,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// Received payment the payer got all of back
+    Refunded,
+    /// Received payment the payer got part of back
+    PartiallyRefunded,
+}
+
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "PENDING",
+            Self::InFlight => "IN_FLIGHT",
+            Self::Processing => "PROCESSING",
+            Self::Retrying => "RETRYING",
+            Self::Succeeded => "SUCCEEDED",
+            Self::Failed => "FAILED",
+            Self::Cancelled => "CANCELLED",
+            Self::Refunded => "REFUNDED",
+            Self::PartiallyRefunded => "PARTIALLY_REFUNDED",
+        }
+    }
+
+    pub fn parse(status: &str) -> Result<Self> {
+        match status {
+            "PENDING" => Ok(Self::Pending),
+            "IN_FLIGHT" => Ok(Self::InFlight),
+            "PROCESSING" => Ok(Self::Processing),
+            "RETRYING" => Ok(Self::Retrying),
+            "SUCCEEDED" => Ok(Self::Succeeded),
+            "FAILED" => Ok(Self::Failed),
+            "CANCELLED" => Ok(Self::Cancelled),
+            "REFUNDED" => Ok(Self::Refunded),
+            "PARTIALLY_REFUNDED" => Ok(Self::PartiallyRefunded),
+            other => Err(anyhow::anyhow!("Unknown payment status: {}", other)),
+        }
+    }
+
+    /// No further change is expected
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            Self::Succeeded
+                | Self::Failed
+                | Self::Cancelled
+                | Self::Refunded
+                | Self::PartiallyRefunded
+        )
+    }
+}
+
+const COLUMNS: &str = "payment_id, wallet_id, direction, amount_msat, invoice, destination, \
+     description, status, payment_hash, retry_count, error_message, created_at, updated_at, \
+     completed_at";
+
+impl PaymentRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            payment_id: row.get(0)?,
+            wallet_id: row.get(1)?,
+            direction: row.get(2)?,
+            amount_msat: row.get(3)?,
+            invoice: row.get(4)?,
+            destination: row.get(5)?,
+            description: row.get(6)?,
+            status: row.get(7)?,
+            payment_hash: row.get(8)?,
+            retry_count: row.get(9)?,
+            error_message: row.get(10)?,
+            created_at: row.get(11)?,
+            updated_at: row.get(12)?,
+            completed_at: row.get(13)?,
+        })
+    }
+}
+
+pub fn insert(conn: &Connection, payment: &PaymentRecord) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO payments ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            COLUMNS
+        ),
+        params![
+            payment.payment_id,
+            payment.wallet_id,
+            payment.direction,
+            payment.amount_msat,
+            payment.invoice,
+            payment.destination,
+            payment.description,
+            payment.status,
+            payment.payment_hash,
+            payment.retry_count,
+            payment.error_message,
+            payment.created_at,
+            payment.updated_at,
+            payment.completed_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Overwrite the mutable fields of an existing payment
+pub fn update(conn: &Connection, payment: &PaymentRecord) -> Result<()> {
+    let updated = conn.execute(
+        "UPDATE payments SET status = ?2, payment_hash = ?3, retry_count = ?4, error_message = ?5,
+             updated_at = ?6, completed_at = ?7
+         WHERE payment_id = ?1",
+        params![
+            payment.payment_id,
+            payment.status,
+            payment.payment_hash,
+            payment.retry_count,
+            payment.error_message,
+            payment.updated_at,
+            payment.completed_at,
+        ],
+    )?;
+
+    if updated == 0 {
+        return Err(anyhow::anyhow!("Payment {} not found", payment.payment_id));
+    }
+    Ok(())
+}
+
+pub fn get(conn: &Connection, payment_id: &str) -> Result<Option<PaymentRecord>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {} FROM payments WHERE payment_id = ?1", COLUMNS),
+            params![payment_id],
+            PaymentRecord::from_row,
+        )
+        .optional()?)
+}
+
+/// Payments for a payment hash, oldest first
+pub fn find_by_hash(conn: &Connection, payment_hash: &str) -> Result<Vec<PaymentRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM payments WHERE payment_hash = ?1 ORDER BY created_at",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![payment_hash], PaymentRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Payments matching `query`, newest first
+pub fn list(conn: &Connection, query: &PaymentQuery) -> Result<Vec<PaymentRecord>> {
+    let mut sql = format!("SELECT {} FROM payments WHERE 1 = 1", COLUMNS);
+    let mut args: Vec<&dyn ToSql> = Vec::new();
+
+    if let Some(wallet_id) = &query.wallet_id {
+        args.push(wallet_id);
+        sql.push_str(&format!(" AND wallet_id = ?{}", args.len()));
+    }
+    if let Some(status) = &query.status {
+        args.push(status);
+        sql.push_str(&format!(" AND status = ?{}", args.len()));
+    }
+    if let Some(direction) = &query.direction {
+        args.push(direction);
+        sql.push_str(&format!(" AND direction = ?{}", args.len()));
+    }
+    if let Some(since) = &query.since {
+        args.push(since);
+        sql.push_str(&format!(" AND created_at >= ?{}", args.len()));
+    }
+    if let Some(until) = &query.until {
+        args.push(until);
+        sql.push_str(&format!(" AND created_at < ?{}", args.len()));
+    }
+    sql.push_str(" ORDER BY created_at DESC, rowid DESC");
+    if let Some(limit) = &query.limit {
+        args.push(limit);
+        sql.push_str(&format!(" LIMIT ?{}", args.len()));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(args.as_slice(), PaymentRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Number of payments per status
+pub fn count_by_status(conn: &Connection) -> Result<HashMap<String, u64>> {
+    let mut stmt = conn.prepare("SELECT status, COUNT(*) FROM payments GROUP BY status")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Insert or replace a refund issued against `payment_id`
+pub fn save_refund(conn: &Connection, payment_id: &str, refund: &Refund) -> Result<()> {
+    conn.execute(
+        "INSERT INTO refunds (refund_id, payment_id, status, k1, data, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(refund_id) DO UPDATE SET status = excluded.status, data = excluded.data",
+        params![
+            refund.refund_id,
+            payment_id,
+            refund.status.as_str(),
+            refund.k1,
+            serde_json::to_string(refund)?,
+            super::now(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Refunds for a payment in the order they were issued
+pub fn list_refunds(conn: &Connection, payment_id: &str) -> Result<Vec<Refund>> {
+    let mut stmt =
+        conn.prepare("SELECT data FROM refunds WHERE payment_id = ?1 ORDER BY created_at, rowid")?;
+    let rows = stmt.query_map(params![payment_id], |row| row.get::<_, String>(0))?;
+    rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+}
+
+/// Payment id and refund for an LNURL-withdraw secret
+pub fn find_refund_by_k1(conn: &Connection, k1: &str) -> Result<Option<(String, Refund)>> {
+    let row = conn
+        .query_row(
+            "SELECT payment_id, data FROM refunds WHERE k1 = ?1",
+            params![k1],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?;
+
+    row.map(|(payment_id, data)| Ok((payment_id, serde_json::from_str(&data)?)))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+
+    fn payment(id: &str, wallet_id: &str, status: &str, created_at: i64) -> PaymentRecord {
+        PaymentRecord {
+            payment_id: id.to_string(),
+            wallet_id: wallet_id.to_string(),
+            direction: "OUTBOUND".to_string(),
+            amount_msat: 1_000_000,
+            invoice: String::new(),
+            destination: None,
+            description: String::new(),
+            status: status.to_string(),
+            payment_hash: format!("hash_{}", id),
+            retry_count: 0,
+            error_message: None,
+            created_at,
+            updated_at: created_at,
+            completed_at: None,
+        }
+    }
+
+    #[test]
+    fn test_query_by_wallet_status_and_time() {
+        let store = Store::open_in_memory().unwrap();
+        store
+            .transaction(|tx| {
+                insert(tx, &payment("p1", "w1", "SUCCEEDED", 100))?;
+                insert(tx, &payment("p2", "w1", "FAILED", 200))?;
+                insert(tx, &payment("p3", "w2", "SUCCEEDED", 300))?;
+                Ok(())
+            })
+            .unwrap();
+
+        let by_wallet = store
+            .with_conn(|c| {
+                list(
+                    c,
+                    &PaymentQuery {
+                        wallet_id: Some("w1".to_string()),
+                        ..Default::default()
+                    },
+                )
+            })
+            .unwrap();
+        assert_eq!(
+            by_wallet
+                .iter()
+                .map(|p| p.payment_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["p2", "p1"]
+        );
+
+        let succeeded_since = store
+            .with_conn(|c| {
+                list(
+                    c,
+                    &PaymentQuery {
+                        status: Some("SUCCEEDED".to_string()),
+                        since: Some(150),
+                        ..Default::default()
+                    },
+                )
+            })
+            .unwrap();
+        assert_eq!(succeeded_since.len(), 1);
+        assert_eq!(succeeded_since[0].payment_id, "p3");
+
+        let counts = store.with_conn(count_by_status).unwrap();
+        assert_eq!(counts["SUCCEEDED"], 2);
+        assert_eq!(counts["FAILED"], 1);
+    }
+
+    #[test]
+    fn test_update_payment() {
+        let store = Store::open_in_memory().unwrap();
+        let mut record = payment("p1", "w1", "PENDING", 100);
+        store.with_conn(|c| insert(c, &record)).unwrap();
+
+        record.status = "SUCCEEDED".to_string();
+        record.completed_at = Some(150);
+        store.with_conn(|c| update(c, &record)).unwrap();
+
+        let stored = store.with_conn(|c| get(c, "p1")).unwrap().unwrap();
+        assert_eq!(stored, record);
+        assert!(store
+            .with_conn(|c| update(c, &payment("missing", "w1", "FAILED", 0)))
+            .is_err());
+    }
+
+    #[test]
+    fn test_status_vocabulary() {
+        for status in [
+            PaymentStatus::Pending,
+            PaymentStatus::InFlight,
+            PaymentStatus::Processing,
+            PaymentStatus::Retrying,
+            PaymentStatus::Succeeded,
+            PaymentStatus::Failed,
+            PaymentStatus::Cancelled,
+            PaymentStatus::Refunded,
+            PaymentStatus::PartiallyRefunded,
+        ] {
+            assert_eq!(PaymentStatus::parse(status.as_str()).unwrap(), status);
+        }
+        assert!(PaymentStatus::parse("PAID").is_err());
+        assert!(PaymentStatus::PartiallyRefunded.is_final());
+        assert!(!PaymentStatus::InFlight.is_final());
+    }
+}
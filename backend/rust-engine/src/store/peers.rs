@@ -0,0 +1,67 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+/// A peer the node should stay connected to
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerRecord {
+    /// Hex encoded node public key
+    pub node_id: String,
+    /// `host:port` the peer was last connected at
+    pub address: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+const COLUMNS: &str = "node_id, address, created_at, updated_at";
+
+impl PeerRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            node_id: row.get(0)?,
+            address: row.get(1)?,
+            created_at: row.get(2)?,
+            updated_at: row.get(3)?,
+        })
+    }
+}
+
+/// Insert a peer or update its address
+pub fn upsert(conn: &Connection, peer: &PeerRecord) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO peers ({})
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(node_id) DO UPDATE SET
+                 address = excluded.address,
+                 updated_at = excluded.updated_at",
+            COLUMNS
+        ),
+        params![peer.node_id, peer.address, peer.created_at, peer.updated_at],
+    )?;
+    Ok(())
+}
+
+pub fn get(conn: &Connection, node_id: &str) -> Result<Option<PeerRecord>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {} FROM peers WHERE node_id = ?1", COLUMNS),
+            params![node_id],
+            PeerRecord::from_row,
+        )
+        .optional()?)
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<PeerRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM peers ORDER BY created_at",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map([], PeerRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Forget a peer; returns whether it was stored
+pub fn delete(conn: &Connection, node_id: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM peers WHERE node_id = ?1", params![node_id])?;
+    Ok(deleted > 0)
+}
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletRecord {
+    pub wallet_id: String,
+    pub label: String,
+    pub node_id: String,
+    pub address: String,
+    pub network: String,
+    pub created_at: i64,
+}
+
+const COLUMNS: &str = "wallet_id, label, node_id, address, network, created_at";
+
+impl WalletRecord {
+    pub fn new(wallet_id: &str, label: &str, node_id: &str, address: &str, network: &str) -> Self {
+        Self {
+            wallet_id: wallet_id.to_string(),
+            label: label.to_string(),
+            node_id: node_id.to_string(),
+            address: address.to_string(),
+            network: network.to_string(),
+            created_at: super::now(),
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            wallet_id: row.get(0)?,
+            label: row.get(1)?,
+            node_id: row.get(2)?,
+            address: row.get(3)?,
+            network: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    }
+}
+
+pub fn insert(conn: &Connection, wallet: &WalletRecord) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO wallets ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            COLUMNS
+        ),
+        params![
+            wallet.wallet_id,
+            wallet.label,
+            wallet.node_id,
+            wallet.address,
+            wallet.network,
+            wallet.created_at,
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get(conn: &Connection, wallet_id: &str) -> Result<Option<WalletRecord>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {} FROM wallets WHERE wallet_id = ?1", COLUMNS),
+            params![wallet_id],
+            WalletRecord::from_row,
+        )
+        .optional()?)
+}
+
+pub fn get_by_label(conn: &Connection, label: &str) -> Result<Option<WalletRecord>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {} FROM wallets WHERE label = ?1", COLUMNS),
+            params![label],
+            WalletRecord::from_row,
+        )
+        .optional()?)
+}
+
+/// The most recently created wallet, if any
+pub fn latest(conn: &Connection) -> Result<Option<WalletRecord>> {
+    Ok(conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM wallets ORDER BY created_at DESC, rowid DESC LIMIT 1",
+                COLUMNS
+            ),
+            [],
+            WalletRecord::from_row,
+        )
+        .optional()?)
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<WalletRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM wallets ORDER BY created_at",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map([], WalletRecord::from_row)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
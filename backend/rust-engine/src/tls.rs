@@ -0,0 +1,505 @@
+//! TLS for the engine's gRPC server and its clients.
+//!
+//! The server side wraps accepted TCP connections with rustls. Certificates
+//! come from PEM files or are generated self-signed on first start, and are
+//! re-read when the files change (or on SIGHUP) without restarting; existing
+//! connections keep the certificate they were accepted with. With a client CA
+//! configured only clients presenting a certificate chaining to it complete
+//! the handshake.
+
+use crate::engine_config::TlsConfig;
+use anyhow::Result;
+use clap::Parser;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::Interceptor;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+use tonic::Status;
+use tracing::{debug, error, info, warn};
+
+/// How often certificate files are checked for changes
+pub const TLS_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Handshakes that take longer are dropped so they cannot tie up the server
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Directory under the data dir holding the generated self-signed certificate
+const SELF_SIGNED_DIR: &str = "tls";
+
+/// Certificate and key files the server uses
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub client_ca: Option<PathBuf>,
+    /// Whether `cert` and `key` are the generated self-signed pair
+    pub self_signed: bool,
+}
+
+impl TlsFiles {
+    pub fn new(config: &TlsConfig, data_dir: &Path) -> Self {
+        let (cert, key, self_signed) = match (&config.cert_path, &config.key_path) {
+            (Some(cert), Some(key)) => (cert.clone(), key.clone(), false),
+            _ => {
+                let dir = data_dir.join(SELF_SIGNED_DIR);
+                (dir.join("engine.crt"), dir.join("engine.key"), true)
+            }
+        };
+        Self {
+            cert,
+            key,
+            client_ca: config.client_ca_path.clone(),
+            self_signed,
+        }
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &Path> {
+        [self.cert.as_path(), self.key.as_path()]
+            .into_iter()
+            .chain(self.client_ca.as_deref())
+    }
+
+    /// Modification times, used to notice rotated certificates
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        self.paths()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+/// Accepts TLS connections with the current certificates
+pub struct TlsAcceptor {
+    files: TlsFiles,
+    current: RwLock<LoadedConfig>,
+}
+
+struct LoadedConfig {
+    config: Arc<ServerConfig>,
+    modified: Vec<Option<SystemTime>>,
+}
+
+impl TlsAcceptor {
+    /// Load the configured certificates, generating the self-signed pair for
+    /// `listen_addr` if no certificate is configured and none exists yet
+    pub fn load(config: &TlsConfig, data_dir: &Path, listen_addr: SocketAddr) -> Result<Self> {
+        let files = TlsFiles::new(config, data_dir);
+        if files.self_signed && !files.cert.exists() {
+            let names = self_signed_names(listen_addr, &config.self_signed_names);
+            generate_self_signed(&files.cert, &files.key, &names)?;
+            info!(
+                "Generated self-signed TLS certificate for {:?} at {}",
+                names,
+                files.cert.display()
+            );
+        }
+
+        let modified = files.modified();
+        let config = Arc::new(server_config(&files)?);
+        Ok(Self {
+            files,
+            current: RwLock::new(LoadedConfig { config, modified }),
+        })
+    }
+
+    pub fn files(&self) -> &TlsFiles {
+        &self.files
+    }
+
+    /// Whether clients must present a certificate
+    pub fn requires_client_cert(&self) -> bool {
+        self.files.client_ca.is_some()
+    }
+
+    fn server_config(&self) -> Arc<ServerConfig> {
+        let current = self.current.read().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(&current.config)
+    }
+
+    /// Re-read the certificates if any of the files changed. Returns whether
+    /// new certificates are in use; on error the previous ones stay active.
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let modified = self.files.modified();
+        {
+            let current = self.current.read().unwrap_or_else(|e| e.into_inner());
+            if current.modified == modified {
+                return Ok(false);
+            }
+        }
+        self.reload_with(modified)?;
+        Ok(true)
+    }
+
+    /// Re-read the certificates unconditionally
+    pub fn reload(&self) -> Result<()> {
+        self.reload_with(self.files.modified())
+    }
+
+    fn reload_with(&self, modified: Vec<Option<SystemTime>>) -> Result<()> {
+        let config = Arc::new(server_config(&self.files)?);
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        *current = LoadedConfig { config, modified };
+        Ok(())
+    }
+
+    /// Reload the certificates when the files change or on SIGHUP
+    pub async fn watch(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(TLS_RELOAD_INTERVAL);
+        interval.tick().await;
+
+        #[cfg(unix)]
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(signal) => Some(signal),
+            Err(e) => {
+                warn!("Cannot listen for SIGHUP, relying on polling: {}", e);
+                None
+            }
+        };
+
+        loop {
+            #[cfg(unix)]
+            let forced = tokio::select! {
+                _ = interval.tick() => false,
+                Some(()) = async {
+                    match hangup.as_mut() {
+                        Some(signal) => signal.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => true,
+            };
+            #[cfg(not(unix))]
+            let forced = {
+                interval.tick().await;
+                false
+            };
+
+            let result = if forced {
+                self.reload().map(|()| true)
+            } else {
+                self.reload_if_changed()
+            };
+            match result {
+                Ok(true) => info!(
+                    "Reloaded TLS certificate from {}",
+                    self.files.cert.display()
+                ),
+                Ok(false) => {}
+                Err(e) => error!(
+                    "Failed to reload TLS certificate, keeping the old one: {:#}",
+                    e
+                ),
+            }
+        }
+    }
+
+    /// Stream of TLS connections accepted on `listener`, for
+    /// `Router::serve_with_incoming`. Handshakes run concurrently; failed
+    /// ones (e.g. clients without a valid certificate) are dropped.
+    pub fn incoming(
+        self: Arc<Self>,
+        listener: TcpListener,
+    ) -> ReceiverStream<io::Result<TlsStream<TcpStream>>> {
+        let (tx, rx) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            while !tx.is_closed() {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        warn!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+
+                let acceptor = tokio_rustls::TlsAcceptor::from(self.server_config());
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send(Ok(stream)).await;
+                        }
+                        Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", peer, e),
+                        Err(_) => debug!("TLS handshake with {} timed out", peer),
+                    }
+                });
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn server_config(files: &TlsFiles) -> Result<ServerConfig> {
+    let certs = read_certs(&files.cert)?;
+    let key = read_key(&files.key)?;
+
+    let builder =
+        ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+    let builder = match &files.client_ca {
+        Some(ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(ca)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider())
+                .build()
+                .map_err(|e| anyhow::anyhow!("Invalid client CA {}: {}", ca.display(), e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| anyhow::anyhow!("Invalid TLS certificate {}: {}", files.cert.display(), e))?;
+    // HTTP/1.1 is still needed for gRPC-web
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(|e| anyhow::anyhow!("Invalid certificate {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No certificate found in {}",
+            path.display()
+        ));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| anyhow::anyhow!("Invalid private key {}: {}", path.display(), e))?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", path.display()))
+}
+
+/// Names the self-signed certificate is valid for: localhost, the listen
+/// address when it is a specific IP, and any configured extras
+fn self_signed_names(listen_addr: SocketAddr, extra: &[String]) -> Vec<String> {
+    let mut names = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    let ip = listen_addr.ip();
+    if !ip.is_unspecified() && !ip.is_loopback() {
+        names.push(ip.to_string());
+    }
+    for name in extra {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names
+}
+
+/// Write a self-signed certificate and its key as PEM files
+pub fn generate_self_signed(cert_path: &Path, key_path: &Path, names: &[String]) -> Result<()> {
+    let certified = rcgen::generate_simple_self_signed(names.to_vec())?;
+
+    for path in [cert_path, key_path] {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+    std::fs::write(cert_path, certified.cert.pem())?;
+    write_private(key_path, certified.key_pair.serialize_pem().as_bytes())?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    std::fs::write(path, contents)
+}
+
+/// Connection flags shared by the `test_*_client` binaries
+#[derive(Debug, Clone, Parser)]
+pub struct ClientConnectArgs {
+    /// Engine gRPC endpoint; use https:// when the server has TLS enabled
+    #[arg(long, default_value = "http://127.0.0.1:50051")]
+    pub server: String,
+    /// PEM CA to verify the server with, e.g. the engine's self-signed
+    /// `<data_dir>/tls/engine.crt`; public roots are used when unset
+    #[arg(long)]
+    pub ca_cert: Option<PathBuf>,
+    /// PEM client certificate for servers requiring mutual TLS
+    #[arg(long, requires = "client_key")]
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key for `--client-cert`
+    #[arg(long, requires = "client_cert")]
+    pub client_key: Option<PathBuf>,
+    /// Name to verify the server certificate against, if not the endpoint host
+    #[arg(long)]
+    pub tls_domain: Option<String>,
+    /// API token to send as `authorization: Bearer <token>`
+    #[arg(long, conflicts_with = "token_file")]
+    pub token: Option<String>,
+    /// File holding the API token, e.g. `<data_dir>/auth/admin.token`
+    #[arg(long)]
+    pub token_file: Option<PathBuf>,
+}
+
+impl ClientConnectArgs {
+    /// Open a channel to the engine with the requested TLS settings
+    pub async fn connect(&self) -> Result<Channel> {
+        // tonic's client uses the process-wide provider, which rustls cannot
+        // pick on its own when more than one is compiled in
+        let _ = ring::default_provider().install_default();
+        let mut endpoint = Channel::from_shared(self.server.clone())?;
+
+        if self.server.starts_with("https://") {
+            let mut tls = ClientTlsConfig::new();
+            tls = match &self.ca_cert {
+                Some(path) => tls.ca_certificate(Certificate::from_pem(std::fs::read(path)?)),
+                None => tls.with_webpki_roots(),
+            };
+            if let (Some(cert), Some(key)) = (&self.client_cert, &self.client_key) {
+                tls = tls.identity(Identity::from_pem(
+                    std::fs::read(cert)?,
+                    std::fs::read(key)?,
+                ));
+            }
+            if let Some(domain) = &self.tls_domain {
+                tls = tls.domain_name(domain.clone());
+            }
+            endpoint = endpoint.tls_config(tls)?;
+        } else if self.ca_cert.is_some() || self.client_cert.is_some() {
+            return Err(anyhow::anyhow!(
+                "TLS options need an https:// server address, got {}",
+                self.server
+            ));
+        }
+
+        Ok(endpoint.connect().await?)
+    }
+
+    /// Interceptor attaching the `--token` or `--token-file` token to every call
+    pub fn interceptor(&self) -> Result<BearerToken> {
+        let token = match (&self.token, &self.token_file) {
+            (Some(token), _) => Some(token.clone()),
+            (None, Some(path)) => Some(std::fs::read_to_string(path)?),
+            (None, None) => None,
+        };
+        let header = token
+            .map(|token| format!("Bearer {}", token.trim()).parse())
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("API token contains invalid characters"))?;
+        Ok(BearerToken(header))
+    }
+}
+
+/// Adds an `authorization` header to outgoing calls when a token is set
+#[derive(Clone)]
+pub struct BearerToken(Option<MetadataValue<Ascii>>);
+
+impl Interceptor for BearerToken {
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        if let Some(header) = &self.0 {
+            request
+                .metadata_mut()
+                .insert("authorization", header.clone());
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_self_signed_names() {
+        let names = self_signed_names("10.0.0.5:50051".parse().unwrap(), &["engine.local".into()]);
+        assert!(names.contains(&"localhost".to_string()));
+        assert!(names.contains(&"10.0.0.5".to_string()));
+        assert!(names.contains(&"engine.local".to_string()));
+
+        let names = self_signed_names("0.0.0.0:50051".parse().unwrap(), &[]);
+        assert!(!names.contains(&"0.0.0.0".to_string()));
+    }
+
+    #[test]
+    fn test_generates_and_reloads_self_signed_certificate() {
+        let dir = temp_dir("satsconnect_tls_test");
+        let config = TlsConfig {
+            enabled: true,
+            ..Default::default()
+        };
+
+        let acceptor =
+            TlsAcceptor::load(&config, &dir, "127.0.0.1:50051".parse().unwrap()).unwrap();
+        assert!(acceptor.files().self_signed);
+        assert!(acceptor.files().cert.exists());
+        assert!(!acceptor.requires_client_cert());
+        assert!(!acceptor.reload_if_changed().unwrap());
+
+        // A broken certificate is refused and the old one stays in use
+        std::fs::write(&acceptor.files().cert, "not a certificate").unwrap();
+        assert!(acceptor.reload().is_err());
+
+        let files = acceptor.files().clone();
+        generate_self_signed(&files.cert, &files.key, &["localhost".to_string()]).unwrap();
+        acceptor.reload().unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_configured_certificate_must_exist() {
+        let dir = temp_dir("satsconnect_tls_missing_test");
+        let config = TlsConfig {
+            enabled: true,
+            cert_path: Some(dir.join("missing.crt")),
+            key_path: Some(dir.join("missing.key")),
+            ..Default::default()
+        };
+
+        let err = TlsAcceptor::load(&config, &dir, "127.0.0.1:50051".parse().unwrap());
+        assert!(err.is_err());
+        assert!(!dir.join(SELF_SIGNED_DIR).exists());
+    }
+}
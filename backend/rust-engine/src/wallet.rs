@@ -1,13 +1,31 @@
+use crate::config::LightningConfig;
+use crate::lightning::route_estimator::{EstimateTarget, RouteEstimate};
+use crate::lightning::send_options::{SendPaymentOptions, SendPaymentOutcome};
 use crate::lightning_engine::LightningEngine;
+use crate::lsp::{JitChannelQuote, LSPClient, Lsps2Error};
 use crate::secure_storage::SecureStorage;
+use crate::store::{self, invoices, wallets, InvoiceRecord, Store, WalletRecord};
 use anyhow::Result;
 use bip39::{Language, Mnemonic};
-use bitcoin::Network;
 use directories::ProjectDirs;
-use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Expiry the Lightning engine uses for generated invoices
+const INVOICE_EXPIRY_SECS: i64 = 3600;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WalletError {
+    #[error("No wallet loaded")]
+    NotLoaded,
+    #[error("Wallet not found")]
+    NotFound,
+    #[error("Wallet {0} already exists")]
+    AlreadyExists(String),
+    #[error("Invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+}
+
 // Simplified wallet types for HTTP API (will be replaced with gRPC later)
 #[derive(Debug, Clone)]
 pub struct Wallet {
@@ -17,31 +35,98 @@ pub struct Wallet {
     pub wallet_id: String,
 }
 
+/// An invoice issued by [`WalletHandler::new_invoice`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewInvoice {
+    pub invoice: String,
+    pub payment_hash: String,
+    /// Fee the LSP deducts when the payment opens a just-in-time channel
+    pub lsp_fee_sats: Option<u64>,
+}
+
 #[derive(Debug)]
 pub struct WalletHandler {
-    wallets: Arc<RwLock<HashMap<String, Wallet>>>,
+    store: Store,
     current_wallet: Arc<RwLock<Option<String>>>,
     secure_storage: Arc<SecureStorage>,
     lightning_engine: Arc<LightningEngine>,
+    /// Opens just-in-time channels for wallets without inbound liquidity
+    lsp_client: Option<Arc<LSPClient>>,
 }
 
 impl WalletHandler {
     pub fn new() -> Result<Self> {
         let dirs = ProjectDirs::from("com", "SatsConnect", "engine")
             .ok_or_else(|| anyhow::anyhow!("Failed to get project directories"))?;
-        let data_dir = dirs.data_dir().to_path_buf();
+
+        let config = LightningConfig {
+            data_dir: dirs.data_dir().to_path_buf(),
+            ..LightningConfig::default()
+        };
+        Self::with_config(config)
+    }
+
+    /// Create a handler for the network and data directory in `config`
+    pub fn with_config(config: LightningConfig) -> Result<Self> {
+        Self::with_engine(Arc::new(LightningEngine::with_config(config)))
+    }
+
+    /// Create a handler on top of a Lightning engine shared with other services
+    pub fn with_engine(lightning_engine: Arc<LightningEngine>) -> Result<Self> {
+        let store = Store::open_in_dir(&lightning_engine.config().data_dir)?;
+        Self::with_store(lightning_engine, store)
+    }
+
+    /// Create a handler that keeps its wallets in `store`, shared with the
+    /// engine's other services
+    pub fn with_store(lightning_engine: Arc<LightningEngine>, store: Store) -> Result<Self> {
+        let config = lightning_engine.config();
+        let data_dir = config.data_dir.clone();
         std::fs::create_dir_all(&data_dir)?;
 
-        let secure_storage = Arc::new(SecureStorage::new(data_dir.clone())?);
+        let secure_storage = Arc::new(SecureStorage::new(data_dir)?);
+        store.bind_network(config.network)?;
 
-        // Initialize Lightning engine with testnet for development
-        let lightning_engine = Arc::new(LightningEngine::new(data_dir, Network::Testnet));
+        // Resume with the most recently created wallet
+        let current_wallet = store.with_conn(wallets::latest)?.map(|w| w.label);
 
         Ok(Self {
-            wallets: Arc::new(RwLock::new(HashMap::new())),
-            current_wallet: Arc::new(RwLock::new(None)),
+            store,
+            current_wallet: Arc::new(RwLock::new(current_wallet)),
             secure_storage,
             lightning_engine,
+            lsp_client: None,
+        })
+    }
+
+    /// Receive through just-in-time channels from `lsp_client`'s LSPS2
+    /// provider when the wallet lacks inbound liquidity
+    pub fn with_lsp_client(mut self, lsp_client: Arc<LSPClient>) -> Self {
+        self.lsp_client = Some(lsp_client);
+        self
+    }
+
+    /// Label of the loaded wallet, if any
+    pub async fn current_wallet_label(&self) -> Option<String> {
+        self.current_wallet.read().await.clone()
+    }
+
+    /// The loaded wallet, as stored
+    async fn current_wallet(&self) -> Result<Wallet> {
+        let current_wallet = self.current_wallet.read().await;
+
+        let wallet_name = current_wallet.as_ref().ok_or(WalletError::NotLoaded)?;
+
+        let record = self
+            .store
+            .with_conn(|c| wallets::get_by_label(c, wallet_name))?
+            .ok_or(WalletError::NotFound)?;
+
+        Ok(Wallet {
+            label: record.label,
+            node_id: record.node_id,
+            address: record.address,
+            wallet_id: record.wallet_id,
         })
     }
 
@@ -70,12 +155,20 @@ impl WalletHandler {
         label: String,
         mnemonic: Option<String>,
     ) -> Result<(String, String)> {
+        if self
+            .store
+            .with_conn(|c| wallets::get_by_label(c, &label))?
+            .is_some()
+        {
+            return Err(WalletError::AlreadyExists(label).into());
+        }
+
         let mnemonic = if let Some(m) = mnemonic {
             if m.is_empty() {
                 Self::generate_mnemonic()?
             } else {
                 // Validate the provided mnemonic
-                Mnemonic::parse(&m)?;
+                Mnemonic::parse(&m).map_err(|e| WalletError::InvalidMnemonic(e.to_string()))?;
                 m
             }
         } else {
@@ -96,16 +189,16 @@ impl WalletHandler {
         // Store mnemonic securely
         self.secure_storage.store_mnemonic(&wallet_id, &mnemonic)?;
 
-        let wallet = Wallet {
-            label: label.clone(),
-            node_id: node_id.clone(),
-            address: address.clone(),
-            wallet_id: wallet_id.clone(),
-        };
+        let record = WalletRecord::new(
+            &wallet_id,
+            &label,
+            &node_id,
+            &address,
+            &self.lightning_engine.network().to_string(),
+        );
+        self.store.with_conn(|c| wallets::insert(c, &record))?;
 
         {
-            let mut wallets = self.wallets.write().await;
-            wallets.insert(label.clone(), wallet);
             let mut current = self.current_wallet.write().await;
             *current = Some(label);
         }
@@ -114,75 +207,140 @@ impl WalletHandler {
     }
 
     pub async fn get_balance(&self) -> Result<(u64, u64)> {
-        let current_wallet = self.current_wallet.read().await;
-        let wallets = self.wallets.read().await;
-
-        let wallet_name = current_wallet
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No wallet loaded"))?;
-
-        let _wallet = wallets
-            .get(wallet_name)
-            .ok_or_else(|| anyhow::anyhow!("Wallet not found"))?;
+        let _wallet = self.current_wallet().await?;
 
         // Get real balances from Lightning engine
         self.lightning_engine.get_balance().await
     }
 
+    /// Invoice for `amount_sats`, without accepting any LSP fee
     pub async fn generate_invoice(
         &self,
         amount_sats: u64,
         memo: String,
     ) -> Result<(String, String)> {
-        let current_wallet = self.current_wallet.read().await;
-        let wallets = self.wallets.read().await;
+        let invoice = self.new_invoice(amount_sats, memo, 0).await?;
+        Ok((invoice.invoice, invoice.payment_hash))
+    }
 
-        let wallet_name = current_wallet
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No wallet loaded"))?;
+    /// What receiving `amount_sats` costs; `None` when the wallet's channels
+    /// can take it, otherwise the fee of the just-in-time channel the LSP
+    /// would open. Clients show it before calling [`Self::new_invoice`].
+    pub async fn quote_invoice(&self, amount_sats: u64) -> Result<Option<JitChannelQuote>> {
+        let _wallet = self.current_wallet().await?;
+
+        match self.jit_lsp(amount_sats).await? {
+            Some(lsp_client) => Ok(Some(
+                lsp_client.quote_jit_channel(amount_sats * 1000).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Invoice for `amount_sats` (0 for amountless). Without enough inbound
+    /// liquidity the invoice carries the LSP's route hint, and is only created
+    /// if the LSP's fee is at most `max_lsp_fee_sats`, the fee the user
+    /// accepted from [`Self::quote_invoice`].
+    pub async fn new_invoice(
+        &self,
+        amount_sats: u64,
+        memo: String,
+        max_lsp_fee_sats: u64,
+    ) -> Result<NewInvoice> {
+        let wallet = self.current_wallet().await?;
+
+        let (invoice, payment_hash, lsp_fee_msat) = match self.jit_lsp(amount_sats).await? {
+            Some(lsp_client) => {
+                let jit = lsp_client
+                    .create_jit_invoice(amount_sats * 1000, &memo, max_lsp_fee_sats * 1000)
+                    .await?;
+                (jit.invoice, jit.payment_hash, Some(jit.quote.fee_msat))
+            }
+            // Generate real Lightning invoice
+            None => {
+                let (invoice, payment_hash) = self
+                    .lightning_engine
+                    .generate_invoice(amount_sats, &memo)
+                    .await?;
+                (invoice, payment_hash, None)
+            }
+        };
 
-        let _wallet = wallets
-            .get(wallet_name)
-            .ok_or_else(|| anyhow::anyhow!("Wallet not found"))?;
+        let created_at = store::now();
+        let record = InvoiceRecord {
+            payment_hash: payment_hash.clone(),
+            wallet_id: wallet.wallet_id,
+            bolt11: invoice.clone(),
+            amount_msat: Some(amount_sats * 1000),
+            description: memo,
+            status: "OPEN".to_string(),
+            created_at,
+            expires_at: Some(created_at + INVOICE_EXPIRY_SECS),
+            paid_at: None,
+            lsp_fee_msat,
+        };
+        self.store.with_conn(|c| invoices::insert(c, &record))?;
 
-        // Generate real Lightning invoice
-        self.lightning_engine
-            .generate_invoice(amount_sats, &memo)
-            .await
+        Ok(NewInvoice {
+            invoice,
+            payment_hash,
+            lsp_fee_sats: lsp_fee_msat.map(|fee_msat| fee_msat.div_ceil(1000)),
+        })
     }
 
-    pub async fn send_payment(&self, invoice: String) -> Result<(String, String)> {
-        let current_wallet = self.current_wallet.read().await;
-        let wallets = self.wallets.read().await;
+    /// The LSP client to receive `amount_sats` through, when the usable
+    /// channels cannot take it and an LSP can open a just-in-time channel
+    async fn jit_lsp(&self, amount_sats: u64) -> Result<Option<&LSPClient>> {
+        let Some(lsp_client) = &self.lsp_client else {
+            return Ok(None);
+        };
+        if !lsp_client.has_jit_provider().await {
+            return Ok(None);
+        }
 
-        let wallet_name = current_wallet
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No wallet loaded"))?;
+        let inbound_msat = self.lightning_engine.inbound_capacity_msat().await?;
+        if amount_sats == 0 {
+            // The LSP's fee depends on the amount, so it cannot be quoted
+            return match inbound_msat {
+                0 => Err(Lsps2Error::AmountRequired.into()),
+                _ => Ok(None),
+            };
+        }
+        Ok((inbound_msat < amount_sats * 1000).then_some(lsp_client.as_ref()))
+    }
 
-        let _wallet = wallets
-            .get(wallet_name)
-            .ok_or_else(|| anyhow::anyhow!("Wallet not found"))?;
+    pub async fn send_payment(&self, invoice: String) -> Result<(String, String)> {
+        let _wallet = self.current_wallet().await?;
 
         // Send real Lightning payment
         self.lightning_engine.send_payment(&invoice).await
     }
 
+    pub async fn send_payment_with_options(
+        &self,
+        invoice: String,
+        options: SendPaymentOptions,
+    ) -> Result<SendPaymentOutcome> {
+        let _wallet = self.current_wallet().await?;
+
+        self.lightning_engine
+            .send_payment_with_options(&invoice, &options)
+            .await
+    }
+
+    pub async fn estimate_routing_fee(&self, target: EstimateTarget) -> Result<RouteEstimate> {
+        let _wallet = self.current_wallet().await?;
+
+        self.lightning_engine.estimate_routing_fee(target).await
+    }
+
     pub async fn buy_airtime(
         &self,
         amount_sats: u64,
         phone_number: String,
         provider: Option<String>,
     ) -> Result<(String, String, String)> {
-        let current_wallet = self.current_wallet.read().await;
-        let wallets = self.wallets.read().await;
-
-        let wallet_name = current_wallet
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No wallet loaded"))?;
-
-        let _wallet = wallets
-            .get(wallet_name)
-            .ok_or_else(|| anyhow::anyhow!("Wallet not found"))?;
+        let _wallet = self.current_wallet().await?;
 
         // Buy airtime using real Lightning engine
         self.lightning_engine
@@ -1,14 +1,18 @@
 use anyhow::Result;
+use clap::Parser;
 use satsconnect_rust_engine::proto::satsconnect::wallet::v1::wallet_service_client::WalletServiceClient;
 use satsconnect_rust_engine::proto::satsconnect::wallet::v1::{
     CreateWalletRequest, GetBalanceRequest,
 };
+use satsconnect_rust_engine::tls::ClientConnectArgs;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = ClientConnectArgs::parse();
     println!("🧪 Testing gRPC client connection...");
 
-    let mut client = WalletServiceClient::connect("http://127.0.0.1:50051").await?;
+    let channel = args.connect().await?;
+    let mut client = WalletServiceClient::with_interceptor(channel, args.interceptor()?);
     println!("✅ Connected to gRPC server");
 
     // Test CreateWallet
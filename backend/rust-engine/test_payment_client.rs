@@ -1,23 +1,29 @@
 use anyhow::Result;
+use clap::Parser;
 use satsconnect_rust_engine::proto::satsconnect::payment::v1::payment_service_client::PaymentServiceClient;
 use satsconnect_rust_engine::proto::satsconnect::payment::v1::PaymentRequest;
 use satsconnect_rust_engine::proto::satsconnect::wallet::v1::wallet_service_client::WalletServiceClient;
 use satsconnect_rust_engine::proto::satsconnect::wallet::v1::{
     NewInvoiceRequest, SendPaymentRequest,
 };
+use satsconnect_rust_engine::tls::ClientConnectArgs;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = ClientConnectArgs::parse();
     println!("🧪 Testing gRPC Payment Service...");
 
-    let mut wallet_client = WalletServiceClient::connect("http://127.0.0.1:50051").await?;
-    let mut payment_client = PaymentServiceClient::connect("http://127.0.0.1:50051").await?;
+    let channel = args.connect().await?;
+    let mut wallet_client =
+        WalletServiceClient::with_interceptor(channel.clone(), args.interceptor()?);
+    let mut payment_client = PaymentServiceClient::with_interceptor(channel, args.interceptor()?);
     println!("✅ Connected to gRPC servers");
 
     // Test NewInvoice
     let request = tonic::Request::new(NewInvoiceRequest {
         amount_sats: 1000,
         memo: "Test invoice".to_string(),
+        max_lsp_fee_sats: 0,
     });
 
     match wallet_client.new_invoice(request).await {
@@ -30,6 +36,8 @@ async fn main() -> Result<()> {
             // Test SendPayment
             let request = tonic::Request::new(SendPaymentRequest {
                 invoice: invoice.invoice.clone(),
+                fee_limit_sats: 10,
+                ..Default::default()
             });
 
             match wallet_client.send_payment(request).await {
@@ -56,6 +64,7 @@ async fn main() -> Result<()> {
         amount_sats: 500,
         invoice: "lnbc500u1p3k2v5cpp5test".to_string(),
         description: "Test payment".to_string(),
+        force: false,
     });
 
     match payment_client.process_payment(request).await {
@@ -1,14 +1,18 @@
 use anyhow::Result;
+use clap::Parser;
 use satsconnect_rust_engine::proto::satsconnect::wallet::v1::wallet_service_client::WalletServiceClient;
 use satsconnect_rust_engine::proto::satsconnect::wallet::v1::{
     CreateWalletRequest, GetBalanceRequest, NewInvoiceRequest, SendPaymentRequest,
 };
+use satsconnect_rust_engine::tls::ClientConnectArgs;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = ClientConnectArgs::parse();
     println!("🧪 Testing gRPC Wallet Service...");
 
-    let mut wallet_client = WalletServiceClient::connect("http://127.0.0.1:50051").await?;
+    let channel = args.connect().await?;
+    let mut wallet_client = WalletServiceClient::with_interceptor(channel, args.interceptor()?);
     println!("✅ Connected to gRPC wallet server");
 
     // Test CreateWallet
@@ -49,6 +53,7 @@ async fn main() -> Result<()> {
     let request = tonic::Request::new(NewInvoiceRequest {
         amount_sats: 1000,
         memo: "Test invoice".to_string(),
+        max_lsp_fee_sats: 0,
     });
 
     match wallet_client.new_invoice(request).await {
@@ -61,6 +66,8 @@ async fn main() -> Result<()> {
             // Test SendPayment
             let request = tonic::Request::new(SendPaymentRequest {
                 invoice: invoice.invoice.clone(),
+                fee_limit_sats: 10,
+                ..Default::default()
             });
 
             match wallet_client.send_payment(request).await {
@@ -0,0 +1,94 @@
+//! End-to-end tests against a local regtest network. They launch bitcoind
+//! and electrs (see `BITCOIND_EXE` and `ELECTRS_EXE`), so they only run with
+//! `cargo test --test regtest_harness -- --ignored`.
+
+use anyhow::Result;
+use satsconnect_rust_engine::config::ChainSource;
+use satsconnect_rust_engine::lightning::regtest_setup::RegtestSetup;
+use satsconnect_rust_engine::network;
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs bitcoind and electrs"]
+async fn test_regtest_setup_creation() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let mut setup = RegtestSetup::new(temp_dir.path().join("regtest"));
+
+    setup.create_test_nodes(2).await?;
+    assert_eq!(setup.get_nodes().len(), 2);
+    for node in setup.get_nodes() {
+        assert_eq!(
+            node.public_key()?.to_string(),
+            *node.node_id.as_ref().unwrap()
+        );
+        network::parse_address(node.address.as_ref().unwrap(), bitcoin::Network::Regtest)?;
+    }
+    assert!(setup.get_network_stats().block_height >= Some(101));
+
+    setup.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs bitcoind and electrs"]
+async fn test_invoice_creation() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let mut setup = RegtestSetup::new(temp_dir.path().join("regtest"));
+
+    setup.create_test_nodes(1).await?;
+    let (invoice, hash) = setup
+        .create_test_invoice("node_1", 1000, "Test invoice")
+        .await?;
+
+    let invoice = network::parse_invoice(&invoice, bitcoin::Network::Regtest)?;
+    assert_eq!(invoice.amount_milli_satoshis(), Some(1_000_000));
+    assert_eq!(invoice.payment_hash().to_string(), hash);
+
+    setup.shutdown().await
+}
+
+/// Create wallets, fund, open, pay directly and through a hop, then close
+async fn full_scenario(chain_source: ChainSource) -> Result<()> {
+    let temp_dir = tempdir()?;
+    let mut setup =
+        RegtestSetup::new(temp_dir.path().join("regtest")).with_chain_source(chain_source);
+
+    setup.create_test_nodes(3).await?;
+    setup.fund_node("node_1", 1_000_000).await?;
+    setup.fund_node("node_2", 1_000_000).await?;
+
+    setup.connect_nodes("node_1", "node_2").await?;
+    setup.connect_nodes("node_2", "node_3").await?;
+    setup
+        .open_channel("node_1", "node_2", 500_000, None)
+        .await?;
+    setup
+        .open_channel("node_2", "node_3", 500_000, None)
+        .await?;
+    assert_eq!(setup.get_network_stats().channels, 2);
+
+    setup.send_test_payment("node_1", "node_2", 20_000).await?;
+    setup.send_test_payment("node_1", "node_3", 10_000).await?;
+    // node_3 never opened a channel, so it pays no commitment fees
+    let node_3 = setup.get_node("node_3").unwrap().running()?;
+    assert_eq!(node_3.list_balances().total_lightning_balance_sats, 10_000);
+
+    setup.close_channel("node_1", "node_2").await?;
+    assert_eq!(setup.get_network_stats().channels, 1);
+    // node_1's side of the channel is back on chain
+    let node_1 = setup.get_node("node_1").unwrap().running()?;
+    assert!(node_1.list_balances().total_onchain_balance_sats > 900_000);
+
+    setup.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs bitcoind and electrs"]
+async fn test_full_scenario_over_esplora() -> Result<()> {
+    full_scenario(ChainSource::Esplora).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs bitcoind"]
+async fn test_full_scenario_over_bitcoind_rpc() -> Result<()> {
+    full_scenario(ChainSource::Bitcoind).await
+}